@@ -0,0 +1,193 @@
+//! Attribute macros for the revl crate.
+//!
+//! These macros are re-exported by revl when the `macros` feature is
+//! enabled, and should be used through these re-exports,
+//! i.e. `#[revl::main]` and `#[revl::thread]`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input,
+    spanned::Spanned,
+    Error,
+    Expr,
+    ExprCall,
+    FnArg,
+    ItemFn,
+    LitStr,
+    ReturnType,
+};
+
+// Properties collected from the attribute arguments.
+#[derive(Default)]
+struct ThreadAttrs {
+    name: Option<LitStr>,
+    public: bool,
+    policy: Option<ExprCall>,
+}
+
+impl ThreadAttrs {
+    fn parse(args: TokenStream) -> Result<Self, Error> {
+        let mut attrs = ThreadAttrs::default();
+        let parser = syn::meta::parser(|meta| {
+            if meta.path.is_ident("name") {
+                attrs.name = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("public") {
+                attrs.public = true;
+                Ok(())
+            } else if meta.path.is_ident("policy") {
+                match meta.value()?.parse::<Expr>()? {
+                    Expr::Call(call) => {
+                        attrs.policy = Some(call);
+                        Ok(())
+                    },
+                    expr => Err(Error::new(expr.span(),
+                                           "expected a policy such as fifo(prio)")),
+                }
+            } else {
+                Err(meta.error("unsupported revl attribute"))
+            }
+        });
+        syn::parse::Parser::parse(parser, args)?;
+        Ok(attrs)
+    }
+    // Build the expression creating the thread builder.
+    fn builder(&self) -> TokenStream2 {
+        let mut builder = quote! { ::revl::thread::Builder::new() };
+        if let Some(name) = &self.name {
+            builder = quote! { #builder.name(#name) };
+        }
+        if self.public {
+            builder = quote! { #builder.public() };
+        }
+        builder
+    }
+    // Build the statement applying the scheduling policy to `me`, if
+    // any.
+    fn policy(&self) -> Result<TokenStream2, Error> {
+        let call = match &self.policy {
+            Some(call) => call,
+            None => return Ok(quote! {}),
+        };
+        let policy = match &*call.func {
+            Expr::Path(path) => path.path.get_ident().map(|i| i.to_string()),
+            _ => None,
+        };
+        let args: Vec<&Expr> = call.args.iter().collect();
+        let param = match (policy.as_deref(), args.as_slice()) {
+            (Some("fifo"), [prio]) => quote! { ::revl::sched::SchedFifo { prio: #prio } },
+            (Some("rr"), [prio, timeslice]) =>
+                quote! { ::revl::sched::SchedRR { prio: #prio, timeslice: #timeslice } },
+            (Some("weak"), [prio]) => quote! { ::revl::sched::SchedWeak { prio: #prio } },
+            (Some("quota"), [group, prio]) =>
+                quote! { ::revl::sched::SchedQuota { group: #group, prio: #prio } },
+            (Some("tp"), [part, prio]) =>
+                quote! { ::revl::sched::SchedTP { part: #part, prio: #prio } },
+            _ => return Err(Error::new(call.span(),
+                                       "expected fifo(prio), rr(prio, timeslice), weak(prio), \
+                                        quota(group, prio) or tp(part, prio)")),
+        };
+        Ok(quote! {
+            me.set_sched(#param).expect("cannot set scheduling policy");
+        })
+    }
+}
+
+/// Attach the main thread to the EVL core before running its body.
+///
+/// The optional arguments are `name = "..."`, `public` and `policy =
+/// fifo(prio)` (or `rr`, `weak`, `quota`, `tp`), with the same
+/// meaning as for `#[revl::thread]`.
+///
+/// ```ignore
+/// #[revl::main(name = "app-main", policy = fifo(10))]
+/// fn main() {
+///     // running attached to the EVL core.
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn main(args: TokenStream, item: TokenStream) -> TokenStream {
+    let func = parse_macro_input!(item as ItemFn);
+    let attrs = match ThreadAttrs::parse(args) {
+        Ok(attrs) => attrs,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let policy = match attrs.policy() {
+        Ok(policy) => policy,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let builder = attrs.builder();
+    let ItemFn { attrs: fattrs, vis, sig, block } = func;
+    quote! {
+        #(#fattrs)*
+        #vis #sig {
+            let me = #builder.attach().expect("cannot attach main thread to EVL core");
+            #policy
+            let _ = &me;
+            #block
+        }
+    }.into()
+}
+
+/// Turn a function into a spawner of an EVL thread running its
+/// body.
+///
+/// The annotated function returns a
+/// `Result<revl::thread::JoinHandle<T>, revl::Error>` instead of
+/// `T`, its arguments are moved to the new thread. The arguments of
+/// the attribute are:
+///
+/// - `name = "..."`: the thread name.
+/// - `public`: make the thread visible in the /dev/evl hierarchy.
+/// - `policy = fifo(prio)`: the scheduling policy, one of
+/// `fifo(prio)`, `rr(prio, timeslice)`, `weak(prio)`, `quota(group,
+/// prio)` or `tp(part, prio)`. `timeslice` is a
+/// [`Duration`][`std::time::Duration`] expression.
+///
+/// ```ignore
+/// #[revl::thread(name = "sampler", policy = fifo(80))]
+/// fn sampler(count: usize) -> u64 {
+///     // EVL thread code
+///     0
+/// }
+///
+/// let total = sampler(1000).unwrap().join().unwrap();
+/// ```
+#[proc_macro_attribute]
+pub fn thread(args: TokenStream, item: TokenStream) -> TokenStream {
+    let func = parse_macro_input!(item as ItemFn);
+    match expand_thread(args, func) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand_thread(args: TokenStream, func: ItemFn) -> Result<TokenStream2, Error> {
+    let attrs = ThreadAttrs::parse(args)?;
+    let builder = attrs.builder();
+    let policy = attrs.policy()?;
+    let ItemFn { attrs: fattrs, vis, mut sig, block } = func;
+    // The arguments are captured by the closure running the body.
+    if let Some(FnArg::Receiver(recv)) = sig.inputs.first() {
+        return Err(Error::new(recv.span(), "EVL thread functions cannot take self"));
+    }
+    let output = match &sig.output {
+        ReturnType::Default => quote! { () },
+        ReturnType::Type(_, ty) => quote! { #ty },
+    };
+    sig.output = syn::parse2(quote! {
+        -> ::std::result::Result<::revl::thread::JoinHandle<#output>, ::revl::Error>
+    })?;
+    Ok(quote! {
+        #(#fattrs)*
+        #vis #sig {
+            #builder.spawn(move |me| -> #output {
+                #policy
+                let _ = me;
+                #block
+            })
+        }
+    })
+}
@@ -0,0 +1,8 @@
+fn main() {
+    // The variadic entry points of the interface cannot be defined
+    // in stable Rust, they are implemented in C.
+    println!("cargo:rerun-if-changed=src/shim.c");
+    cc::Build::new()
+        .file("src/shim.c")
+        .compile("revl_sim_shim");
+}
@@ -0,0 +1,144 @@
+// Memory heaps.
+//
+// Blocks up to the page size are carved from pages dedicated to a
+// power-of-two size class, so that they are aligned on that size.
+// Larger blocks span multiple pages. Freed blocks and page ranges
+// are kept on free lists for reuse.
+
+use std::collections::{BTreeMap, HashMap};
+use std::os::raw::{c_int, c_void};
+use std::sync::{Mutex, PoisonError};
+use libc::EINVAL;
+use crate::evl_heap;
+
+const PAGE_SHIFT: usize = 9;
+const PAGE_SIZE: usize = 1 << PAGE_SHIFT;
+const MIN_BLOCK: usize = 16;
+
+struct HeapState {
+    base: usize,
+    size: usize,
+    // The first page never handed out.
+    brk: usize,
+    used: usize,
+    // Free blocks per size class, and free page ranges by offset.
+    buckets: HashMap<usize, Vec<usize>>,
+    ranges: BTreeMap<usize, usize>,
+    // Size of each live block by offset.
+    blocks: HashMap<usize, usize>,
+}
+
+impl HeapState {
+    fn alloc_pages(&mut self, len: usize) -> Option<usize> {
+        let fit = self.ranges.iter().find(|(_, &l)| l >= len).map(|(&off, &l)| (off, l));
+        if let Some((off, l)) = fit {
+            self.ranges.remove(&off);
+            if l > len {
+                self.ranges.insert(off + len, l - len);
+            }
+            return Some(off);
+        }
+        if self.size - self.brk < len {
+            return None;
+        }
+        let off = self.brk;
+        self.brk += len;
+        Some(off)
+    }
+    fn alloc(&mut self, size: usize) -> Option<usize> {
+        if size == 0 {
+            return None;
+        }
+        let (off, bsize) = if size <= PAGE_SIZE {
+            let bsize = size.next_power_of_two().max(MIN_BLOCK);
+            let free = self.buckets.entry(bsize).or_default();
+            let off = match free.pop() {
+                Some(off) => off,
+                None => {
+                    let page = self.alloc_pages(PAGE_SIZE)?;
+                    let free = self.buckets.entry(bsize).or_default();
+                    free.extend((bsize..PAGE_SIZE).step_by(bsize).rev().map(|o| page + o));
+                    page
+                },
+            };
+            (off, bsize)
+        } else {
+            let bsize = size.next_multiple_of(PAGE_SIZE);
+            (self.alloc_pages(bsize)?, bsize)
+        };
+        self.blocks.insert(off, bsize);
+        self.used += bsize;
+        Some(off)
+    }
+    fn free(&mut self, off: usize) -> Result<(), c_int> {
+        let bsize = self.blocks.remove(&off).ok_or(-EINVAL)?;
+        self.used -= bsize;
+        if bsize <= PAGE_SIZE {
+            self.buckets.entry(bsize).or_default().push(off);
+        } else {
+            self.ranges.insert(off, bsize);
+        }
+        Ok(())
+    }
+}
+
+fn state(heap: *mut evl_heap) -> &'static Mutex<HeapState> {
+    unsafe { &*((*heap).state as *const Mutex<HeapState>) }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_init_heap(heap: *mut evl_heap, mem: *mut c_void, size: usize) -> c_int {
+    let size = size & !(PAGE_SIZE - 1);
+    if mem.is_null() || size == 0 {
+        return -EINVAL;
+    }
+    let state = HeapState {
+        base: mem as usize,
+        size,
+        brk: 0,
+        used: 0,
+        buckets: HashMap::new(),
+        ranges: BTreeMap::new(),
+        blocks: HashMap::new(),
+    };
+    (*heap).state = Box::into_raw(Box::new(Mutex::new(state))) as *mut c_void;
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_destroy_heap(heap: *mut evl_heap) {
+    let state = (*heap).state as *mut Mutex<HeapState>;
+    if !state.is_null() {
+        drop(Box::from_raw(state));
+        (*heap).state = std::ptr::null_mut();
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_alloc_block(heap: *mut evl_heap, size: usize) -> *mut c_void {
+    let mut h = state(heap).lock().unwrap_or_else(PoisonError::into_inner);
+    match h.alloc(size) {
+        Some(off) => (h.base + off) as *mut c_void,
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_free_block(heap: *mut evl_heap, block: *mut c_void) -> c_int {
+    let mut h = state(heap).lock().unwrap_or_else(PoisonError::into_inner);
+    let off = (block as usize).wrapping_sub(h.base);
+    match h.free(off) {
+        Ok(()) => 0,
+        Err(e) => e,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_heap_size(heap: *const evl_heap) -> usize {
+    state(heap as *mut evl_heap).lock().unwrap_or_else(PoisonError::into_inner).size
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_heap_used(heap: *const evl_heap) -> usize {
+    state(heap as *mut evl_heap).lock().unwrap_or_else(PoisonError::into_inner).used
+}
@@ -0,0 +1,414 @@
+// Clocks, timers, cross-buffers, proxies, polling and out-of-band
+// I/O requests.
+
+use std::collections::HashMap;
+use std::os::raw::{c_char, c_int, c_ulong, c_void};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use libc::{EBADF, EINTR, EINVAL, ETIMEDOUT};
+use crate::kernel::{self, check_name, name_of, Kernel, Kind};
+use crate::thread;
+use crate::{
+    evl_poll_event,
+    evl_value,
+    itimerspec,
+    timespec,
+    BuiltinClock,
+    CloneFlags,
+};
+
+/* Clocks */
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_read_clock(clockfd: c_int, tp: *mut timespec) -> c_int {
+    match kernel::now(clockfd) {
+        Ok(now) => {
+            *tp = kernel::ns_to_ts(now);
+            0
+        },
+        Err(e) => e,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_sleep_until(clockfd: c_int, timeout: *const timespec) -> c_int {
+    let mut k = kernel::lock();
+    if let Err(e) = thread::enter_oob(&mut k) {
+        return e;
+    }
+    let date = kernel::ts_to_ns(&*timeout);
+    match kernel::wait(k, clockfd, Some(date), |_| false).1 {
+        Err(e) if e == -ETIMEDOUT => 0,
+        Err(e) => e,
+        Ok(()) => 0,
+    }
+}
+
+/* Timers */
+
+pub(crate) struct TimerState {
+    // Absolute date of the next expiry, if armed.
+    value: Option<u64>,
+    interval: u64,
+}
+
+fn timer_state(k: &mut Kernel, efd: c_int) -> Option<&mut TimerState> {
+    match &mut k.get(efd)?.kind {
+        Kind::Timer(t) => Some(t),
+        _ => None,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn evl_new_timer(clockfd: c_int) -> c_int {
+    if let Err(e) = kernel::now(clockfd) {
+        return e;
+    }
+    let state = TimerState {
+        value: None,
+        interval: 0,
+    };
+    kernel::lock().create(clockfd, Kind::Timer(state), None).unwrap_or_else(|e| e)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_set_timer(efd: c_int, value: *const itimerspec,
+                                       ovalue: *mut itimerspec) -> c_int {
+    if !ovalue.is_null() {
+        let ret = evl_get_timer(efd, ovalue);
+        if ret != 0 {
+            return ret;
+        }
+    }
+    let value = &*value;
+    let date = kernel::ts_to_ns(&value.it_value);
+    let mut k = kernel::lock();
+    let t = match timer_state(&mut k, efd) {
+        Some(t) => t,
+        None => return -EBADF,
+    };
+    // A zero date disarms the timer.
+    t.value = (date > 0).then_some(date);
+    t.interval = kernel::ts_to_ns(&value.it_interval);
+    kernel::wake();
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_get_timer(efd: c_int, value: *mut itimerspec) -> c_int {
+    let mut k = kernel::lock();
+    let clock = match k.get(efd) {
+        Some(element) => element.clock,
+        None => return -EBADF,
+    };
+    let now = match kernel::now(clock) {
+        Ok(now) => now,
+        Err(e) => return e,
+    };
+    let t = match timer_state(&mut k, efd) {
+        Some(t) => t,
+        None => return -EBADF,
+    };
+    // An expired one-shot timer reports a minimal remaining time
+    // until it is read, like the real core does.
+    *value = itimerspec {
+        it_value: kernel::ns_to_ts(t.value.map_or(0, |date| date.saturating_sub(now).max(1))),
+        it_interval: kernel::ns_to_ts(t.interval),
+    };
+    0
+}
+
+// Wait for the next expiry of a timer, returning the number of
+// expiries since the previous read.
+fn read_timer(mut k: MutexGuard<'static, Kernel>, efd: c_int) -> Result<u64, c_int> {
+    thread::enter_oob(&mut k)?;
+    loop {
+        let clock = k.get(efd).ok_or(-EBADF)?.clock;
+        let value = timer_state(&mut k, efd).ok_or(-EBADF)?.value;
+        let now = kernel::now(clock)?;
+        if let Some(date) = value.filter(|&date| now >= date) {
+            let t = timer_state(&mut k, efd).unwrap();
+            if t.interval == 0 {
+                t.value = None;
+                return Ok(1);
+            }
+            let ticks = (now - date) / t.interval + 1;
+            t.value = Some(date + ticks * t.interval);
+            return Ok(ticks);
+        }
+        // Sleep until the expiry date, or until the timer is set
+        // again.
+        let (g, ret) = kernel::wait(k, clock, value, |k| {
+            timer_state(k, efd).is_none_or(|t| t.value != value)
+        });
+        k = g;
+        match ret {
+            Err(e) if e != -ETIMEDOUT => return Err(e),
+            _ => (),
+        }
+    }
+}
+
+/* Cross-buffers */
+
+pub(crate) struct XBufState {
+    // The out-of-band end of the socket pair, the element file
+    // descriptor is the in-band end.
+    peer: c_int,
+}
+
+impl Drop for XBufState {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.peer) };
+    }
+}
+
+fn xbuf_peer(k: &mut Kernel, fd: c_int) -> Option<c_int> {
+    match &k.get(fd)?.kind {
+        Kind::XBuf(x) => Some(x.peer),
+        _ => None,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn revl_sim_create_xbuf(i_bufsz: usize, o_bufsz: usize,
+                                       flags: c_int, name: *const c_char) -> c_int {
+    if i_bufsz == 0 && o_bufsz == 0 {
+        return -EINVAL;
+    }
+    let name = name_of(name);
+    if let Err(e) = check_name(&name, flags) {
+        return e;
+    }
+    let mut type_ = libc::SOCK_STREAM | libc::SOCK_CLOEXEC;
+    if flags as u32 & CloneFlags::NONBLOCK.bits() != 0 {
+        type_ |= libc::SOCK_NONBLOCK;
+    }
+    let mut fds = [0 as c_int; 2];
+    if unsafe { libc::socketpair(libc::AF_UNIX, type_, 0, fds.as_mut_ptr()) } != 0 {
+        return -kernel::errno();
+    }
+    let state = XBufState { peer: fds[1] };
+    let ret = kernel::lock().insert(fds[0], -1, BuiltinClock::MONOTONIC as c_int,
+                                    Kind::XBuf(state), name.as_deref().map(|n| ("xbuf", n)));
+    match ret {
+        Ok(()) => fds[0],
+        Err(e) => {
+            unsafe { libc::close(fds[0]) };
+            e
+        },
+    }
+}
+
+/* Proxies */
+
+#[no_mangle]
+pub extern "C" fn revl_sim_create_proxy(targetfd: c_int, bufsz: usize, granularity: usize,
+                                        flags: c_int, name: *const c_char) -> c_int {
+    // Output goes straight to the target, there is no buffering.
+    let _ = (bufsz, granularity);
+    if let Err(e) = check_name(&name_of(name), flags) {
+        return e;
+    }
+    match unsafe { libc::fcntl(targetfd, libc::F_DUPFD_CLOEXEC, 0) } {
+        fd if fd >= 0 => fd,
+        _ => -kernel::errno(),
+    }
+}
+
+/* Polling */
+
+// The user values attached to the polled file descriptors, indexed
+// by poll set and file descriptor.
+static POLLVALS: Mutex<Option<HashMap<(c_int, c_int), i64>>> = Mutex::new(None);
+
+fn pollvals() -> MutexGuard<'static, Option<HashMap<(c_int, c_int), i64>>> {
+    POLLVALS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// The out-of-band side of a cross-buffer is the one to watch.
+fn poll_target(fd: c_int) -> c_int {
+    xbuf_peer(&mut kernel::lock(), fd).unwrap_or(fd)
+}
+
+#[no_mangle]
+pub extern "C" fn evl_new_poll() -> c_int {
+    let efd = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+    if efd < 0 {
+        return -kernel::errno();
+    }
+    // Drop the leftovers of a previous poll set which had this
+    // descriptor.
+    if let Some(map) = pollvals().as_mut() {
+        map.retain(|&(set, _), _| set != efd);
+    }
+    efd
+}
+
+fn control_pollfd(efd: c_int, op: c_int, fd: c_int, events: u32) -> c_int {
+    let mut event = libc::epoll_event {
+        events,
+        u64: fd as u64,
+    };
+    match unsafe { libc::epoll_ctl(efd, op, poll_target(fd), &mut event) } {
+        0 => 0,
+        _ => -kernel::errno(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn evl_add_pollfd(efd: c_int, fd: c_int, events: u32, pollval: evl_value) -> c_int {
+    let ret = control_pollfd(efd, libc::EPOLL_CTL_ADD, fd, events);
+    if ret == 0 {
+        pollvals().get_or_insert_with(HashMap::new).insert((efd, fd), unsafe { pollval.lval });
+    }
+    ret
+}
+
+#[no_mangle]
+pub extern "C" fn evl_mod_pollfd(efd: c_int, fd: c_int, events: u32, pollval: evl_value) -> c_int {
+    let ret = control_pollfd(efd, libc::EPOLL_CTL_MOD, fd, events);
+    if ret == 0 {
+        pollvals().get_or_insert_with(HashMap::new).insert((efd, fd), unsafe { pollval.lval });
+    }
+    ret
+}
+
+#[no_mangle]
+pub extern "C" fn evl_del_pollfd(efd: c_int, fd: c_int) -> c_int {
+    let ret = match unsafe { libc::epoll_ctl(efd, libc::EPOLL_CTL_DEL, poll_target(fd), std::ptr::null_mut()) } {
+        0 => 0,
+        _ => -kernel::errno(),
+    };
+    if let Some(map) = pollvals().as_mut() {
+        map.remove(&(efd, fd));
+    }
+    ret
+}
+
+fn poll(efd: c_int, pollset: *mut evl_poll_event, nrset: c_int, timeout: Option<u64>) -> c_int {
+    if nrset <= 0 {
+        return -EINVAL;
+    }
+    if let Err(e) = thread::enter_oob(&mut kernel::lock()) {
+        return e;
+    }
+    let mut events = vec![libc::epoll_event { events: 0, u64: 0 }; nrset as usize];
+    let ret = loop {
+        let ms = match timeout {
+            Some(date) => {
+                let now = match kernel::now(BuiltinClock::MONOTONIC as c_int) {
+                    Ok(now) => now,
+                    Err(e) => return e,
+                };
+                if now >= date {
+                    return -ETIMEDOUT;
+                }
+                (date - now).div_ceil(1_000_000).min(c_int::MAX as u64) as c_int
+            },
+            None => -1,
+        };
+        match unsafe { libc::epoll_wait(efd, events.as_mut_ptr(), nrset, ms) } {
+            // Rounding may wake us up early, check the date again.
+            0 => continue,
+            ret if ret > 0 => break ret,
+            _ => {
+                let e = kernel::errno();
+                if e != EINTR || timeout.is_none() {
+                    return -e;
+                }
+            },
+        }
+    };
+    let map = pollvals();
+    for (n, event) in events[..ret as usize].iter().enumerate() {
+        let fd = event.u64 as c_int;
+        let mut pollval: evl_value = unsafe { std::mem::zeroed() };
+        pollval.lval = map.as_ref().and_then(|m| m.get(&(efd, fd)).copied()).unwrap_or(0);
+        unsafe {
+            *pollset.add(n) = evl_poll_event {
+                fd,
+                events: event.events,
+                pollval,
+            };
+        }
+    }
+    ret
+}
+
+#[no_mangle]
+pub extern "C" fn evl_poll(efd: c_int, pollset: *mut evl_poll_event, nrset: c_int) -> c_int {
+    poll(efd, pollset, nrset, None)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_timedpoll(efd: c_int, pollset: *mut evl_poll_event, nrset: c_int,
+                                       timeout: *const timespec) -> c_int {
+    poll(efd, pollset, nrset, Some(kernel::ts_to_ns(&*timeout)))
+}
+
+/* Out-of-band I/O */
+
+fn set_errno(e: c_int) -> isize {
+    unsafe { *libc::__errno_location() = e };
+    -1
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn oob_read(efd: c_int, buf: *mut c_void, count: usize) -> isize {
+    let mut k = kernel::lock();
+    let (timer, peer) = match k.get(efd).map(|element| &element.kind) {
+        Some(Kind::Timer(_)) => (true, None),
+        Some(Kind::XBuf(x)) => (false, Some(x.peer)),
+        Some(_) => return set_errno(EINVAL),
+        None => (false, None),
+    };
+    match peer {
+        _ if timer => {
+            if count < std::mem::size_of::<u64>() {
+                return set_errno(EINVAL);
+            }
+            match read_timer(k, efd) {
+                Ok(ticks) => {
+                    (buf as *mut u64).write_unaligned(ticks);
+                    std::mem::size_of::<u64>() as isize
+                },
+                Err(e) => set_errno(-e),
+            }
+        },
+        Some(peer) => {
+            if let Err(e) = thread::enter_oob(&mut k) {
+                return set_errno(-e);
+            }
+            drop(k);
+            libc::read(peer, buf, count)
+        },
+        None => {
+            drop(k);
+            libc::read(efd, buf, count)
+        },
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn oob_write(efd: c_int, buf: *const c_void, count: usize) -> isize {
+    let mut k = kernel::lock();
+    match xbuf_peer(&mut k, efd) {
+        Some(peer) => {
+            if let Err(e) = thread::enter_oob(&mut k) {
+                return set_errno(-e);
+            }
+            drop(k);
+            libc::write(peer, buf, count)
+        },
+        None if k.get(efd).is_some() => set_errno(EINVAL),
+        None => {
+            drop(k);
+            libc::write(efd, buf, count)
+        },
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn oob_ioctl(efd: c_int, request: c_ulong, arg: *mut c_void) -> c_int {
+    libc::ioctl(efd, request as _, arg)
+}
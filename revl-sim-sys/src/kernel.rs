@@ -0,0 +1,308 @@
+// The emulated core: element table, time base and wait queue.
+//
+// All element states live in a single table guarded by a global
+// lock, and every thread waiting for a state change sleeps on a
+// single condition variable which is broadcast on each change. This
+// is inefficient, but straightforward to get right.
+
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::mem::MaybeUninit;
+use std::os::raw::{c_char, c_int};
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use libc::{EINTR, EINVAL, ETIMEDOUT};
+use crate::io::{TimerState, XBufState};
+use crate::monitor::{EventState, MutexState};
+use crate::observable::ObservableState;
+use crate::thread::{self, ThreadState};
+use crate::vtime;
+use crate::{timespec, BuiltinClock, CloneFlags};
+
+pub(crate) enum Kind {
+    Thread(ThreadState),
+    Mutex(MutexState),
+    Sem(i32),
+    Flags(i32),
+    Event(EventState),
+    Timer(TimerState),
+    XBuf(XBufState),
+    Observable,
+}
+
+pub(crate) struct Element {
+    // Identifies the file open on the descriptor, which may have been
+    // closed and reused behind our back.
+    ino: u64,
+    // The write end of the pipe signaling readiness, if any.
+    aux: c_int,
+    ready: bool,
+    name: Option<(&'static str, String)>,
+    pub clock: c_int,
+    pub kind: Kind,
+    pub observable: Option<ObservableState>,
+}
+
+impl Element {
+    // Make the element file descriptor readable or not, so that
+    // pollers see the state of the element.
+    pub fn set_ready(&mut self, fd: c_int, ready: bool) {
+        if self.aux < 0 || ready == self.ready {
+            return;
+        }
+        let mut byte = 0u8;
+        let p = &mut byte as *mut u8 as *mut libc::c_void;
+        unsafe {
+            if ready {
+                libc::write(self.aux, p, 1);
+            } else {
+                libc::read(fd, p, 1);
+            }
+        }
+        self.ready = ready;
+    }
+}
+
+impl Drop for Element {
+    fn drop(&mut self) {
+        if self.aux >= 0 {
+            unsafe { libc::close(self.aux) };
+        }
+    }
+}
+
+pub(crate) struct Kernel {
+    elements: HashMap<c_int, Element>,
+    names: HashMap<(&'static str, String), c_int>,
+    // Additional descriptors referring to elements opened by name.
+    aliases: HashMap<c_int, c_int>,
+}
+
+static KERNEL: OnceLock<Mutex<Kernel>> = OnceLock::new();
+static WAITQ: Condvar = Condvar::new();
+// Counts the calls to wake(), so that sleepers which have not
+// checked their condition since the last one can be told apart.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) fn lock() -> MutexGuard<'static, Kernel> {
+    KERNEL.get_or_init(|| Mutex::new(Kernel {
+        elements: HashMap::new(),
+        names: HashMap::new(),
+        aliases: HashMap::new(),
+    })).lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Wake up all waiters, so that they check their condition again.
+pub(crate) fn wake() {
+    GENERATION.fetch_add(1, Ordering::AcqRel);
+    WAITQ.notify_all();
+    vtime::notify_settlers();
+}
+
+pub(crate) fn generation() -> u64 {
+    GENERATION.load(Ordering::Acquire)
+}
+
+fn fd_ino(fd: c_int) -> Option<u64> {
+    let mut st = MaybeUninit::<libc::stat>::uninit();
+    match unsafe { libc::fstat(fd, st.as_mut_ptr()) } {
+        0 => Some(unsafe { st.assume_init() }.st_ino),
+        _ => None,
+    }
+}
+
+// Create the file descriptor of a new element, along with the write
+// end of the pipe signaling its readiness.
+pub(crate) fn new_element_fd() -> Result<(c_int, c_int), c_int> {
+    let mut fds = [0 as c_int; 2];
+    match unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK) } {
+        0 => Ok((fds[0], fds[1])),
+        _ => Err(-errno()),
+    }
+}
+
+pub(crate) fn name_of(name: *const c_char) -> Option<String> {
+    // The name comes from the C shim, which passes either null or a
+    // valid string.
+    unsafe { name.as_ref() }.map(|n| unsafe { CStr::from_ptr(n) }.to_string_lossy().into_owned())
+}
+
+pub(crate) fn check_name(name: &Option<String>, flags: c_int) -> Result<(), c_int> {
+    if name.is_none() && flags as u32 & CloneFlags::PUBLIC.bits() != 0 {
+        return Err(-EINVAL);
+    }
+    Ok(())
+}
+
+pub(crate) fn errno() -> c_int {
+    std::io::Error::last_os_error().raw_os_error().unwrap_or(EINVAL)
+}
+
+impl Kernel {
+    /// Register the element open on `fd`, which takes ownership of
+    /// `aux` on success. Named elements must be unique within their
+    /// class.
+    pub fn insert(&mut self, fd: c_int, aux: c_int, clock: c_int, kind: Kind,
+                  name: Option<(&'static str, &str)>) -> Result<(), c_int> {
+        let ino = fd_ino(fd).ok_or(-libc::EBADF)?;
+        // A stale entry may linger if the descriptor was closed
+        // directly.
+        self.remove(fd);
+        if let Some((class, name)) = name {
+            if self.lookup(class, name).is_some() {
+                return Err(-libc::EEXIST);
+            }
+            self.names.insert((class, name.to_string()), fd);
+        }
+        let element = Element {
+            ino,
+            aux,
+            ready: false,
+            name: name.map(|(class, name)| (class, name.to_string())),
+            clock,
+            kind,
+            observable: None,
+        };
+        self.elements.insert(fd, element);
+        Ok(())
+    }
+    /// Create an element with a fresh file descriptor, returning the
+    /// latter.
+    pub fn create(&mut self, clock: c_int, kind: Kind,
+                  name: Option<(&'static str, &str)>) -> Result<c_int, c_int> {
+        let (fd, aux) = new_element_fd()?;
+        match self.insert(fd, aux, clock, kind, name) {
+            Ok(()) => Ok(fd),
+            Err(e) => {
+                unsafe {
+                    libc::close(fd);
+                    libc::close(aux);
+                }
+                Err(e)
+            },
+        }
+    }
+    /// Return the element open on `fd`, if any.
+    pub fn get(&mut self, fd: c_int) -> Option<&mut Element> {
+        let target = self.aliases.get(&fd).copied().unwrap_or(fd);
+        let valid = match self.elements.get(&target) {
+            Some(element) => fd_ino(fd) == Some(element.ino),
+            None => false,
+        };
+        if !valid {
+            self.remove(fd);
+            return None;
+        }
+        self.elements.get_mut(&target)
+    }
+    /// Make `alias`, a duplicate of `fd`, refer to the same element.
+    pub fn alias(&mut self, alias: c_int, fd: c_int) {
+        self.remove(alias);
+        self.aliases.insert(alias, fd);
+    }
+    /// Drop the element open on `fd`, or the alias `fd` is. The
+    /// descriptor itself is left open. Dropping an element
+    /// invalidates its aliases.
+    pub fn remove(&mut self, fd: c_int) -> Option<Element> {
+        if self.aliases.remove(&fd).is_some() {
+            return None;
+        }
+        let element = self.elements.remove(&fd)?;
+        if let Some(name) = &element.name {
+            self.names.remove(name);
+        }
+        Some(element)
+    }
+    /// Find the element named `name` in `class`.
+    pub fn lookup(&mut self, class: &'static str, name: &str) -> Option<c_int> {
+        let fd = *self.names.get(&(class, name.to_string()))?;
+        self.get(fd).map(|_| fd)
+    }
+    /// Iterate over all registered elements, which may include stale
+    /// ones.
+    pub fn elements(&mut self) -> impl Iterator<Item = &mut Element> {
+        self.elements.values_mut()
+    }
+}
+
+pub(crate) fn now(clock: c_int) -> Result<u64, c_int> {
+    let id = match clock {
+        c if c == BuiltinClock::MONOTONIC as c_int => libc::CLOCK_MONOTONIC,
+        c if c == BuiltinClock::REALTIME as c_int => libc::CLOCK_REALTIME,
+        _ => return Err(-EINVAL),
+    };
+    if let Some(now) = vtime::now(id == libc::CLOCK_REALTIME) {
+        return Ok(now);
+    }
+    let mut ts = MaybeUninit::<libc::timespec>::uninit();
+    unsafe { libc::clock_gettime(id, ts.as_mut_ptr()) };
+    let ts = unsafe { ts.assume_init() };
+    Ok(ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64)
+}
+
+pub(crate) fn ts_to_ns(ts: &timespec) -> u64 {
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+pub(crate) fn ns_to_ts(ns: u64) -> timespec {
+    timespec {
+        tv_sec: (ns / 1_000_000_000) as _,
+        tv_nsec: (ns % 1_000_000_000) as _,
+    }
+}
+
+pub(crate) fn sleep(k: MutexGuard<'static, Kernel>) -> MutexGuard<'static, Kernel> {
+    WAITQ.wait(k).unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Wait until `cond` holds, the absolute `deadline` on `clock` is
+/// reached, or the calling thread is unblocked. The kernel lock is
+/// released while asleep.
+pub(crate) fn wait<F>(mut k: MutexGuard<'static, Kernel>, clock: c_int, deadline: Option<u64>,
+                      mut cond: F) -> (MutexGuard<'static, Kernel>, Result<(), c_int>)
+where
+    F: FnMut(&mut Kernel) -> bool,
+{
+    let me = thread::current();
+    if let Some(me) = me {
+        thread::set_waiting(&mut k, me, true);
+    }
+    let ret = loop {
+        // Read the generation first, a wake up racing with the
+        // check then makes us look busy, never settled.
+        let seen = generation();
+        if cond(&mut k) {
+            break Ok(());
+        }
+        if let Some(me) = me {
+            if thread::take_unblock(&mut k, me) {
+                break Err(-EINTR);
+            }
+        }
+        k = match deadline {
+            Some(date) => {
+                let now = match now(clock) {
+                    Ok(now) => now,
+                    Err(e) => break Err(e),
+                };
+                if now >= date {
+                    break Err(-ETIMEDOUT);
+                }
+                if vtime::is_enabled() {
+                    let date = vtime::to_monotonic(clock, date);
+                    vtime::sleep(k, me, Some(date), seen)
+                } else {
+                    WAITQ.wait_timeout(k, Duration::from_nanos(date - now))
+                        .unwrap_or_else(PoisonError::into_inner).0
+                }
+            },
+            None if vtime::is_enabled() => vtime::sleep(k, me, None, seen),
+            None => sleep(k),
+        };
+    };
+    if let Some(me) = me {
+        thread::set_waiting(&mut k, me, false);
+    }
+    (k, ret)
+}
@@ -0,0 +1,314 @@
+//! Userland emulation of the evl-sys interface.
+//!
+//! This crate mirrors the part of the [evl-sys
+//! crate](https://source.denx.de/Xenomai/xenomai4/evl-sys) the revl
+//! crate relies on, implementing the EVL services on top of regular
+//! Linux primitives: std locks for the synchronization elements,
+//! pipes and sockets for the element file descriptors, and epoll for
+//! polling. The revl crate uses it in place of evl-sys with the `sim`
+//! feature, so that applications build and run on development hosts
+//! and CI machines without an EVL kernel.
+//!
+//! This is a functional emulation, which provides no real-time
+//! guarantee. The following limitations apply:
+//!
+//! * Elements live in the emulating process, so public elements cannot
+//!   be shared with other processes, and do not appear in `/dev/evl`.
+//!
+//! * Scheduling attributes are recorded but not applied, all threads
+//!   undergo the regular Linux scheduler. SCHED_TP control requests are
+//!   rejected.
+//!
+//! * Proxies write directly to their target file descriptor.
+//!
+//! * Out-of-band I/O requests on file descriptors which do not belong
+//!   to emulated elements are passed to the regular read, write and
+//!   ioctl system calls.
+//!
+//! The [`vtime`] module provides virtual time, so that tests
+//! involving timeouts and periodic activities run deterministically.
+//!
+//! Like their C counterparts, the entry points trust the pointers
+//! they are given.
+
+#![allow(non_camel_case_types)]
+#![allow(clippy::missing_safety_doc)]
+
+use std::os::raw::{c_char, c_int, c_long, c_uint, c_void};
+use bitflags::bitflags;
+
+mod kernel;
+mod heap;
+mod io;
+mod monitor;
+mod observable;
+mod thread;
+pub mod vtime;
+
+pub use crate::heap::*;
+pub use crate::io::*;
+pub use crate::monitor::*;
+pub use crate::observable::*;
+pub use crate::thread::*;
+
+pub type time_t = libc::time_t;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct timespec {
+    pub tv_sec: time_t,
+    pub tv_nsec: c_long,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct itimerspec {
+    pub it_interval: timespec,
+    pub it_value: timespec,
+}
+
+/// The builtin clocks, passed in place of a clock file descriptor.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinClock {
+    MONOTONIC = -1,
+    REALTIME = -2,
+}
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchedPolicy {
+    FIFO = 1,
+    RR = 2,
+    WEAK = 43,
+    QUOTA = 44,
+    TP = 45,
+}
+
+bitflags! {
+    pub struct CloneFlags: u32 {
+        const PRIVATE = 0;
+        const PUBLIC = 1 << 16;
+        const OBSERVABLE = 1 << 17;
+        const NONBLOCK = 1 << 18;
+        const UNICAST = 1 << 19;
+    }
+}
+
+/// EVL extension of the GPIO character device ABI, enabling
+/// out-of-band operations on a line request. GPIO devices are not
+/// emulated.
+pub const GPIOHANDLE_REQUEST_OOB: u32 = 1 << 8;
+
+bitflags! {
+    pub struct MutexType: u32 {
+        const NORMAL = 0;
+        const RECURSIVE = 1 << 0;
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union evl_value {
+    pub val: i32,
+    pub lval: i64,
+    pub ptr: *mut c_void,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct evl_poll_event {
+    pub fd: c_int,
+    pub events: u32,
+    pub pollval: evl_value,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct evl_notice {
+    pub tag: u32,
+    pub event: evl_value,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct evl_notification {
+    pub tag: u32,
+    pub serial: u32,
+    pub issuer: i32,
+    pub event: evl_value,
+    pub date: timespec,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct sched_rr_param {
+    pub __sched_rr_quantum: timespec,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct sched_quota_param {
+    pub __sched_group: c_int,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct sched_tp_param {
+    pub __sched_partition: c_int,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union sched_u {
+    pub rr: sched_rr_param,
+    pub quota: sched_quota_param,
+    pub tp: sched_tp_param,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct evl_sched_attrs {
+    pub sched_policy: c_int,
+    pub sched_priority: c_int,
+    pub sched_u: sched_u,
+}
+
+#[repr(C)]
+pub struct evl_sched_ctlparam {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+pub struct evl_sched_ctlinfo {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct evl_thread_state {
+    pub eattrs: evl_sched_attrs,
+    pub cpu: c_int,
+    pub state: c_int,
+    pub isw: u64,
+    pub csw: u64,
+    pub sc: u64,
+    pub rwa: u64,
+    pub xtime: u64,
+}
+
+#[repr(C)]
+pub struct evl_version {
+    pub abi_level: c_int,
+    pub api_level: c_int,
+    pub version_string: *const c_char,
+}
+
+// The synchronization elements keep their file descriptor in the
+// user-visible descriptor, mutexes also expose the monitor state
+// shared with the core, which has the layout of the evl-sys bindings.
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct atomic_t {
+    pub val: c_int,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct evl_monitor_state__bindgen_ty_1__bindgen_ty_1 {
+    pub owner: atomic_t,
+    pub ceiling: u32,
+    pub nesting: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct evl_monitor_state__bindgen_ty_1__bindgen_ty_2 {
+    pub value: atomic_t,
+    pub pollrefs: atomic_t,
+    pub gate_offset: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union evl_monitor_state__bindgen_ty_1 {
+    pub gate: evl_monitor_state__bindgen_ty_1__bindgen_ty_1,
+    pub event: evl_monitor_state__bindgen_ty_1__bindgen_ty_2,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct evl_monitor_state {
+    pub flags: u32,
+    pub u: evl_monitor_state__bindgen_ty_1,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct evl_mutex_active {
+    pub efd: c_int,
+    pub state: *mut evl_monitor_state,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct evl_mutex_u {
+    pub active: evl_mutex_active,
+}
+
+#[repr(C)]
+pub struct evl_mutex {
+    pub u: evl_mutex_u,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct evl_element_active {
+    pub efd: c_int,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct evl_element_u {
+    pub active: evl_element_active,
+}
+
+#[repr(C)]
+pub struct evl_sem {
+    pub u: evl_element_u,
+}
+
+#[repr(C)]
+pub struct evl_flags {
+    pub u: evl_element_u,
+}
+
+#[repr(C)]
+pub struct evl_event {
+    pub u: evl_element_u,
+}
+
+#[repr(C)]
+pub struct evl_heap {
+    pub state: *mut c_void,
+}
+
+extern "C" {
+    pub fn evl_attach_thread(flags: c_int, fmt: *const c_char, ...) -> c_int;
+    pub fn evl_create_mutex(mutex: *mut evl_mutex, clockfd: c_int, ceiling: c_uint,
+                            flags: c_int, fmt: *const c_char, ...) -> c_int;
+    pub fn evl_create_sem(sem: *mut evl_sem, clockfd: c_int, initval: c_int,
+                          flags: c_int, fmt: *const c_char, ...) -> c_int;
+    pub fn evl_open_sem(sem: *mut evl_sem, fmt: *const c_char, ...) -> c_int;
+    pub fn evl_create_flags(flags: *mut evl_flags, clockfd: c_int, initval: c_int,
+                            cflags: c_int, fmt: *const c_char, ...) -> c_int;
+    pub fn evl_open_flags(flags: *mut evl_flags, fmt: *const c_char, ...) -> c_int;
+    pub fn evl_create_event(event: *mut evl_event, clockfd: c_int, flags: c_int,
+                            fmt: *const c_char, ...) -> c_int;
+    pub fn evl_create_observable(flags: c_int, fmt: *const c_char, ...) -> c_int;
+    pub fn evl_create_proxy(targetfd: c_int, bufsz: usize, granularity: usize,
+                            flags: c_int, fmt: *const c_char, ...) -> c_int;
+    pub fn evl_create_xbuf(i_bufsz: usize, o_bufsz: usize,
+                           flags: c_int, fmt: *const c_char, ...) -> c_int;
+}
@@ -0,0 +1,688 @@
+// Mutexes, semaphores, flag groups and events.
+
+use std::collections::{HashSet, VecDeque};
+use std::cell::UnsafeCell;
+use std::os::raw::{c_char, c_int, c_uint};
+use std::ptr;
+use std::sync::MutexGuard;
+use std::sync::atomic::{AtomicU32, Ordering};
+use libc::{EAGAIN, EBUSY, EDEADLK, EIDRM, EINVAL, ENOENT, EPERM};
+use crate::kernel::{self, check_name, name_of, Kernel, Kind};
+use crate::thread;
+use crate::{
+    evl_event,
+    evl_flags,
+    evl_mutex,
+    evl_monitor_state,
+    evl_mutex_active,
+    evl_sem,
+    timespec,
+    MutexType,
+};
+
+// Element names are unique among all monitors.
+const CLASS: &str = "monitor";
+
+// Flags of the owner word, the rest is the owner handle.
+const OWNER_CLAIMED: u32 = 0x8000_0000;
+const OWNER_CEILING: u32 = 0x4000_0000;
+
+pub(crate) struct MutexState {
+    owner: c_int,
+    depth: u32,
+    recursive: bool,
+    ceiling: u32,
+    waiters: u32,
+    // Exposed to the user through the mutex descriptor.
+    shared: Box<UnsafeCell<evl_monitor_state>>,
+}
+
+impl MutexState {
+    fn publish(&self) {
+        let word = match self.owner {
+            owner if owner >= 0 => {
+                let mut word = owner as u32;
+                if self.waiters > 0 {
+                    word |= OWNER_CLAIMED;
+                }
+                if self.ceiling > 0 {
+                    word |= OWNER_CEILING;
+                }
+                word
+            },
+            _ => 0,
+        };
+        // The user reads the owner word concurrently.
+        let owner = unsafe { ptr::addr_of_mut!((*self.shared.get()).u.gate.owner.val) };
+        unsafe { &*(owner as *const AtomicU32) }.store(word, Ordering::Release);
+    }
+}
+
+pub(crate) struct EventState {
+    waiters: VecDeque<(c_int, u64)>,
+    signaled: HashSet<u64>,
+    next_ticket: u64,
+}
+
+fn create(clockfd: c_int, kind: Kind, flags: c_int, name: *const c_char) -> Result<c_int, c_int> {
+    kernel::now(clockfd)?;
+    let name = name_of(name);
+    check_name(&name, flags)?;
+    kernel::lock().create(clockfd, kind, name.as_deref().map(|n| (CLASS, n)))
+}
+
+fn open(name: *const c_char, is_kind: fn(&Kind) -> bool) -> Result<c_int, c_int> {
+    let name = name_of(name).ok_or(-EINVAL)?;
+    let mut k = kernel::lock();
+    let fd = k.lookup(CLASS, &name).ok_or(-ENOENT)?;
+    if !is_kind(&k.get(fd).unwrap().kind) {
+        return Err(-EINVAL);
+    }
+    let alias = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+    if alias < 0 {
+        return Err(-kernel::errno());
+    }
+    k.alias(alias, fd);
+    Ok(alias)
+}
+
+fn close(efd: c_int) -> c_int {
+    let mut k = kernel::lock();
+    if k.get(efd).is_none() {
+        return -EINVAL;
+    }
+    k.remove(efd);
+    kernel::wake();
+    drop(k);
+    unsafe { libc::close(efd) };
+    0
+}
+
+fn result(ret: Result<c_int, c_int>) -> c_int {
+    ret.unwrap_or_else(|e| e)
+}
+
+fn deadline(timeout: *const timespec) -> Option<u64> {
+    unsafe { timeout.as_ref() }.map(kernel::ts_to_ns)
+}
+
+/* Mutexes */
+
+fn mutex_state(k: &mut Kernel, efd: c_int) -> Option<&mut MutexState> {
+    match &mut k.get(efd)?.kind {
+        Kind::Mutex(m) => Some(m),
+        _ => None,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn revl_sim_create_mutex(mutex: *mut evl_mutex, clockfd: c_int, ceiling: c_uint,
+                                               flags: c_int, name: *const c_char) -> c_int {
+    if ceiling > 99 {
+        return -EINVAL;
+    }
+    let state = MutexState {
+        owner: -1,
+        depth: 0,
+        recursive: flags as u32 & MutexType::RECURSIVE.bits() != 0,
+        ceiling,
+        waiters: 0,
+        shared: Box::new(UnsafeCell::new(std::mem::zeroed())),
+    };
+    (*state.shared.get()).u.gate.ceiling = ceiling;
+    let shared = state.shared.get();
+    let efd = match create(clockfd, Kind::Mutex(state), flags, name) {
+        Ok(efd) => efd,
+        Err(e) => return e,
+    };
+    (*mutex).u.active = evl_mutex_active { efd, state: shared };
+    efd
+}
+
+// Wait for the mutex to be released then grab it for the calling
+// thread, restoring the recursion depth.
+fn acquire(mut k: MutexGuard<'static, Kernel>, efd: c_int, me: c_int, depth: u32,
+           timeout: Option<u64>, interruptible: bool) -> (MutexGuard<'static, Kernel>, c_int) {
+    let clock = match k.get(efd) {
+        Some(element) => element.clock,
+        None => return (k, -EIDRM),
+    };
+    if let Some(m) = mutex_state(&mut k, efd) {
+        m.waiters += 1;
+        m.publish();
+    }
+    let ret = loop {
+        let (guard, ret) = kernel::wait(k, clock, timeout, |k| {
+            mutex_state(k, efd).is_none_or(|m| m.owner < 0)
+        });
+        k = guard;
+        match ret {
+            Err(e) if e == -libc::EINTR && !interruptible => continue,
+            ret => break ret,
+        }
+    };
+    let m = match mutex_state(&mut k, efd) {
+        Some(m) => m,
+        None => return (k, -EIDRM),
+    };
+    m.waiters -= 1;
+    if ret.is_ok() {
+        m.owner = me;
+        m.depth = depth;
+    }
+    m.publish();
+    (k, ret.err().unwrap_or(0))
+}
+
+fn lock_mutex(mutex: *mut evl_mutex, timeout: Option<u64>) -> c_int {
+    let efd = unsafe { (*mutex).u.active.efd };
+    let mut k = kernel::lock();
+    let me = match thread::enter_oob(&mut k) {
+        Ok(me) => me,
+        Err(e) => return e,
+    };
+    let m = match mutex_state(&mut k, efd) {
+        Some(m) => m,
+        None => return -EINVAL,
+    };
+    if m.owner == me {
+        if !m.recursive {
+            return -EDEADLK;
+        }
+        m.depth += 1;
+        return 0;
+    }
+    acquire(k, efd, me, 1, timeout, true).1
+}
+
+#[no_mangle]
+pub extern "C" fn evl_lock_mutex(mutex: *mut evl_mutex) -> c_int {
+    lock_mutex(mutex, None)
+}
+
+#[no_mangle]
+pub extern "C" fn evl_timedlock_mutex(mutex: *mut evl_mutex, timeout: *const timespec) -> c_int {
+    lock_mutex(mutex, deadline(timeout))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_trylock_mutex(mutex: *mut evl_mutex) -> c_int {
+    let efd = (*mutex).u.active.efd;
+    let mut k = kernel::lock();
+    let me = match thread::enter_oob(&mut k) {
+        Ok(me) => me,
+        Err(e) => return e,
+    };
+    let m = match mutex_state(&mut k, efd) {
+        Some(m) => m,
+        None => return -EINVAL,
+    };
+    match m.owner {
+        owner if owner < 0 => {
+            m.owner = me;
+            m.depth = 1;
+            m.publish();
+            0
+        },
+        owner if owner == me && m.recursive => {
+            m.depth += 1;
+            0
+        },
+        _ => -EBUSY,
+    }
+}
+
+// Release the mutex, returning the recursion depth it was held at.
+fn release(k: &mut Kernel, efd: c_int, me: c_int, all: bool) -> Result<u32, c_int> {
+    let m = mutex_state(k, efd).ok_or(-EINVAL)?;
+    if m.owner != me {
+        return Err(-EPERM);
+    }
+    let depth = m.depth;
+    m.depth = if all { 0 } else { m.depth - 1 };
+    if m.depth == 0 {
+        m.owner = -1;
+        m.publish();
+        kernel::wake();
+    }
+    Ok(depth)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_unlock_mutex(mutex: *mut evl_mutex) -> c_int {
+    let efd = (*mutex).u.active.efd;
+    let me = match thread::current() {
+        Some(me) => me,
+        None => return -EPERM,
+    };
+    match release(&mut kernel::lock(), efd, me, false) {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_set_mutex_ceiling(mutex: *mut evl_mutex, ceiling: c_uint) -> c_int {
+    let efd = (*mutex).u.active.efd;
+    match mutex_state(&mut kernel::lock(), efd) {
+        Some(m) if m.ceiling > 0 && (1..=99).contains(&ceiling) => {
+            m.ceiling = ceiling;
+            0
+        },
+        Some(_) => -EINVAL,
+        None => -EINVAL,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_get_mutex_ceiling(mutex: *mut evl_mutex) -> c_int {
+    let efd = (*mutex).u.active.efd;
+    match mutex_state(&mut kernel::lock(), efd) {
+        Some(m) => m.ceiling as c_int,
+        None => -EINVAL,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_close_mutex(mutex: *mut evl_mutex) -> c_int {
+    close((*mutex).u.active.efd)
+}
+
+/* Semaphores */
+
+fn sem_count(k: &mut Kernel, efd: c_int) -> Option<&mut i32> {
+    match &mut k.get(efd)?.kind {
+        Kind::Sem(count) => Some(count),
+        _ => None,
+    }
+}
+
+fn sem_update(k: &mut Kernel, efd: c_int) {
+    if let Some(element) = k.get(efd) {
+        let ready = matches!(element.kind, Kind::Sem(count) if count > 0);
+        element.set_ready(efd, ready);
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn revl_sim_create_sem(sem: *mut evl_sem, clockfd: c_int, initval: c_int,
+                                             flags: c_int, name: *const c_char) -> c_int {
+    if initval < 0 {
+        return -EINVAL;
+    }
+    let efd = match create(clockfd, Kind::Sem(initval), flags, name) {
+        Ok(efd) => efd,
+        Err(e) => return e,
+    };
+    sem_update(&mut kernel::lock(), efd);
+    (*sem).u.active.efd = efd;
+    efd
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn revl_sim_open_sem(sem: *mut evl_sem, name: *const c_char) -> c_int {
+    match open(name, |kind| matches!(kind, Kind::Sem(_))) {
+        Ok(efd) => {
+            (*sem).u.active.efd = efd;
+            efd
+        },
+        Err(e) => e,
+    }
+}
+
+fn get_sem(sem: *mut evl_sem, timeout: Option<u64>) -> c_int {
+    let efd = unsafe { (*sem).u.active.efd };
+    let mut k = kernel::lock();
+    if let Err(e) = thread::enter_oob(&mut k) {
+        return e;
+    }
+    let clock = match k.get(efd) {
+        Some(element) => element.clock,
+        None => return -EINVAL,
+    };
+    let (mut k, ret) = kernel::wait(k, clock, timeout, |k| {
+        sem_count(k, efd).is_none_or(|count| *count > 0)
+    });
+    if let Err(e) = ret {
+        return e;
+    }
+    match sem_count(&mut k, efd) {
+        Some(count) => *count -= 1,
+        None => return -EIDRM,
+    }
+    sem_update(&mut k, efd);
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn evl_get_sem(sem: *mut evl_sem) -> c_int {
+    get_sem(sem, None)
+}
+
+#[no_mangle]
+pub extern "C" fn evl_timedget_sem(sem: *mut evl_sem, timeout: *const timespec) -> c_int {
+    get_sem(sem, deadline(timeout))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_tryget_sem(sem: *mut evl_sem) -> c_int {
+    let efd = (*sem).u.active.efd;
+    let mut k = kernel::lock();
+    let ret = match sem_count(&mut k, efd) {
+        Some(count) if *count > 0 => {
+            *count -= 1;
+            0
+        },
+        Some(_) => -EAGAIN,
+        None => -EINVAL,
+    };
+    sem_update(&mut k, efd);
+    ret
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_put_sem(sem: *mut evl_sem) -> c_int {
+    let efd = (*sem).u.active.efd;
+    let mut k = kernel::lock();
+    match sem_count(&mut k, efd) {
+        Some(count) => *count += 1,
+        None => return -EINVAL,
+    }
+    sem_update(&mut k, efd);
+    kernel::wake();
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_peek_sem(sem: *mut evl_sem, r_val: *mut c_int) -> c_int {
+    match sem_count(&mut kernel::lock(), (*sem).u.active.efd) {
+        Some(count) => {
+            *r_val = *count;
+            0
+        },
+        None => -EINVAL,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_close_sem(sem: *mut evl_sem) -> c_int {
+    close((*sem).u.active.efd)
+}
+
+/* Flag groups */
+
+fn flags_value(k: &mut Kernel, efd: c_int) -> Option<&mut i32> {
+    match &mut k.get(efd)?.kind {
+        Kind::Flags(value) => Some(value),
+        _ => None,
+    }
+}
+
+fn flags_update(k: &mut Kernel, efd: c_int) {
+    if let Some(element) = k.get(efd) {
+        let ready = matches!(element.kind, Kind::Flags(value) if value != 0);
+        element.set_ready(efd, ready);
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn revl_sim_create_flags(flags: *mut evl_flags, clockfd: c_int, initval: c_int,
+                                               cflags: c_int, name: *const c_char) -> c_int {
+    let efd = match create(clockfd, Kind::Flags(initval), cflags, name) {
+        Ok(efd) => efd,
+        Err(e) => return e,
+    };
+    flags_update(&mut kernel::lock(), efd);
+    (*flags).u.active.efd = efd;
+    efd
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn revl_sim_open_flags(flags: *mut evl_flags, name: *const c_char) -> c_int {
+    match open(name, |kind| matches!(kind, Kind::Flags(_))) {
+        Ok(efd) => {
+            (*flags).u.active.efd = efd;
+            efd
+        },
+        Err(e) => e,
+    }
+}
+
+// Wait for `ready` to hold on the flag group value, then consume the
+// bits returned by `consume`.
+fn wait_flags(flags: *mut evl_flags, timeout: Option<u64>, nonblock: bool,
+              ready: impl Fn(i32) -> bool, consume: impl Fn(i32) -> i32) -> Result<i32, c_int> {
+    let efd = unsafe { (*flags).u.active.efd };
+    let mut k = kernel::lock();
+    if !nonblock {
+        thread::enter_oob(&mut k)?;
+    }
+    let clock = k.get(efd).ok_or(-EINVAL)?.clock;
+    let mut k = if nonblock {
+        if !ready(*flags_value(&mut k, efd).ok_or(-EINVAL)?) {
+            return Err(-EAGAIN);
+        }
+        k
+    } else {
+        let (k, ret) = kernel::wait(k, clock, timeout, |k| {
+            flags_value(k, efd).is_none_or(|value| ready(*value))
+        });
+        ret?;
+        k
+    };
+    let value = flags_value(&mut k, efd).ok_or(-EIDRM)?;
+    let bits = consume(*value);
+    *value &= !bits;
+    flags_update(&mut k, efd);
+    Ok(bits)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_wait_flags(flags: *mut evl_flags, r_bits: *mut c_int) -> c_int {
+    match wait_flags(flags, None, false, |v| v != 0, |v| v) {
+        Ok(bits) => {
+            *r_bits = bits;
+            0
+        },
+        Err(e) => e,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_timedwait_flags(flags: *mut evl_flags, timeout: *const timespec,
+                                             r_bits: *mut c_int) -> c_int {
+    match wait_flags(flags, deadline(timeout), false, |v| v != 0, |v| v) {
+        Ok(bits) => {
+            *r_bits = bits;
+            0
+        },
+        Err(e) => e,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_trywait_flags(flags: *mut evl_flags, r_bits: *mut c_int) -> c_int {
+    match wait_flags(flags, None, true, |v| v != 0, |v| v) {
+        Ok(bits) => {
+            *r_bits = bits;
+            0
+        },
+        Err(e) => e,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn evl_wait_exact_flags(flags: *mut evl_flags, bits: c_int) -> c_int {
+    if bits == 0 {
+        return -EINVAL;
+    }
+    result(wait_flags(flags, None, false, |v| v & bits == bits, |_| bits).map(|_| 0))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_wait_some_flags(flags: *mut evl_flags, bits: c_int,
+                                             r_bits: *mut c_int) -> c_int {
+    if bits == 0 {
+        return -EINVAL;
+    }
+    match wait_flags(flags, None, false, |v| v & bits != 0, |v| v & bits) {
+        Ok(bits) => {
+            *r_bits = bits;
+            0
+        },
+        Err(e) => e,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_peek_flags(flags: *mut evl_flags, r_bits: *mut c_int) -> c_int {
+    match flags_value(&mut kernel::lock(), (*flags).u.active.efd) {
+        Some(value) => {
+            *r_bits = *value;
+            0
+        },
+        None => -EINVAL,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_post_flags(flags: *mut evl_flags, bits: c_int) -> c_int {
+    if bits == 0 {
+        return -EINVAL;
+    }
+    let efd = (*flags).u.active.efd;
+    let mut k = kernel::lock();
+    match flags_value(&mut k, efd) {
+        Some(value) => *value |= bits,
+        None => return -EINVAL,
+    }
+    flags_update(&mut k, efd);
+    kernel::wake();
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_close_flags(flags: *mut evl_flags) -> c_int {
+    close((*flags).u.active.efd)
+}
+
+/* Events */
+
+fn event_state(k: &mut Kernel, efd: c_int) -> Option<&mut EventState> {
+    match &mut k.get(efd)?.kind {
+        Kind::Event(e) => Some(e),
+        _ => None,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn revl_sim_create_event(event: *mut evl_event, clockfd: c_int, flags: c_int,
+                                               name: *const c_char) -> c_int {
+    let state = EventState {
+        waiters: VecDeque::new(),
+        signaled: HashSet::new(),
+        next_ticket: 0,
+    };
+    let efd = match create(clockfd, Kind::Event(state), flags, name) {
+        Ok(efd) => efd,
+        Err(e) => return e,
+    };
+    (*event).u.active.efd = efd;
+    efd
+}
+
+fn wait_event(event: *mut evl_event, mutex: *mut evl_mutex, timeout: Option<u64>) -> c_int {
+    let efd = unsafe { (*event).u.active.efd };
+    let mfd = unsafe { (*mutex).u.active.efd };
+    let mut k = kernel::lock();
+    let me = match thread::enter_oob(&mut k) {
+        Ok(me) => me,
+        Err(e) => return e,
+    };
+    let clock = match k.get(efd) {
+        Some(element) => element.clock,
+        None => return -EINVAL,
+    };
+    let ticket = match event_state(&mut k, efd) {
+        Some(e) => {
+            let ticket = e.next_ticket;
+            e.next_ticket += 1;
+            e.waiters.push_back((me, ticket));
+            ticket
+        },
+        None => return -EINVAL,
+    };
+    // Releasing the mutex and sleeping on the event is atomic, since
+    // the kernel lock is held until the wait begins.
+    let depth = match release(&mut k, mfd, me, true) {
+        Ok(depth) => depth,
+        Err(e) => {
+            if let Some(e) = event_state(&mut k, efd) {
+                e.waiters.retain(|&(_, t)| t != ticket);
+            }
+            return e;
+        },
+    };
+    let (mut k, ret) = kernel::wait(k, clock, timeout, |k| {
+        event_state(k, efd).is_none_or(|e| e.signaled.contains(&ticket))
+    });
+    if let Some(e) = event_state(&mut k, efd) {
+        e.signaled.remove(&ticket);
+        e.waiters.retain(|&(_, t)| t != ticket);
+    }
+    // The mutex is always reacquired, even on timeout or interrupt.
+    let (_k, locked) = acquire(k, mfd, me, depth, None, false);
+    match ret {
+        Err(e) => e,
+        Ok(()) => locked,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn evl_wait_event(event: *mut evl_event, mutex: *mut evl_mutex) -> c_int {
+    wait_event(event, mutex, None)
+}
+
+#[no_mangle]
+pub extern "C" fn evl_timedwait_event(event: *mut evl_event, mutex: *mut evl_mutex,
+                                      timeout: *const timespec) -> c_int {
+    wait_event(event, mutex, deadline(timeout))
+}
+
+fn signal_event(event: *mut evl_event, mut pick: impl FnMut(&mut EventState) -> Vec<u64>) -> c_int {
+    let efd = unsafe { (*event).u.active.efd };
+    let mut k = kernel::lock();
+    match event_state(&mut k, efd) {
+        Some(e) => {
+            for ticket in pick(e) {
+                e.waiters.retain(|&(_, t)| t != ticket);
+                e.signaled.insert(ticket);
+            }
+        },
+        None => return -EINVAL,
+    }
+    kernel::wake();
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn evl_signal_event(event: *mut evl_event) -> c_int {
+    signal_event(event, |e| e.waiters.front().map(|&(_, t)| t).into_iter().collect())
+}
+
+#[no_mangle]
+pub extern "C" fn evl_broadcast_event(event: *mut evl_event) -> c_int {
+    signal_event(event, |e| e.waiters.iter().map(|&(_, t)| t).collect())
+}
+
+#[no_mangle]
+pub extern "C" fn evl_signal_thread(event: *mut evl_event, thrfd: c_int) -> c_int {
+    signal_event(event, |e| {
+        e.waiters.iter().filter(|&&(thread, _)| thread == thrfd).map(|&(_, t)| t).collect()
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_close_event(event: *mut evl_event) -> c_int {
+    close((*event).u.active.efd)
+}
@@ -0,0 +1,241 @@
+// Observables, either standalone or attached to threads.
+
+use std::collections::VecDeque;
+use std::os::raw::{c_char, c_int, c_uint};
+use libc::{EINVAL, ENOENT, EPERM, EXDEV};
+use crate::kernel::{self, check_name, name_of, Kernel, Kind};
+use crate::thread;
+use crate::{
+    evl_notice,
+    evl_notification,
+    evl_value,
+    BuiltinClock,
+    CloneFlags,
+};
+
+// Tags below this value are reserved to the core.
+const NOTICE_USER: u32 = 64;
+
+// Subscription modes.
+const NOTIFY_ONCHANGE: c_int = 1;
+
+#[derive(Clone, Copy)]
+struct Notification {
+    tag: u32,
+    serial: u32,
+    issuer: i32,
+    value: i64,
+    date: u64,
+}
+
+struct Subscriber {
+    thread: c_int,
+    backlog: usize,
+    merge: bool,
+    // The last notice received, for merging.
+    last: Option<(u32, i64)>,
+    pending: VecDeque<Notification>,
+}
+
+pub(crate) struct ObservableState {
+    unicast: bool,
+    serial: u32,
+    subscribers: Vec<Subscriber>,
+    // The next subscriber to receive a unicast notice.
+    next: usize,
+}
+
+impl ObservableState {
+    pub fn new(unicast: bool) -> Self {
+        Self {
+            unicast,
+            serial: 0,
+            subscribers: Vec::new(),
+            next: 0,
+        }
+    }
+    fn post(&mut self, notice: Notification) {
+        let mut targets: Vec<usize> = (0..self.subscribers.len()).collect();
+        if self.unicast && !targets.is_empty() {
+            let n = self.next % targets.len();
+            self.next = n + 1;
+            targets = vec![n];
+        }
+        for n in targets {
+            let sub = &mut self.subscribers[n];
+            let key = (notice.tag, notice.value);
+            if sub.merge && sub.last == Some(key) {
+                continue;
+            }
+            // Notices are lost for subscribers which lag behind.
+            if sub.pending.len() < sub.backlog {
+                sub.pending.push_back(notice);
+                sub.last = Some(key);
+            }
+        }
+    }
+    fn has_pending(&self) -> bool {
+        self.subscribers.iter().any(|s| !s.pending.is_empty())
+    }
+}
+
+fn observable_state(k: &mut Kernel, fd: c_int) -> Option<&mut ObservableState> {
+    k.get(fd)?.observable.as_mut()
+}
+
+// Make the observable readable while notifications are pending.
+fn update_readiness(k: &mut Kernel, fd: c_int) {
+    if let Some(element) = k.get(fd) {
+        let ready = element.observable.as_ref().is_some_and(ObservableState::has_pending);
+        element.set_ready(fd, ready);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn revl_sim_create_observable(flags: c_int, name: *const c_char) -> c_int {
+    let name = name_of(name);
+    if let Err(e) = check_name(&name, flags) {
+        return e;
+    }
+    let unicast = flags as u32 & CloneFlags::UNICAST.bits() != 0;
+    let mut k = kernel::lock();
+    let fd = match k.create(BuiltinClock::MONOTONIC as c_int, Kind::Observable,
+                            name.as_deref().map(|n| ("observable", n))) {
+        Ok(fd) => fd,
+        Err(e) => return e,
+    };
+    k.get(fd).unwrap().observable = Some(ObservableState::new(unicast));
+    fd
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_update_observable(fd: c_int, ntc: *const evl_notice, nr: c_int) -> c_int {
+    if nr < 0 {
+        return -EINVAL;
+    }
+    let notices = std::slice::from_raw_parts(ntc, nr as usize);
+    if notices.iter().any(|n| n.tag < NOTICE_USER) {
+        return -EINVAL;
+    }
+    let date = match kernel::now(BuiltinClock::MONOTONIC as c_int) {
+        Ok(date) => date,
+        Err(e) => return e,
+    };
+    let issuer = libc::gettid();
+    let mut k = kernel::lock();
+    let state = match observable_state(&mut k, fd) {
+        Some(state) => state,
+        None => return -EINVAL,
+    };
+    for notice in notices {
+        state.serial = state.serial.wrapping_add(1);
+        state.post(Notification {
+            tag: notice.tag,
+            serial: state.serial,
+            issuer,
+            value: notice.event.lval,
+            date,
+        });
+    }
+    update_readiness(&mut k, fd);
+    kernel::wake();
+    nr
+}
+
+#[no_mangle]
+pub extern "C" fn evl_subscribe(fd: c_int, backlog: c_uint, mode: c_int) -> c_int {
+    if backlog == 0 {
+        return -EINVAL;
+    }
+    let me = match thread::current() {
+        Some(me) => me,
+        None => return -EPERM,
+    };
+    let mut k = kernel::lock();
+    let state = match observable_state(&mut k, fd) {
+        Some(state) => state,
+        None => return -EINVAL,
+    };
+    // Subscribing again updates the settings, dropping the
+    // pending notifications.
+    state.subscribers.retain(|s| s.thread != me);
+    state.subscribers.push(Subscriber {
+        thread: me,
+        backlog: backlog as usize,
+        merge: mode == NOTIFY_ONCHANGE,
+        last: None,
+        pending: VecDeque::new(),
+    });
+    update_readiness(&mut k, fd);
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn evl_unsubscribe(fd: c_int) -> c_int {
+    let me = match thread::current() {
+        Some(me) => me,
+        None => return -EPERM,
+    };
+    let mut k = kernel::lock();
+    let state = match observable_state(&mut k, fd) {
+        Some(state) => state,
+        None => return -EINVAL,
+    };
+    let count = state.subscribers.len();
+    state.subscribers.retain(|s| s.thread != me);
+    if state.subscribers.len() == count {
+        return -ENOENT;
+    }
+    update_readiness(&mut k, fd);
+    kernel::wake();
+    0
+}
+
+fn subscriber(k: &mut Kernel, fd: c_int, me: c_int) -> Result<&mut Subscriber, c_int> {
+    let state = observable_state(k, fd).ok_or(-EINVAL)?;
+    state.subscribers.iter_mut().find(|s| s.thread == me).ok_or(-EXDEV)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_read_observable(fd: c_int, nf: *mut evl_notification, nr: c_int) -> c_int {
+    if nr <= 0 {
+        return -EINVAL;
+    }
+    let mut k = kernel::lock();
+    let me = match thread::enter_oob(&mut k) {
+        Ok(me) => me,
+        Err(e) => return e,
+    };
+    if let Err(e) = subscriber(&mut k, fd, me) {
+        return e;
+    }
+    let (mut k, ret) = kernel::wait(k, BuiltinClock::MONOTONIC as c_int, None, |k| {
+        !subscriber(k, fd, me).is_ok_and(|s| s.pending.is_empty())
+    });
+    if let Err(e) = ret {
+        return e;
+    }
+    let sub = match subscriber(&mut k, fd, me) {
+        Ok(sub) => sub,
+        Err(e) => return e,
+    };
+    let mut count = 0;
+    while count < nr as usize {
+        let n = match sub.pending.pop_front() {
+            Some(n) => n,
+            None => break,
+        };
+        let mut event: evl_value = std::mem::zeroed();
+        event.lval = n.value;
+        *nf.add(count) = evl_notification {
+            tag: n.tag,
+            serial: n.serial,
+            issuer: n.issuer,
+            event,
+            date: kernel::ns_to_ts(n.date),
+        };
+        count += 1;
+    }
+    update_readiness(&mut k, fd);
+    count as c_int
+}
@@ -0,0 +1,122 @@
+/*
+ * Variadic entry points of the emulated interface. The element name
+ * is formatted here, then the Rust implementation is called.
+ */
+
+#include <stdarg.h>
+#include <stddef.h>
+#include <stdio.h>
+
+#define NAME_MAX_LEN 256
+
+int revl_sim_attach_thread(int flags, const char *name);
+int revl_sim_create_mutex(void *mutex, int clockfd, unsigned int ceiling,
+			  int flags, const char *name);
+int revl_sim_create_sem(void *sem, int clockfd, int initval,
+			int flags, const char *name);
+int revl_sim_open_sem(void *sem, const char *name);
+int revl_sim_create_flags(void *flags, int clockfd, int initval,
+			  int cflags, const char *name);
+int revl_sim_open_flags(void *flags, const char *name);
+int revl_sim_create_event(void *event, int clockfd, int flags,
+			  const char *name);
+int revl_sim_create_observable(int flags, const char *name);
+int revl_sim_create_proxy(int targetfd, size_t bufsz, size_t granularity,
+			  int flags, const char *name);
+int revl_sim_create_xbuf(size_t i_bufsz, size_t o_bufsz,
+			 int flags, const char *name);
+
+#define format_name(buf, fmt)					\
+	({							\
+		const char *__name = NULL;			\
+		va_list __ap;					\
+		if (fmt) {					\
+			va_start(__ap, fmt);			\
+			vsnprintf(buf, sizeof(buf), fmt, __ap);	\
+			va_end(__ap);				\
+			__name = buf;				\
+		}						\
+		__name;						\
+	})
+
+int evl_attach_thread(int flags, const char *fmt, ...)
+{
+	char buf[NAME_MAX_LEN];
+
+	return revl_sim_attach_thread(flags, format_name(buf, fmt));
+}
+
+int evl_create_mutex(void *mutex, int clockfd, unsigned int ceiling,
+		     int flags, const char *fmt, ...)
+{
+	char buf[NAME_MAX_LEN];
+
+	return revl_sim_create_mutex(mutex, clockfd, ceiling, flags,
+				     format_name(buf, fmt));
+}
+
+int evl_create_sem(void *sem, int clockfd, int initval,
+		   int flags, const char *fmt, ...)
+{
+	char buf[NAME_MAX_LEN];
+
+	return revl_sim_create_sem(sem, clockfd, initval, flags,
+				   format_name(buf, fmt));
+}
+
+int evl_open_sem(void *sem, const char *fmt, ...)
+{
+	char buf[NAME_MAX_LEN];
+
+	return revl_sim_open_sem(sem, format_name(buf, fmt));
+}
+
+int evl_create_flags(void *flags, int clockfd, int initval,
+		     int cflags, const char *fmt, ...)
+{
+	char buf[NAME_MAX_LEN];
+
+	return revl_sim_create_flags(flags, clockfd, initval, cflags,
+				     format_name(buf, fmt));
+}
+
+int evl_open_flags(void *flags, const char *fmt, ...)
+{
+	char buf[NAME_MAX_LEN];
+
+	return revl_sim_open_flags(flags, format_name(buf, fmt));
+}
+
+int evl_create_event(void *event, int clockfd, int flags,
+		     const char *fmt, ...)
+{
+	char buf[NAME_MAX_LEN];
+
+	return revl_sim_create_event(event, clockfd, flags,
+				     format_name(buf, fmt));
+}
+
+int evl_create_observable(int flags, const char *fmt, ...)
+{
+	char buf[NAME_MAX_LEN];
+
+	return revl_sim_create_observable(flags, format_name(buf, fmt));
+}
+
+int evl_create_proxy(int targetfd, size_t bufsz, size_t granularity,
+		     int flags, const char *fmt, ...)
+{
+	char buf[NAME_MAX_LEN];
+
+	return revl_sim_create_proxy(targetfd, bufsz, granularity, flags,
+				     format_name(buf, fmt));
+}
+
+int evl_create_xbuf(size_t i_bufsz, size_t o_bufsz,
+		    int flags, const char *fmt, ...)
+{
+	char buf[NAME_MAX_LEN];
+
+	return revl_sim_create_xbuf(i_bufsz, o_bufsz, flags,
+				    format_name(buf, fmt));
+}
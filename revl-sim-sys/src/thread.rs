@@ -0,0 +1,377 @@
+// Thread attachment, scheduling attributes and core information.
+
+use std::cell::Cell;
+use std::mem::{self, MaybeUninit};
+use std::os::raw::{c_char, c_int};
+use libc::{EBUSY, EINVAL, EOPNOTSUPP, EPERM, ESRCH};
+use crate::kernel::{self, Kernel, Kind};
+use crate::observable::ObservableState;
+use crate::{
+    evl_sched_attrs,
+    evl_sched_ctlinfo,
+    evl_sched_ctlparam,
+    evl_thread_state,
+    evl_version,
+    BuiltinClock,
+    CloneFlags,
+    SchedPolicy,
+};
+
+pub(crate) struct ThreadState {
+    pthread: libc::pthread_t,
+    attrs: evl_sched_attrs,
+    mode: c_int,
+    inband: bool,
+    waiting: bool,
+    unblocked: bool,
+    // The monotonic date the thread sleeps until with virtual time,
+    // and the wake generation it last checked its wait condition at.
+    deadline: Option<u64>,
+    seen: u64,
+    isw: u64,
+    csw: u64,
+    sc: u64,
+}
+
+// Raw scheduling attributes are plain data.
+unsafe impl Send for ThreadState {}
+
+impl ThreadState {
+    /// Tell whether the thread is asleep with nothing to do until
+    /// virtual time reaches its deadline, if any.
+    pub fn is_settled(&self, generation: u64, now: u64) -> bool {
+        self.waiting && self.seen == generation && self.deadline.is_none_or(|date| date > now)
+    }
+    pub fn deadline(&self) -> Option<u64> {
+        self.deadline
+    }
+}
+
+// The attachment of the calling thread, which is dropped if the
+// thread exits without detaching.
+struct Attachment(Cell<c_int>);
+
+impl Drop for Attachment {
+    fn drop(&mut self) {
+        let efd = self.0.get();
+        if efd >= 0 {
+            detach(efd);
+        }
+    }
+}
+
+thread_local! {
+    static SELF: Attachment = const { Attachment(Cell::new(-1)) };
+}
+
+/// Return the element file descriptor of the calling thread, if
+/// attached.
+pub(crate) fn current() -> Option<c_int> {
+    let fd = SELF.try_with(|s| s.0.get()).unwrap_or(-1);
+    (fd >= 0).then_some(fd)
+}
+
+fn detach(efd: c_int) {
+    kernel::lock().remove(efd);
+    kernel::wake();
+    unsafe { libc::close(efd) };
+}
+
+fn state(k: &mut Kernel, efd: c_int) -> Option<&mut ThreadState> {
+    match &mut k.get(efd)?.kind {
+        Kind::Thread(t) => Some(t),
+        _ => None,
+    }
+}
+
+/// Account for an out-of-band service call by the calling thread,
+/// which must be attached. Returns its element file descriptor.
+pub(crate) fn enter_oob(k: &mut Kernel) -> Result<c_int, c_int> {
+    let me = current().ok_or(-EPERM)?;
+    let t = state(k, me).ok_or(-EPERM)?;
+    t.inband = false;
+    t.sc += 1;
+    Ok(me)
+}
+
+pub(crate) fn set_waiting(k: &mut Kernel, efd: c_int, waiting: bool) {
+    if let Some(t) = state(k, efd) {
+        t.waiting = waiting;
+        if waiting {
+            t.csw += 1;
+        } else {
+            t.deadline = None;
+        }
+    }
+}
+
+/// Record the virtual time deadline of the sleeping thread, and the
+/// wake generation its wait condition was checked at.
+pub(crate) fn set_sleep(k: &mut Kernel, efd: c_int, deadline: Option<u64>, seen: u64) {
+    if let Some(t) = state(k, efd) {
+        t.deadline = deadline;
+        t.seen = seen;
+    }
+}
+
+/// Consume a pending unblock request for the thread.
+pub(crate) fn take_unblock(k: &mut Kernel, efd: c_int) -> bool {
+    match state(k, efd) {
+        Some(t) => mem::replace(&mut t.unblocked, false),
+        None => false,
+    }
+}
+
+fn default_attrs() -> evl_sched_attrs {
+    let mut attrs: evl_sched_attrs = unsafe { MaybeUninit::zeroed().assume_init() };
+    let mut policy: c_int = 0;
+    let mut param = MaybeUninit::<libc::sched_param>::zeroed();
+    unsafe { libc::pthread_getschedparam(libc::pthread_self(), &mut policy, param.as_mut_ptr()) };
+    // In-band threads undergoing a non real-time policy are mapped to
+    // SCHED_WEAK on attachment.
+    match policy {
+        libc::SCHED_FIFO | libc::SCHED_RR => {
+            attrs.sched_policy = policy;
+            attrs.sched_priority = unsafe { param.assume_init() }.sched_priority;
+        },
+        _ => attrs.sched_policy = SchedPolicy::WEAK as c_int,
+    }
+    attrs
+}
+
+#[no_mangle]
+pub extern "C" fn revl_sim_attach_thread(flags: c_int, name: *const c_char) -> c_int {
+    if current().is_some() {
+        return -EBUSY;
+    }
+    let flags = CloneFlags::from_bits_truncate(flags as u32);
+    let name = kernel::name_of(name);
+    if name.is_none() && flags.contains(CloneFlags::PUBLIC) {
+        return -EINVAL;
+    }
+    let state = ThreadState {
+        pthread: unsafe { libc::pthread_self() },
+        attrs: default_attrs(),
+        mode: 0,
+        inband: true,
+        waiting: false,
+        unblocked: false,
+        deadline: None,
+        seen: 0,
+        isw: 0,
+        csw: 0,
+        sc: 0,
+    };
+    let mut k = kernel::lock();
+    let efd = match k.create(BuiltinClock::MONOTONIC as c_int, Kind::Thread(state),
+                             name.as_deref().map(|n| ("thread", n))) {
+        Ok(efd) => efd,
+        Err(e) => return e,
+    };
+    if flags.contains(CloneFlags::OBSERVABLE) {
+        k.get(efd).unwrap().observable =
+            Some(ObservableState::new(flags.contains(CloneFlags::UNICAST)));
+    }
+    SELF.with(|s| s.0.set(efd));
+    efd
+}
+
+#[no_mangle]
+pub extern "C" fn evl_detach_self() -> c_int {
+    let me = match current() {
+        Some(me) => me,
+        None => return -EPERM,
+    };
+    detach(me);
+    SELF.with(|s| s.0.set(-1));
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn evl_get_self() -> c_int {
+    current().unwrap_or(-EPERM)
+}
+
+#[no_mangle]
+pub extern "C" fn evl_is_inband() -> bool {
+    match current() {
+        Some(me) => state(&mut kernel::lock(), me).is_none_or(|t| t.inband),
+        None => true,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn evl_switch_oob() -> c_int {
+    match enter_oob(&mut kernel::lock()) {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn evl_switch_inband() -> c_int {
+    let me = match current() {
+        Some(me) => me,
+        None => return -EPERM,
+    };
+    if let Some(t) = state(&mut kernel::lock(), me) {
+        if !t.inband {
+            t.inband = true;
+            t.isw += 1;
+        }
+    }
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn evl_yield() -> c_int {
+    std::thread::yield_now();
+    0
+}
+
+fn valid_attrs(attrs: &evl_sched_attrs) -> bool {
+    let prio = attrs.sched_priority;
+    match attrs.sched_policy {
+        p if p == SchedPolicy::FIFO as c_int || p == SchedPolicy::RR as c_int => (1..=99).contains(&prio),
+        p if p == SchedPolicy::WEAK as c_int => (0..=99).contains(&prio),
+        p if p == SchedPolicy::QUOTA as c_int || p == SchedPolicy::TP as c_int => (1..=99).contains(&prio),
+        _ => false,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_set_schedattr(efd: c_int, attrs: *const evl_sched_attrs) -> c_int {
+    let attrs = &*attrs;
+    if !valid_attrs(attrs) {
+        return -EINVAL;
+    }
+    match state(&mut kernel::lock(), efd) {
+        Some(t) => {
+            t.attrs = *attrs;
+            0
+        },
+        None => -ESRCH,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_get_schedattr(efd: c_int, attrs: *mut evl_sched_attrs) -> c_int {
+    match state(&mut kernel::lock(), efd) {
+        Some(t) => {
+            *attrs = t.attrs;
+            0
+        },
+        None => -ESRCH,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_get_state(efd: c_int, statebuf: *mut evl_thread_state) -> c_int {
+    let mut k = kernel::lock();
+    let t = match state(&mut k, efd) {
+        Some(t) => t,
+        None => return -ESRCH,
+    };
+    let mut clockid: libc::clockid_t = 0;
+    let mut xtime = 0;
+    if libc::pthread_getcpuclockid(t.pthread, &mut clockid) == 0 {
+        let mut ts = MaybeUninit::<libc::timespec>::uninit();
+        if libc::clock_gettime(clockid, ts.as_mut_ptr()) == 0 {
+            let ts = ts.assume_init();
+            xtime = ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64;
+        }
+    }
+    *statebuf = evl_thread_state {
+        eattrs: t.attrs,
+        cpu: if Some(efd) == current() { libc::sched_getcpu() } else { -1 },
+        state: 0,
+        isw: t.isw,
+        csw: t.csw,
+        sc: t.sc,
+        rwa: 0,
+        xtime,
+    };
+    0
+}
+
+fn change_mode(efd: c_int, set: c_int, clear: c_int, oldmask: *mut c_int) -> c_int {
+    match state(&mut kernel::lock(), efd) {
+        Some(t) => {
+            if !oldmask.is_null() {
+                unsafe { *oldmask = t.mode };
+            }
+            t.mode = (t.mode | set) & !clear;
+            0
+        },
+        None => -ESRCH,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn evl_set_thread_mode(efd: c_int, mask: c_int, oldmask: *mut c_int) -> c_int {
+    change_mode(efd, mask, 0, oldmask)
+}
+
+#[no_mangle]
+pub extern "C" fn evl_clear_thread_mode(efd: c_int, mask: c_int, oldmask: *mut c_int) -> c_int {
+    change_mode(efd, 0, mask, oldmask)
+}
+
+#[no_mangle]
+pub extern "C" fn evl_unblock_thread(efd: c_int) -> c_int {
+    match state(&mut kernel::lock(), efd) {
+        Some(t) => {
+            // Only a sleeping thread is affected.
+            if t.waiting {
+                t.unblocked = true;
+                kernel::wake();
+            }
+            0
+        },
+        None => -ESRCH,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn evl_demote_thread(efd: c_int) -> c_int {
+    match state(&mut kernel::lock(), efd) {
+        Some(t) => {
+            if !t.inband {
+                t.inband = true;
+                t.isw += 1;
+            }
+            0
+        },
+        None => -ESRCH,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn evl_init() -> c_int {
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn evl_get_version() -> evl_version {
+    evl_version {
+        abi_level: 0,
+        api_level: 0,
+        version_string: c"sim".as_ptr(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evl_get_cpustate(cpu: c_int, state: *mut c_int) -> c_int {
+    if cpu < 0 || cpu as libc::c_long >= libc::sysconf(libc::_SC_NPROCESSORS_CONF) {
+        return -EINVAL;
+    }
+    // Every CPU is usable for emulated out-of-band scheduling.
+    *state = 1;
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn evl_control_sched(_policy: c_int, _param: *const evl_sched_ctlparam,
+                                    _info: *mut evl_sched_ctlinfo, _cpu: c_int) -> c_int {
+    -EOPNOTSUPP
+}
@@ -0,0 +1,184 @@
+//! Virtual time.
+//!
+//! Once enabled, the builtin clocks stop following the host clocks,
+//! and only move when the test driver tells them to: time then runs
+//! from one pending deadline to the next, letting the attached
+//! threads settle at each step. Timed waits are thus reproducible,
+//! and take no time on the host.
+//!
+//! An attached thread has settled when it is blocked on an emulated
+//! service, and has not been woken up since it last checked its wait
+//! condition. A thread blocked outside of the emulated core, e.g.
+//! joining another thread or reading a regular file, is busy as far
+//! as virtual time is concerned, so waiting for it to settle would
+//! hang. Polling timeouts are not virtualized, and busy waits have to
+//! move the clocks explicitly with [`skip_to()`].
+
+use std::os::raw::c_int;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Condvar, MutexGuard, PoisonError};
+use libc::EBUSY;
+use crate::kernel::{self, Kernel, Kind};
+use crate::thread;
+use crate::BuiltinClock;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// The virtual monotonic date, and the offset of the realtime clock
+// from it.
+static NOW: AtomicU64 = AtomicU64::new(0);
+static REALTIME_OFFSET: AtomicU64 = AtomicU64::new(0);
+// Signaled each time a thread settles, or threads are woken up.
+static SETTLEQ: Condvar = Condvar::new();
+
+/// Switch the builtin clocks to virtual time, the monotonic clock
+/// starting at `epoch` nanoseconds while the realtime clock starts
+/// from the current date.
+///
+/// Returns `-EBUSY` if virtual time is enabled already.
+pub fn enable(epoch: u64) -> Result<(), c_int> {
+    let k = kernel::lock();
+    if ENABLED.load(Ordering::Acquire) {
+        return Err(-EBUSY);
+    }
+    let realtime = kernel::now(BuiltinClock::REALTIME as c_int)?;
+    REALTIME_OFFSET.store(realtime.wrapping_sub(epoch), Ordering::Release);
+    NOW.store(epoch, Ordering::Release);
+    ENABLED.store(true, Ordering::Release);
+    drop(k);
+    // Sleepers on the host clocks switch to virtual time.
+    kernel::wake();
+    Ok(())
+}
+
+/// Switch the builtin clocks back to the host clocks.
+pub fn disable() {
+    let k = kernel::lock();
+    ENABLED.store(false, Ordering::Release);
+    drop(k);
+    kernel::wake();
+}
+
+/// Tell whether virtual time is enabled.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+// Move the clocks to `date`, waking up the threads which wait for
+// it.
+fn set_now(date: u64) {
+    NOW.fetch_max(date, Ordering::AcqRel);
+    kernel::wake();
+}
+
+// Wait until all attached threads have settled, returning the
+// earliest deadline among them.
+fn settle_locked(mut k: MutexGuard<'static, Kernel>) -> (MutexGuard<'static, Kernel>, Option<u64>) {
+    loop {
+        if !is_enabled() {
+            return (k, None);
+        }
+        if let (true, next) = all_settled(&mut k) {
+            return (k, next);
+        }
+        k = SETTLEQ.wait(k).unwrap_or_else(PoisonError::into_inner);
+    }
+}
+
+/// Wait until all attached threads have settled.
+pub fn settle() {
+    let _ = settle_locked(kernel::lock());
+}
+
+/// Move the clocks forward by `delta` nanoseconds, stopping at each
+/// deadline on the way until all threads have settled.
+pub fn advance(delta: u64) {
+    let target = NOW.load(Ordering::Acquire).saturating_add(delta);
+    let mut k = kernel::lock();
+    loop {
+        let (g, next) = settle_locked(k);
+        k = g;
+        match next {
+            Some(date) if date <= target => set_now(date),
+            _ => break,
+        }
+    }
+    set_now(target);
+    let _ = settle_locked(k);
+}
+
+/// Move the clocks from one deadline to the next, until all threads
+/// have settled waiting for conditions no date can satisfy. This
+/// never returns if some thread keeps on sleeping periodically.
+pub fn run() {
+    let mut k = kernel::lock();
+    loop {
+        let (g, next) = settle_locked(k);
+        k = g;
+        match next {
+            Some(date) => set_now(date),
+            None => break,
+        }
+    }
+}
+
+/// Move the clocks at once until `clock` reads `date`, unless it is
+/// past already, as if the caller had been busy until then.
+pub fn skip_to(clock: c_int, date: u64) {
+    let k = kernel::lock();
+    set_now(to_monotonic(clock, date));
+    drop(k);
+}
+
+pub(crate) fn notify_settlers() {
+    SETTLEQ.notify_all();
+}
+
+pub(crate) fn now(realtime: bool) -> Option<u64> {
+    if !is_enabled() {
+        return None;
+    }
+    let now = NOW.load(Ordering::Acquire);
+    match realtime {
+        true => Some(now.wrapping_add(REALTIME_OFFSET.load(Ordering::Acquire))),
+        false => Some(now),
+    }
+}
+
+pub(crate) fn to_monotonic(clock: c_int, date: u64) -> u64 {
+    match clock {
+        c if c == BuiltinClock::REALTIME as c_int =>
+            date.wrapping_sub(REALTIME_OFFSET.load(Ordering::Acquire)),
+        _ => date,
+    }
+}
+
+// Tell whether all attached threads have settled, returning the earliest
+// deadline among them.
+fn all_settled(k: &mut Kernel) -> (bool, Option<u64>) {
+    let generation = kernel::generation();
+    let now = NOW.load(Ordering::Acquire);
+    let mut next: Option<u64> = None;
+    for element in k.elements() {
+        if let Kind::Thread(t) = &element.kind {
+            if !t.is_settled(generation, now) {
+                return (false, None);
+            }
+            if let Some(date) = t.deadline() {
+                next = Some(next.map_or(date, |next| next.min(date)));
+            }
+        }
+    }
+    (true, next)
+}
+
+/// Put the calling thread to sleep until woken up, after recording
+/// the virtual `deadline` it waits for and the wake `generation` its
+/// condition was checked at.
+pub(crate) fn sleep(mut k: MutexGuard<'static, Kernel>, me: Option<c_int>,
+                    deadline: Option<u64>, generation: u64) -> MutexGuard<'static, Kernel> {
+    if let Some(me) = me {
+        thread::set_sleep(&mut k, me, deadline, generation);
+    }
+    SETTLEQ.notify_all();
+    kernel::sleep(k)
+}
@@ -0,0 +1,184 @@
+//! Tokio integration for the revl crate.
+//!
+//! This crate lets a tokio-based service running in-band await the
+//! data produced by out-of-band threads:
+//!
+//! * [`Async`] wraps a [cross-buffer](revl::xbuf::XBuf) or a
+//! [proxy](revl::proxy::Proxy), implementing [`AsyncRead`] and
+//! [`AsyncWrite`] over their in-band side.
+//!
+//! * [`AsyncReceiver`] wraps the in-band receiving half of an
+//! [outbound bridge](revl::bridge::outbound), providing an async
+//! [`recv()`](AsyncReceiver::recv).
+//!
+//! Readiness is tracked by the tokio reactor through the file
+//! descriptors of the EVL elements, so no thread is blocked waiting
+//! for data.
+//!
+//! ```no_run
+//! use tokio::io::AsyncReadExt;
+//! use revl::xbuf;
+//! use revl_tokio::Async;
+//!
+//! #[tokio::main]
+//! async fn main() -> std::io::Result<()> {
+//!     let xbuf = xbuf::Builder::new().name("telemetry").outbound(64 * 1024).create().unwrap();
+//!     let mut telemetry = Async::new(xbuf)?;
+//!     let mut buf = [0u8; 1024];
+//!     loop {
+//!         let n = telemetry.read(&mut buf).await?;
+//!         println!("{}", String::from_utf8_lossy(&buf[..n]));
+//!     }
+//! }
+//! ```
+
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncRead, AsyncWrite, Interest, ReadBuf};
+use revl::bridge::InbandReceiver;
+
+fn set_nonblocking(fd: RawFd) -> io::Result<()> {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let ret = unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) };
+    match ret {
+        0.. => return Ok(()),
+        _ => return Err(io::Error::last_os_error()),
+    };
+}
+
+/// An EVL element driven by the tokio reactor from the in-band
+/// stage. [`AsyncRead`] is available for elements which can be read
+/// in-band, such as [`XBuf`](revl::xbuf::XBuf), and [`AsyncWrite`]
+/// for those which can be written in-band, such as
+/// [`XBuf`](revl::xbuf::XBuf) and [`Proxy`](revl::proxy::Proxy).
+pub struct Async<E: AsRawFd> {
+    inner: AsyncFd<E>,
+}
+
+impl<E: AsRawFd> Async<E> {
+    /// Register `element` with the reactor of the current tokio
+    /// runtime, switching its file descriptor to non-blocking mode.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub fn new(element: E) -> io::Result<Self> {
+        set_nonblocking(element.as_raw_fd())?;
+        Ok(Self { inner: AsyncFd::new(element)? })
+    }
+    pub fn get_ref(&self) -> &E {
+        self.inner.get_ref()
+    }
+    /// Deregister the element from the reactor, returning it.
+    pub fn into_inner(self) -> E {
+        self.inner.into_inner()
+    }
+}
+
+impl<E: AsRawFd> AsyncRead for Async<E>
+where for<'a> &'a E: Read
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            let mut guard = ready!(self.inner.poll_read_ready(cx))?;
+            let unfilled = buf.initialize_unfilled();
+            match guard.try_io(|inner| inner.get_ref().read(unfilled)) {
+                Ok(Ok(n)) => {
+                    buf.advance(n);
+                    return Poll::Ready(Ok(()));
+                },
+                Ok(Err(e)) => return Poll::Ready(Err(e)),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+impl<E: AsRawFd> AsyncWrite for Async<E>
+where for<'a> &'a E: Write
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            let mut guard = ready!(self.inner.poll_write_ready(cx))?;
+            match guard.try_io(|inner| inner.get_ref().write(buf)) {
+                Ok(ret) => return Poll::Ready(ret),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// The in-band receiving half of an outbound bridge, awaited from a
+/// tokio task. The cross-buffer underlying the bridge is readable
+/// in-band, unlike the semaphores of the [ring
+/// channels](revl::channel), which the reactor cannot poll.
+///
+/// ```no_run
+/// use revl::bridge;
+/// use revl_tokio::AsyncReceiver;
+///
+/// # async fn serve() -> std::io::Result<()> {
+/// let (tx, rx) = bridge::outbound::<u64>(256).unwrap();
+/// // Pass tx to an out-of-band producer...
+/// let rx = AsyncReceiver::new(rx)?;
+/// loop {
+///     let sample = rx.recv().await?;
+///     println!("sample {}", sample);
+/// }
+/// # }
+/// ```
+pub struct AsyncReceiver<T> {
+    inner: AsyncFd<InbandReceiver<T>>,
+}
+
+impl<T: Copy> AsyncReceiver<T> {
+    /// Register `rx` with the reactor of the current tokio runtime,
+    /// switching the bridge to non-blocking mode. The out-of-band
+    /// sender then gets [`WouldBlock`](revl::Error::WouldBlock)
+    /// instead of waiting when the bridge is full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub fn new(rx: InbandReceiver<T>) -> io::Result<Self> {
+        set_nonblocking(rx.as_raw_fd())?;
+        Ok(Self { inner: AsyncFd::with_interest(rx, Interest::READABLE)? })
+    }
+    /// Wait for the next message.
+    pub async fn recv(&self) -> io::Result<T> {
+        loop {
+            let mut guard = self.inner.readable().await?;
+            match guard.try_io(|inner| inner.get_ref().recv().map_err(io::Error::from)) {
+                Ok(ret) => return ret,
+                Err(_would_block) => continue,
+            }
+        }
+    }
+    pub fn get_ref(&self) -> &InbandReceiver<T> {
+        self.inner.get_ref()
+    }
+    /// Deregister the receiver from the reactor, returning it.
+    pub fn into_inner(self) -> InbandReceiver<T> {
+        self.inner.into_inner()
+    }
+}
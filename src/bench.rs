@@ -0,0 +1,196 @@
+//! Platform latency benchmark.
+//!
+//! [`latency()`] measures the wakeup latency of an EVL thread, i.e.
+//! the delay between the date a thread should have resumed and the
+//! date it actually did, the same way the `latmus` utility does. This
+//! lets applications and test rigs check that the platform meets
+//! their timing requirements before starting real-time work.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use revl::bench::{self, Mode};
+//!
+//! let report = bench::Builder::new()
+//!     .mode(Mode::Timer)
+//!     .cpu(1)
+//!     .priority(98)
+//!     .period(Duration::from_micros(1000))
+//!     .duration(Duration::from_secs(30))
+//!     .run()
+//!     .unwrap();
+//! println!("min {:?} avg {:?} max {:?} over {} samples",
+//!          report.stats.min, report.stats.mean, report.stats.max, report.stats.count);
+//! for (n, count) in report.histogram.iter().enumerate().filter(|(_, c)| **c > 0) {
+//!     println!("{:>6} us: {}", n, count);
+//! }
+//! ```
+
+use std::io::ErrorKind;
+use std::time::Duration;
+use embedded_time::Instant;
+use crate::clock::STEADY_CLOCK;
+use crate::sched::SchedFifo;
+use crate::stats::{JitterStats, Snapshot};
+use crate::thread;
+use crate::timer::Timer;
+use crate::Error;
+
+/// What drives the measurement loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// The thread waits for the expiries of a periodic EVL timer,
+    /// measuring the latency of the timer interrupt and of the
+    /// thread wakeup.
+    Timer,
+    /// The thread sleeps until each release point, measuring the
+    /// latency of the wakeup from a timed sleep.
+    Thread,
+}
+
+/// The outcome of a latency benchmark.
+pub struct LatencyReport {
+    /// The statistics of the wakeup latency.
+    pub stats: Snapshot,
+    /// The counts of samples per bucket of
+    /// [`bucket_width`](Builder::bucket_width), the last bucket
+    /// collecting the samples beyond the range of the others.
+    pub histogram: Vec<u64>,
+    /// The number of release points the thread missed entirely.
+    pub overruns: u64,
+}
+
+pub struct Builder {
+    mode: Mode,
+    cpu: Option<usize>,
+    priority: i32,
+    period: Duration,
+    duration: Duration,
+    bucket_width: Duration,
+    buckets: usize,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            mode: Mode::Timer,
+            cpu: None,
+            priority: 98,
+            period: Duration::from_micros(1000),
+            duration: Duration::from_secs(10),
+            bucket_width: Duration::from_micros(1),
+            buckets: 200,
+        }
+    }
+    /// Set what drives the measurement loop, [`Mode::Timer`] by
+    /// default.
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+    /// Pin the measurement thread to `cpu`, which should be part of
+    /// the out-of-band CPU set.
+    pub fn cpu(mut self, cpu: usize) -> Self {
+        self.cpu = Some(cpu);
+        self
+    }
+    /// Set the SCHED_FIFO priority of the measurement thread, 98 by
+    /// default.
+    pub fn priority(mut self, prio: i32) -> Self {
+        self.priority = prio;
+        self
+    }
+    /// Set the sampling period, 1 ms by default.
+    pub fn period(mut self, period: Duration) -> Self {
+        self.period = period;
+        self
+    }
+    /// Set the duration of the benchmark, 10 s by default.
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+    /// Set the width of the histogram buckets, 1 µs by default.
+    pub fn bucket_width(mut self, width: Duration) -> Self {
+        self.bucket_width = width;
+        self
+    }
+    /// Set the number of histogram buckets, 200 by default.
+    pub fn buckets(mut self, count: usize) -> Self {
+        self.buckets = count;
+        self
+    }
+    pub fn run(self) -> Result<LatencyReport, Error> {
+        latency(self)
+    }
+}
+
+fn now() -> u64 {
+    STEADY_CLOCK.now().duration_since_epoch().integer()
+}
+
+/// Run a latency benchmark from a new EVL thread, retrieving the
+/// settings from a [`builder struct`](Builder). The caller waits
+/// for the benchmark to complete.
+///
+/// # Errors
+///
+/// Besides the errors of [`thread::Builder::spawn()`], this returns
+/// [`InvalidInput`][`std::io::ErrorKind`] if the period or the
+/// bucket width is zero, or any error the measurement loop received
+/// from the core.
+pub fn latency(builder: Builder) -> Result<LatencyReport, Error> {
+    if builder.period.is_zero() {
+        return Err(Error::new(ErrorKind::InvalidInput, "invalid sampling period"));
+    }
+    if builder.bucket_width.is_zero() {
+        return Err(Error::new(ErrorKind::InvalidInput, "invalid histogram bucket width"));
+    }
+    let mut t_builder = thread::Builder::new().name("latency-bench");
+    if let Some(cpu) = builder.cpu {
+        t_builder = t_builder.cpu(cpu);
+    }
+    let handle = t_builder.spawn(move |me| -> Result<LatencyReport, Error> {
+        me.set_sched(SchedFifo { prio: builder.priority })?;
+        let stats = JitterStats::with_histogram(builder.bucket_width, builder.buckets);
+        let period = builder.period.as_nanos() as u64;
+        // Leave some room for setting up before the first sample.
+        let mut expected = now() + period;
+        let end = expected + builder.duration.as_nanos() as u64;
+        let mut overruns = 0;
+        match builder.mode {
+            Mode::Timer => {
+                let timer = Timer::new(&STEADY_CLOCK)?;
+                timer.start_at(Instant::new(expected), Some(builder.period))?;
+                while expected < end {
+                    let missed = timer.wait()?;
+                    expected += missed * period;
+                    overruns += missed;
+                    stats.record_ns(now().saturating_sub(expected));
+                    expected += period;
+                }
+            },
+            Mode::Thread => {
+                while expected < end {
+                    STEADY_CLOCK.sleep_until(Instant::new(expected))?;
+                    stats.record_ns(now().saturating_sub(expected));
+                    expected += period;
+                    let late = now();
+                    if late > expected {
+                        let missed = (late - expected).div_ceil(period);
+                        expected += missed * period;
+                        overruns += missed;
+                    }
+                }
+            },
+        }
+        Ok(LatencyReport {
+            stats: stats.snapshot(),
+            histogram: stats.histogram().map(|h| h.counts()).unwrap_or_default(),
+            overruns,
+        })
+    })?;
+    match handle.join() {
+        Ok(ret) => ret,
+        Err(_) => Err(Error::new(ErrorKind::Other, "latency benchmark panicked")),
+    }
+}
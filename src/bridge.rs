@@ -0,0 +1,181 @@
+//! Cross-stage channel.
+//!
+//! The channels from the [channel](crate::channel) module require
+//! both ends to be EVL threads. A bridge connects an out-of-band
+//! thread to a regular in-band thread instead, which does not have
+//! to be attached to the core, e.g. for shipping logs or telemetry
+//! out of the real-time domain, or feeding commands into it. This is
+//! based on an EVL [cross-buffer](crate::xbuf), which the out-of-band side accesses
+//! with the out-of-band I/O services, and the in-band side with the
+//! regular read/write system calls.
+//!
+//! Messages are plain data copied byte-wise through the cross-buffer,
+//! hence the `T: Copy` bound. Each message is transferred atomically.
+//!
+//! ```no_run
+//! use revl::bridge;
+//!
+//! #[derive(Clone, Copy)]
+//! struct Sample { cycle: u64, latency_ns: u32 }
+//!
+//! let (tx, rx) = bridge::outbound::<Sample>(256).unwrap();
+//! // Regular thread, not attached to the core.
+//! std::thread::spawn(move || {
+//!     while let Ok(s) = rx.recv() {
+//!         println!("cycle {}: {} ns", s.cycle, s.latency_ns);
+//!     }
+//! });
+//! // From the out-of-band thread:
+//! tx.send(Sample { cycle: 1, latency_ns: 2400 }).unwrap();
+//! ```
+
+use std::ffi::c_void;
+use std::io::ErrorKind;
+use std::marker::PhantomData;
+use std::mem::{self, MaybeUninit};
+use std::os::fd::{AsRawFd, RawFd};
+use std::sync::Arc;
+use evl_sys::{oob_read, oob_write};
+use crate::xbuf::{self, XBuf};
+use crate::Error;
+
+fn create_xbuf(i_bufsz: usize, o_bufsz: usize) -> Result<Arc<XBuf>, Error> {
+    Ok(Arc::new(xbuf::Builder::new().inbound(i_bufsz).outbound(o_bufsz).create()?))
+}
+
+fn buffer_size<T>(capacity: usize) -> Result<usize, Error> {
+    match capacity.checked_mul(mem::size_of::<T>()) {
+        Some(size) if size > 0 => Ok(size),
+        _ => Err(Error::new(ErrorKind::InvalidInput, "invalid bridge capacity")),
+    }
+}
+
+// A transfer moves a whole message or fails. Messages are passed
+// to the system calls by address, since they may contain padding
+// bytes which cannot be viewed as a byte slice.
+fn check_transfer<T>(ret: isize) -> Result<(), Error> {
+    if ret < 0 {
+        return Err(Error::last_os_error());
+    }
+    if ret as usize != mem::size_of::<T>() {
+        return Err(Error::new(ErrorKind::InvalidData, "truncated bridge message"));
+    }
+    Ok(())
+}
+
+/// The out-of-band sending half of an [outbound](outbound) bridge.
+pub struct OobSender<T> {
+    xbuf: Arc<XBuf>,
+    _msg: PhantomData<T>,
+}
+
+impl<T: Copy> OobSender<T> {
+    /// Send a message to the in-band side, waiting for room in the
+    /// bridge if full. This must be called from the out-of-band
+    /// stage.
+    pub fn send(&self, msg: T) -> Result<(), Error> {
+        let ret = unsafe {
+            oob_write(self.xbuf.as_raw_fd(), &msg as *const T as *const c_void, mem::size_of::<T>())
+        };
+        check_transfer::<T>(ret)
+    }
+}
+
+/// The in-band receiving half of an [outbound](outbound) bridge.
+pub struct InbandReceiver<T> {
+    xbuf: Arc<XBuf>,
+    _msg: PhantomData<T>,
+}
+
+impl<T: Copy> InbandReceiver<T> {
+    /// Receive the next message from the out-of-band side, waiting
+    /// for one if none is pending. This may be called from any
+    /// thread, attached to the core or not.
+    pub fn recv(&self) -> Result<T, Error> {
+        let mut msg = MaybeUninit::<T>::uninit();
+        let ret = unsafe {
+            libc::read(self.xbuf.as_raw_fd(), msg.as_mut_ptr() as *mut c_void, mem::size_of::<T>())
+        };
+        check_transfer::<T>(ret)?;
+        Ok(unsafe { msg.assume_init() })
+    }
+}
+
+/// The in-band sending half of an [inbound](inbound) bridge.
+pub struct InbandSender<T> {
+    xbuf: Arc<XBuf>,
+    _msg: PhantomData<T>,
+}
+
+impl<T: Copy> InbandSender<T> {
+    /// Send a message to the out-of-band side, waiting for room in
+    /// the bridge if full. This may be called from any thread,
+    /// attached to the core or not.
+    pub fn send(&self, msg: T) -> Result<(), Error> {
+        let ret = unsafe {
+            libc::write(self.xbuf.as_raw_fd(), &msg as *const T as *const c_void, mem::size_of::<T>())
+        };
+        check_transfer::<T>(ret)
+    }
+}
+
+/// The out-of-band receiving half of an [inbound](inbound) bridge.
+pub struct OobReceiver<T> {
+    xbuf: Arc<XBuf>,
+    _msg: PhantomData<T>,
+}
+
+impl<T: Copy> OobReceiver<T> {
+    /// Receive the next message from the in-band side, waiting for
+    /// one if none is pending. This must be called from the
+    /// out-of-band stage.
+    pub fn recv(&self) -> Result<T, Error> {
+        let mut msg = MaybeUninit::<T>::uninit();
+        let ret = unsafe {
+            oob_read(self.xbuf.as_raw_fd(), msg.as_mut_ptr() as *mut c_void, mem::size_of::<T>())
+        };
+        check_transfer::<T>(ret)?;
+        Ok(unsafe { msg.assume_init() })
+    }
+}
+
+macro_rules! impl_as_raw_fd {
+    ($t:ident) => {
+        impl<T> AsRawFd for $t<T> {
+            fn as_raw_fd(&self) -> RawFd {
+                self.xbuf.as_raw_fd()
+            }
+        }
+    };
+}
+
+impl_as_raw_fd!(OobSender);
+impl_as_raw_fd!(InbandReceiver);
+impl_as_raw_fd!(InbandSender);
+impl_as_raw_fd!(OobReceiver);
+
+/// Create a bridge carrying up to `capacity` messages from the
+/// out-of-band stage to the in-band stage.
+///
+/// # Errors
+///
+/// [`InvalidInput`][`std::io::ErrorKind`] is returned if `capacity`
+/// is zero, or `T` is a zero-sized type. Otherwise, the EVL
+/// cross-buffer could not be created.
+pub fn outbound<T: Copy>(capacity: usize) -> Result<(OobSender<T>, InbandReceiver<T>), Error> {
+    let xbuf = create_xbuf(0, buffer_size::<T>(capacity)?)?;
+    Ok((
+        OobSender { xbuf: xbuf.clone(), _msg: PhantomData },
+        InbandReceiver { xbuf, _msg: PhantomData },
+    ))
+}
+
+/// Create a bridge carrying up to `capacity` messages from the
+/// in-band stage to the out-of-band stage. See [`outbound()`].
+pub fn inbound<T: Copy>(capacity: usize) -> Result<(InbandSender<T>, OobReceiver<T>), Error> {
+    let xbuf = create_xbuf(buffer_size::<T>(capacity)?, 0)?;
+    Ok((
+        InbandSender { xbuf: xbuf.clone(), _msg: PhantomData },
+        OobReceiver { xbuf, _msg: PhantomData },
+    ))
+}
@@ -0,0 +1,179 @@
+//! Fan-out channel delivering every message to all subscribers.
+//!
+//! A broadcast channel lets a single producer, e.g. a fieldbus
+//! receiver thread, deliver each message to every subscribed worker.
+//! Each [`Receiver`] owns a private [channel](crate::channel) ring,
+//! into which the [`Sender`] posts a clone of every message. A
+//! subscriber which does not keep up loses its oldest messages
+//! instead of stalling the producer or the other subscribers; the
+//! number of messages it missed is reported by
+//! [`Receiver::lagged()`].
+//!
+//! The channel itself allocates no memory when sending, but the
+//! message is cloned for every subscriber: sending can be done from
+//! the out-of-band stage only if cloning the message does not
+//! allocate either, which is the case for `Copy` types, but not for
+//! e.g. `Vec` or `String`. Subscribing allocates the ring of the new
+//! receiver, which should be done from the in-band stage, typically
+//! during the setup phase.
+//!
+//! ```no_run
+//! use revl::{broadcast, thread};
+//!
+//! // The subscriber list is guarded by an EVL mutex.
+//! let _me = thread::Builder::new().name("producer").attach().unwrap();
+//! let (tx, rx) = broadcast::channel::<u32>(16).unwrap();
+//! for _ in 0..3 {
+//!     let rx = tx.subscribe().unwrap();
+//!     thread::Builder::new().spawn(move |_| {
+//!         while let Ok(frame) = rx.recv_blocking() {
+//!             println!("got frame {}", frame);
+//!         }
+//!     }).unwrap();
+//! }
+//! drop(rx);
+//! for n in 0..100 {
+//!     tx.send(n).unwrap();
+//! }
+//! ```
+
+use std::sync::{
+    Arc,
+    atomic::AtomicUsize,
+    atomic::Ordering::AcqRel,
+    atomic::Ordering::Relaxed,
+};
+use std::time::Duration;
+use crate::channel::{self, RecvError, SendError};
+use crate::mutex::{self, Mutex};
+use crate::Error;
+
+/// The sending half of a broadcast channel, which can be cloned for
+/// multiple producers. Subscribers are disconnected when the last
+/// sender is dropped.
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+    capacity: usize,
+}
+
+struct Shared<T> {
+    subscribers: Mutex<Vec<channel::Sender<T>>>,
+    senders: AtomicUsize,
+}
+
+impl<T: Clone> Sender<T> {
+    /// Deliver a clone of `msg` to every subscriber, without
+    /// blocking. This allocates no memory unless cloning `msg` does. If the ring of a subscriber is full, its oldest
+    /// message is dropped to make room. Subscribers which went away
+    /// are forgotten. Return the number of subscribers the message
+    /// was delivered to.
+    ///
+    /// # Errors
+    ///
+    /// [`BrokenPipe`][`std::io::ErrorKind`] is returned if there are
+    /// no subscribers left, in which case `msg` is dropped.
+    pub fn send(&self, msg: T) -> Result<usize, Error> {
+        let mut subscribers = self.shared.subscribers.lock()?;
+        subscribers.retain(|tx| {
+            !matches!(tx.send_overwrite(msg.clone()), Err(SendError::Disconnected(_)))
+        });
+        if subscribers.is_empty() {
+            return Err(SendError::Disconnected(msg).into());
+        }
+        Ok(subscribers.len())
+    }
+}
+
+impl<T> Sender<T> {
+    /// Create a new subscriber, which receives the messages sent
+    /// from now on. This allocates the ring of the subscriber.
+    pub fn subscribe(&self) -> Result<Receiver<T>, Error> {
+        let (tx, rx) = channel::bounded(self.capacity)?;
+        self.shared.subscribers.lock()?.push(tx);
+        Ok(Receiver { rx })
+    }
+    /// Return the current number of subscribers. This is a snapshot
+    /// which may be outdated on return.
+    pub fn receiver_count(&self) -> Result<usize, Error> {
+        Ok(self.shared.subscribers.lock()?.len())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Relaxed);
+        Self {
+            shared: self.shared.clone(),
+            capacity: self.capacity,
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        // Disconnect the subscribers when the last sender goes.
+        if self.shared.senders.fetch_sub(1, AcqRel) == 1 {
+            if let Ok(mut subscribers) = self.shared.subscribers.lock() {
+                subscribers.clear();
+            }
+        }
+    }
+}
+
+/// The receiving half of a broadcast channel, obtained from
+/// [`Sender::subscribe()`].
+pub struct Receiver<T> {
+    rx: channel::Receiver<T>,
+}
+
+impl<T> Receiver<T> {
+    /// Receive the next message without blocking, see
+    /// [`channel::Receiver::recv()`].
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.rx.recv()
+    }
+    /// Receive the next message, waiting for one if none is pending,
+    /// see [`channel::Receiver::recv_blocking()`].
+    pub fn recv_blocking(&self) -> Result<T, RecvError> {
+        self.rx.recv_blocking()
+    }
+    /// Receive the next message, waiting for at most `delay` for one,
+    /// see [`channel::Receiver::recv_timed()`].
+    pub fn recv_timed(&self, delay: Duration) -> Result<T, RecvError> {
+        self.rx.recv_timed(delay)
+    }
+    /// Return the number of messages this subscriber missed because
+    /// it did not keep up with the sender.
+    pub fn lagged(&self) -> u64 {
+        self.rx.overwritten()
+    }
+    /// Return the number of messages pending for this subscriber.
+    pub fn len(&self) -> usize {
+        self.rx.len()
+    }
+    /// Return true if no message is pending for this subscriber.
+    pub fn is_empty(&self) -> bool {
+        self.rx.is_empty()
+    }
+}
+
+/// Create a broadcast channel, returning its sending half and a first
+/// subscriber. Each subscriber may hold up to `capacity` pending
+/// messages.
+///
+/// # Errors
+///
+/// [`InvalidInput`][`std::io::ErrorKind`] is returned if `capacity`
+/// is zero or exceeds `u32::MAX`, otherwise the EVL elements the
+/// channel is based on could not be created.
+pub fn channel<T>(capacity: usize) -> Result<(Sender<T>, Receiver<T>), Error> {
+    let tx = Sender {
+        shared: Arc::new(Shared {
+            subscribers: mutex::Builder::new().create(Vec::new())?,
+            senders: AtomicUsize::new(1),
+        }),
+        capacity,
+    };
+    let rx = tx.subscribe()?;
+    Ok((tx, rx))
+}
@@ -0,0 +1,1365 @@
+//! Lockless, bounded FIFO channel.
+//!
+//! A channel carries messages from any number of [`Sender`]s to any
+//! number of [`Receiver`]s, through a queue of slots allocated once
+//! at creation. The capacity is either fixed at compile time as
+//! `1 << ORDER` slots with [`create()`], or chosen at runtime with
+//! [`bounded()`]. Sending and receiving never allocate
+//! memory, so both ends can be used from out-of-band threads.
+//!
+//! [`Sender::send()`] and [`Receiver::recv()`] never block: a full
+//! channel refuses the message, handing it back to the sender;
+//! receiving from an empty channel returns nothing. Their blocking
+//! counterparts wait on EVL semaphores counting the free and busy
+//! slots, so that a thread can sleep until some room or data is
+//! available instead of spinning.
+//!
+//! ```no_run
+//! use revl::{channel, thread};
+//!
+//! // Waiting on the EVL semaphores requires EVL threads.
+//! let _me = thread::Builder::new().name("consumer").attach().unwrap();
+//! let (tx, rx) = channel::create::<u32, 4>().unwrap();
+//! thread::Builder::new().name("producer").spawn(move |_| {
+//!     for n in 0..10 {
+//!         tx.send_blocking(n).unwrap();
+//!     }
+//!     // Dropping the last sender disconnects the channel.
+//! }).unwrap();
+//! for msg in rx {
+//!     println!("got {}", msg);
+//! }
+//! ```
+//!
+//! Internally, a ring queue is composed of two lockless ring buffers and a data
+//! vector: dq stores indices of messages pending receive which are
+//! available at the corresponding cells from the data vector, fq
+//! stores indices of free cells into the data vector. fq + dq covers
+//! the whole index space, which is (1 << ORDER) long. The
+//! implementation performs no dynamic memory allocation.
+//!
+//! In practice, an index is pulled from fq, the data vector is filled
+//! with a message at this index next, which is eventually pushed to
+//! dq. The receiver pulls the next available index from dq, extracts
+//! the message at the corresponding position in the vector then
+//! releases the consumed index to fq.
+//!
+//! The lighweight ring buffers are based on Ruslan Nikolaev's
+//! Scalable Circular Queue ([single-width CAS
+//! variant](https://github.com/rusnikola/lfqueue.git)) ported to
+//! Rust. See
+//! <http://drops.dagstuhl.de/opus/volltexte/2019/11335/pdf/LIPIcs-DISC-2019-28.pdf>.
+
+use std::sync::{
+    Arc,
+    atomic::fence,
+    atomic::AtomicUsize,
+    atomic::AtomicU64,
+    atomic::AtomicIsize,
+    atomic::Ordering::Acquire,
+    atomic::Ordering::AcqRel,
+    atomic::Ordering::Relaxed,
+    atomic::Ordering::Release,
+};
+use std::alloc::{self, Layout};
+use std::marker::PhantomData;
+use std::mem::{self, MaybeUninit};
+use std::ops::{Deref, DerefMut};
+use std::error;
+use std::fmt;
+use std::io::ErrorKind;
+use std::hint;
+use std::os::fd::{AsRawFd, RawFd};
+use std::ptr::{self, NonNull};
+use std::time::Duration;
+use embedded_time::{duration::Nanoseconds, Instant};
+use crate::clock::{CoreClock, STEADY_CLOCK};
+use crate::semaphore::{self, Semaphore};
+use crate::Error;
+
+// Conservative: 128 bytes should fit anything we run on. Bottom line:
+// we want to prevent cacheline bouncing in SMP on hot data.
+const CACHELINE_SHIFT: usize = 7;
+
+#[cfg(target_pointer_width = "64")]
+const RING_MIN_ORDER: usize = CACHELINE_SHIFT - 3;
+#[cfg(target_pointer_width = "32")]
+const RING_MIN_ORDER: usize = CACHELINE_SHIFT - 2;
+const RING_EMPTY_VAL: usize = !0;
+const RING_EMPTY_CELL: AtomicUsize = AtomicUsize::new(RING_EMPTY_VAL);
+
+// Revisit: Rust align() attribute currently requires a literal,
+// struct fields do not support alignment directives, complex const
+// generics are not available from the stable channel yet, all of this
+// is a bit of a pain at the moment. We just work around those
+// limitations for now.
+
+#[repr(align(128))]             // CACHELINE_ALIGNMENT
+struct Head {
+    d: AtomicUsize,
+}
+
+#[repr(align(128))]
+struct Threshold {
+    d: AtomicIsize,
+}
+
+#[repr(align(128))]
+struct Tail {
+    d: AtomicUsize,
+}
+
+fn sub_with_overflow(lhs: usize, rhs: usize) -> isize {
+    lhs.overflowing_sub(rhs).0 as isize
+}
+
+#[repr(align(128))]
+struct Ring {
+    // The ring order is set at runtime, so that the capacity of a
+    // channel may be chosen dynamically.
+    cells: Vec<AtomicUsize>,
+    head: Head,
+    threshold: Threshold,
+    tail: Tail,
+    order: usize,
+}
+
+impl Ring {
+    fn new(order: usize) -> Self {
+        let nr_cells = 1usize << (order + 1);
+        let mut this = Self {
+            // To maintain every single ring entry, we need two cells.
+            cells: Vec::with_capacity(nr_cells),
+            head: Head { d: AtomicUsize::new(0) },
+            tail: Tail { d: AtomicUsize::new(0) },
+            threshold: Threshold { d: AtomicIsize::new(-1) },
+            order,
+        };
+        // Populate the vector.
+        this.cells.resize_with(nr_cells, || { RING_EMPTY_CELL });
+        this
+    }
+    fn fill(&mut self) {
+        let half: usize = self.get_nr_entries();
+        let full: usize = self.get_nr_cells();
+        for n in 0..half {
+            self.cells[Ring::map(n, full, self.order + 1)].store(
+                Ring::map(full + n, half, self.order), Relaxed
+            );
+        }
+        for n in half..full {
+            self.cells[Ring::map(n, full, self.order + 1)].store(
+                RING_EMPTY_VAL, Relaxed
+            );
+        }
+        self.head.d.store(0, Relaxed);
+        self.tail.d.store(half, Relaxed);
+        self.threshold.d.store(Ring::get_threshold(half, full), Relaxed);
+    }
+    fn enqueue(&self, eidx: usize) {
+        let mut eidx = eidx;
+        let half: usize = self.get_nr_entries();
+        let full: usize = self.get_nr_cells();
+        eidx ^= full - 1;
+        'again: loop {
+            let tail = self.tail.d.fetch_add(1, AcqRel);
+            let tcycle = (tail << 1) | (2 * full - 1);
+            let tidx = Ring::map(tail, full, self.order + 1);
+            let mut entry = self.cells[tidx].load(Acquire);
+            loop {
+                let ecycle = entry | (2 * full - 1);
+                if sub_with_overflow(ecycle, tcycle) < 0 &&
+                    (entry == ecycle ||
+                     (entry == (ecycle ^ full) &&
+                      sub_with_overflow(self.head.d.load(Acquire), tail) <= 0)) {
+                        match self.cells[tidx].compare_exchange_weak(entry, tcycle ^ eidx, AcqRel, Acquire) {
+                            Ok(_) => break,
+                            Err(ret) => entry = ret,
+                        }
+                    } else {
+                        continue 'again;
+                    }
+            }
+            let t = Ring::get_threshold(half, full);
+            if self.threshold.d.load(Relaxed) != t {
+                self.threshold.d.store(t, Relaxed);
+            }
+            break;
+        }
+    }
+    fn dequeue(&self) -> Option<usize> {
+        if self.threshold.d.load(Relaxed) < 0 {
+            return None;
+        }
+        let full: usize = self.get_nr_cells();
+        loop {
+            let head = self.head.d.fetch_add(1, AcqRel);
+            let hcycle = (head << 1) | (2 * full - 1);
+            let hidx = Ring::map(head, full, self.order + 1);
+            let mut attempt = 0;
+            'again: loop {
+                let mut entry = self.cells[hidx].load(Acquire);
+                loop {
+                    let ecycle = entry | (2 * full - 1);
+                    if ecycle == hcycle {
+                        self.cells[hidx].fetch_or(full - 1, AcqRel);
+                        return Some(entry & (full - 1));
+                    }
+                    let new_entry;
+                    if (entry | full) != ecycle {
+                        new_entry = entry & (!full);
+                        if entry == new_entry {
+                            break;
+                        }
+                    } else {
+                        attempt += 1;
+                        if attempt <= 3000 {
+                            continue 'again;
+                        }
+                        new_entry = hcycle ^ ((!entry) & full);
+                    }
+                    if sub_with_overflow(ecycle, hcycle) >= 0 {
+                        break;
+                    }
+                    match self.cells[hidx].compare_exchange_weak(entry, new_entry, AcqRel, Acquire) {
+                        Ok(_) => break,
+                        Err(ret) => entry = ret,
+                    }
+                }
+                let tail = self.tail.d.load(Acquire);
+                if sub_with_overflow(tail, head + 1) <= 0 {
+                    self.catchup(tail, head + 1);
+                    self.threshold.d.fetch_sub(1, AcqRel);
+                    return None;
+                }
+                if self.threshold.d.fetch_sub(1, AcqRel) <= 0 {
+                    return None;
+                }
+                // Move on to the next head.
+                break;
+            }
+        }
+    }
+    fn get_nr_entries(&self) -> usize {
+        1usize << self.order
+    }
+    fn get_nr_cells(&self) -> usize {
+        1usize << (self.order + 1)
+    }
+    const fn map(idx: usize, limit: usize, order: usize) -> usize {
+        ((idx & (limit - 1)) >> (order - RING_MIN_ORDER)) |
+	 ((idx << RING_MIN_ORDER) & (limit - 1))
+    }
+    fn get_threshold(half: usize, nr: usize) -> isize {
+	(half + nr - 1) as isize
+    }
+    fn catchup(&self, tail: usize, head: usize) {
+        let mut head = head;
+        let mut tail = tail;
+        loop {
+            match self.tail.d.compare_exchange_weak(tail, head, AcqRel, Acquire) {
+                Ok(_) => break,
+                Err(ret) => tail = ret,
+            }
+            head = self.head.d.load(Acquire);
+            if sub_with_overflow(tail, head) >= 0 {
+                break;
+            }
+        }
+    }
+}
+
+/// The error returned by a failed send, which hands back the
+/// undelivered message.
+pub enum SendError<T> {
+    /// The channel is full (non-blocking send only).
+    Full(T),
+    /// All receivers were dropped.
+    Disconnected(T),
+    /// Waiting for a free slot failed, e.g. on timeout.
+    Wait(T, Error),
+}
+
+impl<T> SendError<T> {
+    /// Take back the undelivered message.
+    pub fn into_inner(self) -> T {
+        match self {
+            SendError::Full(msg) => msg,
+            SendError::Disconnected(msg) => msg,
+            SendError::Wait(msg, _) => msg,
+        }
+    }
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SendError::Full(_) => f.write_str("Full(..)"),
+            SendError::Disconnected(_) => f.write_str("Disconnected(..)"),
+            SendError::Wait(_, e) => f.debug_tuple("Wait").field(&"..").field(e).finish(),
+        }
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SendError::Full(_) => write!(f, "sending on a full channel"),
+            SendError::Disconnected(_) => write!(f, "sending on a disconnected channel"),
+            SendError::Wait(_, e) => write!(f, "sending on channel failed: {}", e),
+        }
+    }
+}
+
+impl<T> From<SendError<T>> for Error {
+    fn from(e: SendError<T>) -> Self {
+        match e {
+            SendError::Full(_) => Error::WouldBlock,
+            SendError::Disconnected(_) => disconnected(),
+            SendError::Wait(_, e) => e,
+        }
+    }
+}
+
+/// The error returned by a failed receive.
+#[derive(Debug)]
+pub enum RecvError {
+    /// The channel is empty (non-blocking receive only).
+    Empty,
+    /// The channel is empty and all senders were dropped.
+    Disconnected,
+    /// Waiting for a message failed, e.g. on timeout.
+    Wait(Error),
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecvError::Empty => write!(f, "receiving on an empty channel"),
+            RecvError::Disconnected => write!(f, "receiving on a disconnected channel"),
+            RecvError::Wait(e) => write!(f, "receiving on channel failed: {}", e),
+        }
+    }
+}
+
+impl error::Error for RecvError {}
+
+impl From<RecvError> for Error {
+    fn from(e: RecvError) -> Self {
+        match e {
+            RecvError::Empty => Error::WouldBlock,
+            RecvError::Disconnected => disconnected(),
+            RecvError::Wait(e) => e,
+        }
+    }
+}
+
+/// The sending half of a channel, which can be cloned for multiple
+/// producers. The channel is disconnected for the receivers when the
+/// last sender is dropped.
+pub struct Sender<T, const ORDER: usize = DYNAMIC> {
+    rq: Arc<RingQueue<T>>,
+}
+
+impl<T, const ORDER: usize> Sender<T, ORDER> {
+    /// Send a message without blocking. If the channel is full or
+    /// disconnected, the message is handed back in the error value.
+    pub fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        self.rq.send(msg)
+    }
+    /// Send a message, waiting for a free slot if the channel is
+    /// full. On error, the message is handed back with the cause of
+    /// the failure.
+    pub fn send_blocking(&self, msg: T) -> Result<(), SendError<T>> {
+        self.rq.send_blocking(msg)
+    }
+    /// Send a message, waiting for a free slot until the absolute
+    /// `timeout` date is reached, based on the monotonic clock.
+    ///
+    /// # Errors
+    ///
+    /// [`TimedOut`][`crate::Error::TimedOut`] is reported with the
+    /// message if the channel stayed full until the timeout date.
+    pub fn send_until(&self, msg: T, timeout: Instant<CoreClock>) -> Result<(), SendError<T>> {
+        self.rq.send_until(msg, timeout)
+    }
+    /// Send a message, waiting for at most `delay` for a free slot.
+    /// See [`send_until()`](Self::send_until).
+    pub fn send_timed(&self, msg: T, delay: Duration) -> Result<(), SendError<T>> {
+        self.rq.send_until(msg, STEADY_CLOCK.now() + Nanoseconds(delay.as_nanos() as u64))
+    }
+    /// Reserve a free slot without blocking, so that a message can be
+    /// built in place instead of being copied into the channel.
+    ///
+    /// # Errors
+    ///
+    /// [`WouldBlock`][`crate::Error::WouldBlock`] is returned if the
+    /// channel is full, [`BrokenPipe`][`std::io::ErrorKind`] if all
+    /// receivers were dropped.
+    ///
+    /// ```no_run
+    /// use revl::channel;
+    ///
+    /// struct Frame { samples: [f32; 4096] }
+    ///
+    /// let (tx, rx) = channel::create::<Frame, 2>().unwrap();
+    /// let mut slot = tx.reserve().unwrap();
+    /// let frame = slot.as_uninit().as_mut_ptr();
+    /// for n in 0..4096 {
+    ///     unsafe { (*frame).samples[n] = n as f32; }
+    /// }
+    /// unsafe { slot.commit(); }
+    ///
+    /// let frame = rx.peek_slot().unwrap();
+    /// println!("first sample: {}", frame.samples[0]);
+    /// ```
+    pub fn reserve(&self) -> Result<SendSlot<'_, T>, Error> {
+        self.rq.reserve(false)
+    }
+    /// Reserve a free slot, waiting for one if the channel is full.
+    /// See [`reserve()`](Self::reserve).
+    pub fn reserve_blocking(&self) -> Result<SendSlot<'_, T>, Error> {
+        self.rq.reserve(true)
+    }
+    /// Send a message without blocking, overwriting the oldest
+    /// pending message if the channel is full. The overwritten
+    /// message is returned, if any. This is meant for lossy
+    /// "latest state wins" data flows, such as sensor snapshots.
+    ///
+    /// ```no_run
+    /// use revl::channel;
+    ///
+    /// let (tx, rx) = channel::create::<u32, 4>().unwrap();
+    /// for sample in 0..100 {
+    ///     if tx.send_overwrite(sample).unwrap().is_some() {
+    ///         // The reader is lagging behind.
+    ///     }
+    /// }
+    /// println!("{} samples lost", rx.overwritten());
+    /// ```
+    pub fn send_overwrite(&self, msg: T) -> Result<Option<T>, SendError<T>> {
+        self.rq.send_overwrite(msg)
+    }
+    /// Return the number of messages overwritten by
+    /// [`send_overwrite()`](Self::send_overwrite) since the channel
+    /// was created.
+    pub fn overwritten(&self) -> u64 {
+        self.rq.overwritten.load(Relaxed)
+    }
+    /// Send as many messages from `iter` as the channel can take
+    /// without blocking, returning the number of messages sent. The
+    /// messages which did not fit are left in the iterator. The
+    /// receivers are notified once for the whole batch, and the
+    /// memory barriers are shared by all messages.
+    ///
+    /// # Errors
+    ///
+    /// [`BrokenPipe`][`std::io::ErrorKind`] is returned if all
+    /// receivers were dropped.
+    ///
+    /// ```no_run
+    /// use revl::channel;
+    ///
+    /// let (tx, rx) = channel::create::<u32, 8>().unwrap();
+    /// let mut samples = 0..1000u32;
+    /// while samples.len() > 0 {
+    ///     tx.send_batch(&mut samples).unwrap();
+    ///     let mut buf = Vec::with_capacity(256);
+    ///     rx.recv_batch(&mut buf, 256).unwrap();
+    /// }
+    /// ```
+    pub fn send_batch<I: Iterator<Item = T>>(&self, iter: &mut I) -> Result<usize, Error> {
+        self.rq.send_batch(iter)
+    }
+    /// Return the maximum number of messages the channel can hold.
+    pub fn capacity(&self) -> usize {
+        self.rq.capacity
+    }
+    /// Return the number of messages currently held by the channel,
+    /// see [`Receiver::len()`].
+    pub fn len(&self) -> usize {
+        self.rq.busy.load(Relaxed)
+    }
+    /// Return true if the channel looks empty, see [`len()`](Self::len).
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Return the high watermark of the channel, see
+    /// [`Receiver::high_watermark()`].
+    pub fn high_watermark(&self) -> usize {
+        self.rq.watermark.load(Relaxed)
+    }
+    /// Restart tracking the high watermark, see
+    /// [`Receiver::reset_high_watermark()`].
+    pub fn reset_high_watermark(&self) {
+        self.rq.watermark.store(self.len(), Relaxed);
+    }
+}
+
+impl<T, const ORDER: usize> Clone for Sender<T, ORDER> {
+    fn clone(&self) -> Self {
+        self.rq.senders.fetch_add(1, Relaxed);
+        Self { rq: self.rq.clone() }
+    }
+}
+
+impl<T, const ORDER: usize> Drop for Sender<T, ORDER> {
+    fn drop(&mut self) {
+        if self.rq.senders.fetch_sub(1, AcqRel) == 1 {
+            // Post the disconnection token, waking up a blocked
+            // receiver which passes it on to the next one.
+            let _ = self.rq.items.put();
+        }
+    }
+}
+
+/// The receiving half of a channel, which can be cloned for multiple
+/// consumers. The channel is disconnected for the senders when the
+/// last receiver is dropped.
+pub struct Receiver<T, const ORDER: usize = DYNAMIC> {
+    rq: Arc<RingQueue<T>>,
+}
+
+impl<T, const ORDER: usize> Receiver<T, ORDER> {
+    /// Receive the next message without blocking.
+    ///
+    /// # Errors
+    ///
+    /// [`RecvError::Empty`] is returned if no message is pending,
+    /// [`RecvError::Disconnected`] if in addition all senders were
+    /// dropped, so that no message may arrive anymore.
+    ///
+    /// ```no_run
+    /// use revl::channel::{self, RecvError};
+    ///
+    /// let (tx, rx) = channel::create::<u32, 4>().unwrap();
+    /// tx.send(1).unwrap();
+    /// drop(tx);
+    /// loop {
+    ///     match rx.recv() {
+    ///         Ok(msg) => println!("got {}", msg),
+    ///         Err(RecvError::Empty) => continue,
+    ///         Err(_) => break,
+    ///     }
+    /// }
+    /// ```
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.rq.recv()
+    }
+    /// Receive the next message, waiting for one if the channel is
+    /// empty. The calling thread sleeps in the EVL core meanwhile,
+    /// without spinning. Once all senders are dropped, the pending
+    /// messages are still delivered, then
+    /// [`RecvError::Disconnected`] is returned.
+    pub fn recv_blocking(&self) -> Result<T, RecvError> {
+        self.rq.recv_blocking()
+    }
+    /// Receive the next message, waiting for one until the absolute
+    /// `timeout` date is reached, based on the monotonic clock.
+    ///
+    /// # Errors
+    ///
+    /// [`RecvError::Wait`] is returned with
+    /// [`TimedOut`][`crate::Error::TimedOut`] if no message arrived by
+    /// the timeout date.
+    pub fn recv_until(&self, timeout: Instant<CoreClock>) -> Result<T, RecvError> {
+        self.rq.recv_until(timeout)
+    }
+    /// Receive the next message, waiting for at most `delay` for
+    /// one. See [`recv_until()`](Self::recv_until).
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use revl::channel::{self, RecvError};
+    /// use revl::{thread, Error};
+    ///
+    /// let _me = thread::Builder::new().name("consumer").attach().unwrap();
+    /// let (_tx, rx) = channel::create::<u32, 4>().unwrap();
+    /// loop {
+    ///     match rx.recv_timed(Duration::from_millis(10)) {
+    ///         Ok(msg) => println!("got {}", msg),
+    ///         Err(RecvError::Wait(Error::TimedOut)) => {
+    ///             // Producers are silent, do some housekeeping.
+    ///         },
+    ///         Err(_) => break,
+    ///     }
+    /// }
+    /// ```
+    pub fn recv_timed(&self, delay: Duration) -> Result<T, RecvError> {
+        self.rq.recv_until(STEADY_CLOCK.now() + Nanoseconds(delay.as_nanos() as u64))
+    }
+    /// Receive up to `max` pending messages without blocking,
+    /// appending them to `buf`. The number of messages received is
+    /// returned, which is zero if the channel is empty.
+    ///
+    /// # Errors
+    ///
+    /// [`RecvError::Disconnected`] is returned if no message is
+    /// pending and all senders were dropped.
+    pub fn recv_batch(&self, buf: &mut Vec<T>, max: usize) -> Result<usize, RecvError> {
+        self.rq.recv_batch(buf, max)
+    }
+    /// Return an iterator over the pending messages, which stops as
+    /// soon as the channel is empty. It never blocks.
+    ///
+    /// ```no_run
+    /// use revl::channel;
+    ///
+    /// let (tx, rx) = channel::create::<u32, 4>().unwrap();
+    /// tx.send(1).unwrap();
+    /// for msg in rx.try_iter() {
+    ///     println!("got {}", msg);
+    /// }
+    /// ```
+    pub fn try_iter(&self) -> TryIter<'_, T, ORDER> {
+        TryIter { rx: self }
+    }
+    /// Return an iterator which blocks waiting for messages, ending
+    /// when the channel is disconnected or the wait fails.
+    pub fn iter(&self) -> Iter<'_, T, ORDER> {
+        Iter { rx: self }
+    }
+    /// Return an iterator over the messages pending at the time of
+    /// the call. Unlike [`try_iter()`](Self::try_iter), messages
+    /// sent while iterating are left for the next receive.
+    pub fn drain(&self) -> Drain<'_, T, ORDER> {
+        Drain {
+            rx: self,
+            left: self.pending().unwrap_or(0),
+        }
+    }
+    /// Access the next pending message in place, without blocking.
+    /// The message stays in the channel until the returned slot is
+    /// dropped, which saves a copy for large payloads. Errors are
+    /// the same as with [`recv()`](Self::recv).
+    pub fn peek_slot(&self) -> Result<RecvSlot<'_, T>, RecvError> {
+        self.rq.peek_slot()
+    }
+    /// Return the number of messages overwritten by
+    /// [`Sender::send_overwrite()`] since the channel was created.
+    pub fn overwritten(&self) -> u64 {
+        self.rq.overwritten.load(Relaxed)
+    }
+    pub(crate) fn pending(&self) -> Result<usize, Error> {
+        self.rq.pending()
+    }
+    /// Return the maximum number of messages the channel can hold.
+    pub fn capacity(&self) -> usize {
+        self.rq.capacity
+    }
+    /// Return the number of messages currently held by the channel.
+    /// This is an approximation, which may be outdated on return if
+    /// other threads access the channel concurrently.
+    pub fn len(&self) -> usize {
+        self.rq.busy.load(Relaxed)
+    }
+    /// Return true if the channel looks empty, see [`len()`](Self::len).
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Return the highest number of messages held by the channel at
+    /// once since it was created, or since the last call to
+    /// [`reset_high_watermark()`](Self::reset_high_watermark). A value
+    /// close to [`capacity()`](Self::capacity) hints at a channel too
+    /// small for the load.
+    ///
+    /// ```no_run
+    /// use revl::channel;
+    ///
+    /// let (_tx, rx) = channel::create::<u32, 6>().unwrap();
+    /// // ...run the application for a while...
+    /// println!("peak usage: {}/{}", rx.high_watermark(), rx.capacity());
+    /// ```
+    pub fn high_watermark(&self) -> usize {
+        self.rq.watermark.load(Relaxed)
+    }
+    /// Restart tracking the high watermark from the current number of
+    /// messages held by the channel.
+    pub fn reset_high_watermark(&self) {
+        self.rq.watermark.store(self.len(), Relaxed);
+    }
+}
+
+// Receivers are readable when the semaphore counting the pending
+// messages is.
+impl<T, const ORDER: usize> AsRawFd for Receiver<T, ORDER> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.rq.items.as_raw_fd()
+    }
+}
+
+impl<T, const ORDER: usize> Clone for Receiver<T, ORDER> {
+    fn clone(&self) -> Self {
+        self.rq.receivers.fetch_add(1, Relaxed);
+        Self { rq: self.rq.clone() }
+    }
+}
+
+impl<T, const ORDER: usize> Drop for Receiver<T, ORDER> {
+    fn drop(&mut self) {
+        if self.rq.receivers.fetch_sub(1, AcqRel) == 1 {
+            // Post the disconnection token to the senders.
+            let _ = self.rq.slots.put();
+        }
+    }
+}
+
+/// A non-blocking iterator over the messages of a [`Receiver`], see
+/// [`Receiver::try_iter()`].
+pub struct TryIter<'a, T, const ORDER: usize> {
+    rx: &'a Receiver<T, ORDER>,
+}
+
+impl<'a, T, const ORDER: usize> Iterator for TryIter<'a, T, ORDER> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.recv().ok()
+    }
+}
+
+/// A blocking iterator over the messages of a [`Receiver`], see
+/// [`Receiver::iter()`].
+pub struct Iter<'a, T, const ORDER: usize> {
+    rx: &'a Receiver<T, ORDER>,
+}
+
+impl<'a, T, const ORDER: usize> Iterator for Iter<'a, T, ORDER> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.recv_blocking().ok()
+    }
+}
+
+/// An iterator over the messages pending in a [`Receiver`] when it
+/// was created, see [`Receiver::drain()`].
+pub struct Drain<'a, T, const ORDER: usize> {
+    rx: &'a Receiver<T, ORDER>,
+    left: usize,
+}
+
+impl<'a, T, const ORDER: usize> Iterator for Drain<'a, T, ORDER> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.left == 0 {
+            return None;
+        }
+        self.left -= 1;
+        self.rx.recv().ok()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.left))
+    }
+}
+
+/// A blocking iterator owning a [`Receiver`], see
+/// [`Receiver::iter()`].
+pub struct IntoIter<T, const ORDER: usize> {
+    rx: Receiver<T, ORDER>,
+}
+
+impl<T, const ORDER: usize> Iterator for IntoIter<T, ORDER> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.recv_blocking().ok()
+    }
+}
+
+impl<'a, T, const ORDER: usize> IntoIterator for &'a Receiver<T, ORDER> {
+    type Item = T;
+    type IntoIter = Iter<'a, T, ORDER>;
+
+    fn into_iter(self) -> Iter<'a, T, ORDER> {
+        self.iter()
+    }
+}
+
+impl<T, const ORDER: usize> IntoIterator for Receiver<T, ORDER> {
+    type Item = T;
+    type IntoIter = IntoIter<T, ORDER>;
+
+    fn into_iter(self) -> IntoIter<T, ORDER> {
+        IntoIter { rx: self }
+    }
+}
+
+/// Memory safety on top of the raw data vector is guaranteed by the fact
+/// that at any point in time, only a single thread can refer to any
+/// given data cell, since the corresponding index in the vector is
+/// never shared (no W/W conflict), and a data cell cannot be consumed
+/// before it is fully populated with the message (no R/W conflict).
+
+struct RingQueue<T> {
+    dq: Ring,
+    fq: Ring,
+    capacity: usize,
+    // The data vector, and the distance in bytes between two
+    // consecutive cells, which is a multiple of the cache line size
+    // if cells are padded.
+    data: NonNull<u8>,
+    layout: Layout,
+    stride: usize,
+    // Count the free and busy cells, so that both ends can wait for
+    // them. Holding a unit guarantees that the corresponding ring
+    // can be pulled from, unless the channel is disconnected, in
+    // which case an extra unit is posted to wake up the waiters.
+    slots: Semaphore,
+    items: Semaphore,
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+    overwritten: AtomicU64,
+    // Number of messages held by the data vector, and highest value
+    // reached so far.
+    busy: AtomicUsize,
+    watermark: AtomicUsize,
+}
+
+unsafe impl<T: Send> Send for RingQueue<T> {}
+unsafe impl<T: Send> Sync for RingQueue<T> {}
+
+impl<T> RingQueue<T> {
+    fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        if self.receivers.load(Acquire) == 0 {
+            return Err(SendError::Disconnected(msg));
+        }
+        if !self.slots.try_get() {
+            return Err(SendError::Full(msg));
+        }
+        self.push(msg)
+    }
+    fn send_blocking(&self, msg: T) -> Result<(), SendError<T>> {
+        if let Err(error) = self.slots.get() {
+            return Err(SendError::Wait(msg, error));
+        }
+        self.push(msg)
+    }
+    fn send_until(&self, msg: T, timeout: Instant<CoreClock>) -> Result<(), SendError<T>> {
+        if let Err(error) = self.slots.get_timed(timeout) {
+            return Err(SendError::Wait(msg, error));
+        }
+        self.push(msg)
+    }
+    // Grab an item unit without blocking.
+    fn take_item(&self) -> Result<(), RecvError> {
+        if self.items.try_get() {
+            return Ok(());
+        }
+        if self.senders.load(Acquire) != 0 {
+            return Err(RecvError::Empty);
+        }
+        // The last sender may have sent a message right before
+        // leaving, look again for it or the disconnection token.
+        if self.items.try_get() {
+            return Ok(());
+        }
+        Err(RecvError::Disconnected)
+    }
+    fn recv(&self) -> Result<T, RecvError> {
+        self.take_item()?;
+        self.pull()
+    }
+    fn recv_blocking(&self) -> Result<T, RecvError> {
+        self.items.get().map_err(RecvError::Wait)?;
+        self.pull()
+    }
+    fn recv_until(&self, timeout: Instant<CoreClock>) -> Result<T, RecvError> {
+        self.items.get_timed(timeout).map_err(RecvError::Wait)?;
+        self.pull()
+    }
+    fn pending(&self) -> Result<usize, Error> {
+        let value = self.items.value()? as usize;
+        // Leave out the disconnection token.
+        match self.senders.load(Acquire) {
+            0 => Ok(value.saturating_sub(1)),
+            _ => Ok(value),
+        }
+    }
+    // Count a new busy cell, updating the high watermark.
+    fn account(&self) {
+        let busy = self.busy.fetch_add(1, Relaxed) + 1;
+        self.watermark.fetch_max(busy, Relaxed);
+    }
+    fn send_overwrite(&self, msg: T) -> Result<Option<T>, SendError<T>> {
+        loop {
+            if self.receivers.load(Acquire) == 0 {
+                return Err(SendError::Disconnected(msg));
+            }
+            if self.slots.try_get() {
+                return self.push(msg).map(|_| None);
+            }
+            // Full, steal the oldest message and reuse its cell,
+            // unless a receiver consumed it meanwhile, in which case
+            // a slot is about to be released.
+            if self.items.try_get() {
+                if let Some(oldest) = self.load() {
+                    self.overwritten.fetch_add(1, Relaxed);
+                    self.store(msg);
+                    fence(Release);
+                    let _ = self.items.put();
+                    return Ok(Some(oldest));
+                }
+            }
+            hint::spin_loop();
+        }
+    }
+    fn send_batch<I: Iterator<Item = T>>(&self, iter: &mut I) -> Result<usize, Error> {
+        if self.receivers.load(Acquire) == 0 {
+            return Err(disconnected());
+        }
+        let mut count = 0;
+        while self.slots.try_get() {
+            match iter.next() {
+                Some(msg) => self.store(msg),
+                None => {
+                    let _ = self.slots.put();
+                    break;
+                },
+            }
+            count += 1;
+        }
+        if count > 0 {
+            fence(Release);
+            self.items.put_many(count as u32)?;
+        }
+        Ok(count)
+    }
+    fn recv_batch(&self, buf: &mut Vec<T>, max: usize) -> Result<usize, RecvError> {
+        let mut count = 0;
+        while count < max && self.take_item().is_ok() {
+            match self.load() {
+                Some(msg) => buf.push(msg),
+                None => {
+                    // Disconnected, pass the token on.
+                    let _ = self.items.put();
+                    break;
+                },
+            }
+            count += 1;
+        }
+        if count > 0 {
+            self.slots.put_many(count as u32).map_err(RecvError::Wait)?;
+        } else if self.senders.load(Acquire) == 0 {
+            return Err(RecvError::Disconnected);
+        }
+        Ok(count)
+    }
+    // Called with a slot unit held.
+    fn push(&self, msg: T) -> Result<(), SendError<T>> {
+        if self.receivers.load(Acquire) == 0 {
+            // Nobody will ever receive, pass the unit on to the next
+            // blocked sender.
+            let _ = self.slots.put();
+            return Err(SendError::Disconnected(msg));
+        }
+        self.store(msg);
+        fence(Release);
+        // Posting to a valid semaphore cannot fail.
+        let _ = self.items.put();
+        Ok(())
+    }
+    // Called with an item unit held.
+    fn pull(&self) -> Result<T, RecvError> {
+        match self.load() {
+            Some(msg) => {
+                let _ = self.slots.put();
+                Ok(msg)
+            },
+            None => {
+                let _ = self.items.put();
+                Err(RecvError::Disconnected)
+            },
+        }
+    }
+    fn store(&self, msg: T) {
+        let eidx = self.claim();
+        unsafe { (*self.cell(eidx)).write(msg); }
+        // We have as many free slots than we have data cells, so
+        // enqueuing cannot fail by construction.
+        self.dq.enqueue(eidx);
+        self.account();
+    }
+    fn load(&self) -> Option<T> {
+        let eidx = self.grab()?;
+        // Move the message out, the cell is considered uninitialized
+        // from now on.
+        let msg = unsafe { (*self.cell(eidx)).assume_init_read() };
+        self.fq.enqueue(eidx);
+        Some(msg)
+    }
+    // Called with a slot unit held, return a free cell.
+    fn claim(&self) -> usize {
+        // A free cell must be available.
+        loop {
+            if let Some(eidx) = self.fq.dequeue() {
+                break eidx;
+            }
+            hint::spin_loop();
+        }
+    }
+    // Called with an item unit held, return a busy cell.
+    fn grab(&self) -> Option<usize> {
+        // A message must be pending, unless we got the disconnection
+        // token.
+        let eidx = loop {
+            if let Some(eidx) = self.dq.dequeue() {
+                break eidx;
+            }
+            if self.senders.load(Acquire) == 0 {
+                return None;
+            }
+            hint::spin_loop();
+        };
+        fence(Acquire);
+        self.busy.fetch_sub(1, Relaxed);
+        Some(eidx)
+    }
+    fn reserve(&self, blocking: bool) -> Result<SendSlot<'_, T>, Error> {
+        if self.receivers.load(Acquire) == 0 {
+            return Err(disconnected());
+        }
+        if blocking {
+            self.slots.get()?;
+        } else if !self.slots.try_get() {
+            return Err(Error::WouldBlock);
+        }
+        if self.receivers.load(Acquire) == 0 {
+            let _ = self.slots.put();
+            return Err(disconnected());
+        }
+        Ok(SendSlot { rq: self, eidx: self.claim() })
+    }
+    fn peek_slot(&self) -> Result<RecvSlot<'_, T>, RecvError> {
+        self.take_item()?;
+        match self.grab() {
+            Some(eidx) => Ok(RecvSlot { rq: self, eidx, _marker: PhantomData }),
+            None => {
+                let _ = self.items.put();
+                Err(RecvError::Disconnected)
+            },
+        }
+    }
+}
+
+/// A free slot reserved by [`Sender::reserve()`], in which a message
+/// can be built in place. Dropping the slot without sending gives it
+/// back to the channel.
+pub struct SendSlot<'a, T> {
+    rq: &'a RingQueue<T>,
+    eidx: usize,
+}
+
+impl<'a, T> SendSlot<'a, T> {
+    /// Return the uninitialized storage of the message.
+    pub fn as_uninit(&mut self) -> &mut MaybeUninit<T> {
+        unsafe { &mut *self.rq.cell(self.eidx) }
+    }
+    /// Store `msg` into the slot then send it.
+    pub fn send(mut self, msg: T) {
+        self.as_uninit().write(msg);
+        unsafe { self.commit() }
+    }
+    /// Send the message built in place.
+    ///
+    /// # Safety
+    ///
+    /// The storage returned by [`as_uninit()`](Self::as_uninit) must
+    /// have been fully initialized.
+    pub unsafe fn commit(self) {
+        let this = mem::ManuallyDrop::new(self);
+        this.rq.dq.enqueue(this.eidx);
+        this.rq.account();
+        fence(Release);
+        let _ = this.rq.items.put();
+    }
+}
+
+impl<'a, T> Drop for SendSlot<'a, T> {
+    fn drop(&mut self) {
+        self.rq.fq.enqueue(self.eidx);
+        let _ = self.rq.slots.put();
+    }
+}
+
+/// A pending message held in place by [`Receiver::peek_slot()`]. The
+/// slot is released when dropped, dropping the message with it unless
+/// it was moved out by [`take()`](Self::take).
+pub struct RecvSlot<'a, T> {
+    rq: &'a RingQueue<T>,
+    eidx: usize,
+    // The slot hands out references to the message, so it may only
+    // be shared between threads if the message can.
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> RecvSlot<'a, T> {
+    /// Move the message out of the slot.
+    pub fn take(self) -> T {
+        let this = mem::ManuallyDrop::new(self);
+        let msg = unsafe { (*this.rq.cell(this.eidx)).assume_init_read() };
+        this.rq.fq.enqueue(this.eidx);
+        let _ = this.rq.slots.put();
+        msg
+    }
+}
+
+impl<'a, T> Deref for RecvSlot<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { (*self.rq.cell(self.eidx)).assume_init_ref() }
+    }
+}
+
+impl<'a, T> DerefMut for RecvSlot<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { (*self.rq.cell(self.eidx)).assume_init_mut() }
+    }
+}
+
+impl<'a, T> Drop for RecvSlot<'a, T> {
+    fn drop(&mut self) {
+        unsafe { (*self.rq.cell(self.eidx)).assume_init_drop(); }
+        self.rq.fq.enqueue(self.eidx);
+        let _ = self.rq.slots.put();
+    }
+}
+
+impl<T> RingQueue<T> {
+    fn cell(&self, eidx: usize) -> *mut MaybeUninit<T> {
+        unsafe { self.data.as_ptr().add(eidx * self.stride) as *mut MaybeUninit<T> }
+    }
+}
+
+fn disconnected() -> Error {
+    Error::new(ErrorKind::BrokenPipe, "channel disconnected")
+}
+
+impl<T> Drop for RingQueue<T> {
+    fn drop(&mut self) {
+        // Drop the messages nobody received. The busy cells are the
+        // only initialized ones.
+        while let Some(eidx) = self.dq.dequeue() {
+            unsafe { (*self.cell(eidx)).assume_init_drop(); }
+        }
+        if self.layout.size() > 0 {
+            unsafe { alloc::dealloc(self.data.as_ptr(), self.layout); }
+        }
+    }
+}
+
+/// The `ORDER` parameter of the [`Sender`] and [`Receiver`] types of
+/// a channel whose capacity is chosen at runtime, see [`bounded()`].
+pub const DYNAMIC: usize = usize::MAX;
+
+// Reject invalid orders at build time, including the DYNAMIC
+// default, which has no fixed size. The SPSC rings are checked the
+// same way.
+pub(crate) struct Order<const ORDER: usize>;
+
+impl<const ORDER: usize> Order<ORDER> {
+    pub(crate) const CHECK: () = assert!(ORDER < 32, "invalid ring order");
+}
+
+/// Create a channel of `1 << ORDER` slots, returning its sending and
+/// receiving halves. `ORDER` must be lower than 32, which is checked
+/// at build time.
+///
+/// # Errors
+///
+/// The EVL semaphores the blocking operations are based on could not
+/// be created, see [`Semaphore::new()`][`crate::semaphore::Semaphore::new`].
+pub fn create<T, const ORDER: usize>(
+) -> Result<(Sender<T, ORDER>, Receiver<T, ORDER>), Error> {
+    let () = Order::<ORDER>::CHECK;
+    let r = Arc::new(RingQueue::new(1 << ORDER, false)?);
+    Ok(( Sender { rq: r.clone() }, Receiver { rq: r } ))
+}
+
+/// Create a channel which can hold `capacity` messages, returning its
+/// sending and receiving halves. The capacity may come from a runtime
+/// setting, unlike with [`create()`].
+///
+/// # Errors
+///
+/// [`InvalidInput`][`std::io::ErrorKind`] is returned if `capacity`
+/// is zero or exceeds `u32::MAX`, otherwise see [`create()`].
+///
+/// ```no_run
+/// use revl::channel::{self, Receiver, Sender};
+///
+/// let depth = 100; // e.g. read from a config file
+/// let (tx, rx): (Sender<u32>, Receiver<u32>) = channel::bounded(depth).unwrap();
+/// assert_eq!(tx.capacity(), 100);
+/// ```
+pub fn bounded<T>(capacity: usize) -> Result<(Sender<T>, Receiver<T>), Error> {
+    if capacity == 0 || capacity > u32::MAX as usize {
+        return Err(Error::new(ErrorKind::InvalidInput, "invalid channel capacity"));
+    }
+    let r = Arc::new(RingQueue::new(capacity, false)?);
+    Ok(( Sender { rq: r.clone() }, Receiver { rq: r } ))
+}
+
+/// Create a channel of `1 << ORDER` slots like [`create()`], padding
+/// each slot to a separate cache line. This prevents small messages
+/// handled by different CPUs from bouncing the same cache line, at
+/// the expense of memory. Latency-critical SMP configurations should
+/// consider it for messages smaller than a cache line.
+///
+/// ```no_run
+/// use revl::channel;
+///
+/// // Each u64 takes a full cache line in the channel.
+/// let (tx, rx) = channel::create_padded::<u64, 6>().unwrap();
+/// ```
+pub fn create_padded<T, const ORDER: usize>(
+) -> Result<(Sender<T, ORDER>, Receiver<T, ORDER>), Error> {
+    let () = Order::<ORDER>::CHECK;
+    let r = Arc::new(RingQueue::new(1 << ORDER, true)?);
+    Ok(( Sender { rq: r.clone() }, Receiver { rq: r } ))
+}
+
+/// Create a channel which can hold `capacity` messages like
+/// [`bounded()`], padding each slot to a separate cache line, see
+/// [`create_padded()`].
+pub fn bounded_padded<T>(capacity: usize) -> Result<(Sender<T>, Receiver<T>), Error> {
+    if capacity == 0 || capacity > u32::MAX as usize {
+        return Err(Error::new(ErrorKind::InvalidInput, "invalid channel capacity"));
+    }
+    let r = Arc::new(RingQueue::new(capacity, true)?);
+    Ok(( Sender { rq: r.clone() }, Receiver { rq: r } ))
+}
+
+impl<T> RingQueue<T> {
+    fn new(capacity: usize, padded: bool) -> Result<Self, Error> {
+        // The ring is sized to the next power of two, the semaphore
+        // counting the free slots enforces the exact capacity.
+        let order = (capacity.next_power_of_two().trailing_zeros() as usize)
+            .max(RING_MIN_ORDER);
+        let slots = semaphore::Builder::new().init_value(capacity as u32).create()?;
+        let items = semaphore::Builder::new().create()?;
+        // Padding is obtained by rounding the cells up to a multiple
+        // of the cache line size, with the data vector aligned on a
+        // cache line, so that no two of them share a line.
+        let size = mem::size_of::<T>();
+        let (stride, align) = if padded && size > 0 {
+            let align = mem::align_of::<T>().max(1 << CACHELINE_SHIFT);
+            (size.next_multiple_of(align), align)
+        } else {
+            (size, mem::align_of::<T>())
+        };
+        let layout = stride.checked_mul(1 << order)
+            .and_then(|len| Layout::from_size_align(len, align).ok())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid channel capacity"))?;
+        // The cells of zero-sized messages take no storage, but must
+        // still be aligned.
+        let data = match layout.size() {
+            0 => NonNull::new(ptr::without_provenance_mut(align)),
+            _ => NonNull::new(unsafe { alloc::alloc(layout) }),
+        };
+        let data = data.ok_or_else(|| {
+            Error::new(ErrorKind::OutOfMemory, "cannot allocate channel cells")
+        })?;
+        let mut rq = RingQueue {
+            dq: Ring::new(order),
+            fq: Ring::new(order),
+            capacity,
+            data,
+            layout,
+            stride,
+            slots,
+            items,
+            senders: AtomicUsize::new(1),
+            receivers: AtomicUsize::new(1),
+            overwritten: AtomicU64::new(0),
+            busy: AtomicUsize::new(0),
+            watermark: AtomicUsize::new(0),
+        };
+        // The data vector is uninitialized, start with a full free
+        // ring.
+        rq.fq.fill();
+        Ok(rq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn ring_starts_empty() {
+        let ring = Ring::new(RING_MIN_ORDER);
+        assert_eq!(ring.dequeue(), None);
+    }
+
+    #[test]
+    fn ring_fill_yields_all_indices() {
+        for order in RING_MIN_ORDER..RING_MIN_ORDER + 3 {
+            let mut ring = Ring::new(order);
+            ring.fill();
+            // Indices come out permuted, each of them once.
+            let mut indices: Vec<usize> = std::iter::from_fn(|| ring.dequeue()).collect();
+            indices.sort_unstable();
+            assert!(indices.into_iter().eq(0..ring.get_nr_entries()));
+            assert_eq!(ring.dequeue(), None);
+        }
+    }
+
+    #[test]
+    fn ring_is_fifo() {
+        let ring = Ring::new(RING_MIN_ORDER + 1);
+        let half = ring.get_nr_entries();
+        // Go around the ring many times.
+        for round in 0..100 {
+            for n in 0..half {
+                ring.enqueue((n + round) % half);
+            }
+            for n in 0..half {
+                assert_eq!(ring.dequeue(), Some((n + round) % half));
+            }
+            assert_eq!(ring.dequeue(), None);
+        }
+    }
+
+    #[test]
+    fn ring_keeps_indices_under_contention() {
+        let order = RING_MIN_ORDER + 2;
+        let mut fq = Ring::new(order);
+        fq.fill();
+        let fq = Arc::new(fq);
+        let dq = Arc::new(Ring::new(order));
+        // Pass the indices back and forth between both rings, like
+        // senders and receivers do.
+        let workers: Vec<_> = (0..4).map(|_| {
+            let (fq, dq) = (fq.clone(), dq.clone());
+            thread::spawn(move || {
+                for _ in 0..20_000 {
+                    let eidx = loop {
+                        match fq.dequeue() {
+                            Some(eidx) => break eidx,
+                            None => hint::spin_loop(),
+                        }
+                    };
+                    dq.enqueue(eidx);
+                    let eidx = loop {
+                        match dq.dequeue() {
+                            Some(eidx) => break eidx,
+                            None => hint::spin_loop(),
+                        }
+                    };
+                    fq.enqueue(eidx);
+                }
+            })
+        }).collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(dq.dequeue(), None);
+        let mut indices: Vec<usize> = std::iter::from_fn(|| fq.dequeue()).collect();
+        indices.sort_unstable();
+        assert!(indices.into_iter().eq(0..fq.get_nr_entries()));
+    }
+}
@@ -5,7 +5,8 @@ use libc::{
     c_long,
     time_t,
 };
-use std::io;
+use std::hint;
+use std::time::Duration;
 use embedded_time::{
     clock,
     duration::{Nanoseconds, Seconds},
@@ -18,9 +19,22 @@ use evl_sys::{
     timespec,
     BuiltinClock
 };
+use crate::Error;
 
+#[derive(Debug)]
 pub struct CoreClock(pub(crate) BuiltinClock);
 
+// Convert a date to the timespec format the EVL services expect.
+pub(crate) fn instant_to_timespec(date: Instant<CoreClock>) -> timespec {
+    let dur = date.duration_since_epoch();
+    let secs: Seconds<u64> = Seconds::try_from(dur).unwrap();
+    let nsecs: Nanoseconds<u64> = Nanoseconds::<u64>::try_from(dur).unwrap() % secs;
+    timespec {
+        tv_sec: secs.integer() as time_t,
+        tv_nsec: nsecs.integer() as c_long,
+    }
+}
+
 impl Clock for CoreClock {
     type T = u64;
     const SCALING_FACTOR: Fraction = Fraction::new(1, 1_000_000_000); // ns
@@ -37,20 +51,51 @@ impl Clock for CoreClock {
 }
 
 impl CoreClock {
-    pub fn sleep_until(&self, timeout: Instant<CoreClock>) -> Result<(), io::Error> {
-        let dur = timeout.duration_since_epoch();
-        let secs: Seconds<u64> = Seconds::try_from(dur).unwrap();
-        let nsecs: Nanoseconds<u64> = Nanoseconds::<u64>::try_from(dur).unwrap() % secs;
-        let date = timespec {
-            tv_sec: secs.integer() as time_t,
-            tv_nsec: nsecs.integer() as c_long,
-        };
+    pub fn sleep_until(&self, timeout: Instant<CoreClock>) -> Result<(), Error> {
+        let date = instant_to_timespec(timeout);
         let ret: c_int = unsafe { evl_sleep_until(self.0 as c_int, &date) };
         match ret {
             0 => return Ok(()),
-            _ => return Err(io::Error::from_raw_os_error(-ret)),
+            _ => return Err(Error::from_raw_os_error(-ret)),
         };
     }
+    /// Busy-wait until the clock reaches `deadline`, without
+    /// invoking the scheduler.
+    ///
+    /// This is meant for very short waits below the timer
+    /// resolution, e.g. a few microseconds between device register
+    /// accesses. Since the calling thread keeps the CPU for the whole
+    /// wait, [`sleep_until()`][`CoreClock::sleep_until`] should be
+    /// preferred for longer delays.
+    ///
+    /// ```no_run
+    /// use revl::clock::STEADY_CLOCK;
+    /// use embedded_time::duration::Microseconds;
+    ///
+    /// let deadline = STEADY_CLOCK.now() + Microseconds(5u32);
+    /// STEADY_CLOCK.spin_until(deadline);
+    /// ```
+    pub fn spin_until(&self, deadline: Instant<CoreClock>) {
+        #[cfg(feature = "sim")]
+        if crate::sim::spin_until(self, deadline) {
+            return;
+        }
+        while self.now() < deadline {
+            hint::spin_loop();
+        }
+    }
+    /// Busy-wait for `delay` to elapse, without invoking the
+    /// scheduler. See [`spin_until()`][`CoreClock::spin_until`].
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use revl::clock::STEADY_CLOCK;
+    ///
+    /// STEADY_CLOCK.spin_for(Duration::from_nanos(2_500));
+    /// ```
+    pub fn spin_for(&self, delay: Duration) {
+        self.spin_until(self.now() + Nanoseconds(delay.as_nanos() as u64));
+    }
     pub fn now(&self) -> Instant<Self> {
         self.try_now().unwrap()
     }
@@ -60,3 +105,135 @@ impl CoreClock {
 /// CLOCK_MONOTONIC) and adjustable wallclock (aka POSIX CLOCK_REALTIME).
 pub const STEADY_CLOCK: CoreClock = CoreClock(BuiltinClock::MONOTONIC);
 pub const SYSTEM_CLOCK: CoreClock = CoreClock(BuiltinClock::REALTIME);
+
+/// Jitter and deadline statistics of a [`Periodic`] release schedule.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PeriodicStats {
+    /// The number of release points reached.
+    pub ticks: u64,
+    /// The number of release points skipped because the previous
+    /// cycle overran.
+    pub missed: u64,
+    /// The minimum wakeup delay past a release point.
+    pub min_jitter: Duration,
+    /// The maximum wakeup delay past a release point.
+    pub max_jitter: Duration,
+    /// The average wakeup delay past a release point.
+    pub avg_jitter: Duration,
+}
+
+/// A periodic release schedule based on the monotonic clock.
+///
+/// Each call to [`tick()`](Periodic::tick) sleeps until the next
+/// absolute release point, so that the period does not drift with
+/// the duration of the work done in each cycle. If a cycle overruns
+/// past one or more release points, those are skipped and counted
+/// as missed deadlines. `Periodic` is also an iterator yielding the
+/// results of successive ticks.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use revl::clock::Periodic;
+///
+/// let mut cycle = Periodic::new(Duration::from_micros(500));
+/// for missed in cycle.by_ref().take(10_000) {
+///     if missed.unwrap() > 0 {
+///         // Catch up with the schedule.
+///     }
+///     // Periodic work follows.
+/// }
+/// let stats = cycle.stats();
+/// println!("max jitter {:?}, {} missed", stats.max_jitter, stats.missed);
+/// ```
+pub struct Periodic {
+    period: u64,
+    // The next release point, in nanoseconds.
+    release: u64,
+    ticks: u64,
+    missed: u64,
+    min_jitter: u64,
+    max_jitter: u64,
+    sum_jitter: u128,
+}
+
+impl Periodic {
+    /// Create a schedule releasing every `period`, the first release
+    /// point being one period from now.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    pub fn new(period: Duration) -> Self {
+        assert!(!period.is_zero(), "period must be non-zero");
+        let period = period.as_nanos() as u64;
+        let now = STEADY_CLOCK.now().duration_since_epoch().integer();
+        Self {
+            period,
+            release: now + period,
+            ticks: 0,
+            missed: 0,
+            min_jitter: u64::MAX,
+            max_jitter: 0,
+            sum_jitter: 0,
+        }
+    }
+    /// Sleep until the next release point, returning the number of
+    /// release points skipped since the previous tick because the
+    /// cycle overran.
+    pub fn tick(&mut self) -> Result<u64, Error> {
+        let now = STEADY_CLOCK.now().duration_since_epoch().integer();
+        let mut missed = 0;
+        if now > self.release {
+            missed = (now - self.release) / self.period + 1;
+            self.release += missed * self.period;
+            self.missed += missed;
+        }
+        STEADY_CLOCK.sleep_until(Instant::new(self.release))?;
+        let woke = STEADY_CLOCK.now().duration_since_epoch().integer();
+        let jitter = woke.saturating_sub(self.release);
+        self.ticks += 1;
+        self.min_jitter = self.min_jitter.min(jitter);
+        self.max_jitter = self.max_jitter.max(jitter);
+        self.sum_jitter += jitter as u128;
+        self.release += self.period;
+        Ok(missed)
+    }
+    /// Return the period of the schedule.
+    pub fn period(&self) -> Duration {
+        Duration::from_nanos(self.period)
+    }
+    /// Return the date of the next release point.
+    pub fn next_release(&self) -> Instant<CoreClock> {
+        Instant::new(self.release)
+    }
+    /// Return the statistics collected since the schedule was
+    /// created or the statistics were last reset.
+    pub fn stats(&self) -> PeriodicStats {
+        if self.ticks == 0 {
+            return PeriodicStats { missed: self.missed, ..Default::default() };
+        }
+        PeriodicStats {
+            ticks: self.ticks,
+            missed: self.missed,
+            min_jitter: Duration::from_nanos(self.min_jitter),
+            max_jitter: Duration::from_nanos(self.max_jitter),
+            avg_jitter: Duration::from_nanos((self.sum_jitter / self.ticks as u128) as u64),
+        }
+    }
+    /// Reset the statistics, without altering the schedule.
+    pub fn reset_stats(&mut self) {
+        self.ticks = 0;
+        self.missed = 0;
+        self.min_jitter = u64::MAX;
+        self.max_jitter = 0;
+        self.sum_jitter = 0;
+    }
+}
+
+impl Iterator for Periodic {
+    type Item = Result<u64, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.tick())
+    }
+}
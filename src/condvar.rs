@@ -0,0 +1,538 @@
+//! Condition variable.
+//!
+//! A condition variable is based on an EVL event, which allows a
+//! thread to wait for some condition on shared data to become true
+//! while the data is protected by a [`Mutex`][`crate::mutex::Mutex`].
+//!
+//! The pairing pattern is the usual one: a thread locks the mutex,
+//! then checks the condition and waits on the condition variable
+//! until it is satisfied, which atomically releases the mutex during
+//! the sleep then re-acquires it before returning. A thread changing
+//! the shared data does so with the mutex locked, then notifies the
+//! condition variable. All waiters of a given condition variable
+//! should pass guards to the same mutex.
+//!
+//! ```no_run
+//! use revl::mutex::{self, Mutex};
+//! use revl::condvar::{self, Condvar};
+//!
+//! fn consumer(ready: &Mutex<bool>, cv: &Condvar) -> Result<(), revl::Error> {
+//!     let guard = ready.lock()?;
+//!     let _guard = cv.wait_while(guard, |ready| !*ready)?;
+//!     Ok(())
+//! }
+//!
+//! fn producer(ready: &Mutex<bool>, cv: &Condvar) -> Result<(), revl::Error> {
+//!     *ready.lock()? = true;
+//!     cv.notify_one()?;
+//!     Ok(())
+//! }
+//!
+//! let ready = Mutex::new(false, mutex::Builder::new()).unwrap();
+//! let cv = condvar::Builder::new().create().unwrap();
+//! ```
+
+use std::cell::UnsafeCell;
+use std::ffi::CString;
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::os::raw::c_int;
+use std::ptr;
+use std::time::Duration;
+use libc::ETIMEDOUT;
+use embedded_time::{duration::Nanoseconds, Instant};
+use evl_sys::{
+    evl_event,
+    evl_create_event,
+    evl_close_event,
+    evl_wait_event,
+    evl_timedwait_event,
+    evl_signal_event,
+    evl_broadcast_event,
+    evl_signal_thread,
+    CloneFlags,
+};
+use crate::mutex::{self, Mutex, MutexGuard};
+use crate::clock::{self, CoreClock, STEADY_CLOCK};
+use crate::thread::Thread;
+use crate::Error;
+
+/// A builder for a condition variable.
+pub struct Builder {
+    name: Option<String>,
+    clock: Option<CoreClock>,
+    visible: bool,
+}
+
+impl Builder {
+    /// Create a builder for an anonymous, private condition variable
+    /// timed by the monotonic clock.
+    pub fn new() -> Self {
+        Self {
+            name: None,
+            clock: None,
+            visible: false,
+        }
+    }
+    /// Set the name of the condition variable.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+    /// Make the condition variable visible from the /dev/evl
+    /// hierarchy.
+    pub fn public(mut self) -> Self {
+        self.visible = true;
+        self
+    }
+    /// Hide the condition variable from the /dev/evl hierarchy.
+    pub fn private(mut self) -> Self {
+        self.visible = false;
+        self
+    }
+    /// Set the clock timing the waits. The timeout dates passed to
+    /// [`Condvar::wait_timed()`] are based on this clock, so are the
+    /// delays passed to [`Condvar::wait_for()`].
+    pub fn clock(mut self, clock: CoreClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+    /// Create a condition variable from this builder.
+    pub fn create(self) -> Result<Condvar, Error> {
+        Condvar::new(self)
+    }
+    /// Create a condition variable paired with a mutex guarding
+    /// `data`, both sharing the properties of this builder.
+    pub fn create_pair<T>(self, data: T) -> Result<CondvarPair<T>, Error> {
+        CondvarPair::new(data, self)
+    }
+}
+
+/// The outcome of a timed wait, telling whether the timeout elapsed.
+pub struct WaitTimeoutResult(bool);
+
+impl WaitTimeoutResult {
+    /// Return true if the wait timed out.
+    #[must_use]
+    pub fn timed_out(&self) -> bool {
+        self.0
+    }
+}
+
+/// The error returned by a failed wait on a condition variable,
+/// which carries the guard to the re-acquired mutex.
+pub struct WaitError<'a, T: ?Sized + 'a> {
+    guard: MutexGuard<'a, T>,
+    error: Error,
+}
+
+impl<'a, T: ?Sized> WaitError<'a, T> {
+    fn new(guard: MutexGuard<'a, T>, errno: i32) -> Self {
+        Self {
+            guard,
+            error: Error::from_raw_os_error(errno),
+        }
+    }
+    /// Return the cause of the failure.
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+    /// Take back the guard, keeping the mutex locked.
+    pub fn into_guard(self) -> MutexGuard<'a, T> {
+        self.guard
+    }
+    /// Split into the guard and the cause of the failure.
+    pub fn into_parts(self) -> (MutexGuard<'a, T>, Error) {
+        (self.guard, self.error)
+    }
+}
+
+impl<'a, T: ?Sized> fmt::Debug for WaitError<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WaitError").field("error", &self.error).finish_non_exhaustive()
+    }
+}
+
+impl<'a, T: ?Sized> fmt::Display for WaitError<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<'a, T: ?Sized> From<WaitError<'a, T>> for Error {
+    fn from(e: WaitError<'a, T>) -> Self {
+        e.error
+    }
+}
+
+/// A condition variable.
+pub struct Condvar(UnsafeCell<evl_event>, CoreClock);
+
+unsafe impl Send for Condvar {}
+unsafe impl Sync for Condvar {}
+
+impl Condvar {
+    /// Create a condition variable, retrieving the settings from a
+    /// [`builder struct`](Builder).
+    ///
+    /// # Errors
+    ///
+    /// * [`NameConflict`][`crate::Error::NameConflict`] means the
+    /// name is conflicting with an existing element name.
+    ///
+    /// * [`NoCore`][`crate::Error::NoCore`] means that the EVL core is
+    /// not enabled in the kernel, [`AbiMismatch`][`crate::Error::AbiMismatch`]
+    /// that there is an ABI mismatch with the EVL core.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use revl::condvar::{Builder, Condvar};
+    ///
+    /// fn create_a_condvar() -> Result<Condvar, revl::Error> {
+    ///     Builder::new().name("some_condvar").public().create()
+    /// }
+    /// ```
+    pub fn new(builder: Builder) -> Result<Self, Error> {
+        let clock = builder.clock.unwrap_or(STEADY_CLOCK);
+        let c_clockfd = clock.0 as i32;
+        let this = Self(UnsafeCell::new(unsafe {
+            MaybeUninit::<evl_event>::zeroed().assume_init()
+        }), clock);
+        let mut c_flags = CloneFlags::PRIVATE.bits() as c_int;
+        if builder.visible {
+            c_flags = CloneFlags::PUBLIC.bits() as c_int;
+        }
+        let ret: c_int = unsafe {
+            if let Some(name) = builder.name {
+                let c_name = CString::new(name).expect("CString::new failed");
+                let c_fmt = CString::new("%s").expect("CString::new failed");
+                evl_create_event(
+                    this.0.get(),
+                    c_clockfd,
+                    c_flags,
+                    c_fmt.as_ptr(),
+                    c_name.as_ptr(),
+                )
+            } else {
+                evl_create_event(this.0.get(),
+                               c_clockfd,
+                               c_flags,
+                               ptr::null())
+            }
+        };
+        match ret {
+            0.. => return Ok(this),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    /// Wait for the condition variable to be notified, releasing
+    /// the mutex held by `guard` while asleep. The mutex is locked
+    /// again on return.
+    ///
+    /// Like with any condition variable, spurious wakeups may
+    /// happen, so the condition should be checked again on return,
+    /// which [`wait_while()`](Self::wait_while) does.
+    ///
+    /// # Errors
+    ///
+    /// On failure, the mutex is locked again and the guard is handed
+    /// back with the error in a [`WaitError`], so that the caller may
+    /// retry the wait e.g. after [`Interrupted`][`crate::Error::Interrupted`].
+    /// Using the `?` operator converts it to a plain [`Error`],
+    /// releasing the mutex.
+    ///
+    /// ```no_run
+    /// use revl::mutex::Mutex;
+    /// use revl::condvar::Condvar;
+    /// use revl::Error;
+    ///
+    /// fn wait_retry(m: &Mutex<bool>, cv: &Condvar) -> Result<(), Error> {
+    ///     let mut guard = m.lock()?;
+    ///     while !*guard {
+    ///         guard = match cv.wait(guard) {
+    ///             Ok(guard) => guard,
+    ///             Err(e) if matches!(e.error(), Error::Interrupted) => e.into_guard(),
+    ///             Err(e) => return Err(e.into()),
+    ///         };
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>
+    ) -> Result<MutexGuard<'a, T>, WaitError<'a, T>> {
+        let ret: c_int = unsafe {
+            evl_wait_event(self.0.get(),
+                           guard.as_raw_mut())
+        };
+        match ret {
+            0.. => return Ok(guard),
+            _ => return Err(WaitError::new(guard, -ret)),
+        };
+    }
+    /// Wait on the condition variable as long as `condition` returns
+    /// true.
+    pub fn wait_while<'a, T, F>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: F,
+    ) -> Result<MutexGuard<'a, T>, WaitError<'a, T>>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard) {
+            guard = self.wait(guard)?;
+        }
+        Ok(guard)
+    }
+    /// Wait for the condition variable to be notified until the
+    /// absolute `timeout` date is reached, based on the clock of the
+    /// condition variable.
+    pub fn wait_timed<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        timeout: Instant::<CoreClock>,
+    ) -> Result<(MutexGuard<'a, T>, WaitTimeoutResult), WaitError<'a, T>> {
+        let date = clock::instant_to_timespec(timeout);
+        let ret: c_int = unsafe {
+            evl_timedwait_event(self.0.get(), guard.as_raw_mut(), &date)
+        };
+        if ret == -ETIMEDOUT {
+            return Ok((guard, WaitTimeoutResult(true)));
+        }
+        match ret {
+            0.. => return Ok((guard, WaitTimeoutResult(false))),
+            _ => return Err(WaitError::new(guard, -ret)),
+        };
+    }
+    /// Wait on the condition variable as long as `condition` returns
+    /// true, until the absolute `timeout` date is reached.
+    pub fn wait_timed_while<'a, T, F>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        timeout: Instant::<CoreClock>,
+        mut condition: F,
+    ) -> Result<(MutexGuard<'a, T>, WaitTimeoutResult), WaitError<'a, T>>
+    where F: FnMut(&mut T) -> bool
+    {
+        loop {
+            if !condition(&mut *guard) {
+                return Ok((guard, WaitTimeoutResult(false)));
+            }
+            let result = self.wait_timed(guard, timeout)?;
+            if result.1.0 {
+                return Ok(result);
+            }
+            guard = result.0;
+        }
+    }
+    /// Wait for the condition variable to be notified for at most
+    /// `delay`, which is converted to an absolute date based on the
+    /// clock of the condition variable.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use revl::mutex::Mutex;
+    /// use revl::condvar::Condvar;
+    ///
+    /// fn poll(m: &Mutex<u32>, cv: &Condvar) -> Result<(), revl::Error> {
+    ///     let guard = m.lock()?;
+    ///     let (_guard, res) = cv.wait_for(guard, Duration::from_micros(500))?;
+    ///     if res.timed_out() {
+    ///         println!("no notification");
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn wait_for<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        delay: Duration,
+    ) -> Result<(MutexGuard<'a, T>, WaitTimeoutResult), WaitError<'a, T>> {
+        self.wait_timed(guard, self.deadline(delay))
+    }
+    /// Wait on the condition variable as long as `condition` returns
+    /// true, for at most `delay` overall.
+    pub fn wait_timeout_while<'a, T, F>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        delay: Duration,
+        condition: F,
+    ) -> Result<(MutexGuard<'a, T>, WaitTimeoutResult), WaitError<'a, T>>
+    where F: FnMut(&mut T) -> bool
+    {
+        self.wait_timed_while(guard, self.deadline(delay), condition)
+    }
+    fn deadline(&self, delay: Duration) -> Instant<CoreClock> {
+        self.1.now() + Nanoseconds(delay.as_nanos() as u64)
+    }
+    /// Wake up the waiter leading the wait queue, if any. Waiters
+    /// are queued by order of scheduling priority.
+    ///
+    /// # Errors
+    ///
+    /// The notification is not sent if an error is returned, which
+    /// the caller should handle, since a waiter might otherwise sleep
+    /// indefinitely.
+    pub fn notify_one(&self) -> Result<(), Error> {
+        let ret: c_int = unsafe { evl_signal_event(self.0.get()) };
+        match ret {
+            0 => return Ok(()),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    /// Wake up all waiters.
+    ///
+    /// # Errors
+    ///
+    /// See [`notify_one()`](Self::notify_one).
+    pub fn notify_all(&self) -> Result<(), Error> {
+        let ret: c_int = unsafe { evl_broadcast_event(self.0.get()) };
+        match ret {
+            0 => return Ok(()),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    /// Wake up a particular thread waiting on the condition
+    /// variable.
+    pub fn notify_directed(&self, target: &Thread) -> Result<(), Error> {
+        let ret: c_int = unsafe { evl_signal_thread(self.0.get(), target.0) };
+        match ret {
+            0 => return Ok(()),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+}
+
+impl Drop for Condvar {
+    fn drop(&mut self) {
+        unsafe {
+            evl_close_event(self.0.get());
+        }
+    }
+}
+
+/// A mutex bundled with the condition variable which signals changes
+/// to the data it guards.
+///
+/// Both elements are created with the same clock and visibility, so
+/// that the pairing invariant holds by construction. If the builder
+/// names the pair, the mutex is named after it with a `-mutex`
+/// suffix, the condition variable with a `-event` suffix.
+///
+/// # Examples
+///
+/// ```no_run
+/// use revl::condvar::Builder;
+///
+/// let pair = Builder::new().name("work").create_pair(0u32).unwrap();
+///
+/// // Consumer side.
+/// let guard = pair.lock().unwrap();
+/// let mut guard = pair.wait_while(guard, |pending| *pending == 0).unwrap();
+/// *guard -= 1;
+/// drop(guard);
+///
+/// // Producer side.
+/// *pair.lock().unwrap() += 1;
+/// pair.notify_one().unwrap();
+/// ```
+pub struct CondvarPair<T> {
+    mutex: Mutex<T>,
+    condvar: Condvar,
+}
+
+impl<T> CondvarPair<T> {
+    /// Create a mutex guarding `data` and its condition variable,
+    /// retrieving the common settings from a [`builder
+    /// struct`](Builder).
+    pub fn new(data: T, builder: Builder) -> Result<Self, Error> {
+        let mut mbuilder = mutex::Builder::new();
+        let mut cbuilder = Builder::new();
+        if let Some(name) = &builder.name {
+            mbuilder = mbuilder.name(&format!("{}-mutex", name));
+            cbuilder = cbuilder.name(&format!("{}-event", name));
+        }
+        if builder.visible {
+            mbuilder = mbuilder.public();
+            cbuilder = cbuilder.public();
+        }
+        if let Some(clock) = builder.clock {
+            mbuilder = mbuilder.clock(CoreClock(clock.0));
+            cbuilder = cbuilder.clock(clock);
+        }
+        Ok(Self {
+            mutex: Mutex::new(data, mbuilder)?,
+            condvar: cbuilder.create()?,
+        })
+    }
+    /// Lock the mutex, see [`Mutex::lock()`].
+    pub fn lock(&self) -> Result<MutexGuard<T>, Error> {
+        self.mutex.lock()
+    }
+    /// Wait for a notification, see [`Condvar::wait()`]. `guard`
+    /// must have been obtained from [`lock()`](Self::lock).
+    pub fn wait<'a>(&self, guard: MutexGuard<'a, T>
+    ) -> Result<MutexGuard<'a, T>, WaitError<'a, T>> {
+        self.condvar.wait(guard)
+    }
+    /// Wait as long as `condition` returns true, see
+    /// [`Condvar::wait_while()`].
+    pub fn wait_while<'a, F>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        condition: F,
+    ) -> Result<MutexGuard<'a, T>, WaitError<'a, T>>
+    where F: FnMut(&mut T) -> bool
+    {
+        self.condvar.wait_while(guard, condition)
+    }
+    /// Wait for a notification until an absolute date, see
+    /// [`Condvar::wait_timed()`].
+    pub fn wait_timed<'a>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        timeout: Instant::<CoreClock>,
+    ) -> Result<(MutexGuard<'a, T>, WaitTimeoutResult), WaitError<'a, T>> {
+        self.condvar.wait_timed(guard, timeout)
+    }
+    /// Wait for a notification for at most `delay`, see
+    /// [`Condvar::wait_for()`].
+    pub fn wait_for<'a>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        delay: Duration,
+    ) -> Result<(MutexGuard<'a, T>, WaitTimeoutResult), WaitError<'a, T>> {
+        self.condvar.wait_for(guard, delay)
+    }
+    /// Wait as long as `condition` returns true, for at most `delay`
+    /// overall, see [`Condvar::wait_timeout_while()`].
+    pub fn wait_timeout_while<'a, F>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        delay: Duration,
+        condition: F,
+    ) -> Result<(MutexGuard<'a, T>, WaitTimeoutResult), WaitError<'a, T>>
+    where F: FnMut(&mut T) -> bool
+    {
+        self.condvar.wait_timeout_while(guard, delay, condition)
+    }
+    /// Wake up the leading waiter, see [`Condvar::notify_one()`].
+    pub fn notify_one(&self) -> Result<(), Error> {
+        self.condvar.notify_one()
+    }
+    /// Wake up all waiters, see [`Condvar::notify_all()`].
+    pub fn notify_all(&self) -> Result<(), Error> {
+        self.condvar.notify_all()
+    }
+    /// Return the inner mutex.
+    pub fn mutex(&self) -> &Mutex<T> {
+        &self.mutex
+    }
+    /// Return the inner condition variable.
+    pub fn condvar(&self) -> &Condvar {
+        &self.condvar
+    }
+}
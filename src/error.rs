@@ -0,0 +1,156 @@
+//! Error type.
+//!
+//! The EVL services report failures as negated `errno` values. Some of
+//! these values carry a specific meaning in the context of the EVL
+//! core, which the [`Error`] type conveys by dedicated variants. Any
+//! other status is passed on as a plain [`std::io::Error`].
+
+use std::error;
+use std::fmt;
+use std::io::{self, ErrorKind};
+use libc::{
+    EAGAIN,
+    EBADFD,
+    EEXIST,
+    EINTR,
+    ENOEXEC,
+    ENOSYS,
+    EPERM,
+    ETIMEDOUT,
+};
+
+/// The error type for the operations of this crate.
+#[derive(Debug)]
+pub enum Error {
+    /// The calling thread is not attached to the EVL core, or the
+    /// target element does not belong to an attached thread.
+    NotAttached,
+    /// The element name conflicts with an existing element.
+    NameConflict,
+    /// There is an ABI mismatch between the underlying [evl-sys
+    /// crate](https://source.denx.de/Xenomai/xenomai4/evl-sys) and
+    /// the EVL core. See [these
+    /// explanations](https://evlproject.org/core/under-the-hood/abi/).
+    AbiMismatch,
+    /// The EVL core is not enabled in the running kernel.
+    NoCore,
+    /// The caller lacks the privileges required by the operation,
+    /// such as locking memory. The EVL core also reports calls to
+    /// out-of-band services from a stage they are not allowed from
+    /// this way.
+    PermissionDenied,
+    /// The operation timed out.
+    TimedOut,
+    /// The operation would block.
+    WouldBlock,
+    /// The operation was interrupted, e.g. by
+    /// [`Thread::unblock()`][`crate::thread::Thread::unblock`].
+    Interrupted,
+    /// The lock was poisoned by a thread which panicked while
+    /// holding it.
+    Poisoned,
+    /// Any other error.
+    Io(io::Error),
+}
+
+impl Error {
+    /// Build an error from an `errno` value.
+    pub fn from_raw_os_error(errno: i32) -> Self {
+        match errno {
+            EBADFD => Error::NotAttached,
+            EEXIST => Error::NameConflict,
+            ENOEXEC => Error::AbiMismatch,
+            ENOSYS => Error::NoCore,
+            EPERM => Error::PermissionDenied,
+            ETIMEDOUT => Error::TimedOut,
+            EAGAIN => Error::WouldBlock,
+            EINTR => Error::Interrupted,
+            _ => Error::Io(io::Error::from_raw_os_error(errno)),
+        }
+    }
+    /// Build an error from the last `errno` value of the calling
+    /// thread.
+    pub fn last_os_error() -> Self {
+        io::Error::last_os_error().into()
+    }
+    /// Build a custom error of the given kind.
+    pub fn new<E>(kind: ErrorKind, error: E) -> Self
+    where E: Into<Box<dyn error::Error + Send + Sync>>
+    {
+        Error::Io(io::Error::new(kind, error))
+    }
+    /// Return the `errno` value this error corresponds to, if any.
+    pub fn raw_os_error(&self) -> Option<i32> {
+        match self {
+            Error::NotAttached => Some(EBADFD),
+            Error::NameConflict => Some(EEXIST),
+            Error::AbiMismatch => Some(ENOEXEC),
+            Error::NoCore => Some(ENOSYS),
+            Error::PermissionDenied => Some(EPERM),
+            Error::TimedOut => Some(ETIMEDOUT),
+            Error::WouldBlock => Some(EAGAIN),
+            Error::Interrupted => Some(EINTR),
+            Error::Poisoned => None,
+            Error::Io(e) => e.raw_os_error(),
+        }
+    }
+    /// Return the corresponding [`std::io::ErrorKind`].
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::NameConflict => ErrorKind::AlreadyExists,
+            Error::PermissionDenied => ErrorKind::PermissionDenied,
+            Error::TimedOut => ErrorKind::TimedOut,
+            Error::WouldBlock => ErrorKind::WouldBlock,
+            Error::Interrupted => ErrorKind::Interrupted,
+            Error::Io(e) => e.kind(),
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotAttached => write!(f, "thread not attached to the EVL core"),
+            Error::NameConflict => write!(f, "element name already in use"),
+            Error::AbiMismatch => write!(f, "ABI mismatch with the EVL core"),
+            Error::NoCore => write!(f, "EVL core not available"),
+            Error::PermissionDenied => write!(f, "permission denied"),
+            Error::TimedOut => write!(f, "operation timed out"),
+            Error::WouldBlock => write!(f, "operation would block"),
+            Error::Interrupted => write!(f, "operation interrupted"),
+            Error::Poisoned => write!(f, "poisoned lock: another thread panicked while holding it"),
+            Error::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        match e.raw_os_error() {
+            Some(errno) => Error::from_raw_os_error(errno),
+            None => Error::Io(e),
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            e => match e.raw_os_error() {
+                Some(errno) => io::Error::from_raw_os_error(errno),
+                None => io::Error::new(e.kind(), e),
+            },
+        }
+    }
+}
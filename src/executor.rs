@@ -0,0 +1,283 @@
+//! Cyclic executor for time-triggered tasks.
+//!
+//! A [`Cyclic`] executor runs a set of periodic [`Task`]s, each in
+//! its own EVL thread released by its own EVL [timer](crate::timer).
+//! All tasks share a common time origin, so that the offset of each
+//! task within its period is preserved, e.g. to lay out the phases of
+//! a control loop in a rate-monotonic fashion: the highest priorities
+//! should go to the shortest periods.
+//!
+//! The executor detects two kinds of [`Overrun`]s: release points a
+//! task missed because its previous cycle was still running, and
+//! cycles exceeding the execution time budget (WCET) of the task.
+//! Overruns are counted in the [statistics](TaskStats) of each task,
+//! and passed to the callback given to the [builder](Builder).
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use revl::executor::{self, Task};
+//!
+//! let mut exec = executor::Builder::new()
+//!     .on_overrun(|task, overrun| revl::rt_eprintln!("{}: {:?}", task, overrun))
+//!     .create();
+//!
+//! exec.add(Task::new("control", Duration::from_micros(500))
+//!          .priority(90)
+//!          .budget(Duration::from_micros(150)),
+//!          |_cycle| {
+//!              // Read sensors, compute, drive actuators.
+//!          }).unwrap();
+//! exec.add(Task::new("logging", Duration::from_millis(10))
+//!          .offset(Duration::from_micros(250))
+//!          .priority(50),
+//!          |_cycle| {
+//!              // Flush telemetry.
+//!          }).unwrap();
+//!
+//! exec.start();
+//! std::thread::sleep(Duration::from_secs(10));
+//! exec.stop();
+//! exec.join_timeout(Duration::from_secs(1)).unwrap();
+//! println!("control: {:?}", exec.stats(0));
+//! ```
+
+use std::sync::{
+    Arc,
+    atomic::AtomicU64,
+    atomic::Ordering::Acquire,
+    atomic::Ordering::Relaxed,
+    atomic::Ordering::Release,
+};
+use std::thread as std_thread;
+use std::time::Duration;
+use embedded_time::Instant;
+use crate::clock::STEADY_CLOCK;
+use crate::sched::SchedFifo;
+use crate::thread::{self, Group, Thread};
+use crate::timer::Timer;
+use crate::Error;
+
+/// The timing properties of a periodic task.
+#[derive(Debug, Clone)]
+pub struct Task {
+    name: String,
+    period: Duration,
+    offset: Duration,
+    priority: i32,
+    budget: Option<Duration>,
+    cpu: Option<usize>,
+}
+
+impl Task {
+    /// Describe a task named `name` released every `period`. This
+    /// name is also given to the thread running the task.
+    pub fn new(name: &str, period: Duration) -> Self {
+        Self {
+            name: name.to_string(),
+            period,
+            offset: Duration::ZERO,
+            priority: 1,
+            budget: None,
+            cpu: None,
+        }
+    }
+    /// Set the offset of the release points from the time origin of
+    /// the executor. This is zero by default.
+    pub fn offset(mut self, offset: Duration) -> Self {
+        self.offset = offset;
+        self
+    }
+    /// Set the SCHED_FIFO priority of the task, 1 by default.
+    pub fn priority(mut self, prio: i32) -> Self {
+        self.priority = prio;
+        self
+    }
+    /// Set the execution time budget of a cycle, which is unlimited
+    /// by default.
+    pub fn budget(mut self, budget: Duration) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+    /// Pin the task to `cpu`, which should be part of the out-of-band
+    /// CPU set.
+    pub fn cpu(mut self, cpu: usize) -> Self {
+        self.cpu = Some(cpu);
+        self
+    }
+}
+
+/// An overrun condition, as passed to the overrun callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overrun {
+    /// The task missed `count` release points because its previous
+    /// cycle was still running.
+    Deadline { count: u64 },
+    /// The last cycle of the task ran for `elapsed`, beyond its
+    /// budget.
+    Budget { elapsed: Duration },
+}
+
+/// A snapshot of the statistics of a task, see [`Cyclic::stats()`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TaskStats {
+    /// The number of cycles run.
+    pub cycles: u64,
+    /// The number of release points missed.
+    pub missed: u64,
+    /// The number of cycles which exceeded the budget.
+    pub over_budget: u64,
+    /// The longest execution time of a cycle.
+    pub max_elapsed: Duration,
+}
+
+#[derive(Default)]
+struct Counters {
+    cycles: AtomicU64,
+    missed: AtomicU64,
+    over_budget: AtomicU64,
+    max_elapsed: AtomicU64,
+}
+
+type Callback = Arc<dyn Fn(&str, Overrun) + Send + Sync>;
+
+pub struct Builder {
+    lead: Duration,
+    callback: Option<Callback>,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            lead: Duration::from_millis(10),
+            callback: None,
+        }
+    }
+    /// Set the delay between starting the executor and its time
+    /// origin, which leaves room for all tasks to arm their timer.
+    /// This is 10 ms by default.
+    pub fn lead(mut self, lead: Duration) -> Self {
+        self.lead = lead;
+        self
+    }
+    /// Call `callback` with the name of the task on every overrun.
+    /// The callback runs out-of-band from the thread of the task, so
+    /// it should be short and not issue in-band system calls.
+    pub fn on_overrun<F>(mut self, callback: F) -> Self
+    where F: Fn(&str, Overrun) + Send + Sync + 'static
+    {
+        self.callback = Some(Arc::new(callback));
+        self
+    }
+    pub fn create(self) -> Cyclic {
+        Cyclic::new(self)
+    }
+}
+
+/// A cyclic executor, see the [module documentation](crate::executor).
+/// Dropping the executor stops the tasks.
+pub struct Cyclic {
+    group: Group<()>,
+    counters: Vec<Arc<Counters>>,
+    // Time origin in nanoseconds, published on start.
+    epoch: Arc<AtomicU64>,
+    lead: Duration,
+    callback: Option<Callback>,
+}
+
+impl Cyclic {
+    /// Create an executor with no task, retrieving the settings from
+    /// a [`builder struct`](Builder).
+    pub fn new(builder: Builder) -> Self {
+        Self {
+            group: Group::new(),
+            counters: Vec::new(),
+            epoch: Arc::new(AtomicU64::new(0)),
+            lead: builder.lead,
+            callback: builder.callback,
+        }
+    }
+    /// Spawn the thread running `task`, which calls `f` with the
+    /// cycle count at every release point once the executor is
+    /// started. Returns the index of the task, in order of addition.
+    /// See [`thread::Builder::spawn()`] and [`Thread::set_sched()`]
+    /// for the possible errors.
+    pub fn add<F>(&mut self, task: Task, mut f: F) -> Result<usize, Error>
+    where F: FnMut(u64) + Send + 'static
+    {
+        let mut builder = thread::Builder::new().name(&task.name);
+        if let Some(cpu) = task.cpu {
+            builder = builder.cpu(cpu);
+        }
+        let timer = Timer::new(&STEADY_CLOCK)?;
+        let counters = Arc::new(Counters::default());
+        let c_counters = counters.clone();
+        let epoch = self.epoch.clone();
+        let callback = self.callback.clone();
+        let priority = task.priority;
+        let setup = move |me: &Thread| me.set_sched(SchedFifo { prio: priority });
+        self.group.add_setup(builder, setup, move |_me, stop| {
+            let report = |overrun| {
+                if let Some(callback) = &callback {
+                    callback(&task.name, overrun);
+                }
+            };
+            let start = epoch.load(Acquire) + task.offset.as_nanos() as u64;
+            if timer.start_at(Instant::new(start), Some(task.period)).is_err() {
+                return;
+            }
+            let budget = task.budget.map(|b| b.as_nanos() as u64);
+            let mut cycle = 0;
+            while !stop.is_stopped() {
+                let missed = match timer.wait() {
+                    Ok(missed) => missed,
+                    Err(_) => break,
+                };
+                if missed > 0 {
+                    c_counters.missed.fetch_add(missed, Relaxed);
+                    report(Overrun::Deadline { count: missed });
+                }
+                let t0 = now();
+                f(cycle);
+                let elapsed = now() - t0;
+                cycle += 1;
+                c_counters.cycles.fetch_add(1, Relaxed);
+                c_counters.max_elapsed.fetch_max(elapsed, Relaxed);
+                if budget.is_some_and(|b| elapsed > b) {
+                    c_counters.over_budget.fetch_add(1, Relaxed);
+                    report(Overrun::Budget { elapsed: Duration::from_nanos(elapsed) });
+                }
+            }
+        })?;
+        self.counters.push(counters);
+        Ok(self.counters.len() - 1)
+    }
+    /// Set the time origin of the executor, then release all tasks.
+    pub fn start(&self) {
+        self.epoch.store(now() + self.lead.as_nanos() as u64, Release);
+        self.group.start();
+    }
+    /// Request all tasks to stop, each of them exits after its
+    /// current cycle.
+    pub fn stop(&self) {
+        self.group.stop();
+    }
+    /// Wait for all tasks to exit within `timeout`, see
+    /// [`Group::join_timeout()`].
+    pub fn join_timeout(&mut self, timeout: Duration) -> Result<Vec<std_thread::Result<()>>, Error> {
+        self.group.join_timeout(timeout)
+    }
+    /// Return the statistics of the task at `index`, or `None` if
+    /// there is no such task.
+    pub fn stats(&self, index: usize) -> Option<TaskStats> {
+        self.counters.get(index).map(|c| TaskStats {
+            cycles: c.cycles.load(Relaxed),
+            missed: c.missed.load(Relaxed),
+            over_budget: c.over_budget.load(Relaxed),
+            max_elapsed: Duration::from_nanos(c.max_elapsed.load(Relaxed)),
+        })
+    }
+}
+
+fn now() -> u64 {
+    STEADY_CLOCK.now().duration_since_epoch().integer()
+}
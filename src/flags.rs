@@ -9,26 +9,35 @@
 
 use std::cell::UnsafeCell;
 use std::ffi::CString;
-use std::io::Error;
 use std::mem::MaybeUninit;
+use std::os::fd::{AsRawFd, RawFd};
 use std::os::raw::c_int;
+use std::marker::PhantomData;
 use std::ptr;
 use evl_sys::{
     evl_flags,
     evl_create_flags,
+    evl_open_flags,
     evl_close_flags,
     evl_wait_flags,
+    evl_wait_some_flags,
+    evl_wait_exact_flags,
+    evl_timedwait_flags,
     evl_trywait_flags,
     evl_peek_flags,
     evl_post_flags,
-    BuiltinClock,
     CloneFlags,
 };
+use libc::EAGAIN;
+use embedded_time::Instant;
+use crate::clock::{instant_to_timespec, CoreClock, STEADY_CLOCK};
+use crate::Error;
 
 pub struct Builder {
     name: Option<String>,
     visible: bool,
     initval: u32,
+    clock: Option<CoreClock>,
 }
 
 impl Builder {
@@ -37,6 +46,7 @@ impl Builder {
             name: None,
             visible: false,
             initval: 0u32,
+            clock: None,
         }
     }
     pub fn name(mut self, name: &str) -> Self {
@@ -55,32 +65,100 @@ impl Builder {
         self.initval = initval;
         self
     }
+    /// Set the clock timing the waits. This is the monotonic clock
+    /// by default.
+    pub fn clock(mut self, clock: CoreClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
     pub fn create(self) -> Result<Flags, Error> {
         Flags::new(self)
     }
+    /// Create a flag group carrying the user-defined bit type `B`,
+    /// see [`Bits`].
+    pub fn create_typed<B: Bits>(self) -> Result<Flags<B>, Error> {
+        Flags::new(self)
+    }
 }
 
-pub struct Flags(UnsafeCell<evl_flags>);
+/// A type which converts to and from the 32bit value of a flag
+/// group. This is implemented for `u32`, and by the
+/// [`flag_bits!`][`crate::flag_bits`] macro for types generated by
+/// [`bitflags!`](https://docs.rs/bitflags), so that the events
+/// posted to and received from a flag group are type-checked.
+pub trait Bits: Copy {
+    /// Convert to the raw value posted to the flag group.
+    fn into_bits(self) -> u32;
+    /// Convert from the raw value received from the flag group.
+    fn from_bits(bits: u32) -> Self;
+}
 
-unsafe impl Send for Flags {}
-unsafe impl Sync for Flags {}
+impl Bits for u32 {
+    fn into_bits(self) -> u32 {
+        self
+    }
+    fn from_bits(bits: u32) -> Self {
+        bits
+    }
+}
 
-impl Flags {
+/// Implement [`Bits`][`crate::flags::Bits`] for a type generated by
+/// `bitflags!` with a `u32` representation. Undefined bits received
+/// from a flag group are dropped.
+///
+/// ```no_run
+/// use bitflags::bitflags;
+/// use revl::flags::{Builder, Flags};
+///
+/// bitflags! {
+///     pub struct MyEvents: u32 {
+///         const SENSOR_READY = 1 << 0;
+///         const SHUTDOWN = 1 << 1;
+///     }
+/// }
+/// revl::flag_bits!(MyEvents);
+///
+/// let fgroup: Flags<MyEvents> = Builder::new().create_typed().unwrap();
+/// fgroup.post(MyEvents::SENSOR_READY).unwrap();
+/// if fgroup.wait().unwrap().contains(MyEvents::SHUTDOWN) {
+///     println!("shutting down");
+/// }
+/// ```
+#[macro_export]
+macro_rules! flag_bits {
+    ($t:ty) => {
+        impl $crate::flags::Bits for $t {
+            fn into_bits(self) -> u32 {
+                self.bits()
+            }
+            fn from_bits(bits: u32) -> Self {
+                <$t>::from_bits_truncate(bits)
+            }
+        }
+    };
+}
+
+pub struct Flags<B: Bits = u32>(UnsafeCell<evl_flags>, CoreClock, PhantomData<B>);
+
+unsafe impl<B: Bits> Send for Flags<B> {}
+unsafe impl<B: Bits> Sync for Flags<B> {}
+
+impl<B: Bits> Flags<B> {
     /// Create an EVL event flag group, retrieving the settings from a
     /// [`builder struct`](Builder).
     ///
     /// # Errors
     ///
-    /// * [`AlreadyExists`][`std::io::ErrorKind`] means the group name
+    /// * [`NameConflict`][`crate::Error::NameConflict`] means the group name
     /// is conflicting with an existing group name.
     ///
     /// * [`InvalidInput`][`std::io::ErrorKind`] means that the group
     /// name contains invalid characters: such name must contain only
     /// valid characters in the context of a Linux file name.
     ///
-    /// * `Other`][`std::io::ErrorKind`] may mean that either the EVL
-    /// core is not enabled in the kernel, or there is an ABI mismatch
-    /// between the underlying [evl-sys
+    /// * [`NoCore`][`crate::Error::NoCore`] means that the EVL core is
+    /// not enabled in the kernel, [`AbiMismatch`][`crate::Error::AbiMismatch`]
+    /// that there is an ABI mismatch between the underlying [evl-sys
     /// crate](https://source.denx.de/Xenomai/xenomai4/evl-sys) and
     /// the EVL core. See [these
     /// explanations](https://evlproject.org/core/under-the-hood/abi/)
@@ -91,7 +169,7 @@ impl Flags {
     /// ```no_run
     /// use revl::flags::{Builder, Flags};
     ///
-    /// fn create_a_flag_group(initval: u32) -> Result<Flags, std::io::Error> {
+    /// fn create_a_flag_group(initval: u32) -> Result<Flags, revl::Error> {
     ///     let props = Builder::new().name("some_event_flags").public().init_value(initval);
     ///     let me = Flags::new(props)?;
     ///     Ok(me)
@@ -103,23 +181,23 @@ impl Flags {
     /// ```no_run
     /// use revl::flags::{Builder, Flags};
     ///
-    /// fn create_a_flag_group(initval: u32) -> Result<Flags, std::io::Error> {
+    /// fn create_a_flag_group(initval: u32) -> Result<Flags, revl::Error> {
     ///     let me = Builder::new().name("some_event_flags").public().init_value(initval).create()?;
     ///     Ok(me)
     /// }
     /// ```
     ///
     pub fn new(builder: Builder) -> Result<Self, Error> {
+        let clock = builder.clock.unwrap_or(STEADY_CLOCK);
+        let c_clockfd = clock.0 as i32;
         let this = Self(UnsafeCell::new(unsafe {
             MaybeUninit::<evl_flags>::zeroed().assume_init()
-        }));
+        }), clock, PhantomData);
         let mut c_flags = CloneFlags::PRIVATE.bits() as c_int;
         if builder.visible {
             c_flags = CloneFlags::PUBLIC.bits() as c_int;
         }
         let c_initval = builder.initval as i32;
-        // Revisit: this is too restrictive.
-        let c_clockfd = BuiltinClock::MONOTONIC as i32;
         let ret: c_int = unsafe {
             if let Some(name) = builder.name {
                 let c_name = CString::new(name).expect("CString::new failed");
@@ -145,6 +223,38 @@ impl Flags {
             _ => return Err(Error::from_raw_os_error(-ret)),
         };
     }
+    /// Open an existing public flag group by name, which may have
+    /// been created by another process. The clock of the flag group
+    /// cannot be retrieved, the monotonic clock is assumed.
+    ///
+    /// # Errors
+    ///
+    /// [`NotFound`][`std::io::ErrorKind`] is returned if there is no
+    /// public flag group with such name.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use revl::flags::Flags;
+    ///
+    /// // Post a command to the flag group of a real-time process.
+    /// let cmd: Flags = Flags::open("rt_commands").unwrap();
+    /// cmd.post(1 << 3).unwrap();
+    /// ```
+    pub fn open(name: &str) -> Result<Self, Error> {
+        let this = Self(UnsafeCell::new(unsafe {
+            MaybeUninit::<evl_flags>::zeroed().assume_init()
+        }), STEADY_CLOCK, PhantomData);
+        let c_name = CString::new(name).expect("CString::new failed");
+        let c_fmt = CString::new("%s").expect("CString::new failed");
+        let ret: c_int = unsafe {
+            evl_open_flags(this.0.get(), c_fmt.as_ptr(), c_name.as_ptr())
+        };
+        match ret {
+            0.. => return Ok(this),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
     /// Wait for events on a flag group.
     ///
     /// Waits for events to be available from the flag group. The
@@ -160,65 +270,147 @@ impl Flags {
     /// ```no_run
     /// use revl::flags::Flags;
     ///
-    /// fn wait_flags(fgroup: &Flags) -> Result<u32, std::io::Error> {
+    /// fn wait_flags(fgroup: &Flags) -> Result<u32, revl::Error> {
     ///     fgroup.wait()
     /// }
     ///
-    pub fn wait(&self) -> Result<u32, Error> {
+    pub fn wait(&self) -> Result<B, Error> {
 	let mut mask = MaybeUninit::<i32>::uninit();
         let ret: c_int = unsafe { evl_wait_flags(self.0.get(), mask.as_mut_ptr()) };
         match ret {
-            0 => return Ok(unsafe { mask.assume_init() } as u32),
+            0 => return Ok(B::from_bits(unsafe { mask.assume_init() } as u32)),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    /// Wait for any of the events in `mask`.
+    ///
+    /// Unlike [`wait()`](Self::wait), only the bits from `mask` are
+    /// considered and consumed, others are left pending for other
+    /// consumers. The subset of `mask` which was received is
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use revl::flags::Flags;
+    ///
+    /// const RX_READY: u32 = 1 << 0;
+    /// const TX_DONE: u32 = 1 << 1;
+    ///
+    /// fn wait_io(fgroup: &Flags) -> Result<u32, revl::Error> {
+    ///     fgroup.wait_any(RX_READY | TX_DONE)
+    /// }
+    /// ```
+    pub fn wait_any(&self, mask: B) -> Result<B, Error> {
+	let mut bits = MaybeUninit::<i32>::uninit();
+        let ret: c_int = unsafe {
+            evl_wait_some_flags(self.0.get(), mask.into_bits() as i32, bits.as_mut_ptr())
+        };
+        match ret {
+            0 => return Ok(B::from_bits(unsafe { bits.assume_init() } as u32)),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    /// Wait for all of the events in `mask` to be pending, then
+    /// consume them at once. Bits outside of `mask` are left pending.
+    pub fn wait_all(&self, mask: B) -> Result<(), Error> {
+        let ret: c_int = unsafe { evl_wait_exact_flags(self.0.get(), mask.into_bits() as i32) };
+        match ret {
+            0 => return Ok(()),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    /// Wait for events on a flag group until the absolute `timeout`
+    /// date is reached, based on the clock of the flag group.
+    ///
+    /// # Errors
+    ///
+    /// [`TimedOut`][`crate::Error::TimedOut`] is returned if no event
+    /// was received by the timeout date.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use embedded_time::duration::Milliseconds;
+    /// use revl::clock::STEADY_CLOCK;
+    /// use revl::flags::Flags;
+    ///
+    /// fn wait_flags_10ms(fgroup: &Flags) -> Result<u32, revl::Error> {
+    ///     fgroup.wait_timed(STEADY_CLOCK.now() + Milliseconds(10u32))
+    /// }
+    /// ```
+    pub fn wait_timed(&self, timeout: Instant<CoreClock>) -> Result<B, Error> {
+	let mut mask = MaybeUninit::<i32>::uninit();
+        let date = instant_to_timespec(timeout);
+        let ret: c_int = unsafe {
+            evl_timedwait_flags(self.0.get(), &date, mask.as_mut_ptr())
+        };
+        match ret {
+            0 => return Ok(B::from_bits(unsafe { mask.assume_init() } as u32)),
             _ => return Err(Error::from_raw_os_error(-ret)),
         };
     }
     /// Try receiving events from a flag group.
     ///
     /// Attempt to read from the flag group, without blocking the
-    /// caller if there is none.
+    /// caller if there is none. `None` is returned if no event is
+    /// pending.
     ///
     /// # Examples
     ///
-    /// ```no_runc
+    /// ```no_run
     /// use revl::flags::Flags;
     ///
-    /// if let Some(bits) = fgroup.try_wait() {
-    ///    println!("ok! got events {}", bits);
-    /// } else {
-    ///    println!("no events pending");
+    /// fn poll_flags(fgroup: &Flags) -> Result<(), revl::Error> {
+    ///     if let Some(bits) = fgroup.try_wait()? {
+    ///         println!("ok! got events {}", bits);
+    ///     } else {
+    ///         println!("no events pending");
+    ///     }
+    ///     Ok(())
     /// }
     /// ```
     ///
-    pub fn try_wait(&self) -> Option<u32> {
+    pub fn try_wait(&self) -> Result<Option<B>, Error> {
 	let mut mask = MaybeUninit::<i32>::uninit();
         let ret: c_int = unsafe { evl_trywait_flags(self.0.get(), mask.as_mut_ptr()) };
         match ret {
-            0 => return Some(unsafe { mask.assume_init() } as u32),
-            _ => return None,
+            0 => return Ok(Some(B::from_bits(unsafe { mask.assume_init() } as u32))),
+            _ if ret == -EAGAIN => return Ok(None),
+            _ => return Err(Error::from_raw_os_error(-ret)),
         };
     }
     /// Read the current value of a flag group.
     ///
     /// Returns the value of the flag group without blocking or
     /// altering its state (i.e. the flag group is not zeroed if some
-    /// events are pending).
+    /// events are pending). `None` is returned if no event is
+    /// pending.
     ///
     /// # Examples
     ///
-    /// ```
-    /// if let Some(bits) = fgroup.peek() {
-    ///    println!("ok! got events {}", bits);
-    /// } else {
-    ///    println!("no events pending");
+    /// ```no_run
+    /// use revl::flags::Flags;
+    ///
+    /// fn show_flags(fgroup: &Flags) -> Result<(), revl::Error> {
+    ///     if let Some(bits) = fgroup.peek()? {
+    ///         println!("events pending: {}", bits);
+    ///     } else {
+    ///         println!("no events pending");
+    ///     }
+    ///     Ok(())
     /// }
     /// ```
     ///
-    pub fn peek(&self) -> Option<u32> {
+    pub fn peek(&self) -> Result<Option<B>, Error> {
 	let mut mask = MaybeUninit::<i32>::uninit();
         let ret: c_int = unsafe { evl_peek_flags(self.0.get(), mask.as_mut_ptr()) };
         match ret {
-            0 => return Some(unsafe { mask.assume_init() } as u32),
-            _ => return None,
+            0 => match unsafe { mask.assume_init() } as u32 {
+                0 => return Ok(None),
+                bits => return Ok(Some(B::from_bits(bits))),
+            },
+            _ => return Err(Error::from_raw_os_error(-ret)),
         };
     }
     /// Post events to a flag group.
@@ -233,12 +425,12 @@ impl Flags {
     /// ```no_run
     /// use revl::flags::Flags;
     ///
-    /// fn post_flags(fgroup: &Flags, bits: u32) -> Result<(), std::io::Error> {
+    /// fn post_flags(fgroup: &Flags, bits: u32) -> Result<(), revl::Error> {
     ///     fgroup.post(bits)
     /// }
     ///
-    pub fn post(&self, bits: u32) -> Result<(), Error> {
-        let c_bits = bits as i32;
+    pub fn post(&self, bits: B) -> Result<(), Error> {
+        let c_bits = bits.into_bits() as i32;
         let ret: c_int = unsafe { evl_post_flags(self.0.get(), c_bits) };
         match ret {
             0 => return Ok(()),
@@ -247,7 +439,13 @@ impl Flags {
     }
 }
 
-impl Drop for Flags {
+impl<B: Bits> AsRawFd for Flags<B> {
+    fn as_raw_fd(&self) -> RawFd {
+        unsafe { (*self.0.get()).u.active.efd }
+    }
+}
+
+impl<B: Bits> Drop for Flags<B> {
     fn drop(&mut self) {
         unsafe {
             evl_close_flags(self.0.get());
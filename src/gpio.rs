@@ -0,0 +1,339 @@
+//! Out-of-band GPIO access.
+//!
+//! The EVL core extends the GPIO character device interface, so
+//! that lines requested with out-of-band capabilities can be driven
+//! and monitored from EVL threads with deterministic latency. A
+//! [`Chip`] is opened from the in-band stage, then hands out
+//! [`LineHandle`]s to read and write line values, and [`LineEvents`]
+//! to wait for edge events, both of which operate out-of-band.
+//!
+//! Edge events are timestamped by the core when the interrupt is
+//! received, based on the monotonic clock, which makes them suitable
+//! for input capture.
+//!
+//! ```no_run
+//! use revl::gpio::{Chip, Edge};
+//!
+//! let chip = Chip::open("/dev/gpiochip0").unwrap();
+//! let strobe = chip.request_output(&[17], &[0], "strobe").unwrap();
+//! let input = chip.request_events(27, Edge::Both, "capture").unwrap();
+//! // From an out-of-band thread:
+//! strobe.set(true).unwrap();
+//! let event = input.wait().unwrap();
+//! println!("{:?} edge at {} ns", event.edge(),
+//!          event.timestamp().duration_since_epoch().integer());
+//! strobe.set(false).unwrap();
+//! ```
+
+use std::ffi::{c_void, CString};
+use std::io::ErrorKind;
+use std::mem;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::os::raw::{c_char, c_int, c_ulong};
+use embedded_time::Instant;
+// The EVL extension enabling out-of-band operations on a request
+// comes from the EVL uapi.
+use evl_sys::{oob_ioctl, oob_read, GPIOHANDLE_REQUEST_OOB};
+use crate::clock::CoreClock;
+use crate::Error;
+
+// GPIO character device ABI (v1), see linux/gpio.h. Some fields are
+// only read by the kernel.
+const GPIOHANDLES_MAX: usize = 64;
+const GPIOHANDLE_REQUEST_INPUT: u32 = 1 << 0;
+const GPIOHANDLE_REQUEST_OUTPUT: u32 = 1 << 1;
+const GPIOEVENT_REQUEST_RISING_EDGE: u32 = 1 << 0;
+const GPIOEVENT_REQUEST_FALLING_EDGE: u32 = 1 << 1;
+const GPIOEVENT_EVENT_RISING_EDGE: u32 = 0x01;
+
+#[repr(C)]
+#[allow(dead_code)]
+struct GpioHandleRequest {
+    lineoffsets: [u32; GPIOHANDLES_MAX],
+    flags: u32,
+    default_values: [u8; GPIOHANDLES_MAX],
+    consumer_label: [c_char; 32],
+    lines: u32,
+    fd: c_int,
+}
+
+#[repr(C)]
+#[allow(dead_code)]
+struct GpioEventRequest {
+    lineoffset: u32,
+    handleflags: u32,
+    eventflags: u32,
+    consumer_label: [c_char; 32],
+    fd: c_int,
+}
+
+#[repr(C)]
+struct GpioHandleData {
+    values: [u8; GPIOHANDLES_MAX],
+}
+
+#[repr(C)]
+struct GpioEventData {
+    timestamp: u64,
+    id: u32,
+}
+
+const fn iowr<T>(nr: c_ulong) -> c_ulong {
+    (3 << 30) | ((mem::size_of::<T>() as c_ulong) << 16) | (0xb4 << 8) | nr
+}
+
+const GPIO_GET_LINEHANDLE_IOCTL: c_ulong = iowr::<GpioHandleRequest>(0x03);
+const GPIO_GET_LINEEVENT_IOCTL: c_ulong = iowr::<GpioEventRequest>(0x04);
+const GPIOHANDLE_GET_LINE_VALUES_IOCTL: c_ulong = iowr::<GpioHandleData>(0x08);
+const GPIOHANDLE_SET_LINE_VALUES_IOCTL: c_ulong = iowr::<GpioHandleData>(0x09);
+
+fn copy_label(label: &str, dst: &mut [c_char; 32]) {
+    for (d, s) in dst.iter_mut().zip(label.bytes().take(31)) {
+        *d = s as c_char;
+    }
+}
+
+/// The edges of a line to monitor, and of the events received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Rising,
+    Falling,
+    /// Monitor both edges. Events always carry a single edge.
+    Both,
+}
+
+/// A GPIO chip, from which lines are requested.
+pub struct Chip(c_int);
+
+unsafe impl Send for Chip {}
+unsafe impl Sync for Chip {}
+
+impl Chip {
+    /// Open the GPIO chip device at `path`, e.g. `/dev/gpiochip0`.
+    pub fn open(path: &str) -> Result<Self, Error> {
+        let c_path = CString::new(path).expect("CString::new failed");
+        let fd = unsafe { libc::open(c_path.as_ptr(), libc::O_RDWR | libc::O_CLOEXEC) };
+        match fd {
+            0.. => return Ok(Self(fd)),
+            _ => return Err(Error::last_os_error()),
+        };
+    }
+    fn request_lines(&self, offsets: &[u32], defaults: &[u8], flags: u32, label: &str) -> Result<LineHandle, Error> {
+        if offsets.is_empty() || offsets.len() > GPIOHANDLES_MAX || defaults.len() > offsets.len() {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid line set"));
+        }
+        let mut req: GpioHandleRequest = unsafe { mem::zeroed() };
+        req.lineoffsets[..offsets.len()].copy_from_slice(offsets);
+        req.default_values[..defaults.len()].copy_from_slice(defaults);
+        req.flags = flags | GPIOHANDLE_REQUEST_OOB;
+        req.lines = offsets.len() as u32;
+        copy_label(label, &mut req.consumer_label);
+        let ret = unsafe { libc::ioctl(self.0, GPIO_GET_LINEHANDLE_IOCTL, &mut req) };
+        match ret {
+            0 => return Ok(LineHandle { fd: req.fd, lines: offsets.len() }),
+            _ => return Err(Error::last_os_error()),
+        };
+    }
+    /// Request the lines at `offsets` as inputs, under the consumer
+    /// name `label`.
+    ///
+    /// # Errors
+    ///
+    /// [`InvalidInput`][`std::io::ErrorKind`] is returned if no line
+    /// or more than 64 lines are requested. The kernel returns
+    /// [`Busy`][`std::io::ErrorKind`] if a line is already requested.
+    pub fn request_input(&self, offsets: &[u32], label: &str) -> Result<LineHandle, Error> {
+        self.request_lines(offsets, &[], GPIOHANDLE_REQUEST_INPUT, label)
+    }
+    /// Request the lines at `offsets` as outputs, driven to
+    /// `defaults` initially, under the consumer name `label`. See
+    /// [`request_input()`](Self::request_input).
+    pub fn request_output(&self, offsets: &[u32], defaults: &[u8], label: &str) -> Result<LineHandle, Error> {
+        self.request_lines(offsets, defaults, GPIOHANDLE_REQUEST_OUTPUT, label)
+    }
+    /// Request edge events on the input line at `offset`, under the
+    /// consumer name `label`.
+    pub fn request_events(&self, offset: u32, edge: Edge, label: &str) -> Result<LineEvents, Error> {
+        let mut req: GpioEventRequest = unsafe { mem::zeroed() };
+        req.lineoffset = offset;
+        req.handleflags = GPIOHANDLE_REQUEST_INPUT | GPIOHANDLE_REQUEST_OOB;
+        req.eventflags = match edge {
+            Edge::Rising => GPIOEVENT_REQUEST_RISING_EDGE,
+            Edge::Falling => GPIOEVENT_REQUEST_FALLING_EDGE,
+            Edge::Both => GPIOEVENT_REQUEST_RISING_EDGE | GPIOEVENT_REQUEST_FALLING_EDGE,
+        };
+        copy_label(label, &mut req.consumer_label);
+        let ret = unsafe { libc::ioctl(self.0, GPIO_GET_LINEEVENT_IOCTL, &mut req) };
+        match ret {
+            0 => return Ok(LineEvents(req.fd)),
+            _ => return Err(Error::last_os_error()),
+        };
+    }
+}
+
+impl AsRawFd for Chip {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+impl AsFd for Chip {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.0) }
+    }
+}
+
+impl Drop for Chip {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.0);
+        }
+    }
+}
+
+/// A set of lines requested for reading or writing their values out
+/// of band, see [`Chip::request_input()`] and [`Chip::request_output()`].
+pub struct LineHandle {
+    fd: c_int,
+    lines: usize,
+}
+
+unsafe impl Send for LineHandle {}
+unsafe impl Sync for LineHandle {}
+
+impl LineHandle {
+    /// Read the values of the lines into `values`, in the order the
+    /// lines were requested. This can be called from the out-of-band
+    /// stage.
+    pub fn get_values(&self, values: &mut [u8]) -> Result<(), Error> {
+        let mut data = GpioHandleData { values: [0; GPIOHANDLES_MAX] };
+        let ret = unsafe {
+            oob_ioctl(self.fd, GPIOHANDLE_GET_LINE_VALUES_IOCTL as _, &mut data as *mut _ as *mut c_void)
+        };
+        match ret {
+            0 => {
+                let n = values.len().min(self.lines);
+                values[..n].copy_from_slice(&data.values[..n]);
+                return Ok(());
+            },
+            _ => return Err(Error::last_os_error()),
+        };
+    }
+    /// Set the values of output lines from `values`, in the order the
+    /// lines were requested. This can be called from the out-of-band
+    /// stage.
+    pub fn set_values(&self, values: &[u8]) -> Result<(), Error> {
+        let mut data = GpioHandleData { values: [0; GPIOHANDLES_MAX] };
+        let n = values.len().min(self.lines);
+        data.values[..n].copy_from_slice(&values[..n]);
+        let ret = unsafe {
+            oob_ioctl(self.fd, GPIOHANDLE_SET_LINE_VALUES_IOCTL as _, &mut data as *mut _ as *mut c_void)
+        };
+        match ret {
+            0 => return Ok(()),
+            _ => return Err(Error::last_os_error()),
+        };
+    }
+    /// Read the value of the first line.
+    pub fn get(&self) -> Result<bool, Error> {
+        let mut value = [0u8];
+        self.get_values(&mut value)?;
+        Ok(value[0] != 0)
+    }
+    /// Set the value of the first line.
+    pub fn set(&self, value: bool) -> Result<(), Error> {
+        self.set_values(&[value as u8])
+    }
+    /// Return the number of lines in the set.
+    pub fn lines(&self) -> usize {
+        self.lines
+    }
+}
+
+impl AsRawFd for LineHandle {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl AsFd for LineHandle {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.fd) }
+    }
+}
+
+impl Drop for LineHandle {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+/// An edge event received on a line.
+#[derive(Debug, Clone, Copy)]
+pub struct EdgeEvent {
+    edge: Edge,
+    timestamp: u64,
+}
+
+impl EdgeEvent {
+    /// Return the edge which was detected, either
+    /// [`Edge::Rising`] or [`Edge::Falling`].
+    pub fn edge(&self) -> Edge {
+        self.edge
+    }
+    /// Return the date the edge was detected, based on the monotonic
+    /// clock.
+    pub fn timestamp(&self) -> Instant<CoreClock> {
+        Instant::new(self.timestamp)
+    }
+}
+
+/// A line monitored for edge events, see [`Chip::request_events()`].
+/// The file descriptor can be added to a [poller](crate::poll::Poller)
+/// to wait for several lines at once.
+pub struct LineEvents(c_int);
+
+unsafe impl Send for LineEvents {}
+unsafe impl Sync for LineEvents {}
+
+impl LineEvents {
+    /// Wait for the next edge event. This must be called from the
+    /// out-of-band stage.
+    pub fn wait(&self) -> Result<EdgeEvent, Error> {
+        let mut data: GpioEventData = unsafe { mem::zeroed() };
+        let ret = unsafe {
+            oob_read(self.0,
+                     &mut data as *mut _ as *mut c_void,
+                     mem::size_of::<GpioEventData>())
+        };
+        match ret {
+            0.. => return Ok(EdgeEvent {
+                edge: if data.id == GPIOEVENT_EVENT_RISING_EDGE { Edge::Rising } else { Edge::Falling },
+                timestamp: data.timestamp,
+            }),
+            _ => return Err(Error::last_os_error()),
+        };
+    }
+}
+
+impl AsRawFd for LineEvents {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+impl AsFd for LineEvents {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.0) }
+    }
+}
+
+impl Drop for LineEvents {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.0);
+        }
+    }
+}
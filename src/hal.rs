@@ -0,0 +1,138 @@
+// embedded-hal trait implementations (embedded-hal feature).
+//
+// Drivers written against embedded-hal 1.0 get their delays from the
+// core clock, and their SPI and GPIO accesses from the out-of-band
+// device wrappers, so that they can run from EVL threads.
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{self, ErrorType as DigitalErrorType, InputPin, OutputPin};
+use embedded_hal::spi::{self, ErrorType as SpiErrorType, Operation, SpiDevice};
+use embedded_time::duration::Nanoseconds;
+use std::io::ErrorKind;
+use crate::clock::CoreClock;
+use crate::gpio::LineHandle;
+use crate::spi::OobSpiDevice;
+use crate::Error;
+
+impl spi::Error for Error {
+    fn kind(&self) -> spi::ErrorKind {
+        spi::ErrorKind::Other
+    }
+}
+
+impl digital::Error for Error {
+    fn kind(&self) -> digital::ErrorKind {
+        digital::ErrorKind::Other
+    }
+}
+
+// Sleep on the core clock, which requires the caller to be attached
+// to the core. Otherwise, busy-wait.
+fn delay(clock: &CoreClock, ns: u32) {
+    let deadline = clock.now() + Nanoseconds(ns as u64);
+    if clock.sleep_until(deadline).is_err() {
+        clock.spin_until(deadline);
+    }
+}
+
+impl DelayNs for CoreClock {
+    fn delay_ns(&mut self, ns: u32) {
+        delay(self, ns);
+    }
+}
+
+impl DelayNs for &CoreClock {
+    fn delay_ns(&mut self, ns: u32) {
+        delay(self, ns);
+    }
+}
+
+impl SpiErrorType for OobSpiDevice {
+    type Error = Error;
+}
+
+// A transaction is carried out as a single out-of-band transfer, so
+// that the chip select stays asserted throughout: the operations are
+// laid out one after the other in the frame, which must be large
+// enough to hold them all. Unused bytes at the end of the frame are
+// sent as zeroes.
+impl SpiDevice for OobSpiDevice {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Error> {
+        let mut len = 0;
+        for op in operations.iter() {
+            len += match op {
+                Operation::Read(buf) => buf.len(),
+                Operation::Write(buf) => buf.len(),
+                Operation::Transfer(read, write) => read.len().max(write.len()),
+                Operation::TransferInPlace(buf) => buf.len(),
+                Operation::DelayNs(_) => return Err(Error::new(
+                    ErrorKind::Unsupported, "delays within an out-of-band SPI transaction")),
+            };
+        }
+        if len > self.frame_len() {
+            return Err(Error::new(ErrorKind::InvalidInput, "SPI transaction exceeds the frame length"));
+        }
+        let tx = self.tx_buf();
+        tx.fill(0);
+        let mut offset = 0;
+        for op in operations.iter() {
+            match op {
+                Operation::Read(buf) => offset += buf.len(),
+                Operation::Write(buf) => {
+                    tx[offset..offset + buf.len()].copy_from_slice(buf);
+                    offset += buf.len();
+                },
+                Operation::Transfer(read, write) => {
+                    tx[offset..offset + write.len()].copy_from_slice(write);
+                    offset += read.len().max(write.len());
+                },
+                Operation::TransferInPlace(buf) => {
+                    tx[offset..offset + buf.len()].copy_from_slice(buf);
+                    offset += buf.len();
+                },
+                Operation::DelayNs(_) => unreachable!(),
+            }
+        }
+        self.transfer()?;
+        let rx = self.rx_buf();
+        let mut offset = 0;
+        for op in operations.iter_mut() {
+            match op {
+                Operation::Read(buf) | Operation::TransferInPlace(buf) => {
+                    buf.copy_from_slice(&rx[offset..offset + buf.len()]);
+                    offset += buf.len();
+                },
+                Operation::Write(buf) => offset += buf.len(),
+                Operation::Transfer(read, write) => {
+                    read.copy_from_slice(&rx[offset..offset + read.len()]);
+                    offset += read.len().max(write.len());
+                },
+                Operation::DelayNs(_) => unreachable!(),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DigitalErrorType for LineHandle {
+    type Error = Error;
+}
+
+// Pin traits act on the first line of the handle.
+impl InputPin for LineHandle {
+    fn is_high(&mut self) -> Result<bool, Error> {
+        self.get()
+    }
+    fn is_low(&mut self) -> Result<bool, Error> {
+        self.get().map(|v| !v)
+    }
+}
+
+impl OutputPin for LineHandle {
+    fn set_high(&mut self) -> Result<(), Error> {
+        self.set(true)
+    }
+    fn set_low(&mut self) -> Result<(), Error> {
+        self.set(false)
+    }
+}
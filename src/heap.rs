@@ -0,0 +1,185 @@
+//! Memory heap.
+//!
+//! An EVL [`Heap`] manages a fixed memory area which is allocated,
+//! faulted in and locked once at creation, then serves blocks from
+//! it with a bounded execution time and without issuing any system
+//! call, so that real-time threads may allocate memory from the
+//! out-of-band stage. The heap is serialized by an EVL mutex, so it
+//! can be shared between threads.
+//!
+//! `Heap` implements [`GlobalAlloc`], and with the `allocator_api`
+//! feature which requires a nightly compiler, the
+//! [`Allocator`](core::alloc::Allocator) trait for use with the
+//! allocator-aware collections:
+//!
+//! ```ignore
+//! #![feature(allocator_api)]
+//! use revl::heap::Heap;
+//!
+//! let heap = Heap::new(1024 * 1024).unwrap();
+//! let mut samples: Vec<u32, &Heap> = Vec::with_capacity_in(256, &heap);
+//! samples.push(42);
+//! ```
+
+use std::alloc::{self, GlobalAlloc, Layout};
+use std::cell::UnsafeCell;
+use std::ffi::c_void;
+use std::io::ErrorKind;
+use std::mem::MaybeUninit;
+use std::os::raw::c_int;
+use std::ptr::{self, NonNull};
+use evl_sys::{
+    evl_alloc_block,
+    evl_destroy_heap,
+    evl_free_block,
+    evl_heap,
+    evl_heap_size,
+    evl_heap_used,
+    evl_init_heap,
+};
+use crate::Error;
+
+// The heap organizes its memory in pages of this size, each of
+// which has an entry in a metadata map stored after the usable
+// area. Overestimating the entry size is harmless.
+const HEAP_PAGE_SHIFT: usize = 9;
+const HEAP_PAGE_SIZE: usize = 1 << HEAP_PAGE_SHIFT;
+const HEAP_PGENTRY_SIZE: usize = 16;
+const HEAP_ALIGN: usize = 4096;
+
+// Return the size of the raw memory backing a heap of `size` usable
+// bytes, see the evl_heap_raw_size() helper from libevl.
+fn raw_size(size: usize) -> usize {
+    let map = (size >> HEAP_PAGE_SHIFT) * HEAP_PGENTRY_SIZE;
+    size + map.next_multiple_of(HEAP_PAGE_SIZE)
+}
+
+pub struct Heap {
+    // The heap descriptor embeds an EVL mutex, so it must not move.
+    heap: Box<UnsafeCell<evl_heap>>,
+    mem: *mut u8,
+    layout: Layout,
+}
+
+unsafe impl Send for Heap {}
+unsafe impl Sync for Heap {}
+
+impl Heap {
+    /// Create a heap of `size` usable bytes, rounded up to the page
+    /// size of the heap. The memory is allocated from the system,
+    /// faulted in and locked, which requires in-band services.
+    ///
+    /// # Errors
+    ///
+    /// * [`InvalidInput`][`std::io::ErrorKind`] is returned if `size`
+    /// is zero.
+    ///
+    /// * [`OutOfMemory`][`std::io::ErrorKind`] is returned if the
+    /// memory could not be obtained.
+    ///
+    /// * [`PermissionDenied`][`crate::Error::PermissionDenied`] means
+    /// that the calling thread is not allowed to lock memory.
+    pub fn new(size: usize) -> Result<Self, Error> {
+        if size == 0 {
+            return Err(Error::new(ErrorKind::InvalidInput, "empty heap"));
+        }
+        let size = size.next_multiple_of(HEAP_PAGE_SIZE);
+        let layout = Layout::from_size_align(raw_size(size), HEAP_ALIGN)
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+        // Zeroing faults the pages in.
+        let mem = unsafe { alloc::alloc_zeroed(layout) };
+        if mem.is_null() {
+            return Err(Error::new(ErrorKind::OutOfMemory, "cannot allocate heap memory"));
+        }
+        if unsafe { libc::mlock(mem as *const c_void, layout.size()) } != 0 {
+            let e = Error::last_os_error();
+            unsafe { alloc::dealloc(mem, layout) };
+            return Err(e);
+        }
+        let heap = Box::new(UnsafeCell::new(unsafe {
+            MaybeUninit::<evl_heap>::zeroed().assume_init()
+        }));
+        let ret: c_int = unsafe {
+            evl_init_heap(heap.get(), mem as *mut c_void, layout.size())
+        };
+        match ret {
+            0 => return Ok(Self { heap, mem, layout }),
+            _ => {
+                unsafe {
+                    libc::munlock(mem as *const c_void, layout.size());
+                    alloc::dealloc(mem, layout);
+                }
+                return Err(Error::from_raw_os_error(-ret));
+            },
+        };
+    }
+    /// Allocate a block of `size` bytes, returning `None` if the heap
+    /// is exhausted. Blocks are aligned on the power of two their
+    /// size rounds up to, up to the page size of the heap.
+    pub fn alloc_block(&self, size: usize) -> Option<NonNull<u8>> {
+        let ptr = unsafe { evl_alloc_block(self.heap.get(), size) };
+        NonNull::new(ptr as *mut u8)
+    }
+    /// Release a block obtained from [`alloc_block()`](Self::alloc_block).
+    ///
+    /// # Safety
+    ///
+    /// `block` must have been allocated from this heap, and not
+    /// released already.
+    pub unsafe fn free_block(&self, block: NonNull<u8>) {
+        evl_free_block(self.heap.get(), block.as_ptr() as *mut c_void);
+    }
+    /// Return the usable size of the heap in bytes.
+    pub fn size(&self) -> usize {
+        unsafe { evl_heap_size(self.heap.get()) }
+    }
+    /// Return the number of bytes currently allocated.
+    pub fn used(&self) -> usize {
+        unsafe { evl_heap_used(self.heap.get()) }
+    }
+    // Allocate a block satisfying `layout`. The block size is bumped
+    // to the alignment, so that the natural alignment of the block
+    // provides it.
+    fn alloc_layout(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let block = self.alloc_block(layout.size().max(layout.align()).max(1))?;
+        if (block.as_ptr() as usize) & (layout.align() - 1) != 0 {
+            unsafe { self.free_block(block) };
+            return None;
+        }
+        Some(block)
+    }
+}
+
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.alloc_layout(layout).map_or(ptr::null_mut(), |p| p.as_ptr())
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
+        if let Some(block) = NonNull::new(ptr) {
+            self.free_block(block);
+        }
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+unsafe impl core::alloc::Allocator for Heap {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, core::alloc::AllocError> {
+        match self.alloc_layout(layout) {
+            Some(block) => Ok(NonNull::slice_from_raw_parts(block, layout.size())),
+            None => Err(core::alloc::AllocError),
+        }
+    }
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, _layout: Layout) {
+        self.free_block(ptr);
+    }
+}
+
+impl Drop for Heap {
+    fn drop(&mut self) {
+        unsafe {
+            evl_destroy_heap(self.heap.get());
+            libc::munlock(self.mem as *const c_void, self.layout.size());
+            alloc::dealloc(self.mem, self.layout);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Core initialization and version information.
+
+use std::ffi::CStr;
+use std::os::raw::c_int;
+use evl_sys::{
+    evl_get_version,
+    evl_init,
+};
+use crate::Error;
+
+/// Initialize the EVL interface for the current process.
+///
+/// Calling this routine is optional, since attaching the first
+/// thread to the core performs such initialization implicitly. Doing
+/// it early allows an application to fail fast with a clear
+/// diagnostic if the EVL core is not usable, instead of discovering
+/// it on the first attachment. This routine may be called multiple
+/// times, only the first call has an effect.
+///
+/// # Errors
+///
+/// * [`NoCore`][`crate::Error::NoCore`] means that the EVL core is
+/// not enabled in the running kernel.
+///
+/// * [`AbiMismatch`][`crate::Error::AbiMismatch`] denotes an ABI
+/// mismatch between the underlying [evl-sys
+/// crate](https://source.denx.de/Xenomai/xenomai4/evl-sys) and the
+/// EVL core. See [these
+/// explanations](https://evlproject.org/core/under-the-hood/abi/).
+///
+/// * [`PermissionDenied`][`crate::Error::PermissionDenied`] means that the
+/// process is not allowed to lock its memory.
+///
+/// # Examples
+///
+/// ```no_run
+/// if let Err(e) = revl::init() {
+///     eprintln!("EVL core is not available: {}", e);
+///     std::process::exit(1);
+/// }
+/// println!("running on EVL {} (ABI {})", revl::core_version(), revl::abi_level());
+/// ```
+pub fn init() -> Result<(), Error> {
+    let ret: c_int = unsafe { evl_init() };
+    match ret {
+        0 => return Ok(()),
+        _ => return Err(Error::from_raw_os_error(-ret)),
+    }
+}
+
+/// Return the version string of the EVL core.
+pub fn core_version() -> String {
+    let version = unsafe { evl_get_version() };
+    if version.version_string.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(version.version_string) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Return the ABI level implemented by the EVL core, or a negative
+/// value if the core is not available.
+pub fn abi_level() -> i32 {
+    unsafe { evl_get_version() }.abi_level
+}
+
+/// Return the API level of the EVL library interface.
+pub fn api_level() -> i32 {
+    unsafe { evl_get_version() }.api_level
+}
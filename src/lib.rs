@@ -2,12 +2,105 @@
 //!
 //! Provides an API to call the services of the Xenomai4 [real-time
 //! core](https://evlproject.org/), aka EVL.
+//!
+//! With the `mio` feature enabled, [cross-buffers](xbuf::XBuf),
+//! [proxies](proxy::Proxy) and [observables](observable::Observable)
+//! implement `mio::event::Source`, so that their in-band side can be
+//! driven from a mio-based event loop.
+//!
+//! With the `embedded-hal` feature enabled, the [core
+//! clocks](clock::CoreClock) implement the embedded-hal `DelayNs`
+//! trait, [out-of-band SPI devices](spi::OobSpiDevice) implement
+//! `SpiDevice`, and [GPIO line handles](gpio::LineHandle) implement
+//! `InputPin` and `OutputPin`, so that embedded-hal drivers can run
+//! from EVL threads.
+//!
+//! With the `metrics` feature enabled, a [reporter](metrics::Reporter)
+//! publishes the runtime statistics of EVL threads and application
+//! values through the `metrics` facade.
+//!
+//! With the default features disabled and the `sim` feature enabled,
+//! revl runs on top of a userland emulation of the EVL services
+//! instead of the real-time core, so that applications can be built
+//! and exercised on development hosts and CI machines which have no
+//! EVL kernel. There is no real-time guarantee in this mode:
+//!
+//! ```toml
+//! revl = { version = "0.1", default-features = false, features = ["sim"] }
+//! ```
+//!
+//! The [`sim`] module then provides a virtual test clock, so that
+//! timing-dependent logic can be unit-tested deterministically.
+//!
+//! Fallible operations return the crate-level [`Error`] type, which
+//! can be converted to [`std::io::Error`] when needed.
+
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
+#[cfg(all(feature = "sim", feature = "evl-sys"))]
+compile_error!("the `sim` feature requires disabling the default features");
+#[cfg(not(any(feature = "sim", feature = "evl-sys")))]
+compile_error!("either the `evl-sys` (default) or the `sim` feature must be enabled");
+#[cfg(all(feature = "sim", not(feature = "evl-sys")))]
+extern crate revl_sim_sys as evl_sys;
+
+mod error;
+pub use error::Error;
 
 pub mod clock;
 pub mod mutex;
+pub mod rwlock;
+pub mod once;
 pub mod sched;
 pub mod thread;
 pub mod semaphore;
 pub mod flags;
-pub mod event;
-pub mod ring;
+pub mod notify;
+pub mod condvar;
+pub mod channel;
+pub mod spsc;
+pub mod oneshot;
+pub mod watch;
+pub mod broadcast;
+pub mod xbuf;
+pub mod bridge;
+pub mod proxy;
+pub mod print;
+#[cfg(feature = "log")]
+pub mod logger;
+#[cfg(feature = "tracing")]
+pub mod trace;
+pub mod queue;
+pub mod poll;
+pub mod select;
+pub mod observable;
+pub mod watchdog;
+pub mod timer;
+pub mod wheel;
+pub mod executor;
+pub mod stats;
+pub mod bench;
+pub mod heap;
+pub mod pool;
+pub mod mem;
+pub mod shm;
+pub mod gpio;
+pub mod spi;
+pub mod registry;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "sim")]
+pub mod sim;
+
+#[cfg(feature = "mio")]
+mod source;
+#[cfg(feature = "embedded-hal")]
+mod hal;
+
+mod init;
+pub use init::{init, core_version, abi_level, api_level};
+
+/// Attribute macros generating the attachment boilerplate for the
+/// main thread and spawned EVL threads (`macros` feature).
+#[cfg(feature = "macros")]
+pub use revl_macros::{main, thread};
@@ -0,0 +1,69 @@
+//! [`log`] backend usable from out-of-band threads (`log` feature).
+//!
+//! Regular loggers write records through the in-band stage, often
+//! under a lock, which would demote an out-of-band caller and cause
+//! latency spikes. This backend formats each record into a buffer on
+//! the stack, then writes the result to a [proxy](crate::proxy)
+//! relaying it to the standard error from the in-band stage, so that
+//! the `log` macros can be used unchanged from EVL threads. Records
+//! longer than [`LINE_MAX`](crate::print::LINE_MAX) bytes are
+//! truncated.
+//!
+//! ```no_run
+//! use log::{info, warn, LevelFilter};
+//!
+//! revl::logger::init_with_level(LevelFilter::Debug).unwrap();
+//! // From any thread, including out-of-band ones:
+//! info!("control loop started");
+//! warn!("cycle {} overrun", 42);
+//! ```
+
+use std::io::ErrorKind;
+use log::{LevelFilter, Log, Metadata, Record};
+use crate::print;
+use crate::Error;
+
+struct Logger;
+
+static LOGGER: Logger = Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        // The proxy was created by init(), this cannot fail.
+        if let Ok(proxy) = print::stderr() {
+            let _ = print::print_to(proxy, format_args!(
+                "[{:<5} {}] {}\n", record.level(), record.target(), record.args()));
+        }
+    }
+    fn flush(&self) {
+        // The proxy relays the output asynchronously.
+    }
+}
+
+/// Install the logger, passing records up to the `Info` level. See
+/// [`init_with_level()`].
+pub fn init() -> Result<(), Error> {
+    init_with_level(LevelFilter::Info)
+}
+
+/// Install the logger, passing records up to `level`. This should be
+/// called from the in-band stage, since the proxy relaying the output
+/// is created at this point.
+///
+/// # Errors
+///
+/// [`AlreadyExists`][`std::io::ErrorKind`] is returned if a logger
+/// was already installed, otherwise see [`print::init()`].
+pub fn init_with_level(level: LevelFilter) -> Result<(), Error> {
+    print::stderr()?;
+    log::set_logger(&LOGGER)
+        .map_err(|_| Error::new(ErrorKind::AlreadyExists, "logger already installed"))?;
+    log::set_max_level(level);
+    Ok(())
+}
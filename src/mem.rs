@@ -0,0 +1,188 @@
+//! Memory locking and pre-faulting.
+//!
+//! A real-time thread must not take page faults, which would demote
+//! it to the in-band stage. The memory of the process should be
+//! locked with [`lock_all()`] before real-time work starts, then the
+//! memory the real-time threads will use should be faulted in ahead
+//! of time: the heap arena with [`reserve_heap()`], the stacks with
+//! [`lock_stack()`] or [`prefault_stack()`], and any other buffer
+//! with [`prefault()`].
+//!
+//! ```no_run
+//! use revl::mem;
+//!
+//! if let Err(e) = mem::lock_all() {
+//!     eprintln!("cannot lock memory: {}", e);
+//!     std::process::exit(1);
+//! }
+//! mem::reserve_heap(16 * 1024 * 1024).unwrap();
+//! mem::prefault_stack(256 * 1024);
+//! ```
+
+use std::ffi::c_void;
+use std::hint;
+use std::io::ErrorKind;
+use std::mem::MaybeUninit;
+use std::os::raw::c_int;
+use std::ptr;
+use libc::ENOMEM;
+use crate::Error;
+
+// Stack pages are touched in chunks of the smallest page size.
+const STACK_CHUNK: usize = 4096;
+
+/// Return the size of a memory page.
+pub fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+// Explain the usual cause of a failure to lock memory which is not
+// a permission issue.
+fn lock_error(errno: i32) -> Error {
+    match errno {
+        ENOMEM => Error::new(ErrorKind::OutOfMemory,
+                             "cannot lock memory, RLIMIT_MEMLOCK is exceeded"),
+        _ => Error::from_raw_os_error(errno),
+    }
+}
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap_or(0)
+}
+
+/// Lock all current and future mappings of the process into memory,
+/// see [mlockall(2)](https://man7.org/linux/man-pages/man2/mlockall.2.html).
+/// This is implicitly done by the EVL library when the first thread
+/// attaches to the core, doing it early gives a clear diagnostic.
+///
+/// # Errors
+///
+/// * [`PermissionDenied`][`crate::Error::PermissionDenied`] means
+/// that the process lacks the CAP_IPC_LOCK capability, and its
+/// RLIMIT_MEMLOCK limit is zero.
+///
+/// * [`OutOfMemory`][`std::io::ErrorKind`] means that the memory of
+/// the process exceeds its RLIMIT_MEMLOCK limit, see
+/// [`memlock_limit()`].
+pub fn lock_all() -> Result<(), Error> {
+    match unsafe { libc::mlockall(libc::MCL_CURRENT | libc::MCL_FUTURE) } {
+        0 => return Ok(()),
+        _ => return Err(lock_error(errno())),
+    }
+}
+
+/// Unlock all mappings of the process.
+pub fn unlock_all() -> Result<(), Error> {
+    match unsafe { libc::munlockall() } {
+        0 => return Ok(()),
+        _ => return Err(Error::last_os_error()),
+    }
+}
+
+/// Return the RLIMIT_MEMLOCK limit of the process in bytes, or
+/// `None` if unlimited.
+pub fn memlock_limit() -> Result<Option<u64>, Error> {
+    let mut rlim = MaybeUninit::<libc::rlimit>::uninit();
+    match unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, rlim.as_mut_ptr()) } {
+        0 => {
+            let rlim = unsafe { rlim.assume_init() };
+            if rlim.rlim_cur == libc::RLIM_INFINITY {
+                return Ok(None);
+            }
+            return Ok(Some(rlim.rlim_cur as u64));
+        },
+        _ => return Err(Error::last_os_error()),
+    }
+}
+
+/// Fault in every page of `buf`, so that no page fault happens on
+/// first access later on. The memory should be locked, e.g. by
+/// [`lock_all()`], for the pages to stay resident.
+pub fn prefault(buf: &mut [u8]) {
+    let page = page_size();
+    let base = buf.as_mut_ptr();
+    for offset in (0..buf.len()).step_by(page) {
+        // Writing is required to fault in a private page.
+        unsafe {
+            let p = base.add(offset);
+            ptr::write_volatile(p, ptr::read_volatile(p));
+        }
+    }
+}
+
+/// Grow the heap arena of the process by `size` bytes and fault it
+/// in, so that later allocations up to that size do not take page
+/// faults. The C library is also told to keep the memory released
+/// by the application in the arena instead of returning it to the
+/// system, and to serve large allocations from the arena too. This
+/// only makes sense once the memory is locked by [`lock_all()`].
+///
+/// # Errors
+///
+/// [`OutOfMemory`][`std::io::ErrorKind`] is returned if `size` bytes
+/// could not be allocated.
+pub fn reserve_heap(size: usize) -> Result<(), Error> {
+    unsafe {
+        if libc::mallopt(libc::M_TRIM_THRESHOLD, -1) == 0 ||
+            libc::mallopt(libc::M_MMAP_MAX, 0) == 0 {
+            return Err(Error::new(ErrorKind::Unsupported, "cannot tune the heap arena"));
+        }
+        let arena = libc::malloc(size) as *mut u8;
+        if arena.is_null() {
+            return Err(Error::new(ErrorKind::OutOfMemory, "cannot grow the heap arena"));
+        }
+        // The arena is uninitialized, so it is neither viewed as a
+        // slice nor read, only written to.
+        for offset in (0..size).step_by(page_size()) {
+            ptr::write_volatile(arena.add(offset), 0);
+        }
+        libc::free(arena as *mut c_void);
+    }
+    Ok(())
+}
+
+#[inline(never)]
+fn touch_stack(chunks: usize) {
+    let mut chunk = [0u8; STACK_CHUNK];
+    hint::black_box(&mut chunk);
+    if chunks > 1 {
+        touch_stack(chunks - 1);
+    }
+}
+
+/// Fault in `size` bytes of the stack of the calling thread below
+/// the current frame. This is useful for the main thread, the stack
+/// of which grows on demand; [`lock_stack()`] should be preferred
+/// for other threads.
+pub fn prefault_stack(size: usize) {
+    touch_stack(size.div_ceil(STACK_CHUNK));
+}
+
+/// Fault in and lock the whole stack of the calling thread into
+/// memory. This is only meant for threads created by the std
+/// library, whose stack is fully mapped at creation; the stack of
+/// the main thread grows on demand, see [`prefault_stack()`].
+/// [`thread::Builder::spawn()`](crate::thread::Builder::spawn) does
+/// this by default.
+pub fn lock_stack() -> Result<(), Error> {
+    let mut attr = MaybeUninit::<libc::pthread_attr_t>::uninit();
+    let mut addr: *mut libc::c_void = ptr::null_mut();
+    let mut size: libc::size_t = 0;
+    let ret: c_int = unsafe {
+        let ret = libc::pthread_getattr_np(libc::pthread_self(), attr.as_mut_ptr());
+        if ret != 0 {
+            return Err(Error::from_raw_os_error(ret));
+        }
+        let ret = libc::pthread_attr_getstack(attr.as_ptr(), &mut addr, &mut size);
+        libc::pthread_attr_destroy(attr.as_mut_ptr());
+        ret
+    };
+    if ret != 0 {
+        return Err(Error::from_raw_os_error(ret));
+    }
+    // mlock(2) faults in the pages before locking them.
+    match unsafe { libc::mlock(addr, size) } {
+        0 => return Ok(()),
+        _ => return Err(lock_error(errno())),
+    }
+}
@@ -0,0 +1,200 @@
+//! Runtime statistics reporting through the `metrics` facade.
+//!
+//! A [`Reporter`] runs an in-band thread which periodically scrapes
+//! the statistics the EVL core maintains for every thread in the
+//! system, and any application-level value registered with the
+//! [`Builder`], such as the depth of a queue or the deadline misses
+//! of a [cyclic executor](crate::executor::Cyclic). Values are
+//! published through the [metrics](https://docs.rs/metrics) crate,
+//! so any recorder can export them, e.g. the Prometheus exporter
+//! from the `metrics-exporter-prometheus` crate, which serves them
+//! over HTTP. Scraping only reads sysfs attributes and calls the
+//! closures from the reporter thread, which never runs out-of-band.
+//!
+//! The per-thread metrics, labeled with the thread name, are:
+//!
+//! * `<prefix>_thread_inband_switches_total`: the number of switches
+//!   to the in-band stage.
+//! * `<prefix>_thread_context_switches_total`: the number of context
+//!   switches.
+//! * `<prefix>_thread_syscalls_total`: the number of out-of-band
+//!   system calls.
+//! * `<prefix>_thread_remote_wakeups_total`: the number of wakeups
+//!   sent to other CPUs.
+//! * `<prefix>_thread_cpu_seconds`: the CPU time consumed
+//!   out-of-band.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use std::sync::atomic::{AtomicU64, Ordering};
+//! use std::time::Duration;
+//! use revl::metrics;
+//!
+//! // Bumped by the on_overrun handler of a cyclic executor.
+//! let misses = Arc::new(AtomicU64::new(0));
+//! let m = misses.clone();
+//! let _reporter = metrics::Builder::new()
+//!     .interval(Duration::from_secs(5))
+//!     .counter("control_deadline_misses_total", move || m.load(Ordering::Relaxed))
+//!     .start()
+//!     .unwrap();
+//! ```
+
+use std::io::ErrorKind;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use ::metrics::{counter, gauge};
+use crate::registry::{self, Kind};
+use crate::Error;
+
+type GaugeFn = Box<dyn Fn() -> f64 + Send>;
+type CounterFn = Box<dyn Fn() -> u64 + Send>;
+
+pub struct Builder {
+    interval: Duration,
+    prefix: String,
+    threads: bool,
+    gauges: Vec<(String, GaugeFn)>,
+    counters: Vec<(String, CounterFn)>,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            prefix: "evl".to_string(),
+            threads: true,
+            gauges: Vec::new(),
+            counters: Vec::new(),
+        }
+    }
+    /// Set the scraping interval, 10 seconds by default.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+    /// Set the prefix of the thread metric names, `evl` by default.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+    /// Enable or disable scraping the thread statistics (default).
+    pub fn threads(mut self, enabled: bool) -> Self {
+        self.threads = enabled;
+        self
+    }
+    /// Publish the value returned by `f` as the gauge `name`, e.g.
+    /// the length of a queue.
+    pub fn gauge<F>(mut self, name: &str, f: F) -> Self
+    where
+        F: Fn() -> f64 + Send + 'static,
+    {
+        self.gauges.push((name.to_string(), Box::new(f)));
+        self
+    }
+    /// Publish the value returned by `f` as the counter `name`, which
+    /// should only increase, e.g. a count of deadline misses.
+    pub fn counter<F>(mut self, name: &str, f: F) -> Self
+    where
+        F: Fn() -> u64 + Send + 'static,
+    {
+        self.counters.push((name.to_string(), Box::new(f)));
+        self
+    }
+    pub fn start(self) -> Result<Reporter, Error> {
+        Reporter::new(self)
+    }
+}
+
+/// A running metrics reporter, which stops when dropped.
+pub struct Reporter {
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Reporter {
+    /// Start the reporter thread, retrieving the settings from a
+    /// [`builder struct`](Builder). A recorder should have been
+    /// installed beforehand, otherwise the values are discarded.
+    ///
+    /// # Errors
+    ///
+    /// [`InvalidInput`][`std::io::ErrorKind`] is returned if the
+    /// interval is zero.
+    pub fn new(builder: Builder) -> Result<Self, Error> {
+        if builder.interval.is_zero() {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid scraping interval"));
+        }
+        let (stop, stopped) = mpsc::channel();
+        let handle = thread::Builder::new()
+            .name("evl-metrics".to_string())
+            .spawn(move || {
+                loop {
+                    scrape(&builder);
+                    match stopped.recv_timeout(builder.interval) {
+                        Err(RecvTimeoutError::Timeout) => continue,
+                        _ => break,
+                    }
+                }
+            })?;
+        Ok(Self {
+            stop: Some(stop),
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Drop for Reporter {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn scrape(builder: &Builder) {
+    if builder.threads {
+        scrape_threads(&builder.prefix);
+    }
+    for (name, f) in &builder.gauges {
+        gauge!(name.clone()).set(f());
+    }
+    for (name, f) in &builder.counters {
+        counter!(name.clone()).absolute(f());
+    }
+}
+
+// The stats attribute of a thread reads as "<isw> <csw> <sc> <rwa>
+// <xtime> <load>", the execution time being counted in nanoseconds.
+fn scrape_threads(prefix: &str) {
+    let threads = match registry::list_kind(Kind::Thread) {
+        Ok(threads) => threads,
+        Err(_) => return,
+    };
+    for t in threads {
+        // The thread may have exited since it was listed.
+        let stats = match t.attribute("stats") {
+            Ok(stats) => stats,
+            Err(_) => continue,
+        };
+        let fields: Vec<u64> = stats.split_whitespace()
+            .map_while(|s| s.parse().ok())
+            .collect();
+        if fields.len() < 5 {
+            continue;
+        }
+        let name = t.name().to_string();
+        counter!(format!("{}_thread_inband_switches_total", prefix), "thread" => name.clone())
+            .absolute(fields[0]);
+        counter!(format!("{}_thread_context_switches_total", prefix), "thread" => name.clone())
+            .absolute(fields[1]);
+        counter!(format!("{}_thread_syscalls_total", prefix), "thread" => name.clone())
+            .absolute(fields[2]);
+        counter!(format!("{}_thread_remote_wakeups_total", prefix), "thread" => name.clone())
+            .absolute(fields[3]);
+        gauge!(format!("{}_thread_cpu_seconds", prefix), "thread" => name)
+            .set(fields[4] as f64 / 1e9);
+    }
+}
@@ -12,23 +12,34 @@
 
 use std::ffi::CString;
 use std::cell::UnsafeCell;
-use std::io::Error;
-use std::mem::{forget, MaybeUninit};
+use std::marker::PhantomData;
+use std::mem::{ManuallyDrop, MaybeUninit};
 use std::ops::{Deref, DerefMut};
 use std::os::raw::c_int;
 use std::fmt;
 use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+use std::io::ErrorKind;
+use std::time::Duration;
+use libc::EBUSY;
 use evl_sys::{
     evl_close_mutex,
     evl_create_mutex,
+    evl_get_mutex_ceiling,
+    evl_set_mutex_ceiling,
     evl_lock_mutex,
+    evl_timedlock_mutex,
     evl_trylock_mutex,
     evl_mutex,
     evl_unlock_mutex,
-    BuiltinClock,
     CloneFlags,
     MutexType,
 };
+use embedded_time::{duration::Nanoseconds, Instant};
+use crate::clock::{instant_to_timespec, CoreClock, STEADY_CLOCK};
+use crate::Error;
 
 /// A mutex builder `struct` to configure and create a mutex.
 pub struct Builder {
@@ -36,6 +47,7 @@ pub struct Builder {
     visible: bool,
     recursive: bool,
     ceiling: u32,
+    clock: Option<CoreClock>,
 }
 
 impl Builder {
@@ -58,6 +70,7 @@ impl Builder {
             visible: false,
             recursive: false,
             ceiling: 0,
+            clock: None,
         }
     }
     /// Set the name property.
@@ -100,7 +113,9 @@ impl Builder {
         self.visible = false;
         self
     }
-    /// Allow the mutex to be taken recursively.
+    /// Allow the mutex to be taken recursively. This property is
+    /// only valid for creating a [`ReentrantMutex`], since a
+    /// [`Mutex`] hands out exclusive access to its data on each lock.
     ///
     /// ```no_run
     /// use revl::mutex::Builder;
@@ -126,16 +141,41 @@ impl Builder {
         self.ceiling = ceiling;
         self
     }
+    /// Set the clock the timeouts of the mutex are based on. This is
+    /// the monotonic clock by default.
+    ///
+    /// ```no_run
+    /// use revl::mutex::Builder;
+    /// use revl::clock::SYSTEM_CLOCK;
+    ///
+    /// // A builder for a mutex timed on the wall clock.
+    /// let builder = Builder::new().clock(SYSTEM_CLOCK);
+    /// ```
+    pub fn clock(mut self, clock: CoreClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
     /// Create a mutex from the current properties.
     ///
     pub fn create<T>(self, data: T) -> Result<Mutex<T>, Error> {
         Mutex::new(data, self)
     }
+    /// Create a reentrant mutex from the current properties.
+    pub fn create_reentrant<T>(self, data: T) -> Result<ReentrantMutex<T>, Error> {
+        ReentrantMutex::new(data, self)
+    }
 }
 
 /// The Mutex `struct` implements a mutal exclusion lock.
+///
+/// Like [`std::sync::Mutex`], a mutex is poisoned whenever a thread
+/// panics while holding it, since the protected data may have been
+/// left in an inconsistent state. Once poisoned, locking the mutex
+/// fails with [`Poisoned`][`crate::Error::Poisoned`] until
+/// [`clear_poison()`][`Mutex::clear_poison`] is called.
 pub struct Mutex<T: ?Sized> {
     mutex: CoreMutex,
+    poison: AtomicBool,
     data: UnsafeCell<T>,
 }
 
@@ -146,13 +186,25 @@ impl<T> Mutex<T> {
     /// Create a new mutex for guarding `data`, using the properties
     /// defined by the [`builder`](struct@Builder).
     ///
+    /// # Errors
+    ///
+    /// [`InvalidInput`][`std::io::ErrorKind`] is returned if the
+    /// builder asks for a recursive mutex, use [`ReentrantMutex`]
+    /// instead.
+    ///
     /// ```no_run
-    /// use revl::mutex::Mutex;
-    /// 
+    /// use revl::mutex::{Builder, Mutex};
+    ///
+    /// let mutex = Mutex::new(0u32, Builder::new().name("foo_mutex")).unwrap();
     /// ```
     pub fn new(data: T, builder: Builder) -> Result<Self, Error> {
+        if builder.recursive {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  "recursive locking requires ReentrantMutex"));
+        }
         Ok(Self {
             mutex: CoreMutex::new(builder)?,
+            poison: AtomicBool::new(false),
             data: UnsafeCell::new(data),
         })
     }
@@ -164,7 +216,7 @@ impl<T> Mutex<T> {
     ///
     /// # Errors
     ///
-    /// [`WouldBlock`][`std::io::ErrorKind`] is returned if the
+    /// [`WouldBlock`][`crate::Error::WouldBlock`] is returned if the
     /// request would cause the mutex to be locked more than u32::MAX
     /// times.
     ///
@@ -183,10 +235,7 @@ impl<T> Mutex<T> {
     /// ```
     pub fn lock(&self) -> Result<MutexGuard<T>, Error> {
         self.mutex.lock()?;
-        Ok(MutexGuard {
-            __mutex: &self.mutex,
-            __data: &self.data,
-        })
+        self.guard()
     }
     /// Try locking the mutex. On success, this call returns an RAII
     /// guard which guarantees exclusive read/write access to the
@@ -196,52 +245,191 @@ impl<T> Mutex<T> {
     ///
     /// # Errors
     ///
-    /// WouldBlock is returned if the mutex is already locked.
+    /// [`WouldBlock`][`crate::Error::WouldBlock`] is returned if the
+    /// mutex is already locked.
     ///
     /// ```no_run
-    /// use revl::thread::Thread;
-    /// use revl::mutex::Mutex;
+    /// use std::sync::Arc;
+    /// use revl::thread;
+    /// use revl::mutex::{Builder, Mutex};
+    /// use revl::Error;
     ///
-    /// let mutex = Arc::new(Mutex::new(0));
+    /// let mutex = Arc::new(Mutex::new(0, Builder::new()).unwrap());
     /// let c_mutex = Arc::clone(&mutex);
     ///
-    /// Thread::spawn(move || {
-    ///     if let Ok(ref mut g) = c_mutex.try_lock() {
-    ///         **g = 42;
-    ///     } else {
-    ///         println!("try_lock failed");
+    /// thread::Builder::new().spawn(move |_| {
+    ///     match c_mutex.try_lock() {
+    ///         Ok(mut g) => *g = 42,
+    ///         Err(Error::WouldBlock) => println!("contended, skipping"),
+    ///         Err(e) => panic!("try_lock failed: {}", e),
     ///     }
-    /// }).join().expect("Thread::spawn failed");
-    /// assert_eq!(*mutex.lock().unwrap(), 42);
+    /// }).unwrap().join().unwrap();
     /// ```
     pub fn try_lock(&self) -> Result<MutexGuard<T>, Error> {
         self.mutex.try_lock()?;
-        Ok(MutexGuard {
-            __mutex: &self.mutex,
-            __data: &self.data,
-        })
+        self.guard()
     }
-    /// Consume the mutex, returning the inner data.
+    /// Lock the mutex, waiting until `timeout` at the latest. The
+    /// timeout is an absolute date based on the clock of the mutex.
+    ///
+    /// # Errors
+    ///
+    /// [`TimedOut`][`crate::Error::TimedOut`] is returned if the
+    /// mutex could not be acquired before the timeout elapsed.
+    ///
+    /// ```no_run
+    /// use embedded_time::duration::Microseconds;
+    /// use revl::clock::STEADY_CLOCK;
+    /// use revl::mutex::{Builder, Mutex};
+    /// use revl::Error;
+    ///
+    /// let mutex = Mutex::new(0, Builder::new()).unwrap();
+    /// match mutex.lock_timed(STEADY_CLOCK.now() + Microseconds(50u32)) {
+    ///     Ok(mut g) => *g += 1,
+    ///     Err(Error::TimedOut) => println!("taking the degraded path"),
+    ///     Err(e) => panic!("lock_timed failed: {}", e),
+    /// }
+    /// ```
+    pub fn lock_timed(&self, timeout: Instant<CoreClock>) -> Result<MutexGuard<T>, Error> {
+        self.mutex.lock_timed(timeout)?;
+        self.guard()
+    }
+    /// Lock the mutex, waiting for `delay` at most, as measured by
+    /// the clock of the mutex. See
+    /// [`lock_timed()`][`Mutex::lock_timed`].
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use revl::mutex::{Builder, Mutex};
+    ///
+    /// let mutex = Mutex::new(0, Builder::new()).unwrap();
+    /// if let Ok(mut g) = mutex.lock_for(Duration::from_micros(20)) {
+    ///     *g += 1;
+    /// }
+    /// ```
+    pub fn lock_for(&self, delay: Duration) -> Result<MutexGuard<T>, Error> {
+        self.lock_timed(self.mutex.1.now() + Nanoseconds(delay.as_nanos() as u64))
+    }
+    // Build a guard for the mutex the caller has just locked,
+    // unless it is poisoned.
+    fn guard(&self) -> Result<MutexGuard<T>, Error> {
+        MutexGuard::new(&self.mutex, &self.poison, &self.data)
+    }
+    /// Change the priority ceiling of the mutex, which must have been
+    /// created with the priority ceiling protocol enabled, see
+    /// [`Builder::ceiling()`]. The new ceiling applies the next time
+    /// the mutex is locked.
+    ///
+    /// # Errors
+    ///
+    /// [`InvalidInput`][`std::io::ErrorKind`] is returned if the
+    /// mutex does not enforce the priority ceiling protocol, or the
+    /// ceiling value is out of range.
     ///
     /// ```no_run
     /// use revl::mutex::Mutex;
     ///
-    /// let mutex = Mutex::new(42);
+    /// fn enter_steady_state(mutex: &Mutex<u32>) -> Result<(), revl::Error> {
+    ///     mutex.set_ceiling(90)
+    /// }
+    /// ```
+    pub fn set_ceiling(&self, ceiling: u32) -> Result<(), Error> {
+        self.mutex.set_ceiling(ceiling)
+    }
+    /// Retrieve the current priority ceiling of the mutex. Zero means
+    /// that the mutex enforces priority inheritance instead.
+    pub fn ceiling(&self) -> Result<u32, Error> {
+        self.mutex.ceiling()
+    }
+    /// Tell whether the mutex is currently locked by any thread.
+    /// This is a snapshot of the mutex state, which may change
+    /// immediately after the call returns.
+    pub fn is_locked(&self) -> bool {
+        self.mutex.owner_word().map_or(false, |w| w & !OWNER_FLAG_MASK != 0)
+    }
+    /// Tell whether some thread is currently waiting for the mutex.
+    pub fn is_contended(&self) -> bool {
+        self.mutex.owner_word().map_or(false, |w| w & OWNER_CLAIMED != 0)
+    }
+    /// Return the core handle of the thread currently owning the
+    /// mutex, if any. This handle identifies the owner in the
+    /// traces of the EVL core, and compares equal for the same
+    /// owner across calls, which is helpful for detecting threads
+    /// holding locks for too long.
+    ///
+    /// ```no_run
+    /// use revl::mutex::Mutex;
+    ///
+    /// fn report(mutex: &Mutex<u32>) {
+    ///     if mutex.is_contended() {
+    ///         println!("contended mutex held by {:?}", mutex.owner());
+    ///     }
+    /// }
+    /// ```
+    pub fn owner(&self) -> Option<u32> {
+        match self.mutex.owner_word() {
+            Some(w) if w & !OWNER_FLAG_MASK != 0 => Some(w & !OWNER_FLAG_MASK),
+            _ => None,
+        }
+    }
+    /// Tell whether the mutex is poisoned.
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.load(Ordering::Relaxed)
+    }
+    /// Clear the poisoned state of the mutex, which the caller deems
+    /// consistent again.
+    ///
+    /// ```no_run
+    /// use revl::mutex::{Builder, Mutex};
+    /// use revl::Error;
+    ///
+    /// fn recover(mutex: &Mutex<Vec<u32>>) {
+    ///     if let Err(Error::Poisoned) = mutex.lock() {
+    ///         mutex.clear_poison();
+    ///         mutex.lock().unwrap().clear();
+    ///     }
+    /// }
+    /// ```
+    pub fn clear_poison(&self) {
+        self.poison.store(false, Ordering::Relaxed);
+    }
+    /// Consume the mutex, returning the inner data. The data is
+    /// returned regardless of the poisoned state.
+    ///
+    /// ```no_run
+    /// use revl::mutex::{Builder, Mutex};
+    ///
+    /// let mutex = Mutex::new(42, Builder::new()).unwrap();
     /// assert_eq!(mutex.into_inner(), 42);
     /// ```
     pub fn into_inner(self) -> T {
-        unsafe {
-            let (mutex, data) = {
-                let Self {
-                    ref mutex,
-                    ref data,
-                } = self;
-                (ptr::read(mutex), ptr::read(data))
-            };
-            forget(self);
-            drop(mutex);
-            data.into_inner()
-        }
+        // Mutex has no Drop implementation, so we may move the
+        // fields out. Release the EVL mutex first.
+        let Self { mutex, poison: _, data } = self;
+        drop(mutex);
+        data.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Return a mutable reference to the inner data. Since this call
+    /// borrows the mutex mutably, no locking is needed. The
+    /// reference is returned regardless of the poisoned state.
+    ///
+    /// ```no_run
+    /// use revl::mutex::{Builder, Mutex};
+    ///
+    /// let mut mutex = Mutex::new(0, Builder::new()).unwrap();
+    /// *mutex.get_mut() = 42;
+    /// assert_eq!(*mutex.lock().unwrap(), 42);
+    /// ```
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+    /// Return a raw pointer to the inner data. Dereferencing this
+    /// pointer is only safe while holding the lock.
+    pub fn data_ptr(&self) -> *mut T {
+        self.data.get()
     }
 }
 
@@ -253,13 +441,142 @@ impl<T: ?Sized> fmt::Debug for Mutex<T> {
 
 pub struct MutexGuard<'a, T: ?Sized + 'a> {
     __mutex: &'a CoreMutex,
+    __poison: &'a AtomicBool,
     __data: &'a UnsafeCell<T>,
+    // Whether the owner was panicking already when locking.
+    __panicking: bool,
 }
 
 impl<'a, T: ?Sized> MutexGuard<'a, T> {
+    // Build a guard for a mutex the caller has just locked, unless
+    // it is poisoned.
+    fn new(
+        mutex: &'a CoreMutex,
+        poison: &'a AtomicBool,
+        data: &'a UnsafeCell<T>,
+    ) -> Result<Self, Error> {
+        let guard = MutexGuard {
+            __mutex: mutex,
+            __poison: poison,
+            __data: data,
+            __panicking: thread::panicking(),
+        };
+        if poison.load(Ordering::Relaxed) {
+            // Dropping the guard unlocks the mutex.
+            return Err(Error::Poisoned);
+        }
+        Ok(guard)
+    }
     pub(crate) fn as_raw_mut(&self) -> &'a mut evl_mutex {
         unsafe { &mut *self.__mutex.0.get() }
     }
+    /// Narrow the guard down to a component of the locked data. The
+    /// mutex stays locked until the returned guard is dropped.
+    ///
+    /// This is an associated function, which has to be called as
+    /// `MutexGuard::map(guard, f)`, so that it does not conflict with
+    /// any method of the inner data.
+    ///
+    /// ```no_run
+    /// use revl::mutex::{Builder, Mutex, MutexGuard};
+    ///
+    /// struct State {
+    ///     gains: [f64; 16],
+    ///     offsets: [f64; 16],
+    /// }
+    ///
+    /// fn tune(gains: &mut [f64; 16]) {
+    ///     gains[0] = 1.0;
+    /// }
+    ///
+    /// fn update(state: &Mutex<State>) {
+    ///     let guard = state.lock().unwrap();
+    ///     tune(&mut MutexGuard::map(guard, |s| &mut s.gains));
+    /// }
+    /// ```
+    pub fn map<U: ?Sized, F>(this: Self, f: F) -> MappedMutexGuard<'a, U>
+    where F: FnOnce(&mut T) -> &mut U
+    {
+        let data = f(unsafe { &mut *this.__data.get() }) as *mut U;
+        let this = ManuallyDrop::new(this);
+        MappedMutexGuard {
+            __mutex: this.__mutex,
+            __poison: this.__poison,
+            __data: data,
+            __panicking: this.__panicking,
+            __marker: PhantomData,
+        }
+    }
+    /// Try narrowing the guard down to a component of the locked
+    /// data. If `f` returns `None`, the original guard is passed
+    /// back to the caller.
+    pub fn try_map<U: ?Sized, F>(this: Self, f: F) -> Result<MappedMutexGuard<'a, U>, Self>
+    where F: FnOnce(&mut T) -> Option<&mut U>
+    {
+        let data = match f(unsafe { &mut *this.__data.get() }) {
+            Some(data) => data as *mut U,
+            None => return Err(this),
+        };
+        let this = ManuallyDrop::new(this);
+        Ok(MappedMutexGuard {
+            __mutex: this.__mutex,
+            __poison: this.__poison,
+            __data: data,
+            __panicking: this.__panicking,
+            __marker: PhantomData,
+        })
+    }
+}
+
+/// A guard over a component of the data protected by a mutex, as
+/// returned by [`MutexGuard::map()`].
+pub struct MappedMutexGuard<'a, U: ?Sized + 'a> {
+    __mutex: &'a CoreMutex,
+    __poison: &'a AtomicBool,
+    __data: *mut U,
+    __panicking: bool,
+    __marker: PhantomData<&'a mut U>,
+}
+
+impl<'a, U: ?Sized> MappedMutexGuard<'a, U> {
+    /// Narrow the guard further down to a component of the locked
+    /// data.
+    pub fn map<V: ?Sized, F>(this: Self, f: F) -> MappedMutexGuard<'a, V>
+    where F: FnOnce(&mut U) -> &mut V
+    {
+        let data = f(unsafe { &mut *this.__data }) as *mut V;
+        let this = ManuallyDrop::new(this);
+        MappedMutexGuard {
+            __mutex: this.__mutex,
+            __poison: this.__poison,
+            __data: data,
+            __panicking: this.__panicking,
+            __marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, U: ?Sized> Deref for MappedMutexGuard<'a, U> {
+    type Target = U;
+
+    fn deref(&self) -> &U {
+        unsafe { &*self.__data }
+    }
+}
+
+impl<'a, U: ?Sized> DerefMut for MappedMutexGuard<'a, U> {
+    fn deref_mut(&mut self) -> &mut U {
+        unsafe { &mut *self.__data }
+    }
+}
+
+impl<'a, U: ?Sized> Drop for MappedMutexGuard<'a, U> {
+    fn drop(&mut self) {
+        if !self.__panicking && thread::panicking() {
+            self.__poison.store(true, Ordering::Relaxed);
+        }
+        self.__mutex.unlock();
+    }
 }
 
 impl<'mutex, T: ?Sized> Deref for MutexGuard<'mutex, T> {
@@ -278,11 +595,201 @@ impl<'mutex, T: ?Sized> DerefMut for MutexGuard<'mutex, T> {
 
 impl<'a, T: ?Sized> Drop for MutexGuard<'a, T> {
     fn drop(&mut self) {
+        if !self.__panicking && thread::panicking() {
+            self.__poison.store(true, Ordering::Relaxed);
+        }
         self.__mutex.unlock();
     }
 }
 
-struct CoreMutex(UnsafeCell<evl_mutex>);
+struct CoreMutex(UnsafeCell<evl_mutex>, CoreClock);
+
+/// A mutex which the owner may lock recursively.
+///
+/// Since the data may be accessed from multiple nested lock scopes
+/// at the same time, the guard only hands out shared references to
+/// it, like [`std::sync::ReentrantLock`] does. Interior mutability
+/// (e.g. [`std::cell::Cell`]) is needed for changing the data.
+///
+/// ```no_run
+/// use std::cell::Cell;
+/// use revl::mutex::{Builder, ReentrantMutex};
+///
+/// let mutex = ReentrantMutex::new(Cell::new(0), Builder::new()).unwrap();
+/// let outer = mutex.lock().unwrap();
+/// let inner = mutex.lock().unwrap();
+/// inner.set(outer.get() + 1);
+/// ```
+pub struct ReentrantMutex<T: ?Sized> {
+    mutex: CoreMutex,
+    data: T,
+}
+
+unsafe impl<T: ?Sized + Send> Send for ReentrantMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for ReentrantMutex<T> {}
+
+impl<T> ReentrantMutex<T> {
+    /// Create a new reentrant mutex for guarding `data`, using the
+    /// properties defined by the [`builder`](struct@Builder). The
+    /// mutex is made recursive regardless of the builder settings.
+    pub fn new(data: T, builder: Builder) -> Result<Self, Error> {
+        Ok(Self {
+            mutex: CoreMutex::new(builder.recursive())?,
+            data,
+        })
+    }
+    /// Consume the mutex, returning the inner data.
+    pub fn into_inner(self) -> T {
+        let Self { mutex, data } = self;
+        drop(mutex);
+        data
+    }
+}
+
+impl<T: ?Sized> ReentrantMutex<T> {
+    /// Lock the mutex, which the caller may already hold. See
+    /// [`Mutex::lock()`].
+    pub fn lock(&self) -> Result<ReentrantMutexGuard<T>, Error> {
+        self.mutex.lock()?;
+        Ok(ReentrantMutexGuard {
+            __mutex: &self.mutex,
+            __data: &self.data,
+            __marker: PhantomData,
+        })
+    }
+    /// Try locking the mutex, which the caller may already hold.
+    /// See [`Mutex::try_lock()`].
+    pub fn try_lock(&self) -> Result<ReentrantMutexGuard<T>, Error> {
+        self.mutex.try_lock()?;
+        Ok(ReentrantMutexGuard {
+            __mutex: &self.mutex,
+            __data: &self.data,
+            __marker: PhantomData,
+        })
+    }
+    /// Return a mutable reference to the inner data. Since this call
+    /// borrows the mutex mutably, no locking is needed.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.data
+    }
+}
+
+/// An RAII guard for a [`ReentrantMutex`], granting shared access
+/// to the inner data.
+pub struct ReentrantMutexGuard<'a, T: ?Sized + 'a> {
+    __mutex: &'a CoreMutex,
+    __data: &'a T,
+    // The guard must be released by the owner thread.
+    __marker: PhantomData<*const ()>,
+}
+
+impl<'a, T: ?Sized> Deref for ReentrantMutexGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.__data
+    }
+}
+
+impl<'a, T: ?Sized> Drop for ReentrantMutexGuard<'a, T> {
+    fn drop(&mut self) {
+        self.__mutex.unlock();
+    }
+}
+
+/// A mutex which can be statically initialized, deferring the
+/// creation of the underlying EVL mutex until it is locked for the
+/// first time.
+///
+/// This allows global state to live in statics, without calling
+/// any EVL service before `main()`. The first lock issues an in-band
+/// system call for creating the EVL mutex, which should preferably
+/// happen during the initialization phase of the application. The
+/// EVL mutex is private, anonymous, enforces priority inheritance,
+/// and its timeouts are based on the monotonic clock. Poisoning
+/// works the same way as with [`Mutex`].
+///
+/// ```no_run
+/// use revl::mutex::LazyMutex;
+///
+/// static CALIBRATION: LazyMutex<[f64; 4]> = LazyMutex::new([0.0; 4]);
+///
+/// fn calibrate() -> Result<(), revl::Error> {
+///     let mut table = CALIBRATION.lock()?;
+///     table[0] = 1.5;
+///     Ok(())
+/// }
+/// ```
+pub struct LazyMutex<T: ?Sized> {
+    mutex: OnceLock<CoreMutex>,
+    poison: AtomicBool,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for LazyMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for LazyMutex<T> {}
+
+impl<T> LazyMutex<T> {
+    /// Create a lazily initialized mutex for guarding `data`.
+    pub const fn new(data: T) -> Self {
+        Self {
+            mutex: OnceLock::new(),
+            poison: AtomicBool::new(false),
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+impl<T: ?Sized> LazyMutex<T> {
+    // Get the EVL mutex, creating it on first call.
+    fn core(&self) -> Result<&CoreMutex, Error> {
+        if let Some(mutex) = self.mutex.get() {
+            return Ok(mutex);
+        }
+        // If we raced with another thread, ours is dropped.
+        let _ = self.mutex.set(CoreMutex::new(Builder::new())?);
+        Ok(self.mutex.get().unwrap())
+    }
+    /// Lock the mutex, creating it first if need be. See
+    /// [`Mutex::lock()`].
+    pub fn lock(&self) -> Result<MutexGuard<T>, Error> {
+        let mutex = self.core()?;
+        mutex.lock()?;
+        MutexGuard::new(mutex, &self.poison, &self.data)
+    }
+    /// Try locking the mutex, creating it first if need be. See
+    /// [`Mutex::try_lock()`].
+    pub fn try_lock(&self) -> Result<MutexGuard<T>, Error> {
+        let mutex = self.core()?;
+        mutex.try_lock()?;
+        MutexGuard::new(mutex, &self.poison, &self.data)
+    }
+    /// Lock the mutex with a timeout, creating it first if need
+    /// be. See [`Mutex::lock_timed()`].
+    pub fn lock_timed(&self, timeout: Instant<CoreClock>) -> Result<MutexGuard<T>, Error> {
+        let mutex = self.core()?;
+        mutex.lock_timed(timeout)?;
+        MutexGuard::new(mutex, &self.poison, &self.data)
+    }
+    /// Tell whether the mutex is poisoned.
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.load(Ordering::Relaxed)
+    }
+    /// Clear the poisoned state of the mutex.
+    pub fn clear_poison(&self) {
+        self.poison.store(false, Ordering::Relaxed);
+    }
+}
+
+// Flags mixed with the owner handle in the monitor state: waiters
+// are pending (claimed), ceiling is in effect.
+const OWNER_CLAIMED: u32 = 0x8000_0000;
+const OWNER_CEILING: u32 = 0x4000_0000;
+const OWNER_FLAG_MASK: u32 = OWNER_CLAIMED | OWNER_CEILING;
+
+// The core serializes accesses to the mutex.
+unsafe impl Send for CoreMutex {}
+unsafe impl Sync for CoreMutex {}
 
 impl Drop for CoreMutex {
     fn drop(&mut self) {
@@ -300,9 +807,11 @@ impl fmt::Debug for CoreMutex {
 
 impl CoreMutex {
     fn new(builder: Builder) -> Result<Self, Error> {
+        let clock = builder.clock.unwrap_or(STEADY_CLOCK);
+        let c_clockfd = clock.0 as i32;
         let this = Self(UnsafeCell::new(unsafe {
             MaybeUninit::<evl_mutex>::zeroed().assume_init()
-        }));
+        }), clock);
         let mut c_flags = CloneFlags::PRIVATE.bits() as c_int;
         if builder.visible {
             c_flags = CloneFlags::PUBLIC.bits() as c_int;
@@ -311,8 +820,6 @@ impl CoreMutex {
             c_flags |= MutexType::RECURSIVE.bits() as c_int;
         }
         let c_ceiling = builder.ceiling;
-        // Revisit: clock should be configurable.
-        let c_clockfd = BuiltinClock::MONOTONIC as i32;
         let ret: c_int = unsafe {
             if let Some(name) = builder.name {
                 let c_name = CString::new(name).expect("CString::new failed");
@@ -349,12 +856,107 @@ impl CoreMutex {
         let ret: c_int = unsafe { evl_trylock_mutex(self.0.get()) };
         match ret {
             0 => return Ok(()),
+            // The core reports contention as EBUSY.
+            _ if ret == -EBUSY => return Err(Error::WouldBlock),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    fn lock_timed(&self, timeout: Instant<CoreClock>) -> Result<(), Error> {
+        let date = instant_to_timespec(timeout);
+        let ret: c_int = unsafe { evl_timedlock_mutex(self.0.get(), &date) };
+        match ret {
+            0 => return Ok(()),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    fn set_ceiling(&self, ceiling: u32) -> Result<(), Error> {
+        let ret: c_int = unsafe { evl_set_mutex_ceiling(self.0.get(), ceiling) };
+        match ret {
+            0 => return Ok(()),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    fn ceiling(&self) -> Result<u32, Error> {
+        let ret: c_int = unsafe { evl_get_mutex_ceiling(self.0.get()) };
+        match ret {
+            0.. => return Ok(ret as u32),
             _ => return Err(Error::from_raw_os_error(-ret)),
         };
     }
+    // Read the owner word from the gate state shared with the core,
+    // which updates it concurrently.
+    fn owner_word(&self) -> Option<u32> {
+        let state = unsafe { (*self.0.get()).u.active.state };
+        if state.is_null() {
+            return None;
+        }
+        let owner = unsafe { ptr::addr_of!((*state).u.gate.owner.val) };
+        Some(unsafe { &*(owner as *const AtomicU32) }.load(Ordering::Acquire))
+    }
     fn unlock(&self) {
         unsafe {
             evl_unlock_mutex(self.0.get());
         };
     }
 }
+
+/// A raw EVL mutex implementing the [`lock_api`] traits (`lock_api`
+/// feature), so that the `lock_api`-based types can be backed by
+/// EVL locking, e.g. `lock_api::Mutex<RawMutex, T>`.
+///
+/// Since such mutex may be statically initialized, the underlying
+/// EVL mutex is created on first use, which requires an in-band
+/// system call. This mutex is private, anonymous, enforces priority
+/// inheritance, and its timeouts are based on the monotonic clock.
+/// Failing to create it, or to lock it, causes a panic.
+///
+/// ```no_run
+/// use revl::mutex::RawMutex;
+///
+/// static COUNTER: lock_api::Mutex<RawMutex, u32> = lock_api::Mutex::const_new(
+///     <RawMutex as lock_api::RawMutex>::INIT, 0);
+///
+/// *COUNTER.lock() += 1;
+/// ```
+#[cfg(feature = "lock_api")]
+pub struct RawMutex(OnceLock<CoreMutex>);
+
+#[cfg(feature = "lock_api")]
+impl RawMutex {
+    fn core(&self) -> &CoreMutex {
+        self.0.get_or_init(|| {
+            CoreMutex::new(Builder::new()).expect("cannot create EVL mutex")
+        })
+    }
+}
+
+#[cfg(feature = "lock_api")]
+unsafe impl lock_api::RawMutex for RawMutex {
+    const INIT: Self = RawMutex(OnceLock::new());
+
+    // An EVL mutex must be released by its owner.
+    type GuardMarker = lock_api::GuardNoSend;
+
+    fn lock(&self) {
+        self.core().lock().expect("cannot lock EVL mutex");
+    }
+    fn try_lock(&self) -> bool {
+        self.core().try_lock().is_ok()
+    }
+    unsafe fn unlock(&self) {
+        self.core().unlock();
+    }
+}
+
+#[cfg(feature = "lock_api")]
+unsafe impl lock_api::RawMutexTimed for RawMutex {
+    type Duration = Duration;
+    type Instant = Instant<CoreClock>;
+
+    fn try_lock_for(&self, timeout: Self::Duration) -> bool {
+        self.try_lock_until(STEADY_CLOCK.now() + Nanoseconds(timeout.as_nanos() as u64))
+    }
+    fn try_lock_until(&self, timeout: Self::Instant) -> bool {
+        self.core().lock_timed(timeout).is_ok()
+    }
+}
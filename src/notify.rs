@@ -0,0 +1,72 @@
+//! Single permit wakeup.
+//!
+//! [`Notify`] implements the common "kick the worker" pattern: a
+//! producer signals that some work is pending, a worker waits for
+//! such signal. Notifications are not counted: posting several times
+//! before the worker waits leaves a single permit, which the next
+//! wait consumes. This is based on a flag group using a single bit.
+
+use std::time::Duration;
+use embedded_time::{duration::Nanoseconds, Instant};
+use crate::clock::{CoreClock, STEADY_CLOCK};
+use crate::flags::{self, Flags};
+use crate::Error;
+
+const PERMIT: u32 = 1;
+
+/// A wakeup notification carrying at most one permit.
+///
+/// # Examples
+///
+/// ```no_run
+/// use revl::notify::Notify;
+///
+/// let kick = Notify::new().unwrap();
+///
+/// // Producer side.
+/// kick.notify_one().unwrap();
+///
+/// // Worker side.
+/// loop {
+///     kick.notified().unwrap();
+///     // Process the pending work.
+/// }
+/// ```
+pub struct Notify(Flags);
+
+impl Notify {
+    /// Create a notification object with no pending permit.
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self(flags::Builder::new().create()?))
+    }
+    /// Store the permit, waking up the leading waiter if any. The
+    /// permit is not accumulated if already pending.
+    pub fn notify_one(&self) -> Result<(), Error> {
+        self.0.post(PERMIT)
+    }
+    /// Wait for the permit, consuming it. This returns immediately
+    /// if the permit is already pending.
+    pub fn notified(&self) -> Result<(), Error> {
+        self.0.wait().map(|_| ())
+    }
+    /// Wait for the permit until the absolute `timeout` date is
+    /// reached, based on the monotonic clock.
+    ///
+    /// # Errors
+    ///
+    /// [`TimedOut`][`crate::Error::TimedOut`] is returned if no
+    /// permit was received by the timeout date.
+    pub fn notified_timed(&self, timeout: Instant<CoreClock>) -> Result<(), Error> {
+        self.0.wait_timed(timeout).map(|_| ())
+    }
+    /// Wait for the permit for at most `delay`, see
+    /// [`notified_timed()`](Self::notified_timed).
+    pub fn notified_for(&self, delay: Duration) -> Result<(), Error> {
+        self.notified_timed(STEADY_CLOCK.now() + Nanoseconds(delay.as_nanos() as u64))
+    }
+    /// Consume the permit if pending, without blocking. Return true
+    /// if a permit was consumed.
+    pub fn try_notified(&self) -> Result<bool, Error> {
+        Ok(self.0.try_wait()?.is_some())
+    }
+}
@@ -0,0 +1,349 @@
+//! Observable elements.
+//!
+//! An observable is an event source which threads may subscribe to,
+//! implementing the observer pattern across the out-of-band and
+//! in-band stages. A publisher posts [`Notice`]s to the observable,
+//! each subscriber then reads them in the form of
+//! [`Notification`]s, in order of publication. Observable threads,
+//! see [`thread::Builder::observable()`](crate::thread::Builder::observable),
+//! can be observed the same way through their file descriptor, in
+//! which case the core publishes health monitoring notices.
+//!
+//! Subscriptions belong to the calling thread: a thread has to
+//! subscribe to an observable before it can read notifications from
+//! it.
+//!
+//! ```no_run
+//! use revl::observable::{self, Notice};
+//!
+//! const SETPOINT: u32 = observable::NOTICE_USER;
+//!
+//! let obs = observable::Builder::new().name("setpoints").create().unwrap();
+//! obs.subscribe(16, false).unwrap();
+//! obs.update(&[Notice::new(SETPOINT, 1500)]).unwrap();
+//! for nf in obs.notifications().take(1) {
+//!     let nf = nf.unwrap();
+//!     println!("tag {} value {} from {}", nf.tag(), nf.value(), nf.issuer());
+//! }
+//! ```
+
+use std::ffi::CString;
+use std::fmt;
+use std::io::ErrorKind;
+use std::mem::MaybeUninit;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::os::raw::c_int;
+use std::ptr;
+use embedded_time::Instant;
+use evl_sys::{
+    evl_create_observable,
+    evl_notice,
+    evl_notification,
+    evl_read_observable,
+    evl_subscribe,
+    evl_unsubscribe,
+    evl_update_observable,
+    evl_value,
+    CloneFlags,
+};
+use crate::clock::CoreClock;
+use crate::Error;
+
+/// The first tag available to applications, lower values are
+/// reserved to the core.
+pub const NOTICE_USER: u32 = 64;
+
+// Subscription modes.
+const NOTIFY_ALL: u32 = 0;
+const NOTIFY_ONCHANGE: u32 = 1;
+
+/// A notice posted to an observable. This has the layout of the
+/// notice descriptor the core reads, so that [`Observable::update()`]
+/// passes the notices through without copying them.
+#[derive(Clone, Copy)]
+#[repr(transparent)]
+pub struct Notice(evl_notice);
+
+impl Notice {
+    /// Create a notice of kind `tag`, which should be at least
+    /// [`NOTICE_USER`], carrying `value`.
+    pub fn new(tag: u32, value: i64) -> Self {
+        let mut c_notice = unsafe { MaybeUninit::<evl_notice>::zeroed().assume_init() };
+        c_notice.tag = tag;
+        c_notice.event = unsafe { MaybeUninit::<evl_value>::zeroed().assume_init() };
+        c_notice.event.lval = value;
+        Self(c_notice)
+    }
+    /// Return the tag of the notice.
+    pub fn tag(&self) -> u32 {
+        self.0.tag
+    }
+    /// Return the value of the notice.
+    pub fn value(&self) -> i64 {
+        unsafe { self.0.event.lval }
+    }
+}
+
+impl PartialEq for Notice {
+    fn eq(&self, other: &Self) -> bool {
+        self.tag() == other.tag() && self.value() == other.value()
+    }
+}
+
+impl Eq for Notice {}
+
+impl fmt::Debug for Notice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Notice")
+            .field("tag", &self.tag())
+            .field("value", &self.value())
+            .finish()
+    }
+}
+
+/// A notice received by a subscriber.
+#[derive(Clone, Copy, Debug)]
+pub struct Notification {
+    tag: u32,
+    serial: u32,
+    issuer: u32,
+    value: i64,
+    date: u64,
+}
+
+impl Notification {
+    /// Return the tag of the notice.
+    pub fn tag(&self) -> u32 {
+        self.tag
+    }
+    /// Return the value of the notice.
+    pub fn value(&self) -> i64 {
+        self.value
+    }
+    /// Return the serial number of the notice, which increases with
+    /// each publication on the observable.
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+    /// Return the process id of the thread which posted the notice.
+    pub fn issuer(&self) -> u32 {
+        self.issuer
+    }
+    /// Return the date the notice was posted, based on the monotonic
+    /// clock.
+    pub fn date(&self) -> Instant<CoreClock> {
+        Instant::new(self.date)
+    }
+}
+
+pub struct Builder {
+    name: Option<String>,
+    visible: bool,
+    unicast: bool,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            name: None,
+            visible: false,
+            unicast: false,
+        }
+    }
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+    pub fn public(mut self) -> Self {
+        self.visible = true;
+        self
+    }
+    pub fn private(mut self) -> Self {
+        self.visible = false;
+        self
+    }
+    /// Deliver each notice to a single subscriber in turn, instead
+    /// of broadcasting it to all of them.
+    pub fn unicast(mut self) -> Self {
+        self.unicast = true;
+        self
+    }
+    pub fn create(self) -> Result<Observable, Error> {
+        Observable::new(self)
+    }
+}
+
+pub struct Observable(c_int);
+
+unsafe impl Send for Observable {}
+unsafe impl Sync for Observable {}
+
+impl Observable {
+    /// Create an EVL observable, retrieving the settings from a
+    /// [`builder struct`](Builder).
+    ///
+    /// # Errors
+    ///
+    /// * [`NameConflict`][`crate::Error::NameConflict`] means the
+    /// observable name is conflicting with an existing one.
+    ///
+    /// * [`InvalidInput`][`std::io::ErrorKind`] means that the name
+    /// contains invalid characters.
+    pub fn new(builder: Builder) -> Result<Self, Error> {
+        let mut c_flags = CloneFlags::PRIVATE.bits() as c_int;
+        if builder.visible {
+            c_flags = CloneFlags::PUBLIC.bits() as c_int;
+        }
+        if builder.unicast {
+            c_flags |= CloneFlags::UNICAST.bits() as c_int;
+        }
+        let ret: c_int = unsafe {
+            if let Some(name) = builder.name {
+                let c_name = CString::new(name).expect("CString::new failed");
+                let c_fmt = CString::new("%s").expect("CString::new failed");
+                evl_create_observable(c_flags, c_fmt.as_ptr(), c_name.as_ptr())
+            } else {
+                evl_create_observable(c_flags, ptr::null())
+            }
+        };
+        match ret {
+            0.. => return Ok(Self(ret)),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    /// Post `notices` to the subscribers with a single call to the
+    /// core, returning the number of notices posted.
+    ///
+    /// # Errors
+    ///
+    /// [`InvalidInput`][`std::io::ErrorKind`] is returned if a tag is
+    /// lower than [`NOTICE_USER`], or there are more than `i32::MAX`
+    /// notices.
+    pub fn update(&self, notices: &[Notice]) -> Result<usize, Error> {
+        let nr = c_int::try_from(notices.len())
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "too many notices"))?;
+        let ret: c_int = unsafe {
+            evl_update_observable(self.0, notices.as_ptr().cast::<evl_notice>(), nr)
+        };
+        match ret {
+            0.. => return Ok(ret as usize),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    /// Subscribe the calling thread to this observable, see
+    /// [`subscribe()`].
+    pub fn subscribe(&self, backlog: u32, merge: bool) -> Result<(), Error> {
+        subscribe(self, backlog, merge)
+    }
+    /// Unsubscribe the calling thread from this observable.
+    pub fn unsubscribe(&self) -> Result<(), Error> {
+        unsubscribe(self)
+    }
+    /// Wait for the next notification, see [`read()`].
+    pub fn read(&self) -> Result<Notification, Error> {
+        read(self)
+    }
+    /// Iterate over the notifications received by the calling
+    /// thread, see [`notifications()`].
+    pub fn notifications(&self) -> Notifications<'_, Self> {
+        notifications(self)
+    }
+}
+
+impl AsRawFd for Observable {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+impl AsFd for Observable {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.0) }
+    }
+}
+
+impl Drop for Observable {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.0);
+        }
+    }
+}
+
+/// Subscribe the calling thread to the `target` observable, which
+/// may be an [`Observable`] or an observable [thread](crate::thread::Thread).
+/// Up to `backlog` notifications may be pending for the subscriber,
+/// further ones are lost until it reads. If `merge` is set,
+/// consecutive notices carrying the same tag and value are merged
+/// into a single notification.
+pub fn subscribe<S: AsRawFd + ?Sized>(target: &S, backlog: u32, merge: bool) -> Result<(), Error> {
+    let mode = if merge { NOTIFY_ONCHANGE } else { NOTIFY_ALL };
+    let ret: c_int = unsafe {
+        evl_subscribe(target.as_raw_fd(), backlog as _, mode as c_int)
+    };
+    match ret {
+        0 => return Ok(()),
+        _ => return Err(Error::from_raw_os_error(-ret)),
+    };
+}
+
+/// Unsubscribe the calling thread from the `target` observable.
+pub fn unsubscribe<S: AsRawFd + ?Sized>(target: &S) -> Result<(), Error> {
+    let ret: c_int = unsafe { evl_unsubscribe(target.as_raw_fd()) };
+    match ret {
+        0 => return Ok(()),
+        _ => return Err(Error::from_raw_os_error(-ret)),
+    };
+}
+
+/// Wait for the next notification from the `target` observable the
+/// calling thread subscribed to.
+///
+/// # Errors
+///
+/// [`WouldBlock`][`crate::Error::WouldBlock`] is returned if the
+/// observable is non-blocking and no notification is pending.
+pub fn read<S: AsRawFd + ?Sized>(target: &S) -> Result<Notification, Error> {
+    let mut nf = MaybeUninit::<evl_notification>::uninit();
+    let ret: c_int = unsafe { evl_read_observable(target.as_raw_fd(), nf.as_mut_ptr(), 1) };
+    match ret {
+        1.. => {
+            let nf = unsafe { nf.assume_init() };
+            return Ok(Notification {
+                tag: nf.tag,
+                serial: nf.serial,
+                issuer: nf.issuer as u32,
+                value: unsafe { nf.event.lval },
+                date: nf.date.tv_sec as u64 * 1_000_000_000 + nf.date.tv_nsec as u64,
+            });
+        },
+        _ => return Err(Error::from_raw_os_error(-ret)),
+    };
+}
+
+/// Iterate over the notifications received by the calling thread
+/// from the `target` observable, waiting for each of them. The
+/// iteration stops after the first error, which is yielded.
+pub fn notifications<S: AsRawFd + ?Sized>(target: &S) -> Notifications<'_, S> {
+    Notifications { target, done: false }
+}
+
+/// An iterator over the notifications of an observable, see
+/// [`notifications()`].
+pub struct Notifications<'a, S: ?Sized> {
+    target: &'a S,
+    done: bool,
+}
+
+impl<'a, S: AsRawFd + ?Sized> Iterator for Notifications<'a, S> {
+    type Item = Result<Notification, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let ret = read(self.target);
+        self.done = ret.is_err();
+        Some(ret)
+    }
+}
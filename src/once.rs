@@ -0,0 +1,149 @@
+//! One-time initialization.
+//!
+//! [`OnceLock`] is a cell which is written only once, typically for
+//! building a lookup table shared by several real-time threads on
+//! first use. Unlike [`std::sync::OnceLock`], threads racing with the
+//! initializer wait on an EVL condition variable instead of the
+//! in-band futex parking, so that out-of-band threads may wait for
+//! the value without being demoted.
+
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::condvar::{self, CondvarPair};
+use crate::Error;
+
+/// A cell initialized once, by the first thread asking for its
+/// value.
+///
+/// # Examples
+///
+/// ```no_run
+/// use revl::once::OnceLock;
+///
+/// let table: OnceLock<Vec<f32>> = OnceLock::new().unwrap();
+///
+/// // From any thread: the first caller builds the table, others
+/// // wait for it then share the result.
+/// let t = table.get_or_init(|| (0..1024).map(|i| (i as f32).sin()).collect()).unwrap();
+/// assert_eq!(t.len(), 1024);
+/// ```
+pub struct OnceLock<T> {
+    done: AtomicBool,
+    // True while an initializer runs.
+    running: CondvarPair<bool>,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+unsafe impl<T: Send> Send for OnceLock<T> {}
+unsafe impl<T: Send + Sync> Sync for OnceLock<T> {}
+
+// Hand over to the next initializer if the current one fails or
+// panics, wake up the waiters otherwise.
+struct Completion<'a, T>(&'a OnceLock<T>);
+
+impl<'a, T> Drop for Completion<'a, T> {
+    fn drop(&mut self) {
+        if let Ok(mut running) = self.0.running.lock() {
+            *running = false;
+        }
+        let _ = self.0.running.notify_all();
+    }
+}
+
+impl<T> OnceLock<T> {
+    /// Create an empty cell. The EVL elements used for waiting are
+    /// created at this point, so that no in-band call is needed
+    /// afterwards.
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            done: AtomicBool::new(false),
+            running: condvar::Builder::new().create_pair(false)?,
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        })
+    }
+    /// Return the value, or `None` if the cell is not initialized
+    /// yet. This call never blocks.
+    pub fn get(&self) -> Option<&T> {
+        if self.done.load(Ordering::Acquire) {
+            Some(unsafe { (*self.value.get()).assume_init_ref() })
+        } else {
+            None
+        }
+    }
+    /// Return the value, initializing it with `f` if the cell is
+    /// empty. Concurrent callers wait for the initializer to
+    /// complete.
+    ///
+    /// If `f` panics, the panic is propagated to the caller and the
+    /// cell stays empty, one of the waiters running its own
+    /// initializer next.
+    pub fn get_or_init<F>(&self, f: F) -> Result<&T, Error>
+    where F: FnOnce() -> T
+    {
+        self.get_or_try_init(|| Ok::<T, Error>(f()))
+    }
+    /// Return the value, initializing it with the fallible `f` if the
+    /// cell is empty. If `f` fails, its error is returned and the
+    /// cell stays empty.
+    pub fn get_or_try_init<F, E>(&self, f: F) -> Result<&T, E>
+    where F: FnOnce() -> Result<T, E>,
+          E: From<Error>
+    {
+        if let Some(value) = self.get() {
+            return Ok(value);
+        }
+        let guard = self.running.lock()?;
+        let mut guard = self.running.wait_while(guard, |running| *running)
+            .map_err(Error::from)?;
+        if let Some(value) = self.get() {
+            return Ok(value);
+        }
+        *guard = true;
+        drop(guard);
+        let _completion = Completion(self);
+        let value = f()?;
+        unsafe { (*self.value.get()).write(value); }
+        self.done.store(true, Ordering::Release);
+        Ok(unsafe { (*self.value.get()).assume_init_ref() })
+    }
+    /// Return a mutable reference to the value, if initialized.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        if *self.done.get_mut() {
+            Some(unsafe { self.value.get_mut().assume_init_mut() })
+        } else {
+            None
+        }
+    }
+    /// Take the value out, leaving the cell empty.
+    pub fn take(&mut self) -> Option<T> {
+        if *self.done.get_mut() {
+            *self.done.get_mut() = false;
+            Some(unsafe { self.value.get_mut().assume_init_read() })
+        } else {
+            None
+        }
+    }
+    /// Consume the cell, returning the value if initialized.
+    pub fn into_inner(mut self) -> Option<T> {
+        self.take()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for OnceLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.get() {
+            Some(value) => f.debug_tuple("OnceLock").field(value).finish(),
+            None => f.write_str("OnceLock(<uninit>)"),
+        }
+    }
+}
+
+impl<T> Drop for OnceLock<T> {
+    fn drop(&mut self) {
+        if *self.done.get_mut() {
+            unsafe { self.value.get_mut().assume_init_drop(); }
+        }
+    }
+}
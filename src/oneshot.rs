@@ -0,0 +1,191 @@
+//! Single-use channel for request/response.
+//!
+//! A oneshot channel carries exactly one message, typically the reply
+//! to a request an RT thread sent to some worker. The [`Receiver`]
+//! sleeps on a single bit of an EVL flag group until the [`Sender`]
+//! delivers the reply or is dropped, so that waiting can be done from
+//! the out-of-band stage.
+//!
+//! ```no_run
+//! use revl::{oneshot, thread};
+//!
+//! // Waiting for the reply requires an EVL thread.
+//! let _me = thread::Builder::new().name("requester").attach().unwrap();
+//! let (tx, rx) = oneshot::channel::<u64>().unwrap();
+//! thread::Builder::new().name("worker").spawn(move |_| {
+//!     let result = (1..=20).product();
+//!     tx.send(result).unwrap();
+//! }).unwrap();
+//! println!("20! = {}", rx.recv().unwrap());
+//! ```
+
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::{
+    Arc,
+    atomic::AtomicU8,
+    atomic::Ordering::AcqRel,
+    atomic::Ordering::Acquire,
+};
+use std::time::Duration;
+use embedded_time::{duration::Nanoseconds, Instant};
+use crate::channel::{RecvError, SendError};
+use crate::clock::{CoreClock, STEADY_CLOCK};
+use crate::flags::{self, Flags};
+use crate::Error;
+
+// Channel states.
+const EMPTY: u8 = 0;
+const SENT: u8 = 1;
+const TAKEN: u8 = 2;
+const SENDER_GONE: u8 = 3;
+const RECEIVER_GONE: u8 = 4;
+
+const READY: u32 = 1;
+
+struct Oneshot<T> {
+    state: AtomicU8,
+    value: UnsafeCell<MaybeUninit<T>>,
+    ready: Flags,
+}
+
+unsafe impl<T: Send> Send for Oneshot<T> {}
+unsafe impl<T: Send> Sync for Oneshot<T> {}
+
+/// The sending half of a oneshot channel. Dropping it without sending
+/// disconnects the receiver.
+pub struct Sender<T> {
+    inner: Arc<Oneshot<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Deliver the message, waking up the receiver. This never
+    /// blocks.
+    ///
+    /// # Errors
+    ///
+    /// [`SendError::Disconnected`] hands back the message if the
+    /// receiver was dropped.
+    pub fn send(self, msg: T) -> Result<(), SendError<T>> {
+        let inner = &self.inner;
+        unsafe { (*inner.value.get()).write(msg); }
+        match inner.state.compare_exchange(EMPTY, SENT, AcqRel, Acquire) {
+            Ok(_) => {
+                let _ = inner.ready.post(READY);
+                Ok(())
+            },
+            Err(_) => {
+                let msg = unsafe { (*inner.value.get()).assume_init_read() };
+                Err(SendError::Disconnected(msg))
+            },
+        }
+    }
+    /// Return true if the receiver was dropped, in which case sending
+    /// is pointless.
+    pub fn is_closed(&self) -> bool {
+        self.inner.state.load(Acquire) == RECEIVER_GONE
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        // Nothing to do if the message was sent or nobody listens.
+        if self.inner.state.compare_exchange(EMPTY, SENDER_GONE, AcqRel, Acquire).is_ok() {
+            let _ = self.inner.ready.post(READY);
+        }
+    }
+}
+
+/// The receiving half of a oneshot channel.
+pub struct Receiver<T> {
+    inner: Arc<Oneshot<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Wait for the message.
+    ///
+    /// # Errors
+    ///
+    /// [`RecvError::Disconnected`] is returned if the sender was
+    /// dropped without sending, or the message was already received,
+    /// e.g. by another thread sharing the receiver.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        loop {
+            match self.try_recv() {
+                Err(RecvError::Empty) => {
+                    self.inner.ready.wait().map_err(RecvError::Wait)?;
+                },
+                ret => return self.pass_on(ret),
+            }
+        }
+    }
+    /// Wait for the message until the absolute `timeout` date is
+    /// reached, based on the monotonic clock.
+    ///
+    /// # Errors
+    ///
+    /// [`RecvError::Wait`] is returned with
+    /// [`TimedOut`][`crate::Error::TimedOut`] if no message was
+    /// received by the timeout date, otherwise see [`recv()`](Self::recv).
+    pub fn recv_until(&self, timeout: Instant<CoreClock>) -> Result<T, RecvError> {
+        loop {
+            match self.try_recv() {
+                Err(RecvError::Empty) => {
+                    self.inner.ready.wait_timed(timeout).map_err(RecvError::Wait)?;
+                },
+                ret => return self.pass_on(ret),
+            }
+        }
+    }
+    /// Wait for the message for at most `delay`, see
+    /// [`recv_until()`](Self::recv_until).
+    pub fn recv_timed(&self, delay: Duration) -> Result<T, RecvError> {
+        self.recv_until(STEADY_CLOCK.now() + Nanoseconds(delay.as_nanos() as u64))
+    }
+    /// Receive the message if delivered, without blocking.
+    ///
+    /// # Errors
+    ///
+    /// [`RecvError::Empty`] is returned if no message was sent yet,
+    /// otherwise see [`recv()`](Self::recv).
+    pub fn try_recv(&self) -> Result<T, RecvError> {
+        // The receiver may be shared between threads, only the one
+        // claiming the message reads it.
+        match self.inner.state.compare_exchange(SENT, TAKEN, AcqRel, Acquire) {
+            Ok(_) => Ok(unsafe { (*self.inner.value.get()).assume_init_read() }),
+            Err(EMPTY) => Err(RecvError::Empty),
+            Err(_) => Err(RecvError::Disconnected),
+        }
+    }
+    // The ready bit is consumed by the first waiter it wakes up, post
+    // it again for the other threads waiting on a shared receiver,
+    // which would sleep forever otherwise.
+    fn pass_on(&self, ret: Result<T, RecvError>) -> Result<T, RecvError> {
+        let _ = self.inner.ready.post(READY);
+        ret
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        if self.inner.state.swap(RECEIVER_GONE, AcqRel) == SENT {
+            unsafe { (*self.inner.value.get()).assume_init_drop(); }
+        }
+    }
+}
+
+/// Create a oneshot channel, returning its sending and receiving
+/// halves.
+///
+/// # Errors
+///
+/// The EVL flag group the receiver waits on could not be created,
+/// see [`Flags::new()`].
+pub fn channel<T>() -> Result<(Sender<T>, Receiver<T>), Error> {
+    let inner = Arc::new(Oneshot {
+        state: AtomicU8::new(EMPTY),
+        value: UnsafeCell::new(MaybeUninit::uninit()),
+        ready: flags::Builder::new().create()?,
+    });
+    Ok(( Sender { inner: inner.clone() }, Receiver { inner } ))
+}
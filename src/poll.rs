@@ -0,0 +1,316 @@
+//! Polling interface.
+//!
+//! A [`Poller`] waits for I/O readiness on a set of file descriptors,
+//! which may refer to any EVL element supporting polling, such as
+//! [cross-buffers](crate::xbuf), [semaphores](crate::semaphore),
+//! [flag groups](crate::flags), [timers](crate::timer), proxies or
+//! out-of-band sockets. This is the EVL counterpart of
+//! [epoll(7)](https://man7.org/linux/man-pages/man7/epoll.7.html),
+//! usable from the out-of-band stage: an event-driven real-time
+//! thread typically registers all of its inputs with a poller, then
+//! dispatches the readiness events it collects in a loop.
+//!
+//! [`select::Selector`](crate::select::Selector) provides a simpler
+//! interface for the common case of waiting on a single readable
+//! source at a time.
+//!
+//! ```no_run
+//! use revl::clock::STEADY_CLOCK;
+//! use revl::poll::{Events, Interest, Poller, Trigger};
+//! use revl::timer::Timer;
+//! use revl::xbuf;
+//! use std::time::Duration;
+//!
+//! let timer = Timer::new(&STEADY_CLOCK).unwrap();
+//! let input = xbuf::Builder::new().inbound(4096).create().unwrap();
+//!
+//! let mut poller = Poller::new().unwrap();
+//! poller.add(&timer, Interest::READABLE, Trigger::Level, 0).unwrap();
+//! poller.add(&input, Interest::READABLE, Trigger::Edge, 1).unwrap();
+//! timer.start_after(Duration::from_millis(1), Some(Duration::from_millis(1))).unwrap();
+//!
+//! let mut events = Events::with_capacity(8);
+//! loop {
+//!     poller.wait(&mut events).unwrap();
+//!     for event in events.iter() {
+//!         match event.token() {
+//!             0 => { timer.wait().unwrap(); },
+//!             1 => { /* Drain the cross-buffer. */ },
+//!             _ => unreachable!(),
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::os::raw::c_int;
+use bitflags::bitflags;
+use embedded_time::Instant;
+use evl_sys::{
+    evl_add_pollfd,
+    evl_del_pollfd,
+    evl_mod_pollfd,
+    evl_new_poll,
+    evl_poll,
+    evl_poll_event,
+    evl_timedpoll,
+    evl_value,
+};
+use crate::clock::{instant_to_timespec, CoreClock};
+use crate::Error;
+
+bitflags! {
+    /// The readiness conditions of a file descriptor, see
+    /// [poll(2)](https://man7.org/linux/man-pages/man2/poll.2.html).
+    pub struct Interest: u32 {
+        /// Data is available for reading.
+        const READABLE = libc::POLLIN as u32;
+        /// Data can be written without blocking.
+        const WRITABLE = libc::POLLOUT as u32;
+        /// Some exceptional condition is pending.
+        const PRIORITY = libc::POLLPRI as u32;
+        /// An error condition is pending. This is always reported,
+        /// whether requested or not.
+        const ERROR = libc::POLLERR as u32;
+        /// The peer closed its end. This is always reported, whether
+        /// requested or not.
+        const HANGUP = libc::POLLHUP as u32;
+    }
+}
+
+/// How readiness is reported for a file descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// Report the descriptor on every wait as long as it is ready.
+    Level,
+    /// Report the descriptor once when it becomes ready, then only
+    /// after its state changed again. The descriptor should be
+    /// drained until it would block before waiting again.
+    Edge,
+}
+
+fn poll_events(interest: Interest, trigger: Trigger) -> u32 {
+    match trigger {
+        Trigger::Level => interest.bits(),
+        Trigger::Edge => interest.bits() | libc::EPOLLET as u32,
+    }
+}
+
+fn poll_value(token: u64) -> evl_value {
+    let mut value = unsafe { MaybeUninit::<evl_value>::zeroed().assume_init() };
+    value.lval = token as i64;
+    value
+}
+
+/// A readiness event, as collected by [`Poller::wait()`].
+#[derive(Debug, Clone, Copy)]
+pub struct Event {
+    fd: RawFd,
+    events: u32,
+    token: u64,
+}
+
+impl Event {
+    fn from_raw(ev: &evl_poll_event) -> Self {
+        Self {
+            fd: ev.fd,
+            events: ev.events,
+            token: unsafe { ev.pollval.lval } as u64,
+        }
+    }
+    /// Return the token the file descriptor was registered with.
+    pub fn token(&self) -> u64 {
+        self.token
+    }
+    /// Return the file descriptor which is ready.
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+    /// Return the readiness conditions of the file descriptor.
+    pub fn readiness(&self) -> Interest {
+        Interest::from_bits_truncate(self.events)
+    }
+    pub fn is_readable(&self) -> bool {
+        self.readiness().contains(Interest::READABLE)
+    }
+    pub fn is_writable(&self) -> bool {
+        self.readiness().contains(Interest::WRITABLE)
+    }
+    /// Return true if an error or hangup condition is pending.
+    pub fn is_error(&self) -> bool {
+        self.readiness().intersects(Interest::ERROR | Interest::HANGUP)
+    }
+}
+
+/// A buffer collecting the events of a [`Poller`], allocated once
+/// and reused across waits.
+pub struct Events {
+    buf: Vec<MaybeUninit<evl_poll_event>>,
+    len: usize,
+}
+
+impl Events {
+    /// Create a buffer receiving up to `capacity` events per wait.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut buf = Vec::with_capacity(capacity);
+        buf.resize_with(capacity, MaybeUninit::uninit);
+        Self { buf, len: 0 }
+    }
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+    /// Return the number of events collected by the last wait.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    /// Iterate over the events collected by the last wait.
+    pub fn iter(&self) -> impl Iterator<Item = Event> + '_ {
+        self.buf[..self.len].iter().map(|ev| Event::from_raw(unsafe { ev.assume_init_ref() }))
+    }
+}
+
+/// A set of file descriptors to wait on, each identified by a
+/// token. The file descriptors must outlive the poller.
+pub struct Poller<'a> {
+    efd: c_int,
+    _sources: PhantomData<&'a ()>,
+}
+
+impl<'a> Poller<'a> {
+    /// Create an empty poller.
+    pub fn new() -> Result<Self, Error> {
+        let ret: c_int = unsafe { evl_new_poll() };
+        match ret {
+            0.. => return Ok(Self { efd: ret, _sources: PhantomData }),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    /// Monitor `source` for the conditions in `interest`, reported
+    /// according to `trigger` along with `token`.
+    ///
+    /// # Errors
+    ///
+    /// [`NameConflict`][`crate::Error::NameConflict`] is returned if
+    /// the source is already monitored by this poller. The EVL core
+    /// rejects sources which do not support polling, and
+    /// [`WouldBlock`][`crate::Error::WouldBlock`] denotes a cycle
+    /// between nested pollers.
+    pub fn add<S: AsRawFd + ?Sized>(
+        &mut self,
+        source: &'a S,
+        interest: Interest,
+        trigger: Trigger,
+        token: u64,
+    ) -> Result<(), Error> {
+        let ret: c_int = unsafe {
+            evl_add_pollfd(self.efd,
+                           source.as_raw_fd(),
+                           poll_events(interest, trigger),
+                           poll_value(token))
+        };
+        match ret {
+            0 => return Ok(()),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    /// Change the conditions, trigger mode and token of a monitored
+    /// `source`.
+    pub fn modify<S: AsRawFd + ?Sized>(
+        &mut self,
+        source: &S,
+        interest: Interest,
+        trigger: Trigger,
+        token: u64,
+    ) -> Result<(), Error> {
+        let ret: c_int = unsafe {
+            evl_mod_pollfd(self.efd,
+                           source.as_raw_fd(),
+                           poll_events(interest, trigger),
+                           poll_value(token))
+        };
+        match ret {
+            0 => return Ok(()),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    /// Stop monitoring `source`.
+    pub fn remove<S: AsRawFd + ?Sized>(&mut self, source: &S) -> Result<(), Error> {
+        let ret: c_int = unsafe { evl_del_pollfd(self.efd, source.as_raw_fd()) };
+        match ret {
+            0 => return Ok(()),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    /// Wait for at least one monitored source to be ready, collecting
+    /// up to [`Events::capacity()`] events into `events`. Returns the
+    /// number of events collected.
+    pub fn wait(&self, events: &mut Events) -> Result<usize, Error> {
+        events.len = 0;
+        events.len = self.poll(&mut events.buf, None)?;
+        Ok(events.len)
+    }
+    /// Wait for at least one monitored source to be ready until the
+    /// absolute `timeout` date is reached, based on the monotonic
+    /// clock. See [`wait()`](Self::wait).
+    ///
+    /// # Errors
+    ///
+    /// [`TimedOut`][`crate::Error::TimedOut`] is returned if no
+    /// source was ready by the timeout date.
+    pub fn wait_timed(&self, events: &mut Events, timeout: Instant<CoreClock>) -> Result<usize, Error> {
+        events.len = 0;
+        events.len = self.poll(&mut events.buf, Some(timeout))?;
+        Ok(events.len)
+    }
+    /// Wait for a single event, without the need for an [`Events`]
+    /// buffer.
+    pub(crate) fn wait_one(&self, timeout: Option<Instant<CoreClock>>) -> Result<Event, Error> {
+        let mut buf = [MaybeUninit::<evl_poll_event>::uninit()];
+        self.poll(&mut buf, timeout)?;
+        Ok(Event::from_raw(unsafe { buf[0].assume_init_ref() }))
+    }
+    fn poll(
+        &self,
+        buf: &mut [MaybeUninit<evl_poll_event>],
+        timeout: Option<Instant<CoreClock>>,
+    ) -> Result<usize, Error> {
+        let ptr = buf.as_mut_ptr() as *mut evl_poll_event;
+        let nr = buf.len() as c_int;
+        let ret: c_int = match timeout {
+            Some(timeout) => {
+                let date = instant_to_timespec(timeout);
+                unsafe { evl_timedpoll(self.efd, ptr, nr, &date) }
+            },
+            None => unsafe { evl_poll(self.efd, ptr, nr) },
+        };
+        match ret {
+            0.. => return Ok(ret as usize),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+}
+
+impl<'a> AsRawFd for Poller<'a> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.efd
+    }
+}
+
+impl<'a> AsFd for Poller<'a> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.efd) }
+    }
+}
+
+impl<'a> Drop for Poller<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.efd);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! Fixed-size object pool.
+//!
+//! A [`Pool`] holds `N` objects built once at creation, which are
+//! handed out as [`PoolBox`] guards and return to the pool when the
+//! guards are dropped. Objects are recycled as is, so that the heap
+//! memory they own, such as the storage of a `Vec` or a `String`, is
+//! reused instead of being released to the system allocator, which
+//! real-time threads should not call. Taking and returning objects
+//! is lock-free, and does not call into the EVL core.
+//!
+//! Since guards are `Send` and `'static`, they can be passed through
+//! a [channel](crate::channel) as message payloads, the receiver
+//! dropping them after use.
+//!
+//! ```no_run
+//! use revl::channel;
+//! use revl::pool::{Pool, PoolBox};
+//!
+//! let pool = Pool::<Vec<u8>, 64>::new(|| Vec::with_capacity(1500));
+//! let (tx, rx) = channel::create::<PoolBox<Vec<u8>, 64>, 6>().unwrap();
+//!
+//! // Producer:
+//! let mut frame = pool.take().expect("pool exhausted");
+//! frame.clear();
+//! frame.extend_from_slice(b"payload");
+//! tx.send(frame).ok();
+//!
+//! // Consumer:
+//! if let Ok(frame) = rx.recv() {
+//!     println!("{} bytes", frame.len());
+//!     // The buffer returns to the pool here.
+//! }
+//! ```
+
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{
+    Arc,
+    atomic::AtomicU32,
+    atomic::AtomicU64,
+    atomic::AtomicUsize,
+    atomic::Ordering::Acquire,
+    atomic::Ordering::Relaxed,
+    atomic::Ordering::Release,
+};
+
+const NIL: u32 = u32::MAX;
+
+// The head of the free list packs a generation count with the index
+// of the first free object, so that a head which was popped then
+// pushed back in the meantime is detected (ABA).
+fn pack(generation: u32, index: u32) -> u64 {
+    (generation as u64) << 32 | index as u64
+}
+
+fn unpack(head: u64) -> (u32, u32) {
+    ((head >> 32) as u32, head as u32)
+}
+
+/// A pool of `N` objects of type `T`, see the [module
+/// documentation](crate::pool).
+pub struct Pool<T, const N: usize> {
+    objects: Box<[UnsafeCell<T>]>,
+    next: Box<[AtomicU32]>,
+    head: AtomicU64,
+    available: AtomicUsize,
+}
+
+unsafe impl<T: Send, const N: usize> Send for Pool<T, N> {}
+unsafe impl<T: Send, const N: usize> Sync for Pool<T, N> {}
+
+impl<T, const N: usize> Pool<T, N> {
+    /// Create a pool of `N` objects, each built by `init`. This
+    /// allocates memory, so it should be done before real-time work
+    /// starts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is zero or does not fit in 32 bits.
+    pub fn new<F: FnMut() -> T>(mut init: F) -> Arc<Self> {
+        assert!(N > 0 && N < NIL as usize, "invalid pool size");
+        Arc::new(Self {
+            objects: (0..N).map(|_| UnsafeCell::new(init())).collect(),
+            next: (0..N).map(|n| {
+                AtomicU32::new(if n + 1 < N { n as u32 + 1 } else { NIL })
+            }).collect(),
+            head: AtomicU64::new(pack(0, 0)),
+            available: AtomicUsize::new(N),
+        })
+    }
+    /// Take an object from the pool, returning `None` if all of them
+    /// are in use.
+    pub fn take(self: &Arc<Self>) -> Option<PoolBox<T, N>> {
+        let mut head = self.head.load(Acquire);
+        loop {
+            let (generation, index) = unpack(head);
+            if index == NIL {
+                return None;
+            }
+            let next = self.next[index as usize].load(Relaxed);
+            match self.head.compare_exchange_weak(
+                head, pack(generation.wrapping_add(1), next), Acquire, Acquire) {
+                Ok(_) => {
+                    self.available.fetch_sub(1, Relaxed);
+                    return Some(PoolBox { pool: self.clone(), index });
+                },
+                Err(current) => head = current,
+            }
+        }
+    }
+    fn put(&self, index: u32) {
+        let mut head = self.head.load(Relaxed);
+        loop {
+            let (generation, first) = unpack(head);
+            self.next[index as usize].store(first, Relaxed);
+            match self.head.compare_exchange_weak(
+                head, pack(generation.wrapping_add(1), index), Release, Relaxed) {
+                Ok(_) => break,
+                Err(current) => head = current,
+            }
+        }
+        self.available.fetch_add(1, Relaxed);
+    }
+    /// Return the number of objects of the pool.
+    pub fn capacity(&self) -> usize {
+        N
+    }
+    /// Return the number of objects currently available. This is an
+    /// approximation if other threads access the pool concurrently.
+    pub fn available(&self) -> usize {
+        self.available.load(Relaxed)
+    }
+}
+
+/// An object taken from a [`Pool`], which returns to the pool when
+/// dropped, as is.
+pub struct PoolBox<T, const N: usize> {
+    pool: Arc<Pool<T, N>>,
+    index: u32,
+}
+
+unsafe impl<T: Send, const N: usize> Send for PoolBox<T, N> {}
+unsafe impl<T: Sync, const N: usize> Sync for PoolBox<T, N> {}
+
+impl<T, const N: usize> Deref for PoolBox<T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // We own the object until the guard is dropped.
+        unsafe { &*self.pool.objects[self.index as usize].get() }
+    }
+}
+
+impl<T, const N: usize> DerefMut for PoolBox<T, N> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.pool.objects[self.index as usize].get() }
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for PoolBox<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T, const N: usize> Drop for PoolBox<T, N> {
+    fn drop(&mut self) {
+        self.pool.put(self.index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+
+    #[test]
+    fn take_until_exhausted() {
+        let pool = Pool::<u32, 4>::new(|| 0);
+        assert_eq!(pool.capacity(), 4);
+        let taken: Vec<_> = std::iter::from_fn(|| pool.take()).collect();
+        assert_eq!(taken.len(), 4);
+        assert_eq!(pool.available(), 0);
+        assert!(pool.take().is_none());
+        drop(taken);
+        assert_eq!(pool.available(), 4);
+    }
+
+    #[test]
+    fn objects_are_recycled_as_is() {
+        let pool = Pool::<Vec<u8>, 1>::new(|| Vec::with_capacity(64));
+        let mut buf = pool.take().unwrap();
+        buf.extend_from_slice(b"payload");
+        let data = buf.as_ptr();
+        drop(buf);
+        let buf = pool.take().unwrap();
+        assert_eq!(&buf[..], b"payload");
+        assert_eq!(buf.as_ptr(), data);
+    }
+
+    #[test]
+    fn objects_are_not_shared() {
+        let pool = Pool::<AtomicBool, 8>::new(|| AtomicBool::new(false));
+        let workers: Vec<_> = (0..4).map(|_| {
+            let pool = pool.clone();
+            thread::spawn(move || {
+                for _ in 0..50_000 {
+                    if let Some(obj) = pool.take() {
+                        assert!(!obj.swap(true, Relaxed), "object handed out twice");
+                        obj.store(false, Relaxed);
+                    }
+                }
+            })
+        }).collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(pool.available(), 8);
+        let taken: Vec<_> = std::iter::from_fn(|| pool.take()).collect();
+        assert_eq!(taken.len(), 8);
+    }
+}
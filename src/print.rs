@@ -0,0 +1,167 @@
+//! Formatted output from out-of-band threads.
+//!
+//! The standard [`println!`] macro writes to the standard output
+//! through the in-band stage, which demotes an out-of-band caller.
+//! The [`rt_print!`](crate::rt_print), [`rt_println!`](crate::rt_println),
+//! [`rt_eprint!`](crate::rt_eprint) and
+//! [`rt_eprintln!`](crate::rt_eprintln) macros format their arguments
+//! into a buffer on the stack instead, then write the result through
+//! a [proxy](crate::proxy) relaying it to the standard output or
+//! error. Output longer than [`LINE_MAX`] bytes is truncated. Errors
+//! are ignored, so that debug output never disrupts the caller.
+//!
+//! The proxies are created on first use, which requires in-band
+//! services. Calling [`init()`] from the in-band stage during the
+//! setup phase of the application avoids this.
+//!
+//! ```no_run
+//! use revl::{rt_println, rt_eprintln};
+//!
+//! revl::print::init().unwrap();
+//! // From an out-of-band thread:
+//! rt_println!("cycle {} took {} ns", 42, 2400);
+//! rt_eprintln!("deadline missed");
+//! ```
+
+use std::fmt::{self, Write};
+use std::os::fd::RawFd;
+use std::sync::OnceLock;
+use crate::proxy::{self, Proxy};
+use crate::Error;
+
+/// The maximum length of the output of a single print.
+pub const LINE_MAX: usize = 256;
+
+static STDOUT: OnceLock<Proxy> = OnceLock::new();
+static STDERR: OnceLock<Proxy> = OnceLock::new();
+
+// A fixed-size buffer on the stack, truncating the output past its
+// capacity.
+#[derive(Clone, Copy)]
+pub(crate) struct LineBuf {
+    buf: [u8; LINE_MAX],
+    len: usize,
+}
+
+impl LineBuf {
+    pub(crate) fn new() -> Self {
+        Self { buf: [0; LINE_MAX], len: 0 }
+    }
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl Write for LineBuf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let n = s.len().min(LINE_MAX - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+fn get_proxy(cell: &'static OnceLock<Proxy>, fd: RawFd) -> Result<&'static Proxy, Error> {
+    if let Some(proxy) = cell.get() {
+        return Ok(proxy);
+    }
+    // If we race with another thread, the extra proxy is dropped.
+    let _ = cell.set(Proxy::new(fd, proxy::Builder::new().buffer_size(16384))?);
+    Ok(cell.get().unwrap())
+}
+
+/// Create the proxies relaying the output of the print macros to the
+/// standard output and error. This should be called from the in-band
+/// stage, before out-of-band threads start printing.
+pub fn init() -> Result<(), Error> {
+    stdout()?;
+    stderr()?;
+    Ok(())
+}
+
+/// Return the proxy relaying to the standard output.
+pub fn stdout() -> Result<&'static Proxy, Error> {
+    get_proxy(&STDOUT, libc::STDOUT_FILENO)
+}
+
+/// Return the proxy relaying to the standard error.
+pub fn stderr() -> Result<&'static Proxy, Error> {
+    get_proxy(&STDERR, libc::STDERR_FILENO)
+}
+
+/// Format `args` into a stack buffer then write the result to
+/// `proxy` at once, in the fashion of `evl_printf()`. This never
+/// allocates memory.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use revl::{print, proxy};
+///
+/// let log = File::create("/tmp/rt.log").unwrap();
+/// let proxy = proxy::Builder::new().create(&log).unwrap();
+/// print::print_to(&proxy, format_args!("temperature: {:.1}\n", 41.5)).unwrap();
+/// ```
+pub fn print_to(proxy: &Proxy, args: fmt::Arguments) -> Result<(), Error> {
+    let mut line = LineBuf::new();
+    let _ = line.write_fmt(args);
+    proxy.send(line.as_bytes())?;
+    Ok(())
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    if let Ok(proxy) = stdout() {
+        let _ = print_to(proxy, args);
+    }
+}
+
+#[doc(hidden)]
+pub fn _eprint(args: fmt::Arguments) {
+    if let Ok(proxy) = stderr() {
+        let _ = print_to(proxy, args);
+    }
+}
+
+/// Print to the standard output without leaving the out-of-band
+/// stage, see the [print module](crate::print).
+#[macro_export]
+macro_rules! rt_print {
+    ($($arg:tt)*) => {
+        $crate::print::_print(format_args!($($arg)*))
+    };
+}
+
+/// Print to the standard output with a newline without leaving the
+/// out-of-band stage, see the [print module](crate::print).
+#[macro_export]
+macro_rules! rt_println {
+    () => {
+        $crate::print::_print(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::print::_print(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+/// Print to the standard error without leaving the out-of-band
+/// stage, see the [print module](crate::print).
+#[macro_export]
+macro_rules! rt_eprint {
+    ($($arg:tt)*) => {
+        $crate::print::_eprint(format_args!($($arg)*))
+    };
+}
+
+/// Print to the standard error with a newline without leaving the
+/// out-of-band stage, see the [print module](crate::print).
+#[macro_export]
+macro_rules! rt_eprintln {
+    () => {
+        $crate::print::_eprint(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::print::_eprint(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
@@ -0,0 +1,195 @@
+//! Proxy to in-band files.
+//!
+//! Writing to a regular file descriptor from an out-of-band thread
+//! would demote it to the in-band stage. A [`Proxy`] element buffers
+//! the output instead, then relays it to the target file descriptor
+//! from the in-band stage, so that out-of-band threads may send
+//! bytes to a log file, a socket or the standard output without
+//! stage switches.
+
+use std::ffi::{c_void, CString};
+use std::io::{self, Write};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::os::raw::c_int;
+use std::ptr;
+use evl_sys::{
+    evl_create_proxy,
+    oob_write,
+    CloneFlags,
+};
+use crate::thread;
+use crate::Error;
+
+pub struct Builder {
+    name: Option<String>,
+    visible: bool,
+    bufsz: usize,
+    granularity: usize,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            name: None,
+            visible: false,
+            bufsz: 4096,
+            granularity: 0,
+        }
+    }
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+    pub fn public(mut self) -> Self {
+        self.visible = true;
+        self
+    }
+    pub fn private(mut self) -> Self {
+        self.visible = false;
+        self
+    }
+    /// Set the size in bytes of the buffer holding the output until
+    /// it is relayed to the target file. This is 4 KiB by default.
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        self.bufsz = size;
+        self
+    }
+    /// Set the size in bytes of the chunks relayed to the target
+    /// file, which must accept writes of such size at once, e.g.
+    /// for devices requiring fixed-size transfers. Zero, the
+    /// default, lets the proxy relay any amount of pending output.
+    pub fn granularity(mut self, size: usize) -> Self {
+        self.granularity = size;
+        self
+    }
+    /// Create a proxy relaying the output to `target`.
+    pub fn create<F: AsRawFd + ?Sized>(self, target: &F) -> Result<Proxy, Error> {
+        Proxy::new(target.as_raw_fd(), self)
+    }
+}
+
+pub struct Proxy(c_int);
+
+unsafe impl Send for Proxy {}
+unsafe impl Sync for Proxy {}
+
+impl Proxy {
+    /// Create an EVL proxy relaying the output to the `target` file
+    /// descriptor, retrieving the settings from a [`builder
+    /// struct`](Builder). The proxy holds its own reference on the
+    /// target file, so that `target` may be closed afterwards.
+    ///
+    /// # Errors
+    ///
+    /// * [`NameConflict`][`crate::Error::NameConflict`] means the
+    /// proxy name is conflicting with an existing one.
+    ///
+    /// * [`InvalidInput`][`std::io::ErrorKind`] means that the buffer
+    /// size is zero or not a multiple of the granularity, or the name
+    /// contains invalid characters.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use std::io::Write;
+    /// use revl::proxy::Builder;
+    ///
+    /// let log = File::create("/var/log/rt.log").unwrap();
+    /// let mut proxy = Builder::new().buffer_size(16384).create(&log).unwrap();
+    /// // From an out-of-band thread:
+    /// writeln!(proxy, "cycle {} overrun", 42).unwrap();
+    /// ```
+    pub fn new(target: RawFd, builder: Builder) -> Result<Self, Error> {
+        let mut c_flags = CloneFlags::PRIVATE.bits() as c_int;
+        if builder.visible {
+            c_flags = CloneFlags::PUBLIC.bits() as c_int;
+        }
+        let ret: c_int = unsafe {
+            if let Some(name) = builder.name {
+                let c_name = CString::new(name).expect("CString::new failed");
+                let c_fmt = CString::new("%s").expect("CString::new failed");
+                evl_create_proxy(
+                    target,
+                    builder.bufsz,
+                    builder.granularity,
+                    c_flags,
+                    c_fmt.as_ptr(),
+                    c_name.as_ptr(),
+                )
+            } else {
+                evl_create_proxy(target,
+                                 builder.bufsz,
+                                 builder.granularity,
+                                 c_flags,
+                                 ptr::null())
+            }
+        };
+        match ret {
+            0.. => return Ok(Self(ret)),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    /// Write to the proxy, returning the number of bytes buffered.
+    /// Out-of-band callers use the out-of-band I/O service, in-band
+    /// callers the regular write system call, so that this can be
+    /// called from any stage without switching.
+    ///
+    /// # Errors
+    ///
+    /// [`WouldBlock`][`crate::Error::WouldBlock`] is returned if the
+    /// buffer is full.
+    pub fn send(&self, buf: &[u8]) -> Result<usize, Error> {
+        let ret = unsafe {
+            if thread::is_inband() {
+                libc::write(self.0, buf.as_ptr() as *const c_void, buf.len())
+            } else {
+                oob_write(self.0, buf.as_ptr() as *const c_void, buf.len())
+            }
+        };
+        match ret {
+            0.. => return Ok(ret as usize),
+            _ => return Err(Error::last_os_error()),
+        };
+    }
+}
+
+impl Write for &Proxy {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(self.send(buf)?)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        // The output is relayed asynchronously, there is nothing we
+        // could wait for.
+        Ok(())
+    }
+}
+
+impl Write for Proxy {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsRawFd for Proxy {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+impl AsFd for Proxy {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.0) }
+    }
+}
+
+impl Drop for Proxy {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.0);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Blocking bounded queue.
+//!
+//! [`Bounded`] bundles both ends of a [channel](crate::channel) into
+//! a single object which can be shared by producers and consumers.
+//! Senders block when the queue is full, receivers block when it is
+//! empty, which provides backpressure without busy waiting.
+
+use std::time::Duration;
+use crate::channel::{self, Receiver, Sender, DYNAMIC};
+use crate::Error;
+
+/// A bounded multi-producer, multi-consumer queue, with blocking push
+/// and pop operations. The capacity is either `1 << ORDER` messages,
+/// or chosen at runtime by [`Bounded::with_capacity()`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use revl::queue::Bounded;
+/// use revl::thread;
+///
+/// // Both ends block on EVL semaphores, so they run in EVL threads.
+/// let _me = thread::Builder::new().name("consumer").attach().unwrap();
+/// let q = Arc::new(Bounded::<u64, 4>::new().unwrap());
+/// let c_q = Arc::clone(&q);
+///
+/// thread::Builder::new().name("producer").spawn(move |_| {
+///     for n in 0..100 {
+///         c_q.push(n).unwrap();
+///     }
+/// }).unwrap();
+/// for _ in 0..100 {
+///     let n = q.pop().unwrap();
+///     println!("got {}", n);
+/// }
+/// ```
+pub struct Bounded<T, const ORDER: usize = DYNAMIC> {
+    tx: Sender<T, ORDER>,
+    rx: Receiver<T, ORDER>,
+}
+
+impl<T> Bounded<T> {
+    /// Create an empty queue holding up to `capacity` messages, see
+    /// [`channel::bounded()`].
+    pub fn with_capacity(capacity: usize) -> Result<Self, Error> {
+        let (tx, rx) = channel::bounded(capacity)?;
+        Ok(Self { tx, rx })
+    }
+}
+
+impl<T, const ORDER: usize> Bounded<T, ORDER> {
+    /// Create an empty queue of `1 << ORDER` messages. `ORDER` must
+    /// be given explicitly, a queue with the default `ORDER` is
+    /// created by [`with_capacity()`](Bounded::with_capacity)
+    /// instead. This is checked at build time:
+    ///
+    /// ```compile_fail
+    /// use revl::queue::Bounded;
+    ///
+    /// let q = Bounded::<u64>::new();
+    /// ```
+    pub fn new() -> Result<Self, Error> {
+        let (tx, rx) = channel::create::<T, ORDER>()?;
+        Ok(Self { tx, rx })
+    }
+    /// Return the maximum number of messages the queue can hold.
+    pub fn capacity(&self) -> usize {
+        self.tx.capacity()
+    }
+    /// Push a message, waiting for a free slot if the queue is full.
+    pub fn push(&self, msg: T) -> Result<(), Error> {
+        Ok(self.tx.send_blocking(msg)?)
+    }
+    /// Push a message if a slot is free, without blocking.
+    ///
+    /// # Errors
+    ///
+    /// [`WouldBlock`][`crate::Error::WouldBlock`] is returned if the
+    /// queue is full.
+    pub fn try_push(&self, msg: T) -> Result<(), Error> {
+        self.tx.send(msg).map_err(|_| Error::WouldBlock)
+    }
+    /// Push a message, waiting for at most `delay` for a free slot.
+    ///
+    /// # Errors
+    ///
+    /// [`TimedOut`][`crate::Error::TimedOut`] is returned if the
+    /// queue stayed full for the whole delay.
+    pub fn push_for(&self, msg: T, delay: Duration) -> Result<(), Error> {
+        Ok(self.tx.send_timed(msg, delay)?)
+    }
+    /// Pop the next message, waiting for one if the queue is empty.
+    pub fn pop(&self) -> Result<T, Error> {
+        Ok(self.rx.recv_blocking()?)
+    }
+    /// Pop the next message if any, without blocking.
+    ///
+    /// # Errors
+    ///
+    /// [`WouldBlock`][`crate::Error::WouldBlock`] is returned if the
+    /// queue is empty.
+    pub fn try_pop(&self) -> Result<T, Error> {
+        Ok(self.rx.recv()?)
+    }
+    /// Pop the next message, waiting for at most `delay` for one.
+    ///
+    /// # Errors
+    ///
+    /// [`TimedOut`][`crate::Error::TimedOut`] is returned if the
+    /// queue stayed empty for the whole delay.
+    pub fn pop_for(&self, delay: Duration) -> Result<T, Error> {
+        Ok(self.rx.recv_timed(delay)?)
+    }
+    /// Return the number of messages pending in the queue. This is
+    /// a snapshot which may be outdated on return.
+    pub fn len(&self) -> Result<usize, Error> {
+        self.rx.pending()
+    }
+}
@@ -0,0 +1,249 @@
+//! Element discovery.
+//!
+//! The EVL core exports every existing element to sysfs, under
+//! `/sys/devices/virtual/<class>/<name>`, and public elements also
+//! appear in the `/dev/evl/<class>` hierarchy. [`list()`] walks these
+//! hierarchies, returning a typed description of each element, which
+//! is the information the `evl ps` command displays. This only reads
+//! files, so it runs from the in-band stage without being attached to
+//! the core, and does not disturb real-time threads.
+//!
+//! ```no_run
+//! use revl::registry::{self, Kind, State};
+//!
+//! for element in registry::list_kind(Kind::Thread).unwrap() {
+//!     if let Ok(State::Thread(t)) = element.state() {
+//!         println!("{:<24} pid={:<6} cpu={:?} prio={:?}",
+//!                  element.name(), t.pid, t.cpu, t.priority);
+//!     }
+//! }
+//! ```
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use crate::Error;
+
+const SYSFS_ROOT: &str = "/sys/devices/virtual";
+const DEV_ROOT: &str = "/dev/evl";
+
+/// The class of an element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Thread,
+    /// A mutex, which the core implements as a gate monitor.
+    Mutex,
+    /// A semaphore, a flag group or an event for condition
+    /// variables, which the core all implements as event monitors
+    /// and does not tell apart.
+    Event,
+    Observable,
+    Proxy,
+    XBuf,
+}
+
+impl Kind {
+    // Mutexes and events share the monitor class.
+    fn class(self) -> &'static str {
+        match self {
+            Kind::Thread => "thread",
+            Kind::Mutex | Kind::Event => "monitor",
+            Kind::Observable => "observable",
+            Kind::Proxy => "proxy",
+            Kind::XBuf => "xbuf",
+        }
+    }
+}
+
+/// Whether an element is visible in the `/dev/evl` hierarchy, see
+/// e.g. [`mutex::Builder::public()`](crate::mutex::Builder::public).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    Public,
+    Private,
+}
+
+/// The state of a thread, as reported by the core.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThreadState {
+    /// The in-band process id of the thread.
+    pub pid: Option<i32>,
+    /// The CPU the thread runs on.
+    pub cpu: Option<usize>,
+    /// The base priority of the thread.
+    pub base_priority: Option<i32>,
+    /// The current priority of the thread, which is boosted above
+    /// the base priority while the thread holds a contended mutex.
+    pub priority: Option<i32>,
+    /// The name of the scheduling policy, e.g. `fifo`.
+    pub policy: Option<String>,
+}
+
+/// The state of an element, see [`Element::state()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum State {
+    Thread(ThreadState),
+    /// `owner` is the process id of the thread holding the mutex if
+    /// any, `ceiling` the priority ceiling, zero if none.
+    Mutex { owner: Option<i32>, ceiling: u32 },
+    /// The count of a semaphore, or the value of a flag group.
+    Event { value: u64 },
+    /// Any other element, with the raw contents of its `state`
+    /// attribute, if any.
+    Other(String),
+}
+
+/// The description of an existing element.
+#[derive(Debug, Clone)]
+pub struct Element {
+    kind: Kind,
+    name: String,
+    visibility: Visibility,
+    path: PathBuf,
+}
+
+impl Element {
+    /// Return the class of the element.
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+    /// Return the name of the element.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Return whether the element is public or private.
+    pub fn visibility(&self) -> Visibility {
+        self.visibility
+    }
+    /// Read the raw contents of the sysfs attribute `attr` of the
+    /// element, e.g. `stats` for a thread.
+    ///
+    /// # Errors
+    ///
+    /// [`NotFound`][`std::io::ErrorKind`] is returned if the element
+    /// was deleted in the meantime, or has no such attribute.
+    pub fn attribute(&self, attr: &str) -> Result<String, Error> {
+        Ok(fs::read_to_string(self.path.join(attr))?.trim_end().to_string())
+    }
+    /// Read the current state of the element. Since elements live on
+    /// independently, this is a snapshot which may be stale already.
+    ///
+    /// # Errors
+    ///
+    /// [`NotFound`][`std::io::ErrorKind`] is returned if the element
+    /// was deleted in the meantime.
+    pub fn state(&self) -> Result<State, Error> {
+        match self.kind {
+            Kind::Thread => Ok(State::Thread(self.thread_state()?)),
+            Kind::Mutex => {
+                let state = self.attribute("state")?;
+                let mut fields = state.split_whitespace();
+                let owner = fields.next().and_then(|s| s.parse().ok()).filter(|&pid: &i32| pid > 0);
+                let ceiling = fields.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+                Ok(State::Mutex { owner, ceiling })
+            },
+            Kind::Event => {
+                let state = self.attribute("state")?;
+                match parse_value(&state) {
+                    Some(value) => Ok(State::Event { value }),
+                    None => Ok(State::Other(state)),
+                }
+            },
+            _ => match self.attribute("state") {
+                Ok(state) => Ok(State::Other(state)),
+                Err(Error::Io(e)) if e.kind() == ErrorKind::NotFound && self.path.exists() =>
+                    Ok(State::Other(String::new())),
+                Err(e) => Err(e),
+            },
+        }
+    }
+    fn thread_state(&self) -> Result<ThreadState, Error> {
+        let mut state = ThreadState {
+            pid: self.attribute("pid")?.parse().ok(),
+            ..Default::default()
+        };
+        // The sched attribute reads as "<cpu> <bprio> <cprio> <policy> ...".
+        let sched = self.attribute("sched")?;
+        let mut fields = sched.split_whitespace();
+        state.cpu = fields.next().and_then(|s| s.parse().ok());
+        state.base_priority = fields.next().and_then(|s| s.parse().ok());
+        state.priority = fields.next().and_then(|s| s.parse().ok());
+        state.policy = fields.next().map(|s| s.to_string());
+        Ok(state)
+    }
+}
+
+fn parse_value(s: &str) -> Option<u64> {
+    let s = s.split_whitespace().next()?;
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+// Monitors are told apart by the format of their state: gates report
+// an owner and a ceiling, events a single value.
+fn monitor_kind(path: &Path) -> Kind {
+    match fs::read_to_string(path.join("state")) {
+        Ok(state) if state.split_whitespace().count() > 1 => Kind::Mutex,
+        _ => Kind::Event,
+    }
+}
+
+fn list_class(class: &str, kinds: &[Kind]) -> Result<Vec<Element>, Error> {
+    let dir = match fs::read_dir(Path::new(SYSFS_ROOT).join(class)) {
+        Ok(dir) => dir,
+        // No element of this class was ever created.
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut elements = Vec::new();
+    for entry in dir {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = entry.path();
+        // Skip the sysfs plumbing, and the clone device of the class.
+        if !path.join("dev").exists() || name == "clone" {
+            continue;
+        }
+        let kind = match class {
+            "monitor" => monitor_kind(&path),
+            _ => kinds[0],
+        };
+        if !kinds.contains(&kind) {
+            continue;
+        }
+        let visibility = match Path::new(DEV_ROOT).join(class).join(&name).exists() {
+            true => Visibility::Public,
+            false => Visibility::Private,
+        };
+        elements.push(Element { kind, name, visibility, path });
+    }
+    elements.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(elements)
+}
+
+/// List the existing elements of class `kind`, sorted by name.
+///
+/// # Errors
+///
+/// [`PermissionDenied`][`crate::Error::PermissionDenied`] is returned
+/// if sysfs cannot be read.
+pub fn list_kind(kind: Kind) -> Result<Vec<Element>, Error> {
+    list_class(kind.class(), &[kind])
+}
+
+/// List all existing elements, grouped by class and sorted by name.
+///
+/// # Errors
+///
+/// [`PermissionDenied`][`crate::Error::PermissionDenied`] is returned
+/// if sysfs cannot be read.
+pub fn list() -> Result<Vec<Element>, Error> {
+    let mut elements = list_class("thread", &[Kind::Thread])?;
+    elements.extend(list_class("monitor", &[Kind::Mutex, Kind::Event])?);
+    elements.extend(list_class("observable", &[Kind::Observable])?);
+    elements.extend(list_class("proxy", &[Kind::Proxy])?);
+    elements.extend(list_class("xbuf", &[Kind::XBuf])?);
+    Ok(elements)
+}
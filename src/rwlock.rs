@@ -0,0 +1,206 @@
+//! Reader-writer lock.
+//!
+//! A reader-writer lock allows multiple readers to access the shared
+//! data concurrently, or a single writer exclusively. This is
+//! typically useful for read-mostly data, such as calibration tables
+//! read by several real-time threads and written rarely.
+//!
+//! The lock is built from an EVL mutex serializing the accesses to
+//! its internal state, and a pair of EVL events for waiting. Writers
+//! are given preference: no new reader may enter as long as a writer
+//! is waiting, which prevents writer starvation. Since the internal
+//! mutex enforces priority inheritance, contenders are queued by
+//! priority, and a high priority thread may only be delayed by the
+//! bounded time a lower priority thread needs for updating the lock
+//! state. However, the priority of a thread holding the lock for
+//! reading or writing is not boosted while others wait for it.
+
+use std::cell::UnsafeCell;
+use std::ops::{Deref, DerefMut};
+use crate::condvar::{self, Condvar};
+use crate::mutex::{self, Mutex};
+use crate::Error;
+
+struct State {
+    readers: u32,
+    writer: bool,
+    waiting_writers: u32,
+}
+
+/// A reader-writer lock with writer preference.
+///
+/// # Examples
+///
+/// ```no_run
+/// use revl::rwlock::RwLock;
+///
+/// let table = RwLock::new([0.0f64; 64]).unwrap();
+/// {
+///     let t = table.read().unwrap();
+///     println!("gain = {}", t[0]);
+/// }
+/// table.write().unwrap()[0] = 1.5;
+/// ```
+pub struct RwLock<T: ?Sized> {
+    state: Mutex<State>,
+    readable: Condvar,
+    writable: Condvar,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    /// Create a reader-writer lock guarding `data`.
+    pub fn new(data: T) -> Result<Self, Error> {
+        let state = State {
+            readers: 0,
+            writer: false,
+            waiting_writers: 0,
+        };
+        Ok(Self {
+            state: Mutex::new(state, mutex::Builder::new())?,
+            readable: condvar::Builder::new().create()?,
+            writable: condvar::Builder::new().create()?,
+            data: UnsafeCell::new(data),
+        })
+    }
+    /// Consume the lock, returning the inner data.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Lock for reading, waiting for any current or pending writer
+    /// to release the lock first.
+    pub fn read(&self) -> Result<RwLockReadGuard<T>, Error> {
+        let guard = self.state.lock()?;
+        let mut guard = self.readable.wait_while(guard, |s| {
+            s.writer || s.waiting_writers > 0
+        })?;
+        guard.readers += 1;
+        Ok(RwLockReadGuard { lock: self })
+    }
+    /// Try locking for reading, without waiting.
+    ///
+    /// # Errors
+    ///
+    /// [`WouldBlock`][`crate::Error::WouldBlock`] is returned if a
+    /// writer holds or waits for the lock.
+    pub fn try_read(&self) -> Result<RwLockReadGuard<T>, Error> {
+        let mut guard = self.state.lock()?;
+        if guard.writer || guard.waiting_writers > 0 {
+            return Err(Error::WouldBlock);
+        }
+        guard.readers += 1;
+        Ok(RwLockReadGuard { lock: self })
+    }
+    /// Lock for writing, waiting for all readers and any current
+    /// writer to release the lock first.
+    pub fn write(&self) -> Result<RwLockWriteGuard<T>, Error> {
+        let mut guard = self.state.lock()?;
+        guard.waiting_writers += 1;
+        match self.writable.wait_while(guard, |s| s.writer || s.readers > 0) {
+            Ok(mut guard) => {
+                guard.waiting_writers -= 1;
+                guard.writer = true;
+                Ok(RwLockWriteGuard { lock: self })
+            },
+            Err(e) => {
+                let (mut guard, e) = e.into_parts();
+                guard.waiting_writers -= 1;
+                if guard.waiting_writers == 0 && !guard.writer {
+                    let _ = self.readable.notify_all();
+                }
+                Err(e)
+            },
+        }
+    }
+    /// Try locking for writing, without waiting.
+    ///
+    /// # Errors
+    ///
+    /// [`WouldBlock`][`crate::Error::WouldBlock`] is returned if the
+    /// lock is held by any reader or writer.
+    pub fn try_write(&self) -> Result<RwLockWriteGuard<T>, Error> {
+        let mut guard = self.state.lock()?;
+        if guard.writer || guard.readers > 0 {
+            return Err(Error::WouldBlock);
+        }
+        guard.writer = true;
+        Ok(RwLockWriteGuard { lock: self })
+    }
+    /// Return a mutable reference to the inner data. Since this call
+    /// borrows the lock mutably, no locking is needed.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+    // The unlock paths run from the guard destructors, which have no
+    // way to report a failure to notify.
+    fn read_unlock(&self) {
+        if let Ok(mut guard) = self.state.lock() {
+            guard.readers -= 1;
+            if guard.readers == 0 && guard.waiting_writers > 0 {
+                let _ = self.writable.notify_one();
+            }
+        }
+    }
+    fn write_unlock(&self) {
+        if let Ok(mut guard) = self.state.lock() {
+            guard.writer = false;
+            let _ = if guard.waiting_writers > 0 {
+                self.writable.notify_one()
+            } else {
+                self.readable.notify_all()
+            };
+        }
+    }
+}
+
+/// An RAII guard granting shared read access to the data of a
+/// [`RwLock`].
+pub struct RwLockReadGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+}
+
+impl<'a, T: ?Sized> Deref for RwLockReadGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.read_unlock();
+    }
+}
+
+/// An RAII guard granting exclusive write access to the data of a
+/// [`RwLock`].
+pub struct RwLockWriteGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+}
+
+impl<'a, T: ?Sized> Deref for RwLockWriteGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for RwLockWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.write_unlock();
+    }
+}
@@ -1,18 +1,83 @@
 use core::mem::MaybeUninit;
+use std::fs;
+use std::io::ErrorKind;
+use std::mem;
+use std::os::raw::{c_int, c_long};
+use std::ptr;
+use std::time::Duration;
+use libc::time_t;
 use evl_sys::{
+    evl_control_sched,
+    evl_get_cpustate,
+    evl_get_schedattr,
+    evl_get_self,
     evl_sched_attrs,
+    evl_sched_ctlinfo,
+    evl_sched_ctlparam,
+    evl_set_schedattr,
+    timespec,
     SchedPolicy
 };
+use crate::Error;
 
 // Other mods may need visibility on evl_sched_attrs (e.g. thread)
 pub struct SchedAttrs(pub(crate) evl_sched_attrs);
 
+impl SchedAttrs {
+    /// The scheduling policy, as defined by [`SchedPolicy`].
+    pub fn policy(&self) -> i32 {
+        self.0.sched_policy
+    }
+    /// The scheduling priority.
+    pub fn priority(&self) -> i32 {
+        self.0.sched_priority
+    }
+    /// Decode the attributes into a typed policy description.
+    pub fn to_policy(&self) -> Policy {
+        let prio = self.0.sched_priority;
+        match self.0.sched_policy {
+            p if p == SchedPolicy::FIFO as i32 => Policy::Fifo { prio },
+            p if p == SchedPolicy::RR as i32 => Policy::RR {
+                prio,
+                timeslice: timespec_to_duration(unsafe {
+                    &self.0.sched_u.rr.__sched_rr_quantum
+                }),
+            },
+            p if p == SchedPolicy::WEAK as i32 => Policy::Weak { prio },
+            p if p == SchedPolicy::QUOTA as i32 => Policy::Quota {
+                group: unsafe { self.0.sched_u.quota.__sched_group },
+                prio,
+            },
+            p if p == SchedPolicy::TP as i32 => Policy::TP {
+                part: unsafe { self.0.sched_u.tp.__sched_partition },
+                prio,
+            },
+            policy => Policy::Other { policy, prio },
+        }
+    }
+}
+
+/// The scheduling policy and parameters of a thread, as reported by
+/// [`Thread::sched()`][`crate::thread::Thread::sched`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    Fifo { prio: i32 },
+    RR { prio: i32, timeslice: Duration },
+    Weak { prio: i32 },
+    Quota { group: i32, prio: i32 },
+    TP { part: i32, prio: i32 },
+    /// A policy this crate does not know about.
+    Other { policy: i32, prio: i32 },
+}
+
 pub struct SchedFifo {
     pub prio: i32,
 }
 
 pub struct SchedRR {
     pub prio: i32,
+    /// The round-robin time slice.
+    pub timeslice: Duration,
 }
 
 pub struct SchedWeak {
@@ -29,6 +94,101 @@ pub struct SchedTP {
     pub prio: i32,
 }
 
+/// Priority range of the SCHED_FIFO and SCHED_RR policies.
+pub const FIFO_MIN_PRIO: i32 = 1;
+pub const FIFO_MAX_PRIO: i32 = 99;
+/// Priority range of the SCHED_WEAK policy.
+pub const WEAK_MIN_PRIO: i32 = 0;
+pub const WEAK_MAX_PRIO: i32 = 99;
+/// Priority range of the SCHED_QUOTA policy.
+pub const QUOTA_MIN_PRIO: i32 = FIFO_MIN_PRIO;
+pub const QUOTA_MAX_PRIO: i32 = FIFO_MAX_PRIO;
+/// Priority range of the SCHED_TP policy.
+pub const TP_MIN_PRIO: i32 = FIFO_MIN_PRIO;
+pub const TP_MAX_PRIO: i32 = FIFO_MAX_PRIO;
+
+// Check a priority against the limits of a policy.
+fn check_prio(policy: &str, prio: i32, min: i32, max: i32) -> Result<(), Error> {
+    if prio < min || prio > max {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} priority {} out of range [{}-{}]", policy, prio, min, max),
+        ));
+    }
+    Ok(())
+}
+
+impl SchedFifo {
+    /// Create SCHED_FIFO parameters, checking `prio` against
+    /// [`FIFO_MIN_PRIO`] and [`FIFO_MAX_PRIO`].
+    ///
+    /// ```no_run
+    /// use revl::sched::SchedFifo;
+    ///
+    /// assert!(SchedFifo::new(42).is_ok());
+    /// assert!(SchedFifo::new(0).is_err());
+    /// ```
+    pub fn new(prio: i32) -> Result<Self, Error> {
+        check_prio("SCHED_FIFO", prio, FIFO_MIN_PRIO, FIFO_MAX_PRIO)?;
+        Ok(Self { prio })
+    }
+}
+
+impl SchedRR {
+    /// Create SCHED_RR parameters, checking `prio` against
+    /// [`FIFO_MIN_PRIO`] and [`FIFO_MAX_PRIO`]. Threads of the same
+    /// priority are given the CPU in turn for `timeslice`, which
+    /// cannot be zero.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use revl::sched::SchedRR;
+    ///
+    /// let param = SchedRR::new(10, Duration::from_millis(1)).unwrap();
+    /// ```
+    pub fn new(prio: i32, timeslice: Duration) -> Result<Self, Error> {
+        check_prio("SCHED_RR", prio, FIFO_MIN_PRIO, FIFO_MAX_PRIO)?;
+        if timeslice.is_zero() {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  "SCHED_RR time slice cannot be zero"));
+        }
+        Ok(Self { prio, timeslice })
+    }
+}
+
+impl SchedWeak {
+    /// Create SCHED_WEAK parameters, checking `prio` against
+    /// [`WEAK_MIN_PRIO`] and [`WEAK_MAX_PRIO`].
+    pub fn new(prio: i32) -> Result<Self, Error> {
+        check_prio("SCHED_WEAK", prio, WEAK_MIN_PRIO, WEAK_MAX_PRIO)?;
+        Ok(Self { prio })
+    }
+}
+
+impl SchedQuota {
+    /// Create SCHED_QUOTA parameters for `group`, checking `prio`
+    /// against [`QUOTA_MIN_PRIO`] and [`QUOTA_MAX_PRIO`].
+    pub fn new(group: i32, prio: i32) -> Result<Self, Error> {
+        check_prio("SCHED_QUOTA", prio, QUOTA_MIN_PRIO, QUOTA_MAX_PRIO)?;
+        Ok(Self { group, prio })
+    }
+}
+
+impl SchedTP {
+    /// Create SCHED_TP parameters for partition `part`, checking
+    /// `prio` against [`TP_MIN_PRIO`] and [`TP_MAX_PRIO`].
+    pub fn new(part: i32, prio: i32) -> Result<Self, Error> {
+        if part < 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid SCHED_TP partition {}", part),
+            ));
+        }
+        check_prio("SCHED_TP", prio, TP_MIN_PRIO, TP_MAX_PRIO)?;
+        Ok(Self { part, prio })
+    }
+}
+
 pub trait PolicyParam {
     fn to_attr(&self) -> SchedAttrs;
 }
@@ -37,6 +197,24 @@ pub fn get_zero_attrs() -> SchedAttrs {
     SchedAttrs(unsafe { MaybeUninit::<evl_sched_attrs>::zeroed().assume_init() })
 }
 
+impl PolicyParam for Policy {
+    fn to_attr(&self) -> SchedAttrs {
+        match *self {
+            Policy::Fifo { prio } => SchedFifo { prio }.to_attr(),
+            Policy::RR { prio, timeslice } => SchedRR { prio, timeslice }.to_attr(),
+            Policy::Weak { prio } => SchedWeak { prio }.to_attr(),
+            Policy::Quota { group, prio } => SchedQuota { group, prio }.to_attr(),
+            Policy::TP { part, prio } => SchedTP { part, prio }.to_attr(),
+            Policy::Other { policy, prio } => {
+                let mut x = get_zero_attrs();
+                x.0.sched_policy = policy;
+                x.0.sched_priority = prio;
+                x
+            },
+        }
+    }
+}
+
 impl PolicyParam for SchedFifo {
     fn to_attr(&self) -> SchedAttrs {
         let mut x = get_zero_attrs();
@@ -51,6 +229,7 @@ impl PolicyParam for SchedRR {
         let mut x = get_zero_attrs();
         x.0.sched_policy = SchedPolicy::RR as i32;
         x.0.sched_priority = self.prio;
+        x.0.sched_u.rr.__sched_rr_quantum = duration_to_timespec(self.timeslice);
         x
     }
 }
@@ -83,3 +262,280 @@ impl PolicyParam for SchedTP {
         x
     }
 }
+
+// Retrieve the element file descriptor of the calling thread.
+fn self_efd() -> Result<c_int, Error> {
+    let efd: c_int = unsafe { evl_get_self() };
+    match efd {
+        0.. => return Ok(efd),
+        _ => return Err(Error::from_raw_os_error(-efd)),
+    }
+}
+
+/// Set the scheduling attributes of the calling thread to `param`,
+/// which must be attached to the EVL core.
+///
+/// # Examples
+///
+/// ```no_run
+/// use revl::sched::{self, SchedFifo};
+///
+/// sched::set_self(SchedFifo { prio: 90 }).expect("cannot boost");
+/// ```
+pub fn set_self(param: impl PolicyParam) -> Result<(), Error> {
+    let efd = self_efd()?;
+    let ret: c_int = unsafe { evl_set_schedattr(efd, &param.to_attr().0) };
+    match ret {
+        0 => return Ok(()),
+        _ => return Err(Error::from_raw_os_error(-ret)),
+    }
+}
+
+/// Retrieve the scheduling attributes of the calling thread, which
+/// must be attached to the EVL core.
+///
+/// # Examples
+///
+/// ```no_run
+/// use revl::sched;
+///
+/// let policy = sched::get_self().expect("not attached");
+/// println!("running with {:?}", policy);
+/// ```
+pub fn get_self() -> Result<Policy, Error> {
+    let efd = self_efd()?;
+    let mut attrs = MaybeUninit::<evl_sched_attrs>::uninit();
+    let ret: c_int = unsafe { evl_get_schedattr(efd, attrs.as_mut_ptr()) };
+    match ret {
+        0 => return Ok(SchedAttrs(unsafe { attrs.assume_init() }).to_policy()),
+        _ => return Err(Error::from_raw_os_error(-ret)),
+    }
+}
+
+// The sysfs attribute exporting the list of out-of-band CPUs.
+const OOB_CPUS_ATTR: &str = "/sys/devices/virtual/evl/control/cpus";
+
+// Parse a CPU list in the kernel format, e.g. "0-3,6".
+fn parse_cpu_list(list: &str) -> Result<Vec<usize>, Error> {
+    let mut cpus = Vec::new();
+    let bad = |_| Error::new(ErrorKind::InvalidData, "malformed CPU list");
+    for range in list.trim().split(',').filter(|r| !r.is_empty()) {
+        if let Some((first, last)) = range.split_once('-') {
+            let first: usize = first.parse().map_err(bad)?;
+            let last: usize = last.parse().map_err(bad)?;
+            cpus.extend(first..=last);
+        } else {
+            cpus.push(range.parse().map_err(bad)?);
+        }
+    }
+    Ok(cpus)
+}
+
+// Read the list of CPUs enabled for out-of-band scheduling.
+pub(crate) fn oob_cpu_list() -> Result<Vec<usize>, Error> {
+    parse_cpu_list(&fs::read_to_string(OOB_CPUS_ATTR)?)
+}
+
+// CPU state bits reported by evl_get_cpustate().
+const CPU_OOB: c_int = 1 << 0;
+const CPU_ISOL: c_int = 1 << 1;
+const CPU_OFFLINE: c_int = 1 << 2;
+
+/// The state of a CPU with respect to out-of-band scheduling, as
+/// reported by [`oob_cpus()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuState {
+    /// The CPU number.
+    pub cpu: usize,
+    /// Whether the CPU is enabled for running out-of-band threads.
+    pub oob: bool,
+    /// Whether the CPU is isolated from the in-band scheduler
+    /// (i.e. listed in isolcpus=).
+    pub isolated: bool,
+    /// Whether the CPU is online.
+    pub online: bool,
+}
+
+/// Retrieve the state of every CPU present in the system with
+/// respect to out-of-band scheduling. This can be used for checking
+/// a CPU pinning plan against the configuration of the EVL core at
+/// startup.
+///
+/// # Examples
+///
+/// ```no_run
+/// use revl::sched;
+///
+/// let usable: Vec<usize> = sched::oob_cpus()
+///     .expect("cannot read CPU states")
+///     .into_iter()
+///     .filter(|c| c.oob && c.online)
+///     .map(|c| c.cpu)
+///     .collect();
+/// assert!(usable.contains(&2), "CPU2 is not available to EVL");
+/// ```
+pub fn oob_cpus() -> Result<Vec<CpuState>, Error> {
+    let nr_cpus = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_CONF) };
+    if nr_cpus < 0 {
+        return Err(Error::last_os_error());
+    }
+    let mut cpus = Vec::with_capacity(nr_cpus as usize);
+    for cpu in 0..nr_cpus as usize {
+        let mut state: c_int = 0;
+        let ret: c_int = unsafe { evl_get_cpustate(cpu as c_int, &mut state) };
+        if ret != 0 {
+            return Err(Error::from_raw_os_error(-ret));
+        }
+        cpus.push(CpuState {
+            cpu,
+            oob: state & CPU_OOB != 0,
+            isolated: state & CPU_ISOL != 0,
+            online: state & CPU_OFFLINE == 0,
+        });
+    }
+    Ok(cpus)
+}
+
+// Operation codes of the SCHED_TP control interface.
+const TP_INSTALL: c_int = 0;
+const TP_UNINSTALL: c_int = 1;
+const TP_START: c_int = 2;
+const TP_STOP: c_int = 3;
+
+// The layout of the SCHED_TP control block, which ends with a
+// variable-length array of windows.
+#[repr(C)]
+struct TpCtlHeader {
+    op: c_int,
+    nr_windows: c_int,
+}
+
+#[repr(C)]
+struct TpCtlWindow {
+    offset: timespec,
+    duration: timespec,
+    ptid: c_int,
+}
+
+fn duration_to_timespec(d: Duration) -> timespec {
+    timespec {
+        tv_sec: d.as_secs() as time_t,
+        tv_nsec: d.subsec_nanos() as c_long,
+    }
+}
+
+fn timespec_to_duration(ts: &timespec) -> Duration {
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+// Issue a request to the SCHED_TP control interface for `cpu`.
+fn tp_control(cpu: usize, op: c_int, windows: &[TpCtlWindow]) -> Result<(), Error> {
+    // Use u64 cells to get a properly aligned control block.
+    let len = mem::size_of::<TpCtlHeader>() + mem::size_of_val(windows);
+    let mut buf = vec![0u64; (len + 7) / 8];
+    let ret: c_int = unsafe {
+        let header = buf.as_mut_ptr() as *mut TpCtlHeader;
+        (*header).op = op;
+        (*header).nr_windows = windows.len() as c_int;
+        let cells = header.add(1) as *mut TpCtlWindow;
+        ptr::copy_nonoverlapping(windows.as_ptr(), cells, windows.len());
+        evl_control_sched(SchedPolicy::TP as c_int,
+                          buf.as_ptr() as *const evl_sched_ctlparam,
+                          ptr::null_mut::<evl_sched_ctlinfo>(),
+                          cpu as c_int)
+    };
+    match ret {
+        0 => return Ok(()),
+        _ => return Err(Error::from_raw_os_error(-ret)),
+    }
+}
+
+/// A temporal partitioning schedule for the SCHED_TP policy.
+///
+/// A schedule is a time frame divided into a sequence of windows,
+/// each of them assigned to a partition, repeating periodically. Only
+/// threads belonging to the partition of the current window may run
+/// under the SCHED_TP policy, see [`SchedTP`]. Any time left at the
+/// end of the frame after the last window is idle.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use revl::sched::{self, TpSchedule};
+///
+/// TpSchedule::new(Duration::from_millis(10))
+///     .window(0, Duration::from_millis(4))
+///     .window(1, Duration::from_millis(4))
+///     .install(1)
+///     .expect("cannot install TP schedule");
+/// sched::tp_start(1).expect("cannot start TP scheduling");
+/// ```
+pub struct TpSchedule {
+    frame: Duration,
+    windows: Vec<(i32, Duration)>,
+}
+
+impl TpSchedule {
+    /// Create an empty schedule spanning `frame`.
+    pub fn new(frame: Duration) -> Self {
+        Self {
+            frame,
+            windows: Vec::new(),
+        }
+    }
+    /// Append a window of length `duration` assigned to partition
+    /// `part`. A negative partition number denotes an idle window.
+    pub fn window(mut self, part: i32, duration: Duration) -> Self {
+        self.windows.push((part, duration));
+        self
+    }
+    /// Install the schedule on `cpu`, replacing the current one.
+    /// This does not start the TP scheduler, see [`tp_start()`].
+    ///
+    /// # Errors
+    ///
+    /// [`InvalidInput`][`std::io::ErrorKind`] is returned if the
+    /// windows do not fit into the frame, or the schedule is
+    /// rejected by the core.
+    pub fn install(&self, cpu: usize) -> Result<(), Error> {
+        let mut cells = Vec::with_capacity(self.windows.len() + 1);
+        let mut offset = Duration::ZERO;
+        for &(part, duration) in &self.windows {
+            cells.push(TpCtlWindow {
+                offset: duration_to_timespec(offset),
+                duration: duration_to_timespec(duration),
+                ptid: if part < 0 { -1 } else { part },
+            });
+            offset += duration;
+        }
+        if offset > self.frame {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  "TP windows exceed the frame length"));
+        }
+        // Pad the frame with an idle window.
+        if offset < self.frame {
+            cells.push(TpCtlWindow {
+                offset: duration_to_timespec(offset),
+                duration: duration_to_timespec(self.frame - offset),
+                ptid: -1,
+            });
+        }
+        tp_control(cpu, TP_INSTALL, &cells)
+    }
+}
+
+/// Remove the TP schedule installed on `cpu`.
+pub fn tp_uninstall(cpu: usize) -> Result<(), Error> {
+    tp_control(cpu, TP_UNINSTALL, &[])
+}
+
+/// Start the TP scheduler on `cpu`, using the installed schedule.
+pub fn tp_start(cpu: usize) -> Result<(), Error> {
+    tp_control(cpu, TP_START, &[])
+}
+
+/// Stop the TP scheduler on `cpu`.
+pub fn tp_stop(cpu: usize) -> Result<(), Error> {
+    tp_control(cpu, TP_STOP, &[])
+}
@@ -0,0 +1,109 @@
+//! Waiting on multiple sources.
+//!
+//! A [`Selector`] blocks the calling thread until any of the sources
+//! it monitors is ready, then reports which one. This allows a single
+//! dispatcher thread to multiplex several real-time inputs, such as
+//! channel [receivers](crate::channel::Receiver), [flag
+//! groups](crate::flags::Flags) and [semaphores](crate::semaphore::Semaphore).
+//! This is based on the EVL polling service, so waiting can be done
+//! from the out-of-band stage.
+//!
+//! Readiness is only a hint: since other threads may consume from
+//! the same source concurrently, the selected source should be read
+//! with a non-blocking operation.
+//!
+//! This is a thin layer over [`poll::Poller`](crate::poll::Poller),
+//! which should be used directly to wait for other conditions or to
+//! collect multiple events at once.
+
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use embedded_time::Instant;
+use crate::clock::CoreClock;
+use crate::poll::{Interest, Poller, Trigger};
+use crate::Error;
+
+/// A set of sources to wait on, each identified by a token.
+///
+/// # Examples
+///
+/// ```no_run
+/// use revl::channel;
+/// use revl::flags;
+/// use revl::select::Selector;
+///
+/// let (_tx, rx) = channel::create::<u32, 4>().unwrap();
+/// let cmd: flags::Flags = flags::Builder::new().create().unwrap();
+///
+/// let mut sel = Selector::new().unwrap();
+/// let data = sel.add(&rx).unwrap();
+/// let control = sel.add(&cmd).unwrap();
+/// loop {
+///     let token = sel.wait().unwrap();
+///     if token == data {
+///         if let Ok(msg) = rx.recv() {
+///             println!("got {}", msg);
+///         }
+///     } else if token == control {
+///         if let Ok(Some(bits)) = cmd.try_wait() {
+///             println!("command {:#x}", bits);
+///         }
+///     }
+/// }
+/// ```
+pub struct Selector<'a> {
+    poller: Poller<'a>,
+    nr: usize,
+}
+
+impl<'a> Selector<'a> {
+    /// Create an empty selector.
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self { poller: Poller::new()?, nr: 0 })
+    }
+    /// Monitor `source` for readability, returning the token which
+    /// identifies it in the results of [`wait()`](Self::wait).
+    ///
+    /// # Errors
+    ///
+    /// [`NameConflict`][`crate::Error::NameConflict`] is returned if
+    /// the source is already monitored by this selector. The EVL core
+    /// rejects sources which do not support polling.
+    pub fn add<S: AsRawFd + ?Sized>(&mut self, source: &'a S) -> Result<usize, Error> {
+        let token = self.nr;
+        self.poller.add(source, Interest::READABLE, Trigger::Level, token as u64)?;
+        self.nr += 1;
+        Ok(token)
+    }
+    /// Stop monitoring `source`. Its token is not reused.
+    pub fn remove<S: AsRawFd + ?Sized>(&mut self, source: &S) -> Result<(), Error> {
+        self.poller.remove(source)
+    }
+    /// Wait for any source to be ready, returning its token. If
+    /// several sources are ready, the first one reported by the core
+    /// is returned.
+    pub fn wait(&self) -> Result<usize, Error> {
+        self.poller.wait_one(None).map(|event| event.token() as usize)
+    }
+    /// Wait for any source to be ready until the absolute `timeout`
+    /// date is reached, based on the monotonic clock.
+    ///
+    /// # Errors
+    ///
+    /// [`TimedOut`][`crate::Error::TimedOut`] is returned if no
+    /// source was ready by the timeout date.
+    pub fn wait_timed(&self, timeout: Instant<CoreClock>) -> Result<usize, Error> {
+        self.poller.wait_one(Some(timeout)).map(|event| event.token() as usize)
+    }
+}
+
+impl<'a> AsRawFd for Selector<'a> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.poller.as_raw_fd()
+    }
+}
+
+impl<'a> AsFd for Selector<'a> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.poller.as_fd()
+    }
+}
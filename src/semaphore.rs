@@ -2,25 +2,32 @@
 
 use std::cell::UnsafeCell;
 use std::ffi::CString;
-use std::io::Error;
 use std::mem::MaybeUninit;
+use std::os::fd::{AsRawFd, RawFd};
 use std::os::raw::c_int;
 use std::ptr;
+use std::time::Duration;
+use embedded_time::{duration::Nanoseconds, Instant};
 use evl_sys::{
     evl_close_sem,
     evl_create_sem,
     evl_get_sem,
+    evl_open_sem,
+    evl_peek_sem,
     evl_put_sem,
     evl_sem,
+    evl_timedget_sem,
     evl_tryget_sem,
-    BuiltinClock,
     CloneFlags,
 };
+use crate::clock::{instant_to_timespec, CoreClock, STEADY_CLOCK};
+use crate::Error;
 
 pub struct Builder {
     name: Option<String>,
     visible: bool,
     initval: u32,
+    clock: Option<CoreClock>,
 }
 
 impl Builder {
@@ -29,6 +36,7 @@ impl Builder {
             name: None,
             visible: false,
             initval: 0u32,
+            clock: None,
         }
     }
     pub fn name(mut self, name: &str) -> Self {
@@ -47,12 +55,18 @@ impl Builder {
         self.initval = initval;
         self
     }
+    /// Set the clock timing the waits. This is the monotonic clock
+    /// by default.
+    pub fn clock(mut self, clock: CoreClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
     pub fn create(self) -> Result<Semaphore, Error> {
         Semaphore::new(self)
     }
 }
 
-pub struct Semaphore(UnsafeCell<evl_sem>);
+pub struct Semaphore(UnsafeCell<evl_sem>, CoreClock);
 
 unsafe impl Send for Semaphore {}
 unsafe impl Sync for Semaphore {}
@@ -63,7 +77,7 @@ impl Semaphore {
     ///
     /// # Errors
     ///
-    /// * [`AlreadyExists`][`std::io::ErrorKind`] means the semaphore
+    /// * [`NameConflict`][`crate::Error::NameConflict`] means the semaphore
     /// name is conflicting with an existing semaphore name.
     ///
     /// * [`InvalidInput`][`std::io::ErrorKind`] means that the
@@ -71,9 +85,9 @@ impl Semaphore {
     /// contain only valid characters in the context of a Linux file
     /// name.
     ///
-    /// * [`Other`][`std::io::ErrorKind`] may mean that either the EVL
-    /// core is not enabled in the kernel, or there is an ABI mismatch
-    /// between the underlying [evl-sys
+    /// * [`NoCore`][`crate::Error::NoCore`] means that the EVL core is
+    /// not enabled in the kernel, [`AbiMismatch`][`crate::Error::AbiMismatch`]
+    /// that there is an ABI mismatch between the underlying [evl-sys
     /// crate](https://source.denx.de/Xenomai/xenomai4/evl-sys) and
     /// the EVL core. See [these
     /// explanations](https://evlproject.org/core/under-the-hood/abi/)
@@ -84,7 +98,7 @@ impl Semaphore {
     /// ```no_run
     /// use revl::semaphore::{Builder, Semaphore};
     ///
-    /// fn create_a_semaphore(initval: u32) -> Result<Semaphore, std::io::Error> {
+    /// fn create_a_semaphore(initval: u32) -> Result<Semaphore, revl::Error> {
     ///     let props = Builder::new().name("a_sema4").public().init_value(initval);
     ///     let me = Semaphore::new(props)?;
     ///     Ok(me)
@@ -92,16 +106,16 @@ impl Semaphore {
     /// ```
     ///
     pub fn new(builder: Builder) -> Result<Self, Error> {
+        let clock = builder.clock.unwrap_or(STEADY_CLOCK);
+        let c_clockfd = clock.0 as i32;
         let this = Self(UnsafeCell::new(unsafe {
             MaybeUninit::<evl_sem>::zeroed().assume_init()
-        }));
+        }), clock);
         let mut c_flags = CloneFlags::PRIVATE.bits() as c_int;
         if builder.visible {
             c_flags = CloneFlags::PUBLIC.bits() as c_int;
         }
         let c_initval = builder.initval as i32;
-        // Revisit: this is too restrictive.
-        let c_clockfd = BuiltinClock::MONOTONIC as i32;
         let ret: c_int = unsafe {
             if let Some(name) = builder.name {
                 let c_name = CString::new(name).expect("CString::new failed");
@@ -127,6 +141,40 @@ impl Semaphore {
             _ => return Err(Error::from_raw_os_error(-ret)),
         };
     }
+    /// Open an existing public semaphore by name, which may have been
+    /// created by another process. The clock of the semaphore cannot
+    /// be retrieved, so the monotonic clock is assumed for the
+    /// relative timeouts passed to [`get_for()`](Self::get_for).
+    ///
+    /// # Errors
+    ///
+    /// [`NotFound`][`std::io::ErrorKind`] is returned if there is no
+    /// public semaphore with such name.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use revl::semaphore::Semaphore;
+    ///
+    /// // Producer process: Builder::new().name("pipeline").public().create()
+    /// // Consumer process:
+    /// let sem = Semaphore::open("pipeline").unwrap();
+    /// sem.get().unwrap();
+    /// ```
+    pub fn open(name: &str) -> Result<Self, Error> {
+        let this = Self(UnsafeCell::new(unsafe {
+            MaybeUninit::<evl_sem>::zeroed().assume_init()
+        }), STEADY_CLOCK);
+        let c_name = CString::new(name).expect("CString::new failed");
+        let c_fmt = CString::new("%s").expect("CString::new failed");
+        let ret: c_int = unsafe {
+            evl_open_sem(this.0.get(), c_fmt.as_ptr(), c_name.as_ptr())
+        };
+        match ret {
+            0.. => return Ok(this),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
     pub fn get(&self) -> Result<(), Error> {
         let ret: c_int = unsafe { evl_get_sem(self.0.get()) };
         match ret {
@@ -134,6 +182,46 @@ impl Semaphore {
             _ => return Err(Error::from_raw_os_error(-ret)),
         };
     }
+    /// Acquire the semaphore, waiting until the absolute `timeout`
+    /// date is reached at the latest, based on the clock of the
+    /// semaphore.
+    ///
+    /// # Errors
+    ///
+    /// [`TimedOut`][`crate::Error::TimedOut`] is returned if the
+    /// semaphore could not be acquired by the timeout date.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use embedded_time::duration::Milliseconds;
+    /// use revl::clock::STEADY_CLOCK;
+    /// use revl::semaphore::Semaphore;
+    /// use revl::Error;
+    ///
+    /// fn consume(sem: &Semaphore) -> Result<(), Error> {
+    ///     match sem.get_timed(STEADY_CLOCK.now() + Milliseconds(5u32)) {
+    ///         Err(Error::TimedOut) => {
+    ///             println!("producer stalled");
+    ///             Ok(())
+    ///         },
+    ///         res => res,
+    ///     }
+    /// }
+    /// ```
+    pub fn get_timed(&self, timeout: Instant<CoreClock>) -> Result<(), Error> {
+        let date = instant_to_timespec(timeout);
+        let ret: c_int = unsafe { evl_timedget_sem(self.0.get(), &date) };
+        match ret {
+            0 => return Ok(()),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    /// Acquire the semaphore, waiting for at most `delay`. See
+    /// [`get_timed()`](Self::get_timed).
+    pub fn get_for(&self, delay: Duration) -> Result<(), Error> {
+        self.get_timed(self.1.now() + Nanoseconds(delay.as_nanos() as u64))
+    }
     pub fn try_get(&self) -> bool {
         let ret: c_int = unsafe { evl_tryget_sem(self.0.get()) };
         match ret {
@@ -148,6 +236,54 @@ impl Semaphore {
             _ => return Err(Error::from_raw_os_error(-ret)),
         };
     }
+    /// Release the semaphore `n` times, waking up as many waiters if
+    /// present. This stops at the first failure, in which case the
+    /// error is returned after some units may have been released
+    /// already.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use revl::semaphore::Semaphore;
+    ///
+    /// // Start a set of 8 workers waiting on the semaphore.
+    /// fn release_the_herd(sem: &Semaphore) -> Result<(), revl::Error> {
+    ///     sem.put_many(8)
+    /// }
+    /// ```
+    pub fn put_many(&self, n: u32) -> Result<(), Error> {
+        for _ in 0..n {
+            self.put()?;
+        }
+        Ok(())
+    }
+    /// Return the current count of the semaphore, without altering
+    /// its state. The value may be outdated as soon as it is
+    /// returned, so it is only meant for monitoring purposes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use revl::semaphore::Semaphore;
+    ///
+    /// fn backlog(sem: &Semaphore) -> Result<u32, revl::Error> {
+    ///     sem.value()
+    /// }
+    /// ```
+    pub fn value(&self) -> Result<u32, Error> {
+	let mut count = MaybeUninit::<c_int>::uninit();
+        let ret: c_int = unsafe { evl_peek_sem(self.0.get(), count.as_mut_ptr()) };
+        match ret {
+            0 => return Ok(unsafe { count.assume_init() } as u32),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+}
+
+impl AsRawFd for Semaphore {
+    fn as_raw_fd(&self) -> RawFd {
+        unsafe { (*self.0.get()).u.active.efd }
+    }
 }
 
 impl Drop for Semaphore {
@@ -0,0 +1,246 @@
+//! Shared memory regions.
+//!
+//! A [`SharedRegion`] maps a shared memory area holding a value of
+//! type `T`, which multiple processes can access concurrently. Named
+//! regions are POSIX shared memory objects, which other processes
+//! open by name; anonymous regions are backed by a memfd, which can
+//! be inherited across `fork()` or passed over a UNIX socket. The
+//! pages of the region are faulted in and locked into memory, so that
+//! real-time threads can access it without taking page faults.
+//!
+//! The value should only contain plain data and atomics, the
+//! processes usually synchronizing through public EVL elements, such
+//! as a [semaphore](crate::semaphore::Semaphore::open) or a [flag
+//! group](crate::flags::Flags::open) opened by name:
+//!
+//! ```no_run
+//! use std::sync::atomic::{AtomicU64, Ordering};
+//! use revl::semaphore::{self, Semaphore};
+//! use revl::shm::{self, SharedRegion};
+//!
+//! #[repr(C)]
+//! struct Telemetry {
+//!     samples: [AtomicU64; 64],
+//! }
+//!
+//! // Producer process:
+//! let region = shm::Builder::new().name("telemetry")
+//!     .create(Telemetry { samples: std::array::from_fn(|_| AtomicU64::new(0)) }).unwrap();
+//! let ready = semaphore::Builder::new().name("telemetry-ready").public().create().unwrap();
+//! region.samples[0].store(42, Ordering::Release);
+//! ready.put().unwrap();
+//!
+//! // Consumer process:
+//! let region = unsafe { SharedRegion::<Telemetry>::open("telemetry") }.unwrap();
+//! let ready = Semaphore::open("telemetry-ready").unwrap();
+//! ready.get().unwrap();
+//! println!("{}", region.samples[0].load(Ordering::Acquire));
+//! ```
+
+use std::ffi::{c_void, CString};
+use std::io::ErrorKind;
+use std::marker::PhantomData;
+use std::mem::{self, MaybeUninit};
+use std::ops::Deref;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::os::raw::c_int;
+use std::ptr::{self, NonNull};
+use crate::Error;
+
+fn shm_name(name: &str) -> CString {
+    CString::new(format!("/{}", name)).expect("CString::new failed")
+}
+
+pub struct Builder {
+    name: Option<String>,
+    lock: bool,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            name: None,
+            lock: true,
+        }
+    }
+    /// Set the name other processes open the region by. The region
+    /// is anonymous otherwise, which is the default.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+    /// Enable or disable locking the region into memory (default).
+    pub fn lock(mut self, enabled: bool) -> Self {
+        self.lock = enabled;
+        self
+    }
+    pub fn create<T>(self, init: T) -> Result<SharedRegion<T>, Error> {
+        SharedRegion::new(init, self)
+    }
+}
+
+/// A shared memory region holding a `T`, see the [module
+/// documentation](crate::shm). The mapping is released on drop, a
+/// named region persists until [`unlink()`] is called.
+pub struct SharedRegion<T> {
+    fd: c_int,
+    ptr: NonNull<T>,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: Send + Sync> Send for SharedRegion<T> {}
+unsafe impl<T: Send + Sync> Sync for SharedRegion<T> {}
+
+impl<T> SharedRegion<T> {
+    /// Create a region holding `init`, retrieving the settings from a
+    /// [`builder struct`](Builder).
+    ///
+    /// # Errors
+    ///
+    /// * [`NameConflict`][`crate::Error::NameConflict`] is returned
+    /// if a region with the same name exists.
+    ///
+    /// * [`OutOfMemory`][`std::io::ErrorKind`] means that the region
+    /// could not be locked into memory, see
+    /// [`mem::memlock_limit()`](crate::mem::memlock_limit).
+    pub fn new(init: T, builder: Builder) -> Result<Self, Error> {
+        let fd: c_int = unsafe {
+            match &builder.name {
+                Some(name) => libc::shm_open(shm_name(name).as_ptr(),
+                                             libc::O_RDWR | libc::O_CREAT | libc::O_EXCL,
+                                             0o600),
+                None => {
+                    let c_name = CString::new("revl-shm").expect("CString::new failed");
+                    libc::memfd_create(c_name.as_ptr(), libc::MFD_CLOEXEC)
+                },
+            }
+        };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        let size = mem::size_of::<T>().max(1);
+        let ret = match unsafe { libc::ftruncate(fd, size as libc::off_t) } {
+            0 => Self::map(fd, builder.lock),
+            _ => Err(Error::last_os_error()),
+        };
+        match ret {
+            Ok(this) => {
+                unsafe { ptr::write(this.ptr.as_ptr(), init) };
+                Ok(this)
+            },
+            Err(e) => {
+                unsafe {
+                    libc::close(fd);
+                    if let Some(name) = &builder.name {
+                        libc::shm_unlink(shm_name(name).as_ptr());
+                    }
+                }
+                Err(e)
+            },
+        }
+    }
+    /// Open the named region `name` created by another process, and
+    /// lock it into memory.
+    ///
+    /// # Errors
+    ///
+    /// * [`NotFound`][`std::io::ErrorKind`] is returned if there is
+    /// no region with such name.
+    ///
+    /// * [`InvalidData`][`std::io::ErrorKind`] is returned if the
+    /// region is smaller than a `T`.
+    ///
+    /// # Safety
+    ///
+    /// The region must hold a valid `T`, which is the case if it was
+    /// created with the same type by a program built with the same
+    /// compiler. Types with a `#[repr(C)]` layout are recommended.
+    pub unsafe fn open(name: &str) -> Result<Self, Error> {
+        let fd = libc::shm_open(shm_name(name).as_ptr(), libc::O_RDWR, 0);
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        let mut st = MaybeUninit::<libc::stat>::uninit();
+        let ret = if libc::fstat(fd, st.as_mut_ptr()) != 0 {
+            Err(Error::last_os_error())
+        } else if (st.assume_init().st_size as usize) < mem::size_of::<T>() {
+            Err(Error::new(ErrorKind::InvalidData, "shared region is too small"))
+        } else {
+            Self::map(fd, true)
+        };
+        if ret.is_err() {
+            libc::close(fd);
+        }
+        ret
+    }
+    fn map(fd: c_int, lock: bool) -> Result<Self, Error> {
+        let size = mem::size_of::<T>().max(1);
+        let addr = unsafe {
+            libc::mmap(ptr::null_mut(), size,
+                       libc::PROT_READ | libc::PROT_WRITE,
+                       libc::MAP_SHARED | if lock { libc::MAP_POPULATE } else { 0 },
+                       fd, 0)
+        };
+        if addr == libc::MAP_FAILED {
+            return Err(Error::last_os_error());
+        }
+        if lock && unsafe { libc::mlock(addr, size) } != 0 {
+            let e = match Error::last_os_error() {
+                Error::Io(e) if e.raw_os_error() == Some(libc::ENOMEM) =>
+                    Error::new(ErrorKind::OutOfMemory, "cannot lock shared region"),
+                e => e,
+            };
+            unsafe { libc::munmap(addr, size) };
+            return Err(e);
+        }
+        Ok(Self {
+            fd,
+            ptr: NonNull::new(addr as *mut T).unwrap(),
+            _marker: PhantomData,
+        })
+    }
+    /// Return a raw pointer to the shared value.
+    pub fn as_ptr(&self) -> *mut T {
+        self.ptr.as_ptr()
+    }
+}
+
+impl<T: Sync> Deref for SharedRegion<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> AsRawFd for SharedRegion<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl<T> AsFd for SharedRegion<T> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.fd) }
+    }
+}
+
+impl<T> Drop for SharedRegion<T> {
+    fn drop(&mut self) {
+        // The value is shared, other processes may still use it, so
+        // it is not dropped.
+        unsafe {
+            libc::munmap(self.ptr.as_ptr() as *mut c_void, mem::size_of::<T>().max(1));
+            libc::close(self.fd);
+        }
+    }
+}
+
+/// Remove the named region `name`. Processes which mapped it keep
+/// their mapping, but the region cannot be opened anymore.
+pub fn unlink(name: &str) -> Result<(), Error> {
+    match unsafe { libc::shm_unlink(shm_name(name).as_ptr()) } {
+        0 => return Ok(()),
+        _ => return Err(Error::last_os_error()),
+    }
+}
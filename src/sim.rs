@@ -0,0 +1,137 @@
+//! Deterministic virtual time for tests, with the `sim` feature.
+//!
+//! A [`TestClock`] switches the [core clocks](crate::clock) of the
+//! simulation backend to virtual time, which only moves when the
+//! test tells it to. [`advance()`](TestClock::advance) runs the clock
+//! from one pending deadline to the next, letting the attached
+//! threads react to each expiry before moving on, so that sleeps,
+//! timers and timed waits expire in the same order at the same dates
+//! on every run, without taking any wall-clock time. This makes
+//! periodic tasks, timeouts and watchdogs unit-testable.
+//!
+//! Time only moves forward once all attached threads have settled,
+//! i.e. are blocked on an EVL service. A thread blocked on anything
+//! else, e.g. joining another thread, never settles, so the test
+//! driver should not be an attached thread itself. Polling timeouts
+//! are not virtualized.
+//!
+//! The clocks are global to the process, so test clocks are handed
+//! out one at a time: [`Builder::start()`] waits for the current one
+//! to be dropped, which serializes the tests using virtual time.
+//! Other tests running concurrently observe virtual time too.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use revl::clock::{Periodic, STEADY_CLOCK};
+//! use revl::sim;
+//! use revl::thread;
+//!
+//! let clock = sim::Builder::new().start().unwrap();
+//! let start = clock.now().duration_since_epoch().integer();
+//! let handle = thread::Builder::new().spawn(|_| {
+//!     let mut cycle = Periodic::new(Duration::from_millis(10));
+//!     for _ in 0..5 {
+//!         cycle.tick().unwrap();
+//!     }
+//!     STEADY_CLOCK.now().duration_since_epoch().integer()
+//! }).unwrap();
+//! clock.advance(Duration::from_millis(100));
+//! let end = handle.join().unwrap();
+//! assert_eq!(end - start, 50_000_000);
+//! ```
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use embedded_time::Instant;
+use evl_sys::vtime;
+use crate::clock::{CoreClock, STEADY_CLOCK};
+use crate::Error;
+
+// Held by the active test clock.
+static ACTIVE: Mutex<()> = Mutex::new(());
+
+pub struct Builder {
+    epoch: Duration,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            epoch: Duration::from_secs(1),
+        }
+    }
+    /// Set the date the monotonic clock starts from, one second by
+    /// default. The realtime clock starts from the current date.
+    pub fn epoch(mut self, epoch: Duration) -> Self {
+        self.epoch = epoch;
+        self
+    }
+    /// Switch to virtual time. See [`TestClock::new()`].
+    pub fn start(self) -> Result<TestClock, Error> {
+        TestClock::new(self)
+    }
+}
+
+/// Virtual time control, the core clocks follow the host clocks
+/// again when dropped.
+pub struct TestClock {
+    _active: MutexGuard<'static, ()>,
+}
+
+impl TestClock {
+    /// Switch the core clocks to virtual time, waiting for any other
+    /// test clock to be dropped first.
+    ///
+    /// # Errors
+    ///
+    /// [`ResourceBusy`][`std::io::ErrorKind`] means that virtual time
+    /// was enabled directly through the simulation backend.
+    pub fn new(builder: Builder) -> Result<Self, Error> {
+        // A test which panicked with the clock held is not our
+        // concern.
+        let active = ACTIVE.lock().unwrap_or_else(PoisonError::into_inner);
+        match vtime::enable(builder.epoch.as_nanos() as u64) {
+            Ok(()) => return Ok(Self { _active: active }),
+            Err(e) => return Err(Error::from_raw_os_error(-e)),
+        };
+    }
+    /// Read the virtual monotonic clock.
+    pub fn now(&self) -> Instant<CoreClock> {
+        STEADY_CLOCK.now()
+    }
+    /// Move the clocks forward by `delta`, stopping at every deadline
+    /// on the way until the attached threads have settled, and
+    /// finally once more at the target date.
+    pub fn advance(&self, delta: Duration) {
+        vtime::advance(delta.as_nanos() as u64);
+    }
+    /// Move the clocks from one deadline to the next until the
+    /// attached threads are left waiting for conditions no date can
+    /// satisfy, typically after they all exited. This never returns
+    /// if a thread keeps on sleeping periodically, use
+    /// [`advance()`](Self::advance) in that case.
+    pub fn run(&self) {
+        vtime::run();
+    }
+    /// Wait for the attached threads to settle, without moving the
+    /// clocks.
+    pub fn settle(&self) {
+        vtime::settle();
+    }
+}
+
+impl Drop for TestClock {
+    fn drop(&mut self) {
+        vtime::disable();
+    }
+}
+
+// Busy waits do not leave the CPU to other threads, so virtual time
+// moves directly to the end of the wait.
+pub(crate) fn spin_until(clock: &CoreClock, deadline: Instant<CoreClock>) -> bool {
+    if !vtime::is_enabled() {
+        return false;
+    }
+    vtime::skip_to(clock.0 as i32, deadline.duration_since_epoch().integer());
+    true
+}
@@ -0,0 +1,34 @@
+// mio event sources for EVL elements (mio feature).
+//
+// The in-band side of an element is pollable like any regular file
+// descriptor, so the elements are registered with a mio registry by
+// their file descriptor.
+
+use std::io;
+use std::os::fd::AsRawFd;
+use mio::event::Source;
+use mio::unix::SourceFd;
+use mio::{Interest, Registry, Token};
+use crate::observable::Observable;
+use crate::proxy::Proxy;
+use crate::xbuf::XBuf;
+
+macro_rules! impl_source {
+    ($($t:ty),*) => {
+        $(
+            impl Source for $t {
+                fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+                    SourceFd(&self.as_raw_fd()).register(registry, token, interests)
+                }
+                fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+                    SourceFd(&self.as_raw_fd()).reregister(registry, token, interests)
+                }
+                fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+                    SourceFd(&self.as_raw_fd()).deregister(registry)
+                }
+            }
+        )*
+    };
+}
+
+impl_source!(XBuf, Proxy, Observable);
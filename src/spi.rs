@@ -0,0 +1,239 @@
+//! Out-of-band SPI transfers.
+//!
+//! The EVL core extends the spidev interface with an out-of-band
+//! mode: the device is configured once for a fixed frame size, the
+//! driver then allocates a DMA-safe I/O area which is mapped into
+//! the application, and each transfer of a frame is triggered from
+//! the out-of-band stage, bypassing the in-band SPI stack. Only
+//! controllers with out-of-band support in their driver accept this
+//! mode.
+//!
+//! ```no_run
+//! use revl::spi;
+//!
+//! let mut dev = spi::Builder::new()
+//!     .frame_len(4)
+//!     .speed_hz(10_000_000)
+//!     .mode(spi::MODE_0)
+//!     .open("/dev/spidev0.0")
+//!     .unwrap();
+//! // From the 10 kHz control loop:
+//! dev.tx_buf().copy_from_slice(&[0x80, 0x00, 0x00, 0x00]);
+//! dev.transfer().unwrap();
+//! let sample = u16::from_be_bytes([dev.rx_buf()[2], dev.rx_buf()[3]]);
+//! ```
+
+use std::ffi::{c_void, CString};
+use std::io::ErrorKind;
+use std::mem;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::os::raw::{c_int, c_ulong};
+use std::ptr;
+use std::slice;
+use evl_sys::oob_ioctl;
+use crate::Error;
+
+/// Clock polarity 0, clock phase 0.
+pub const MODE_0: u32 = 0;
+/// Clock polarity 0, clock phase 1.
+pub const MODE_1: u32 = 1;
+/// Clock polarity 1, clock phase 0.
+pub const MODE_2: u32 = 2;
+/// Clock polarity 1, clock phase 1.
+pub const MODE_3: u32 = 3;
+
+// Out-of-band extension of the spidev ABI.
+const SPI_IOC_MAGIC: c_ulong = b'k' as c_ulong;
+
+#[repr(C)]
+struct SpiOobSetup {
+    // Input.
+    frame_len: u32,
+    speed_hz: u32,
+    mode: u32,
+    bits_per_word: u8,
+    // Output.
+    iobuf_len: u32,
+    tx_offset: u32,
+    rx_offset: u32,
+}
+
+const SPI_IOC_ENABLE_OOB_MODE: c_ulong =
+    (3 << 30) | ((mem::size_of::<SpiOobSetup>() as c_ulong) << 16) | (SPI_IOC_MAGIC << 8) | 50;
+const SPI_IOC_DISABLE_OOB_MODE: c_ulong = (SPI_IOC_MAGIC << 8) | 51;
+const SPI_IOC_RUN_OOB_XFER: c_ulong = (SPI_IOC_MAGIC << 8) | 52;
+
+pub struct Builder {
+    frame_len: usize,
+    speed_hz: u32,
+    mode: u32,
+    bits_per_word: u8,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            frame_len: 0,
+            speed_hz: 1_000_000,
+            mode: MODE_0,
+            bits_per_word: 8,
+        }
+    }
+    /// Set the size in bytes of the frames exchanged by each
+    /// transfer. This is mandatory.
+    pub fn frame_len(mut self, len: usize) -> Self {
+        self.frame_len = len;
+        self
+    }
+    /// Set the clock rate of the transfers, 1 MHz by default.
+    pub fn speed_hz(mut self, hz: u32) -> Self {
+        self.speed_hz = hz;
+        self
+    }
+    /// Set the SPI mode, see [`MODE_0`] to [`MODE_3`]. Mode 0 is the
+    /// default.
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.mode = mode;
+        self
+    }
+    /// Set the word size, 8 bits by default.
+    pub fn bits_per_word(mut self, bits: u8) -> Self {
+        self.bits_per_word = bits;
+        self
+    }
+    pub fn open(self, path: &str) -> Result<OobSpiDevice, Error> {
+        OobSpiDevice::open(path, self)
+    }
+}
+
+/// A spidev device switched to out-of-band mode.
+pub struct OobSpiDevice {
+    fd: c_int,
+    iobuf: *mut u8,
+    iobuf_len: usize,
+    tx_offset: usize,
+    rx_offset: usize,
+    frame_len: usize,
+}
+
+unsafe impl Send for OobSpiDevice {}
+
+impl OobSpiDevice {
+    /// Open the spidev device at `path`, e.g. `/dev/spidev0.0`, and
+    /// switch it to out-of-band mode with the settings from a
+    /// [`builder struct`](Builder). This requires in-band services.
+    ///
+    /// # Errors
+    ///
+    /// * [`InvalidInput`][`std::io::ErrorKind`] is returned if the
+    /// frame length is zero, or the settings are not supported by the
+    /// controller.
+    ///
+    /// * [`Unsupported`][`std::io::ErrorKind`] may be returned by a
+    /// kernel without out-of-band support in the SPI controller
+    /// driver.
+    pub fn open(path: &str, builder: Builder) -> Result<Self, Error> {
+        if builder.frame_len == 0 {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid SPI frame length"));
+        }
+        let c_path = CString::new(path).expect("CString::new failed");
+        let fd = unsafe { libc::open(c_path.as_ptr(), libc::O_RDWR | libc::O_CLOEXEC) };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        let mut setup = SpiOobSetup {
+            frame_len: builder.frame_len as u32,
+            speed_hz: builder.speed_hz,
+            mode: builder.mode,
+            bits_per_word: builder.bits_per_word,
+            iobuf_len: 0,
+            tx_offset: 0,
+            rx_offset: 0,
+        };
+        if unsafe { libc::ioctl(fd, SPI_IOC_ENABLE_OOB_MODE, &mut setup) } != 0 {
+            let e = Error::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(e);
+        }
+        let iobuf_len = setup.iobuf_len as usize;
+        let iobuf = unsafe {
+            libc::mmap(ptr::null_mut(), iobuf_len,
+                       libc::PROT_READ | libc::PROT_WRITE,
+                       libc::MAP_SHARED, fd, 0)
+        };
+        if iobuf == libc::MAP_FAILED {
+            let e = Error::last_os_error();
+            unsafe {
+                libc::ioctl(fd, SPI_IOC_DISABLE_OOB_MODE);
+                libc::close(fd);
+            }
+            return Err(e);
+        }
+        Ok(Self {
+            fd,
+            iobuf: iobuf as *mut u8,
+            iobuf_len,
+            tx_offset: setup.tx_offset as usize,
+            rx_offset: setup.rx_offset as usize,
+            frame_len: builder.frame_len,
+        })
+    }
+    /// Return the output frame, sent by the next transfer.
+    pub fn tx_buf(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.iobuf.add(self.tx_offset), self.frame_len) }
+    }
+    /// Return the input frame, received by the last transfer.
+    pub fn rx_buf(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.iobuf.add(self.rx_offset), self.frame_len) }
+    }
+    /// Return the frame length.
+    pub fn frame_len(&self) -> usize {
+        self.frame_len
+    }
+    /// Exchange a frame with the device: the output frame is sent
+    /// while the input frame is received. This must be called from
+    /// the out-of-band stage.
+    pub fn transfer(&mut self) -> Result<(), Error> {
+        let ret = unsafe { oob_ioctl(self.fd, SPI_IOC_RUN_OOB_XFER as _, ptr::null_mut::<c_void>()) };
+        match ret {
+            0 => return Ok(()),
+            _ => return Err(Error::last_os_error()),
+        };
+    }
+    /// Send `tx` and receive into `rx` in a single transfer, copying
+    /// through the I/O area. Both slices are truncated or padded with
+    /// zeroes to the frame length.
+    pub fn transfer_with(&mut self, tx: &[u8], rx: &mut [u8]) -> Result<(), Error> {
+        let frame = self.tx_buf();
+        let n = tx.len().min(frame.len());
+        frame[..n].copy_from_slice(&tx[..n]);
+        frame[n..].fill(0);
+        self.transfer()?;
+        let frame = self.rx_buf();
+        let n = rx.len().min(frame.len());
+        rx[..n].copy_from_slice(&frame[..n]);
+        Ok(())
+    }
+}
+
+impl AsRawFd for OobSpiDevice {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl AsFd for OobSpiDevice {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.fd) }
+    }
+}
+
+impl Drop for OobSpiDevice {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.iobuf as *mut c_void, self.iobuf_len);
+            libc::ioctl(self.fd, SPI_IOC_DISABLE_OOB_MODE);
+            libc::close(self.fd);
+        }
+    }
+}
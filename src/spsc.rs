@@ -0,0 +1,216 @@
+//! Wait-free, single-producer single-consumer ring.
+//!
+//! When both ends of a stream are known, e.g. an audio callback
+//! feeding a worker thread, the multi-producer [channel](crate::channel)
+//! pays for a generality which is not needed. The ring implemented
+//! here only maintains a head index advanced by the [`Consumer`] and
+//! a tail index advanced by the [`Producer`], each in its own cache
+//! line, so that sending and receiving a message costs a bounded
+//! number of instructions, with no retry loop.
+//!
+//! Both ends never block nor issue any system call. A thread which
+//! has to sleep until some data is available may pair the ring with
+//! a [`Notify`](crate::notify::Notify) object.
+//!
+//! ```no_run
+//! use revl::spsc;
+//!
+//! let (tx, rx) = spsc::create::<f32, 8>();
+//! std::thread::spawn(move || {
+//!     for n in 0..10 {
+//!         while tx.send(n as f32).is_err() {}
+//!     }
+//! });
+//! let mut count = 0;
+//! while count < 10 {
+//!     if let Ok(sample) = rx.recv() {
+//!         println!("got {}", sample);
+//!         count += 1;
+//!     }
+//! }
+//! ```
+
+use std::cell::{Cell, UnsafeCell};
+use std::mem::MaybeUninit;
+use std::sync::{
+    Arc,
+    atomic::{fence, AtomicUsize},
+    atomic::Ordering::Acquire,
+    atomic::Ordering::Relaxed,
+    atomic::Ordering::Release,
+};
+use crate::channel::{Order, RecvError, SendError};
+
+#[repr(align(128))]             // CACHELINE_ALIGNMENT
+struct Index {
+    d: AtomicUsize,
+}
+
+struct Spsc<T> {
+    // Next cell to read, only advanced by the consumer.
+    head: Index,
+    // Next cell to write, only advanced by the producer.
+    tail: Index,
+    mask: usize,
+    data: Box<[UnsafeCell<MaybeUninit<T>>]>,
+}
+
+unsafe impl<T: Send> Send for Spsc<T> {}
+unsafe impl<T: Send> Sync for Spsc<T> {}
+
+impl<T> Spsc<T> {
+    fn new(order: usize) -> Self {
+        let data = (0..1usize << order)
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect();
+        Self {
+            head: Index { d: AtomicUsize::new(0) },
+            tail: Index { d: AtomicUsize::new(0) },
+            mask: (1 << order) - 1,
+            data,
+        }
+    }
+    fn capacity(&self) -> usize {
+        self.mask + 1
+    }
+    fn len(&self) -> usize {
+        let tail = self.tail.d.load(Acquire);
+        let head = self.head.d.load(Acquire);
+        tail.wrapping_sub(head)
+    }
+    fn cell(&self, index: usize) -> *mut MaybeUninit<T> {
+        self.data[index & self.mask].get()
+    }
+}
+
+impl<T> Drop for Spsc<T> {
+    fn drop(&mut self) {
+        let tail = *self.tail.d.get_mut();
+        let mut head = *self.head.d.get_mut();
+        while head != tail {
+            unsafe { (*self.cell(head)).assume_init_drop(); }
+            head = head.wrapping_add(1);
+        }
+    }
+}
+
+/// The sending end of a SPSC ring. There is exactly one producer per
+/// ring, which can be moved to another thread but not shared.
+pub struct Producer<T> {
+    q: Arc<Spsc<T>>,
+    // Last head index observed, so that the consumer's cache line is
+    // only read when the ring looks full.
+    head: Cell<usize>,
+}
+
+unsafe impl<T: Send> Send for Producer<T> {}
+
+impl<T> Producer<T> {
+    /// Send a message without blocking. If the ring is full or the
+    /// consumer was dropped, the message is handed back in the error
+    /// value.
+    pub fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        if self.is_disconnected() {
+            return Err(SendError::Disconnected(msg));
+        }
+        let tail = self.q.tail.d.load(Relaxed);
+        if tail.wrapping_sub(self.head.get()) == self.q.capacity() {
+            self.head.set(self.q.head.d.load(Acquire));
+            if tail.wrapping_sub(self.head.get()) == self.q.capacity() {
+                return Err(SendError::Full(msg));
+            }
+        }
+        unsafe { (*self.q.cell(tail)).write(msg); }
+        self.q.tail.d.store(tail.wrapping_add(1), Release);
+        Ok(())
+    }
+    /// Return the number of messages the ring can hold.
+    pub fn capacity(&self) -> usize {
+        self.q.capacity()
+    }
+    /// Return the number of messages pending in the ring. This is
+    /// a snapshot which may be outdated on return.
+    pub fn len(&self) -> usize {
+        self.q.len()
+    }
+    /// Return true if the ring looks empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Return true if the consumer was dropped.
+    pub fn is_disconnected(&self) -> bool {
+        Arc::strong_count(&self.q) == 1
+    }
+}
+
+/// The receiving end of a SPSC ring. There is exactly one consumer
+/// per ring, which can be moved to another thread but not shared.
+pub struct Consumer<T> {
+    q: Arc<Spsc<T>>,
+    // Last tail index observed, so that the producer's cache line is
+    // only read when the ring looks empty.
+    tail: Cell<usize>,
+}
+
+unsafe impl<T: Send> Send for Consumer<T> {}
+
+impl<T> Consumer<T> {
+    /// Receive the next message without blocking.
+    ///
+    /// # Errors
+    ///
+    /// [`RecvError::Empty`] is returned if no message is pending,
+    /// [`RecvError::Disconnected`] if the producer was dropped in
+    /// addition.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let head = self.q.head.d.load(Relaxed);
+        if head == self.tail.get() {
+            self.tail.set(self.q.tail.d.load(Acquire));
+            if head == self.tail.get() {
+                if !self.is_disconnected() {
+                    return Err(RecvError::Empty);
+                }
+                // The producer may have sent a last message right
+                // before leaving, look again for it.
+                fence(Acquire);
+                self.tail.set(self.q.tail.d.load(Acquire));
+                if head == self.tail.get() {
+                    return Err(RecvError::Disconnected);
+                }
+            }
+        }
+        let msg = unsafe { (*self.q.cell(head)).assume_init_read() };
+        self.q.head.d.store(head.wrapping_add(1), Release);
+        Ok(msg)
+    }
+    /// Return the number of messages the ring can hold.
+    pub fn capacity(&self) -> usize {
+        self.q.capacity()
+    }
+    /// Return the number of messages pending in the ring. This is
+    /// a snapshot which may be outdated on return.
+    pub fn len(&self) -> usize {
+        self.q.len()
+    }
+    /// Return true if the ring looks empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Return true if the producer was dropped. Messages sent before
+    /// may still be pending.
+    pub fn is_disconnected(&self) -> bool {
+        Arc::strong_count(&self.q) == 1
+    }
+}
+
+/// Create a SPSC ring of `1 << ORDER` slots, returning its producer
+/// and consumer ends. The slots are allocated once, at this point.
+/// `ORDER` must be lower than 32, which is checked at build time.
+pub fn create<T, const ORDER: usize>() -> (Producer<T>, Consumer<T>) {
+    let () = Order::<ORDER>::CHECK;
+    let q = Arc::new(Spsc::new(ORDER));
+    (
+        Producer { q: q.clone(), head: Cell::new(0) },
+        Consumer { q, tail: Cell::new(0) },
+    )
+}
@@ -0,0 +1,308 @@
+//! Latency and jitter statistics.
+//!
+//! A [`JitterStats`] accumulator collects timing samples, such as the
+//! wakeup latency or the execution time of a control loop, from a
+//! real-time thread. Recording a sample is wait-free and does not
+//! call into the EVL core, so it can be done out-of-band on every
+//! cycle. Consistent [snapshots](Snapshot) of the statistics can be
+//! read concurrently from any other thread, typically an in-band
+//! reporter, optionally along with a fixed-bucket [`Histogram`] of
+//! the samples.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//! use revl::clock::STEADY_CLOCK;
+//! use revl::stats::JitterStats;
+//!
+//! let stats = Arc::new(JitterStats::with_histogram(Duration::from_micros(1), 200));
+//! let c_stats = stats.clone();
+//! std::thread::spawn(move || loop {
+//!     std::thread::sleep(Duration::from_secs(1));
+//!     let s = c_stats.drain();
+//!     println!("min {:?} avg {:?} max {:?} stddev {:?}",
+//!              s.min, s.mean, s.max, s.std_dev());
+//! });
+//!
+//! // From the control loop:
+//! let t0 = STEADY_CLOCK.now().duration_since_epoch().integer();
+//! // Do the work.
+//! let t1 = STEADY_CLOCK.now().duration_since_epoch().integer();
+//! stats.record_ns(t1 - t0);
+//! ```
+
+use std::sync::atomic::{
+    fence,
+    AtomicBool,
+    AtomicU64,
+    Ordering::Acquire,
+    Ordering::Relaxed,
+    Ordering::Release,
+};
+use std::time::Duration;
+
+/// A consistent view of a [`JitterStats`] accumulator.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Snapshot {
+    /// The number of samples.
+    pub count: u64,
+    /// The smallest sample.
+    pub min: Duration,
+    /// The largest sample.
+    pub max: Duration,
+    /// The arithmetic mean of the samples.
+    pub mean: Duration,
+    /// The variance of the samples, in square nanoseconds.
+    pub variance: f64,
+}
+
+impl Snapshot {
+    /// Return the standard deviation of the samples.
+    pub fn std_dev(&self) -> Duration {
+        Duration::from_nanos(self.variance.sqrt() as u64)
+    }
+}
+
+/// Counts samples into buckets of equal width, the last bucket
+/// collecting all samples beyond the range of the others.
+pub struct Histogram {
+    width: u64,
+    buckets: Box<[AtomicU64]>,
+}
+
+impl Histogram {
+    /// Create a histogram of `count` buckets of `width` each, plus
+    /// the overflow bucket.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is zero.
+    pub fn new(width: Duration, count: usize) -> Self {
+        assert!(!width.is_zero(), "bucket width must be non-zero");
+        Self {
+            width: width.as_nanos() as u64,
+            buckets: (0..=count).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+    /// Count a sample expressed in nanoseconds.
+    pub fn record_ns(&self, ns: u64) {
+        let last = self.buckets.len() - 1;
+        let n = ((ns / self.width) as usize).min(last);
+        self.buckets[n].fetch_add(1, Relaxed);
+    }
+    /// Return the width of the buckets.
+    pub fn width(&self) -> Duration {
+        Duration::from_nanos(self.width)
+    }
+    /// Return the counts of all buckets, the overflow bucket last.
+    pub fn counts(&self) -> Vec<u64> {
+        self.buckets.iter().map(|b| b.load(Relaxed)).collect()
+    }
+    /// Return the counts of all buckets, zeroing them.
+    pub fn drain(&self) -> Vec<u64> {
+        self.buckets.iter().map(|b| b.swap(0, Relaxed)).collect()
+    }
+}
+
+/// A wait-free accumulator of timing statistics, see the [module
+/// documentation](crate::stats).
+///
+/// Samples must be recorded by a single thread at a time, any thread
+/// may read the statistics concurrently.
+pub struct JitterStats {
+    // Sequence lock: odd while the recorder updates the fields.
+    seq: AtomicU64,
+    count: AtomicU64,
+    min: AtomicU64,
+    max: AtomicU64,
+    // Running mean and sum of squared deviations (Welford), as f64
+    // bits.
+    mean: AtomicU64,
+    m2: AtomicU64,
+    reset: AtomicBool,
+    histogram: Option<Histogram>,
+}
+
+impl JitterStats {
+    pub fn new() -> Self {
+        Self {
+            seq: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+            min: AtomicU64::new(u64::MAX),
+            max: AtomicU64::new(0),
+            mean: AtomicU64::new(0f64.to_bits()),
+            m2: AtomicU64::new(0f64.to_bits()),
+            reset: AtomicBool::new(false),
+            histogram: None,
+        }
+    }
+    /// Create an accumulator which also counts the samples into a
+    /// [`Histogram`] of `count` buckets of `width` each.
+    pub fn with_histogram(width: Duration, count: usize) -> Self {
+        Self {
+            histogram: Some(Histogram::new(width, count)),
+            ..Self::new()
+        }
+    }
+    /// Record a sample.
+    pub fn record(&self, sample: Duration) {
+        self.record_ns(sample.as_nanos() as u64);
+    }
+    /// Record a sample expressed in nanoseconds.
+    pub fn record_ns(&self, ns: u64) {
+        let seq = self.seq.load(Relaxed);
+        self.seq.store(seq + 1, Relaxed);
+        fence(Release);
+        let (mut count, mut min, mut max, mut mean, mut m2) = (
+            self.count.load(Relaxed),
+            self.min.load(Relaxed),
+            self.max.load(Relaxed),
+            f64::from_bits(self.mean.load(Relaxed)),
+            f64::from_bits(self.m2.load(Relaxed)),
+        );
+        if self.reset.swap(false, Relaxed) {
+            (count, min, max, mean, m2) = (0, u64::MAX, 0, 0.0, 0.0);
+        }
+        count += 1;
+        min = min.min(ns);
+        max = max.max(ns);
+        let delta = ns as f64 - mean;
+        mean += delta / count as f64;
+        m2 += delta * (ns as f64 - mean);
+        self.count.store(count, Relaxed);
+        self.min.store(min, Relaxed);
+        self.max.store(max, Relaxed);
+        self.mean.store(mean.to_bits(), Relaxed);
+        self.m2.store(m2.to_bits(), Relaxed);
+        self.seq.store(seq + 2, Release);
+        if let Some(histogram) = &self.histogram {
+            histogram.record_ns(ns);
+        }
+    }
+    /// Return a consistent snapshot of the statistics. This retries
+    /// while a sample is being recorded, so it should not be called
+    /// from a thread which may preempt the recorder.
+    pub fn snapshot(&self) -> Snapshot {
+        loop {
+            let seq = self.seq.load(Acquire);
+            if seq & 1 != 0 {
+                std::hint::spin_loop();
+                continue;
+            }
+            let count = self.count.load(Relaxed);
+            let min = self.min.load(Relaxed);
+            let max = self.max.load(Relaxed);
+            let mean = f64::from_bits(self.mean.load(Relaxed));
+            let m2 = f64::from_bits(self.m2.load(Relaxed));
+            fence(Acquire);
+            if self.seq.load(Relaxed) != seq {
+                continue;
+            }
+            if count == 0 || self.reset.load(Relaxed) {
+                return Snapshot::default();
+            }
+            return Snapshot {
+                count,
+                min: Duration::from_nanos(min),
+                max: Duration::from_nanos(max),
+                mean: Duration::from_nanos(mean as u64),
+                variance: if count > 1 { m2 / (count - 1) as f64 } else { 0.0 },
+            };
+        }
+    }
+    /// Return a snapshot of the statistics, then start over. The
+    /// recorder clears the statistics on its next sample, so samples
+    /// recorded concurrently with this call may be lost. The
+    /// histogram, if any, is not reset.
+    pub fn drain(&self) -> Snapshot {
+        let snapshot = self.snapshot();
+        self.reset.store(true, Relaxed);
+        snapshot
+    }
+    /// Return the histogram of the samples, if any.
+    pub fn histogram(&self) -> Option<&Histogram> {
+        self.histogram.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn empty_snapshot() {
+        let s = JitterStats::new().snapshot();
+        assert_eq!(s.count, 0);
+        assert_eq!(s.max, Duration::ZERO);
+    }
+
+    #[test]
+    fn moments() {
+        let stats = JitterStats::new();
+        for ns in [10, 20, 30] {
+            stats.record_ns(ns);
+        }
+        let s = stats.snapshot();
+        assert_eq!(s.count, 3);
+        assert_eq!(s.min, Duration::from_nanos(10));
+        assert_eq!(s.max, Duration::from_nanos(30));
+        assert_eq!(s.mean, Duration::from_nanos(20));
+        assert!((s.variance - 100.0).abs() < 1e-9);
+        assert_eq!(s.std_dev(), Duration::from_nanos(10));
+    }
+
+    #[test]
+    fn drain_starts_over() {
+        let stats = JitterStats::new();
+        stats.record_ns(100);
+        assert_eq!(stats.drain().count, 1);
+        assert_eq!(stats.snapshot().count, 0);
+        stats.record_ns(5);
+        let s = stats.snapshot();
+        assert_eq!(s.count, 1);
+        assert_eq!((s.min, s.max), (Duration::from_nanos(5), Duration::from_nanos(5)));
+    }
+
+    #[test]
+    fn histogram_buckets() {
+        let stats = JitterStats::with_histogram(Duration::from_nanos(10), 3);
+        for ns in [0, 9, 10, 25, 29, 30, 1000] {
+            stats.record_ns(ns);
+        }
+        let histogram = stats.histogram().unwrap();
+        assert_eq!(histogram.counts(), [2, 1, 2, 2]);
+        // Draining the statistics leaves the histogram alone.
+        stats.drain();
+        assert_eq!(histogram.drain(), [2, 1, 2, 2]);
+        assert_eq!(histogram.counts(), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn snapshots_are_consistent() {
+        const NR_SAMPLES: u64 = 200_000;
+        let stats = Arc::new(JitterStats::new());
+        let recorder = {
+            let stats = stats.clone();
+            thread::spawn(move || {
+                for ns in 1..=NR_SAMPLES {
+                    stats.record_ns(ns);
+                }
+            })
+        };
+        // With increasing samples from 1, a torn read would show a
+        // maximum out of sync with the count.
+        loop {
+            let s = stats.snapshot();
+            if s.count > 0 {
+                assert_eq!(s.min, Duration::from_nanos(1));
+                assert_eq!(s.max, Duration::from_nanos(s.count));
+            }
+            if s.count == NR_SAMPLES {
+                break;
+            }
+        }
+        recorder.join().unwrap();
+    }
+}
@@ -7,28 +7,54 @@
 
 use core::mem::MaybeUninit;
 use std::thread;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Condvar, Mutex as StdMutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::ptr;
 use std::os::raw::c_int;
-use std::io::Error;
 use std::ffi::CString;
+use std::io::ErrorKind;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd};
+use std::mem::{self, ManuallyDrop};
+use std::time::{Duration, Instant as StdInstant};
+use bitflags::bitflags;
 use evl_sys::{
     evl_attach_thread,
+    evl_detach_self,
+    evl_get_self,
     evl_unblock_thread,
     evl_demote_thread,
     evl_sched_attrs,
     evl_set_schedattr,
     evl_get_schedattr,
+    evl_get_state,
+    evl_set_thread_mode,
+    evl_clear_thread_mode,
+    evl_is_inband,
+    evl_switch_inband,
+    evl_switch_oob,
+    evl_yield,
+    evl_thread_state,
     CloneFlags,
 };
 use crate::sched;
+use crate::Error;
+
+pub mod debug;
+mod health;
+pub use health::{HealthEvent, HealthMonitor, HealthReport};
 
 /// A thread factory, which can be used in order to configure the
 /// properties of a new EVL thread.
+#[derive(Clone)]
 pub struct Builder {
     name: Option<String>,
     visible: bool,
     observable: bool,
     unicast: bool,
+    stack_size: Option<usize>,
+    prefault_stack: bool,
+    cpu: Option<usize>,
 }
 
 impl Builder {
@@ -47,12 +73,20 @@ impl Builder {
     /// - `unicast`: if observable, specifies whether notifications
     /// should be sent to a single observer instead of broadcast
     /// to all of them.
+    /// - `stack_size`: the size of the stack for a spawned thread.
+    /// - `prefault_stack`: whether the stack of a spawned thread
+    /// should be locked into memory before it attaches to the core
+    /// (default).
+    /// - `cpu`: the CPU a spawned thread should be pinned to.
     pub fn new() -> Self {
         Self {
             name: None,
             visible: false,
             observable: false,
             unicast: false,
+            stack_size: None,
+            prefault_stack: true,
+            cpu: None,
         }
     }
     /// Set the thread name. This name must conform to the [naming
@@ -106,35 +140,83 @@ impl Builder {
         self.unicast = true;
         self
     }
+    /// Set the size of the stack (in bytes) for a thread created by
+    /// [`spawn()`][`Builder::spawn`]. The actual stack size may be
+    /// rounded up to the page size. If unset, the default stack size
+    /// for [`std::thread`][`thread`] applies.
+    ///
+    /// ```no_run
+    /// use revl::thread;
+    ///
+    /// let builder = thread::Builder::new().name("bar_thread").stack_size(256 * 1024);
+    /// ```
+    pub fn stack_size(mut self, size: usize) -> Self {
+        self.stack_size = Some(size);
+        self
+    }
+    /// Enable or disable pre-faulting the stack of a thread created
+    /// by [`spawn()`][`Builder::spawn`]. When enabled (default), the
+    /// whole stack is faulted in and locked into memory by the new
+    /// thread before it attaches to the core, so that it cannot take
+    /// a page fault on first deep use of its stack later on.
+    pub fn prefault_stack(mut self, enabled: bool) -> Self {
+        self.prefault_stack = enabled;
+        self
+    }
+    /// Pin a thread created by [`spawn()`][`Builder::spawn`] to
+    /// `cpu`, before it attaches to the core. This CPU should be part
+    /// of the out-of-band CPU set. [`spawn()`][`Builder::spawn`] fails
+    /// with [`InvalidInput`][`std::io::ErrorKind`] if `cpu` is not
+    /// lower than `CPU_SETSIZE`.
+    pub fn cpu(mut self, cpu: usize) -> Self {
+        self.cpu = Some(cpu);
+        self
+    }
     /// Attach the calling thread to the EVL core, consuming the
     /// builder.
     ///
+    /// The returned [`Thread`] handle owns the attachment: dropping
+    /// it from the attached thread detaches the latter from the
+    /// core, so it should be kept alive for as long as real-time
+    /// services are needed.
+    ///
     /// # Examples
     ///
     /// ```no_run
     /// use revl::thread;
     ///
-    /// thread::Builder::new()
+    /// let _me = thread::Builder::new()
     ///		.name("foo_thread")
     ///		.private()
     ///		.observable()
     ///		.attach().expect("cannot attach thread to EVL core");
     /// ```
+    #[must_use = "dropping the handle detaches the thread"]
     pub fn attach(self) -> Result<Thread, Error> {
         Thread::attach(self)
     }
     /// Spawn a new EVL thread using the current properties, consuming
     /// the builder.
     ///
+    /// The new thread is attached to the EVL core before the closure
+    /// runs, and this call waits for the outcome of such attachment
+    /// before returning. The closure never runs if the attachment
+    /// fails. Otherwise, it receives a reference to the [`Thread`]
+    /// handle of the new thread, which remains attached until the
+    /// closure returns.
+    ///
     /// On success, this call returns a join handle, which implements
-    /// the [`join()`][`thread::JoinHandle::join`] method that can be
-    /// used to wait for the spawned thread to exit.
+    /// the [`join()`][`JoinHandle::join`] method that can be
+    /// used to wait for the spawned thread to exit. The value returned
+    /// by the closure is passed back to the joiner.
     ///
     /// The spawned thread may outlive the caller (unless the caller
     /// thread is the main thread; the whole process is terminated
     /// when the main thread finishes). The join handle can be used to
     /// block on termination of the spawned thread, including
-    /// recovering its panics.
+    /// recovering its panics. A panicking thread is switched to the
+    /// in-band stage and detached from the core before unwinding any
+    /// further.
     ///
     /// The reason for the `'static + Send` bounds required from the
     /// closure type are explained in the documentation of the
@@ -142,26 +224,19 @@ impl Builder {
     ///
     /// # Errors
     ///
-    /// On error, this call may directly return an error status from
-    /// [`std::thread::spawn()`][`thread::spawn`] without starting the
-    /// thread. Otherwise, joining the spawned thread may return an
-    /// error status related to attaching the thread to the EVL
-    /// core. See below.
+    /// This call may return an error status from
+    /// [`std::thread::spawn()`][`thread::spawn`] if the thread could
+    /// not be started, or any of the following statuses if the new
+    /// thread could not be attached to the core:
     ///
-    /// ## Join errors
-    ///
-    /// On error attaching the new thread to the core,
-    /// [`join()`][`thread::JoinHandle::join`] may return any of the
-    /// following statuses:
-    ///
-    /// * [`AlreadyExists`][`std::io::ErrorKind`] is returned if an
+    /// * [`NameConflict`][`crate::Error::NameConflict`] is returned if an
     /// existing thread already goes by the same name.
     ///
     /// * [`InvalidInput`][`std::io::ErrorKind`] may denote a badly formed
     /// name. Check these
     /// [rules](https://evlproject.org/core/user-api/#element-naming-convention).
     ///
-    /// * [`PermissionDenied`][`std::io::ErrorKind`] means that the
+    /// * [`PermissionDenied`][`crate::Error::PermissionDenied`] means that the
     /// calling thread is not allowed to lock memory by a call to
     /// [mlockall(2)](https://man7.org/linux/man-pages/man2/mlock.2.html),
     /// which is a showstopper for real-time execution.
@@ -175,25 +250,464 @@ impl Builder {
     /// ```no_run
     /// use revl::thread;
     ///
-    /// let builder = thread::Builder::new();
+    /// let builder = thread::Builder::new().name("foo_thread");
     ///
-    /// let handle = builder.spawn(|| {
+    /// let handle = builder.spawn(|me| {
     ///     // your EVL thread code
-    /// }).unwrap();
+    ///     me.info().unwrap().inband_switches
+    /// }).expect("cannot spawn EVL thread");
     ///
-    /// handle.join().unwrap();
+    /// assert_eq!(handle.join().unwrap(), 0);
     /// ```
-    pub fn spawn<F>(self, f: F) -> Result<thread::JoinHandle<Result<(), Error>>, Error>
-    where F: FnOnce() + Send + 'static
+    pub fn spawn<F, T>(self, f: F) -> Result<JoinHandle<T>, Error>
+    where
+        F: FnOnce(&Thread) -> T + Send + 'static,
+        T: Send + 'static,
     {
-        Ok(thread::Builder::new().spawn(move || -> Result<(), Error> {
-            self.attach()?;
-            Ok(f())
-        })?)
+        self.spawn_setup(|_me| Ok(()), f)
+    }
+    // Like spawn(), running `setup` from the new thread once it is
+    // attached. The closure does not run if the setup fails, in which
+    // case the thread exits and the error is returned.
+    pub(crate) fn spawn_setup<S, F, T>(self, setup: S, f: F) -> Result<JoinHandle<T>, Error>
+    where
+        S: FnOnce(&Thread) -> Result<(), Error> + Send + 'static,
+        F: FnOnce(&Thread) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (tx, rx) = mpsc::sync_channel::<Result<(), Error>>(1);
+        let mut std_builder = thread::Builder::new();
+        if let Some(size) = self.stack_size {
+            std_builder = std_builder.stack_size(size);
+        }
+        let handle = std_builder.spawn(move || -> Option<T> {
+            match self.setup().and_then(|me| setup(&me).map(|()| me)) {
+                Ok(me) => {
+                    // The spawner cannot go away before receiving.
+                    let _ = tx.send(Ok(()));
+                    match panic::catch_unwind(AssertUnwindSafe(|| f(&me))) {
+                        Ok(ret) => Some(ret),
+                        Err(payload) => {
+                            // Do not unwind from the out-of-band
+                            // stage: switch in-band and detach first,
+                            // then pass the panic on to the joiner.
+                            let _ = switch_inband();
+                            let _ = me.detach();
+                            panic::resume_unwind(payload);
+                        },
+                    }
+                },
+                Err(e) => {
+                    let _ = tx.send(Err(e));
+                    None
+                },
+            }
+        })?;
+        // Wait for the child to report the setup status. The channel
+        // may only disconnect without a status if the child panicked,
+        // which we report from join().
+        match rx.recv() {
+            Ok(Err(e)) => {
+                let _ = handle.join();
+                return Err(e);
+            },
+            _ => (),
+        }
+        Ok(JoinHandle(handle))
+    }
+    // Prepare a spawned thread then attach it to the core.
+    fn setup(self) -> Result<Thread, Error> {
+        if let Some(cpu) = self.cpu {
+            pin_to_cpu(cpu)?;
+        }
+        if self.prefault_stack {
+            crate::mem::lock_stack()?;
+        }
+        self.attach()
+    }
+}
+
+/// Switch the calling thread to the out-of-band stage.
+///
+/// EVL threads switch to the out-of-band stage automatically when
+/// issuing an EVL system call which requires it; this call forces
+/// the switch ahead of time, e.g. before entering a time-critical
+/// section.
+///
+/// # Errors
+///
+/// * [`PermissionDenied`][`crate::Error::PermissionDenied`] means that the
+/// calling thread is not attached to the EVL core.
+///
+/// # Examples
+///
+/// ```no_run
+/// use revl::thread;
+///
+/// thread::switch_oob().expect("cannot switch to out-of-band stage");
+/// debug_assert!(!thread::is_inband());
+/// ```
+pub fn switch_oob() -> Result<(), Error> {
+    let ret: c_int = unsafe { evl_switch_oob() };
+    match ret {
+        0 => return Ok(()),
+        _ => return Err(Error::from_raw_os_error(-ret)),
+    }
+}
+
+/// Switch the calling thread to the in-band stage.
+///
+/// This should be done before issuing system calls which are not
+/// EVL-safe from a real-time standpoint, in order to make the stage
+/// switch explicit. The thread is switched back to the out-of-band
+/// stage by the next EVL system call requiring it, or by calling
+/// [`switch_oob()`].
+///
+/// # Errors
+///
+/// * [`PermissionDenied`][`crate::Error::PermissionDenied`] means that the
+/// calling thread is not attached to the EVL core.
+///
+/// # Examples
+///
+/// ```no_run
+/// use revl::thread;
+///
+/// thread::switch_inband().expect("cannot switch to in-band stage");
+/// debug_assert!(thread::is_inband());
+/// println!("some non-EVL-safe output");
+/// ```
+pub fn switch_inband() -> Result<(), Error> {
+    let ret: c_int = unsafe { evl_switch_inband() };
+    match ret {
+        0 => return Ok(()),
+        _ => return Err(Error::from_raw_os_error(-ret)),
+    }
+}
+
+/// Tell whether the calling thread is currently running on the
+/// in-band stage. Threads which are not attached to the EVL core
+/// always run in-band.
+pub fn is_inband() -> bool {
+    unsafe { evl_is_inband() }
+}
+
+/// Yield the CPU to another EVL thread of the same priority.
+///
+/// The calling thread is moved to the end of the run queue for its
+/// priority level, which allows threads undergoing the same
+/// scheduling policy and priority to share the CPU cooperatively,
+/// e.g. from busy polling loops. Unlike
+/// [`std::thread::yield_now()`][`thread::yield_now`], this call does
+/// not cause the caller to switch to the in-band stage.
+///
+/// # Examples
+///
+/// ```no_run
+/// use revl::thread;
+///
+/// fn poll(ready: &dyn Fn() -> bool) {
+///     while !ready() {
+///         thread::yield_now();
+///     }
+/// }
+/// ```
+pub fn yield_now() {
+    unsafe {
+        evl_yield();
+    }
+}
+
+/// Spawn one EVL thread per out-of-band CPU, each pinned to its CPU.
+///
+/// The threads are configured from the `template` builder. If the
+/// latter is named, the CPU number is appended to the name of each
+/// thread, e.g. `worker-2` for CPU #2 given `worker` as a template
+/// name. Every thread runs `f`, which receives the [`Thread`] handle
+/// and the CPU number.
+///
+/// # Errors
+///
+/// The set of out-of-band CPUs may not be readable, e.g. if the EVL
+/// core is not enabled. Otherwise, the first error returned by
+/// [`Builder::spawn()`] is passed back to the caller; the threads
+/// spawned so far keep running in this case.
+///
+/// # Examples
+///
+/// ```no_run
+/// use revl::thread;
+///
+/// let handles = thread::spawn_per_cpu(thread::Builder::new().name("worker"), |_me, cpu| {
+///     // per-CPU EVL thread code
+///     cpu
+/// }).expect("cannot spawn workers");
+///
+/// for h in handles {
+///     h.join().unwrap();
+/// }
+/// ```
+pub fn spawn_per_cpu<F, T>(template: Builder, f: F) -> Result<Vec<JoinHandle<T>>, Error>
+where
+    F: Fn(&Thread, usize) -> T + Send + Sync + 'static,
+    T: Send + 'static,
+{
+    let f = Arc::new(f);
+    let mut handles = Vec::new();
+    for cpu in sched::oob_cpu_list()? {
+        let mut builder = template.clone().cpu(cpu);
+        if let Some(name) = &template.name {
+            builder = builder.name(&format!("{}-{}", name, cpu));
+        }
+        let f = f.clone();
+        handles.push(builder.spawn(move |me| f(me, cpu))?);
+    }
+    Ok(handles)
+}
+
+/// A token telling the members of a [`Group`] when to stop.
+#[derive(Clone)]
+pub struct StopToken(Arc<AtomicBool>);
+
+impl StopToken {
+    /// Tell whether the group was requested to stop.
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Acquire)
     }
 }
 
-pub struct Thread(pub(crate) c_int);
+/// A group of EVL threads which start and stop together.
+///
+/// Every thread added to the group is spawned and attached to the
+/// core immediately, then waits for [`start()`][`Group::start`] to
+/// be called before running its closure, so that all members are
+/// configured before any of them starts working. The closure
+/// receives a [`StopToken`] which should be checked periodically;
+/// [`stop()`][`Group::stop`] raises it for all members.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::{Duration, Instant as StdInstant};
+/// use revl::thread::{Builder, Group};
+///
+/// let mut group = Group::new();
+/// for n in 0..4 {
+///     group.add(Builder::new().name(&format!("member-{}", n)), |_me, stop| {
+///         let mut loops = 0;
+///         while !stop.is_stopped() {
+///             loops += 1;
+///         }
+///         loops
+///     }).expect("cannot add group member");
+/// }
+/// group.start();
+/// // ...
+/// group.stop();
+/// let results = group.join_timeout(Duration::from_secs(1)).expect("members are stuck");
+/// ```
+pub struct Group<T> {
+    handles: Vec<JoinHandle<T>>,
+    gate: Arc<(StdMutex<bool>, Condvar)>,
+    stop: StopToken,
+}
+
+impl<T: Send + 'static> Group<T> {
+    /// Create an empty thread group.
+    pub fn new() -> Self {
+        Self {
+            handles: Vec::new(),
+            gate: Arc::new((StdMutex::new(false), Condvar::new())),
+            stop: StopToken(Arc::new(AtomicBool::new(false))),
+        }
+    }
+    /// Spawn a new member from `builder`, which will run `f` once
+    /// the group is started. See [`Builder::spawn()`] for the
+    /// possible errors.
+    pub fn add<F>(&mut self, builder: Builder, f: F) -> Result<(), Error>
+    where F: FnOnce(&Thread, &StopToken) -> T + Send + 'static
+    {
+        self.add_setup(builder, |_me| Ok(()), f)
+    }
+    // Like add(), running `setup` from the new member before it waits
+    // for the group to start. The member exits if the setup fails,
+    // in which case the error is returned.
+    pub(crate) fn add_setup<S, F>(&mut self, builder: Builder, setup: S, f: F) -> Result<(), Error>
+    where
+        S: FnOnce(&Thread) -> Result<(), Error> + Send + 'static,
+        F: FnOnce(&Thread, &StopToken) -> T + Send + 'static,
+    {
+        let gate = self.gate.clone();
+        let stop = self.stop.clone();
+        let handle = builder.spawn_setup(setup, move |me| {
+            let (lock, cvar) = &*gate;
+            let mut open = lock.lock().unwrap();
+            while !*open {
+                open = cvar.wait(open).unwrap();
+            }
+            drop(open);
+            f(me, &stop)
+        })?;
+        self.handles.push(handle);
+        Ok(())
+    }
+    /// Release all members, which start running their closure.
+    pub fn start(&self) {
+        let (lock, cvar) = &*self.gate;
+        *lock.lock().unwrap() = true;
+        cvar.notify_all();
+    }
+    /// Request all members to stop, by raising their stop token.
+    pub fn stop(&self) {
+        self.stop.0.store(true, Ordering::Release);
+    }
+    /// Wait for all members to exit within `timeout`, returning the
+    /// outcome of each member, in the order they were added.
+    ///
+    /// # Errors
+    ///
+    /// [`TimedOut`][`crate::Error::TimedOut`] is returned if some member
+    /// is still running when the timeout elapses, in which case the
+    /// group is left unchanged, so that the caller may try joining
+    /// again.
+    pub fn join_timeout(&mut self, timeout: Duration) -> Result<Vec<thread::Result<T>>, Error> {
+        let deadline = StdInstant::now() + timeout;
+        while !self.handles.iter().all(|h| h.is_finished()) {
+            if StdInstant::now() >= deadline {
+                return Err(Error::TimedOut);
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        Ok(self.handles.drain(..).map(|h| h.join()).collect())
+    }
+}
+
+impl<T> Drop for Group<T> {
+    fn drop(&mut self) {
+        // Do not leave members waiting forever on the gate.
+        self.stop.0.store(true, Ordering::Release);
+        let (lock, cvar) = &*self.gate;
+        if let Ok(mut open) = lock.lock() {
+            *open = true;
+        }
+        cvar.notify_all();
+    }
+}
+
+// Restrict the affinity of the calling thread to a single CPU.
+fn pin_to_cpu(cpu: usize) -> Result<(), Error> {
+    // CPU_SET() panics past the capacity of the set.
+    if cpu >= libc::CPU_SETSIZE as usize {
+        return Err(Error::new(ErrorKind::InvalidInput, "invalid CPU number"));
+    }
+    let ret: c_int = unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    match ret {
+        0 => return Ok(()),
+        _ => return Err(Error::last_os_error()),
+    }
+}
+
+/// An owned permission to join on an EVL thread spawned by
+/// [`Builder::spawn()`].
+pub struct JoinHandle<T>(thread::JoinHandle<Option<T>>);
+
+impl<T> JoinHandle<T> {
+    /// Wait for the associated thread to finish, returning the value
+    /// produced by its closure. If the thread panicked, `Err` is
+    /// returned with the parameter given to `panic!`, like
+    /// [`std::thread::JoinHandle::join()`][`thread::JoinHandle::join`]
+    /// does.
+    pub fn join(self) -> thread::Result<T> {
+        // The closure always runs once spawn() has returned the
+        // handle, so there must be a value unless it panicked.
+        self.0.join().map(|ret| ret.expect("EVL thread closure did not run"))
+    }
+    /// Extract a handle to the underlying native thread.
+    pub fn thread(&self) -> &thread::Thread {
+        self.0.thread()
+    }
+    /// Check whether the associated thread has finished running its
+    /// closure.
+    pub fn is_finished(&self) -> bool {
+        self.0.is_finished()
+    }
+}
+
+/// A handle to an EVL thread, wrapping its element file descriptor.
+///
+/// A handle obtained from [`Thread::attach()`] owns the attachment
+/// of the calling thread to the core. When such handle is dropped by
+/// the thread it refers to, the latter is detached from the core,
+/// which releases the element file descriptor. Dropping it from any
+/// other thread is a no-op, the attached thread keeps running; the
+/// core eventually detaches it when it exits.
+///
+/// A handle obtained from [`Thread::from_owned_fd()`] owns the
+/// element file descriptor only, which is closed when the handle is
+/// dropped.
+pub struct Thread(pub(crate) c_int, Ownership);
+
+// What a thread handle releases when dropped.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Ownership {
+    Attachment,
+    Fd,
+}
+
+bitflags! {
+    /// Mode bits of an EVL thread, which can be changed by
+    /// [`Thread::set_mode()`] and [`Thread::clear_mode()`]. See
+    /// [this document](https://evlproject.org/core/user-api/thread/#evl_set_thread_mode).
+    pub struct ThreadMode: i32 {
+        /// Warn on stage switch: SIGDEBUG is sent to the thread
+        /// whenever it switches to the in-band stage inadvertently.
+        const WOSS = 0x0000_8000;
+        /// Warn on locking inconsistency: SIGDEBUG is sent to the
+        /// thread when it sleeps while holding a mutex, or upon lock
+        /// imbalance.
+        const WOLI = 0x0001_0000;
+        /// Warn on stage exclusion: SIGDEBUG is sent to the thread
+        /// when it attempts to access a resource reserved to the
+        /// out-of-band stage while running in-band.
+        const WOSX = 0x0002_0000;
+        /// Notify health monitoring events via SIGDEBUG.
+        const HMSIG = 0x0010_0000;
+        /// Notify health monitoring events via the observable
+        /// channel of the thread.
+        const HMOBS = 0x0020_0000;
+    }
+}
+
+/// A snapshot of the state and runtime statistics of an EVL thread,
+/// as returned by [`Thread::info()`].
+#[derive(Debug, Clone, Copy)]
+pub struct ThreadInfo {
+    /// The current scheduling policy.
+    pub policy: i32,
+    /// The current priority, which may be boosted by a priority
+    /// inheritance or ceiling protocol.
+    pub priority: i32,
+    /// The CPU the thread last ran on.
+    pub cpu: i32,
+    /// The run state bits, as defined by the EVL core (T_*).
+    pub state: u32,
+    /// Count of switches to the in-band stage. An increasing value
+    /// while the thread is supposed to run out-of-band denotes
+    /// inadvertent stage demotions.
+    pub inband_switches: u32,
+    /// Count of context switches.
+    pub context_switches: u32,
+    /// Count of out-of-band system calls.
+    pub syscalls: u32,
+    /// Count of remote wakeups.
+    pub remote_wakeups: u32,
+    /// Cumulated execution time, in nanoseconds.
+    pub exec_time: u64,
+}
 
 unsafe impl Send for Thread {}
 unsafe impl Sync for Thread {}
@@ -206,22 +720,22 @@ impl Thread {
     ///
     /// # Errors
     ///
-    /// * [`AlreadyExists`][`std::io::ErrorKind`] means the thread
+    /// * [`NameConflict`][`crate::Error::NameConflict`] means the thread
     /// name is conflicting with an existing thread name.
     ///
     /// * [`InvalidInput`][`std::io::ErrorKind`] means that the thread
     /// name contains invalid characters: such name must contain only
     /// valid characters in the context of a Linux file name.
     ///
-    /// * [`Other`][`std::io::ErrorKind`] may mean that either the EVL
-    /// core is not enabled in the kernel, or there is an ABI mismatch
-    /// between the underlying [evl-sys
+    /// * [`NoCore`][`crate::Error::NoCore`] means that the EVL core is
+    /// not enabled in the kernel, [`AbiMismatch`][`crate::Error::AbiMismatch`]
+    /// that there is an ABI mismatch between the underlying [evl-sys
     /// crate](https://source.denx.de/Xenomai/xenomai4/evl-sys) and
     /// the EVL core. See [these
     /// explanations](https://evlproject.org/core/under-the-hood/abi/)
     /// for the latter.
     ///
-    /// * [`PermissionDenied`][`std::io::ErrorKind`] means that the
+    /// * [`PermissionDenied`][`crate::Error::PermissionDenied`] means that the
     /// calling context is not granted the privileges required by the
     /// attachment operation, such as locking memory via the
     /// [mlockall(2)](http://man7.org/linux/man-pages/man2/mlock.2.html)
@@ -233,8 +747,9 @@ impl Thread {
     /// use revl::thread;
     ///
     /// let props = thread::Builder::new().name("foo_thread").public();
-    /// thread::Thread::attach(props).expect("cannot attach thread to EVL core");
+    /// let _me = thread::Thread::attach(props).expect("cannot attach thread to EVL core");
     /// ```
+    #[must_use = "dropping the handle detaches the thread"]
     pub fn attach(builder: Builder) -> Result<Self, Error> {
 	let mut c_flags = CloneFlags::PRIVATE.bits() as c_int;
         if builder.visible {
@@ -259,10 +774,107 @@ impl Thread {
 	};
 	// evl_attach_thread() returns a valid file descriptor or -errno.
 	match ret {
-	    0.. => return Ok(Thread(ret)),
+	    0.. => return Ok(Thread(ret, Ownership::Attachment)),
             _ => return Err(Error::from_raw_os_error(-ret)),
 	};
     }
+    /// Create a thread handle from an owned element file descriptor,
+    /// e.g. obtained by opening a public thread element from the
+    /// `/dev/evl/threads` hierarchy, or received from another
+    /// process. The descriptor is closed when the handle is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use std::os::fd::OwnedFd;
+    /// use revl::thread::Thread;
+    ///
+    /// let file = File::open("/dev/evl/threads/foo_thread").unwrap();
+    /// let thread = Thread::from_owned_fd(OwnedFd::from(file));
+    /// thread.unblock().unwrap();
+    /// ```
+    pub fn from_owned_fd(fd: OwnedFd) -> Self {
+        Thread(fd.into_raw_fd(), Ownership::Fd)
+    }
+    /// Detach the calling thread from the EVL core, consuming its
+    /// handle.
+    ///
+    /// Once detached, the thread becomes a regular in-band thread
+    /// again, its element file descriptor is closed. It may be
+    /// attached anew later on.
+    ///
+    /// # Errors
+    ///
+    /// * [`NotAttached`][`crate::Error::NotAttached`] means that the
+    /// handle does not refer to the calling thread, or the latter is
+    /// not attached to the core.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use revl::thread;
+    ///
+    /// let me = thread::Builder::new().name("foo_thread").attach().unwrap();
+    /// // Real-time work...
+    /// me.detach().expect("cannot detach thread from EVL core");
+    /// ```
+    pub fn detach(self) -> Result<(), Error> {
+        if self.1 != Ownership::Attachment || !self.is_self() {
+            return Err(Error::NotAttached);
+        }
+        // evl_detach_self() closes the element fd.
+        let _this = ManuallyDrop::new(self);
+        let ret: c_int = unsafe { evl_detach_self() };
+        match ret {
+            0 => return Ok(()),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        }
+    }
+    // Tell whether the handle refers to the calling thread.
+    fn is_self(&self) -> bool {
+        unsafe { evl_get_self() == self.0 }
+    }
+    /// Set mode bits for the target thread, returning the previous
+    /// mode.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use revl::thread::{Thread, ThreadMode};
+    ///
+    /// fn enable_warnings(t: &Thread) -> Result<ThreadMode, revl::Error> {
+    ///     t.set_mode(ThreadMode::WOSS | ThreadMode::WOLI)
+    /// }
+    /// ```
+    pub fn set_mode(&self, mode: ThreadMode) -> Result<ThreadMode, Error> {
+        let mut oldmask: c_int = 0;
+        let ret: c_int = unsafe { evl_set_thread_mode(self.0, mode.bits(), &mut oldmask) };
+        match ret {
+            0 => return Ok(ThreadMode::from_bits_truncate(oldmask)),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        }
+    }
+    /// Clear mode bits for the target thread, returning the previous
+    /// mode.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use revl::thread::{Thread, ThreadMode};
+    ///
+    /// fn disable_warnings(t: &Thread) -> Result<ThreadMode, revl::Error> {
+    ///     t.clear_mode(ThreadMode::all())
+    /// }
+    /// ```
+    pub fn clear_mode(&self, mode: ThreadMode) -> Result<ThreadMode, Error> {
+        let mut oldmask: c_int = 0;
+        let ret: c_int = unsafe { evl_clear_thread_mode(self.0, mode.bits(), &mut oldmask) };
+        match ret {
+            0 => return Ok(ThreadMode::from_bits_truncate(oldmask)),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        }
+    }
     /// Unblock the target thread.
     ///
     /// If the target thread is currently sleeping on some EVL core
@@ -275,7 +887,7 @@ impl Thread {
     /// ```no_run
     /// use revl::thread;
     ///
-    /// fn unblock_some_thread(t: &thread::Thread) -> Result<(), std::io::Error> {
+    /// fn unblock_some_thread(t: &thread::Thread) -> Result<(), revl::Error> {
     ///     t.unblock()
     /// }
     /// ```
@@ -299,7 +911,7 @@ impl Thread {
     /// ```no_run
     /// use revl::thread;
     ///
-    /// fn demote_some_thread(t: &thread::Thread) -> Result<(), std::io::Error> {
+    /// fn demote_some_thread(t: &thread::Thread) -> Result<(), revl::Error> {
     ///     t.demote()
     /// }
     /// ```
@@ -316,8 +928,9 @@ impl Thread {
     ///
     /// ```no_run
     /// use revl::thread;
+    /// use revl::sched::SchedFifo;
     ///
-    /// fn set_thread_sched(t: &thread::Thread) -> Result<(), std::io::Error> {
+    /// fn set_thread_sched(t: &thread::Thread) -> Result<(), revl::Error> {
     ///     t.set_sched(SchedFifo { prio: 42 })
     /// }
     /// ```
@@ -329,12 +942,104 @@ impl Thread {
             _ => return Err(Error::from_raw_os_error(-ret)),
 	}
     }
-    pub fn get_sched(&self) -> Result<impl sched::PolicyParam, Error> {
-	let mut attrs = MaybeUninit::<evl_sched_attrs>::uninit();
-	let ret: c_int = unsafe { evl_get_schedattr(self.0, attrs.as_mut_ptr()) };
-	match ret {
-	    0 => return Ok(sched::SchedFifo { prio: 2 }),
+    /// Retrieve the current scheduling attributes of the thread.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use revl::thread;
+    /// use revl::sched::Policy;
+    ///
+    /// fn check_sched(t: &thread::Thread) -> bool {
+    ///     matches!(t.sched(), Ok(Policy::Fifo { prio: 42 }))
+    /// }
+    /// ```
+    pub fn sched(&self) -> Result<sched::Policy, Error> {
+        let mut attrs = MaybeUninit::<evl_sched_attrs>::uninit();
+        let ret: c_int = unsafe { evl_get_schedattr(self.0, attrs.as_mut_ptr()) };
+        match ret {
+            0 => return Ok(sched::SchedAttrs(unsafe { attrs.assume_init() }).to_policy()),
             _ => return Err(Error::from_raw_os_error(-ret)),
-	}
+        }
+    }
+    /// Retrieve the current state and runtime statistics of the
+    /// target thread.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use revl::thread;
+    ///
+    /// fn count_demotions(t: &thread::Thread) -> Result<u32, revl::Error> {
+    ///     Ok(t.info()?.inband_switches)
+    /// }
+    /// ```
+    pub fn info(&self) -> Result<ThreadInfo, Error> {
+        let mut statebuf = MaybeUninit::<evl_thread_state>::uninit();
+        let ret: c_int = unsafe { evl_get_state(self.0, statebuf.as_mut_ptr()) };
+        match ret {
+            0 => {
+                let state = unsafe { statebuf.assume_init() };
+                return Ok(ThreadInfo {
+                    policy: state.eattrs.sched_policy,
+                    priority: state.eattrs.sched_priority,
+                    cpu: state.cpu,
+                    state: state.state as u32,
+                    inband_switches: state.isw as u32,
+                    context_switches: state.csw as u32,
+                    syscalls: state.sc as u32,
+                    remote_wakeups: state.rwa as u32,
+                    exec_time: state.xtime as u64,
+                });
+            },
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        }
+    }
+    /// Retrieve the CPU time consumed by the target thread since it
+    /// attached to the EVL core, accounting for out-of-band execution
+    /// only.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use revl::thread;
+    ///
+    /// fn check_budget(t: &thread::Thread, budget: std::time::Duration) -> bool {
+    ///     t.cpu_time().map_or(false, |used| used <= budget)
+    /// }
+    /// ```
+    pub fn cpu_time(&self) -> Result<Duration, Error> {
+        Ok(Duration::from_nanos(self.info()?.exec_time))
+    }
+}
+
+impl AsRawFd for Thread {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+impl AsFd for Thread {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.0) }
+    }
+}
+
+impl Drop for Thread {
+    fn drop(&mut self) {
+        match self.1 {
+            Ownership::Attachment => {
+                if self.is_self() {
+                    unsafe {
+                        evl_detach_self();
+                    }
+                }
+            },
+            Ownership::Fd => {
+                unsafe {
+                    libc::close(self.0);
+                }
+            },
+        }
     }
 }
@@ -0,0 +1,280 @@
+//! Stage switch diagnostics.
+//!
+//! The EVL core may notify a thread about unexpected conditions such
+//! as an inadvertent demotion to the in-band stage or a locking
+//! inconsistency by sending it the SIGDEBUG signal, with a cause code
+//! attached. Which conditions trigger a notification is controlled
+//! by the [mode bits][`crate::thread::ThreadMode`] of the thread. See
+//! [this document](https://evlproject.org/core/user-api/thread/#health-monitoring)
+//! for details.
+//!
+//! This module installs a handler for SIGDEBUG, which records the
+//! cause of each notification, leaving it to a dispatcher thread to
+//! deliver a [`Report`] to a user-defined callback. SIGXCPU signals
+//! which do not come from the EVL core are passed on to the handler
+//! installed previously.
+
+use std::fmt;
+use std::io::ErrorKind;
+use std::mem;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicI32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::sync::mpsc;
+use std::thread;
+use crate::Error;
+
+/// The signal the EVL core uses for notifying diagnostics.
+pub const SIGDEBUG: c_int = libc::SIGXCPU;
+
+/// The cause of a SIGDEBUG notification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cause {
+    /// The thread was demoted to in-band upon receiving a signal.
+    SignalDemotion,
+    /// The thread was demoted to in-band by issuing an in-band
+    /// system call.
+    SyscallDemotion,
+    /// The thread was demoted to in-band upon a CPU exception
+    /// (e.g. page fault).
+    FaultDemotion,
+    /// The watchdog fired on a runaway thread.
+    Watchdog,
+    /// The thread slept on a mutex held by an in-band thread.
+    LockDependency,
+    /// The thread released a mutex it did not hold, or left a mutex
+    /// locked on return to user space.
+    LockImbalance,
+    /// The thread went to sleep while holding a mutex.
+    LockSleep,
+    /// The thread accessed an out-of-band only resource while
+    /// running in-band.
+    StageExclusion,
+    /// Unknown cause code.
+    Unknown(i32),
+}
+
+impl Cause {
+    pub(crate) fn from_raw(code: i32) -> Self {
+        match code {
+            1 => Cause::SignalDemotion,
+            2 => Cause::SyscallDemotion,
+            3 => Cause::FaultDemotion,
+            4 => Cause::Watchdog,
+            5 => Cause::LockDependency,
+            6 => Cause::LockImbalance,
+            7 => Cause::LockSleep,
+            8 => Cause::StageExclusion,
+            _ => Cause::Unknown(code),
+        }
+    }
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Cause::SignalDemotion => write!(f, "switched in-band (signal)"),
+            Cause::SyscallDemotion => write!(f, "switched in-band (syscall)"),
+            Cause::FaultDemotion => write!(f, "switched in-band (fault)"),
+            Cause::Watchdog => write!(f, "watchdog triggered"),
+            Cause::LockDependency => write!(f, "mutex owner is in-band"),
+            Cause::LockImbalance => write!(f, "mutex lock/unlock imbalance"),
+            Cause::LockSleep => write!(f, "sleeping while holding a mutex"),
+            Cause::StageExclusion => write!(f, "stage exclusion"),
+            Cause::Unknown(code) => write!(f, "unknown cause ({})", code),
+        }
+    }
+}
+
+/// A diagnostic report for a SIGDEBUG notification.
+#[derive(Debug)]
+pub struct Report {
+    /// The decoded cause.
+    pub cause: Cause,
+    /// The kernel task id of the offending thread.
+    pub tid: i32,
+}
+
+type Callback = Box<dyn Fn(Report) + Send + Sync>;
+
+static CALLBACK: OnceLock<Callback> = OnceLock::new();
+
+// The EVL core tags the signal value of its notifications.
+const SIGDEBUG_MARKER: u32 = 0xfccf0000;
+const SIGDEBUG_MARKER_MASK: u32 = 0xffff0000;
+const SIGDEBUG_CAUSE_MASK: u32 = 0xff;
+
+// The signal handler may not allocate nor lock, so the pending
+// notifications are recorded into a fixed set of slots, each one
+// holding the cause code in the upper half and the thread id in the
+// lower half, zero if free. Notifications are dropped if all slots
+// are busy.
+const NR_PENDING: usize = 64;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const FREE_SLOT: AtomicU64 = AtomicU64::new(0);
+
+static PENDING: [AtomicU64; NR_PENDING] = [FREE_SLOT; NR_PENDING];
+static NEXT_SLOT: AtomicUsize = AtomicUsize::new(0);
+// The eventfd kicking the dispatcher thread.
+static KICK_FD: AtomicI32 = AtomicI32::new(-1);
+// The handler SIGDEBUG was bound to before ours.
+static PREVIOUS: OnceLock<libc::sigaction> = OnceLock::new();
+
+fn record(code: u32, tid: i32) {
+    let value = (code as u64) << 32 | tid as u32 as u64;
+    let first = NEXT_SLOT.fetch_add(1, Ordering::Relaxed);
+    for n in 0..NR_PENDING {
+        let slot = &PENDING[(first + n) % NR_PENDING];
+        if slot.compare_exchange(0, value, Ordering::Release, Ordering::Relaxed).is_ok() {
+            let one: u64 = 1;
+            unsafe {
+                libc::write(KICK_FD.load(Ordering::Acquire),
+                            &one as *const u64 as *const c_void, mem::size_of::<u64>());
+            }
+            return;
+        }
+    }
+}
+
+// Pass a foreign signal on to the previous handler.
+unsafe fn chain(sig: c_int, si: *mut libc::siginfo_t, ctx: *mut c_void) {
+    let previous = match PREVIOUS.get() {
+        Some(previous) => previous,
+        None => return,
+    };
+    match previous.sa_sigaction {
+        libc::SIG_IGN => (),
+        libc::SIG_DFL => {
+            // Let the default action take place.
+            libc::signal(sig, libc::SIG_DFL);
+            libc::raise(sig);
+        },
+        handler if previous.sa_flags & libc::SA_SIGINFO != 0 => {
+            let handler: extern "C" fn(c_int, *mut libc::siginfo_t, *mut c_void) =
+                mem::transmute(handler);
+            handler(sig, si, ctx);
+        },
+        handler => {
+            let handler: extern "C" fn(c_int) = mem::transmute(handler);
+            handler(sig);
+        },
+    }
+}
+
+extern "C" fn sigdebug_handler(sig: c_int, si: *mut libc::siginfo_t, ctx: *mut c_void) {
+    // The marker and cause code are passed as the signal value.
+    let value = unsafe { (*si).si_value().sival_ptr as usize as u32 };
+    if value & SIGDEBUG_MARKER_MASK != SIGDEBUG_MARKER {
+        unsafe { chain(sig, si, ctx) };
+        return;
+    }
+    // Only async-signal-safe services from here on, which must not
+    // clobber errno for the interrupted code.
+    let errno = unsafe { *libc::__errno_location() };
+    let tid = unsafe { libc::syscall(libc::SYS_gettid) } as i32;
+    record(value & SIGDEBUG_CAUSE_MASK, tid);
+    unsafe { *libc::__errno_location() = errno };
+}
+
+// Deliver the recorded notifications to the callback.
+fn dispatch(kick_fd: c_int, callback: &Callback) {
+    loop {
+        let mut count: u64 = 0;
+        let ret = unsafe {
+            libc::read(kick_fd, &mut count as *mut u64 as *mut c_void, mem::size_of::<u64>())
+        };
+        if ret < 0 && Error::last_os_error().kind() != ErrorKind::Interrupted {
+            return;
+        }
+        for slot in PENDING.iter() {
+            let value = slot.swap(0, Ordering::Acquire);
+            if value != 0 {
+                callback(Report {
+                    cause: Cause::from_raw((value >> 32) as i32),
+                    tid: value as u32 as i32,
+                });
+            }
+        }
+    }
+}
+
+/// Install a SIGDEBUG handler delivering a [`Report`] to `callback`.
+///
+/// The signal handler only records the notification, the callback
+/// runs later from a regular dispatcher thread this call spawns.
+/// Notifications may be dropped if they come in faster than the
+/// callback processes them. The handler can be installed only once
+/// per process.
+///
+/// # Errors
+///
+/// * [`AlreadyExists`][`std::io::ErrorKind`] means that a handler
+/// was installed already.
+///
+/// * Otherwise, the dispatcher thread or the eventfd kicking it
+/// could not be created, or the handler could not be installed.
+///
+/// # Examples
+///
+/// ```no_run
+/// use revl::thread::debug;
+///
+/// debug::install(|report| {
+///     eprintln!("thread {}: {}", report.tid, report.cause);
+/// }).expect("cannot install SIGDEBUG handler");
+/// ```
+pub fn install<F>(callback: F) -> Result<(), Error>
+where F: Fn(Report) + Send + Sync + 'static
+{
+    if CALLBACK.set(Box::new(callback)).is_err() {
+        return Err(Error::new(ErrorKind::AlreadyExists,
+                              "SIGDEBUG handler already installed"));
+    }
+    let kick_fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+    if kick_fd < 0 {
+        return Err(Error::last_os_error());
+    }
+    KICK_FD.store(kick_fd, Ordering::Release);
+    thread::Builder::new()
+        .name("sigdebug".to_string())
+        .spawn(move || dispatch(kick_fd, CALLBACK.get().unwrap()))?;
+    let ret: c_int = unsafe {
+        let mut previous: libc::sigaction = mem::zeroed();
+        libc::sigaction(SIGDEBUG, ptr::null(), &mut previous);
+        let _ = PREVIOUS.set(previous);
+        let mut sa: libc::sigaction = mem::zeroed();
+        sa.sa_sigaction = sigdebug_handler as usize;
+        sa.sa_flags = libc::SA_SIGINFO;
+        libc::sigemptyset(&mut sa.sa_mask);
+        libc::sigaction(SIGDEBUG, &sa, ptr::null_mut())
+    };
+    match ret {
+        0 => return Ok(()),
+        _ => return Err(Error::last_os_error()),
+    }
+}
+
+/// Install a SIGDEBUG handler delivering each [`Report`] to the
+/// returned channel. See [`install()`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use revl::thread::debug;
+///
+/// let reports = debug::channel().expect("cannot install SIGDEBUG handler");
+/// std::thread::spawn(move || {
+///     for report in reports {
+///         eprintln!("thread {}: {}", report.tid, report.cause);
+///     }
+/// });
+/// ```
+pub fn channel() -> Result<mpsc::Receiver<Report>, Error> {
+    let (tx, rx) = mpsc::sync_channel::<Report>(NR_PENDING);
+    install(move |report| {
+        let _ = tx.send(report);
+    })?;
+    Ok(rx)
+}
@@ -0,0 +1,162 @@
+// Health monitoring through the observable channel of a thread.
+
+use std::fmt;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use embedded_time::Instant;
+use crate::clock::CoreClock;
+use crate::observable::{self, Notification};
+use crate::thread::debug::Cause;
+use crate::thread::{Thread, ThreadMode};
+use crate::Error;
+
+// Diagnostics pending for the monitor before some are lost.
+const HM_BACKLOG: u32 = 32;
+
+/// A health monitoring event, decoded from the notifications the
+/// core sends through the observable channel of a thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthEvent {
+    /// The thread was demoted to in-band upon receiving `signal`.
+    SignalDemotion { signal: i32 },
+    /// The thread was demoted to in-band by issuing the in-band
+    /// system call number `syscall`.
+    SyscallDemotion { syscall: i64 },
+    /// The thread was demoted to in-band upon the CPU exception
+    /// number `trap`, e.g. a page fault.
+    FaultDemotion { trap: i32 },
+    /// The watchdog fired on the runaway thread.
+    Watchdog,
+    /// The thread slept on a mutex held by an in-band thread.
+    LockDependency,
+    /// The thread released a mutex it did not hold, or left a mutex
+    /// locked on return to user space.
+    LockImbalance,
+    /// The thread went to sleep while holding a mutex.
+    LockSleep,
+    /// The thread accessed an out-of-band only resource while
+    /// running in-band.
+    StageExclusion,
+    /// Unknown diagnostic code, with its value.
+    Unknown { code: u32, value: i64 },
+}
+
+impl HealthEvent {
+    fn decode(nf: &Notification) -> Self {
+        match Cause::from_raw(nf.tag() as i32) {
+            Cause::SignalDemotion => HealthEvent::SignalDemotion { signal: nf.value() as i32 },
+            Cause::SyscallDemotion => HealthEvent::SyscallDemotion { syscall: nf.value() },
+            Cause::FaultDemotion => HealthEvent::FaultDemotion { trap: nf.value() as i32 },
+            Cause::Watchdog => HealthEvent::Watchdog,
+            Cause::LockDependency => HealthEvent::LockDependency,
+            Cause::LockImbalance => HealthEvent::LockImbalance,
+            Cause::LockSleep => HealthEvent::LockSleep,
+            Cause::StageExclusion => HealthEvent::StageExclusion,
+            Cause::Unknown(_) => HealthEvent::Unknown { code: nf.tag(), value: nf.value() },
+        }
+    }
+    /// Return true if the event denotes a demotion to the in-band
+    /// stage.
+    pub fn is_demotion(&self) -> bool {
+        matches!(self,
+                 HealthEvent::SignalDemotion { .. } |
+                 HealthEvent::SyscallDemotion { .. } |
+                 HealthEvent::FaultDemotion { .. })
+    }
+}
+
+impl fmt::Display for HealthEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HealthEvent::SignalDemotion { signal } =>
+                write!(f, "switched in-band (signal {})", signal),
+            HealthEvent::SyscallDemotion { syscall } =>
+                write!(f, "switched in-band (syscall {})", syscall),
+            HealthEvent::FaultDemotion { trap } =>
+                write!(f, "switched in-band (fault {})", trap),
+            HealthEvent::Unknown { code, value } =>
+                write!(f, "unknown diagnostic ({}, {})", code, value),
+            HealthEvent::Watchdog => fmt::Display::fmt(&Cause::Watchdog, f),
+            HealthEvent::LockDependency => fmt::Display::fmt(&Cause::LockDependency, f),
+            HealthEvent::LockImbalance => fmt::Display::fmt(&Cause::LockImbalance, f),
+            HealthEvent::LockSleep => fmt::Display::fmt(&Cause::LockSleep, f),
+            HealthEvent::StageExclusion => fmt::Display::fmt(&Cause::StageExclusion, f),
+        }
+    }
+}
+
+/// A health monitoring event, with the date it was issued.
+#[derive(Debug)]
+pub struct HealthReport {
+    /// The decoded event.
+    pub event: HealthEvent,
+    /// The date the core issued the event, based on the monotonic
+    /// clock.
+    pub date: Instant<CoreClock>,
+}
+
+/// Receives the health monitoring events of an observable thread.
+///
+/// The target thread must have been created
+/// [observable](crate::thread::Builder::observable). The calling
+/// thread, which must be attached to the core, subscribes to its
+/// observable channel, then typically runs a supervisory loop
+/// receiving the events.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::os::fd::AsFd;
+/// use revl::thread::{self, HealthMonitor, Thread, ThreadMode};
+///
+/// let me = thread::Builder::new().name("worker").observable().attach().unwrap();
+/// let target = Thread::from_owned_fd(me.as_fd().try_clone_to_owned().unwrap());
+/// thread::Builder::new().name("supervisor").spawn(move |_| {
+///     let monitor = HealthMonitor::new(&target, ThreadMode::WOSS | ThreadMode::WOLI).unwrap();
+///     monitor.run(|report| eprintln!("worker: {}", report.event)).unwrap();
+/// }).unwrap();
+/// // Real-time work follows.
+/// ```
+pub struct HealthMonitor {
+    // Our own reference on the target, which may be released by its
+    // owner while we monitor it.
+    target: OwnedFd,
+}
+
+impl HealthMonitor {
+    /// Enable the diagnostics selected by `mode` for `target`,
+    /// routing them to its observable channel, then subscribe the
+    /// calling thread to the latter.
+    pub fn new(target: &Thread, mode: ThreadMode) -> Result<Self, Error> {
+        let fd = unsafe { libc::dup(target.as_raw_fd()) };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        let target_fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        target.set_mode(mode | ThreadMode::HMOBS)?;
+        observable::subscribe(&target_fd, HM_BACKLOG, false)?;
+        Ok(Self { target: target_fd })
+    }
+    /// Wait for the next event.
+    pub fn next_report(&self) -> Result<HealthReport, Error> {
+        let nf = observable::read(&self.target)?;
+        Ok(HealthReport {
+            event: HealthEvent::decode(&nf),
+            date: nf.date(),
+        })
+    }
+    /// Deliver the events to `callback` as they are received. This
+    /// only returns upon error.
+    pub fn run<F>(&self, mut callback: F) -> Result<(), Error>
+    where F: FnMut(HealthReport)
+    {
+        loop {
+            callback(self.next_report()?);
+        }
+    }
+}
+
+impl Drop for HealthMonitor {
+    fn drop(&mut self) {
+        let _ = observable::unsubscribe(&self.target);
+    }
+}
@@ -0,0 +1,166 @@
+//! Timer interface.
+//!
+//! An EVL timer is a file descriptor which expires at a given date
+//! on one of the [core clocks](crate::clock), once or periodically.
+//! A thread waits for the next expiry with [`Timer::wait()`], which
+//! also reports the expiries it missed, making timers the basic
+//! building block for periodic processing.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use revl::clock::STEADY_CLOCK;
+//! use revl::timer::Timer;
+//!
+//! let timer = Timer::new(&STEADY_CLOCK).unwrap();
+//! timer.start_after(Duration::from_millis(1), Some(Duration::from_micros(250))).unwrap();
+//! loop {
+//!     let overruns = timer.wait().unwrap();
+//!     if overruns > 0 {
+//!         eprintln!("missed {} periods", overruns);
+//!     }
+//!     // Periodic work follows.
+//! }
+//! ```
+
+use std::ffi::c_void;
+use std::mem;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::os::raw::c_int;
+use std::ptr;
+use std::time::Duration;
+use libc::{c_long, time_t};
+use embedded_time::Instant;
+use evl_sys::{
+    evl_get_timer,
+    evl_new_timer,
+    evl_set_timer,
+    itimerspec,
+    oob_read,
+    timespec,
+    BuiltinClock,
+};
+use crate::clock::{instant_to_timespec, CoreClock};
+use crate::Error;
+
+fn duration_to_timespec(d: Duration) -> timespec {
+    timespec {
+        tv_sec: d.as_secs() as time_t,
+        tv_nsec: d.subsec_nanos() as c_long,
+    }
+}
+
+fn timespec_to_duration(ts: &timespec) -> Duration {
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+const TIMESPEC_ZERO: timespec = timespec {
+    tv_sec: 0,
+    tv_nsec: 0,
+};
+
+pub struct Timer {
+    efd: c_int,
+    clock: BuiltinClock,
+}
+
+unsafe impl Send for Timer {}
+unsafe impl Sync for Timer {}
+
+impl Timer {
+    /// Create a disarmed timer based on `clock`.
+    pub fn new(clock: &CoreClock) -> Result<Self, Error> {
+        let ret: c_int = unsafe { evl_new_timer(clock.0 as c_int) };
+        match ret {
+            0.. => return Ok(Self { efd: ret, clock: clock.0 }),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    fn set(&self, value: timespec, interval: Option<Duration>) -> Result<(), Error> {
+        let its = itimerspec {
+            it_value: value,
+            it_interval: interval.map_or(TIMESPEC_ZERO, duration_to_timespec),
+        };
+        let ret: c_int = unsafe { evl_set_timer(self.efd, &its, ptr::null_mut()) };
+        match ret {
+            0 => return Ok(()),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    /// Arm the timer to expire at the absolute `date`, then every
+    /// `interval` if some, which makes the timer periodic. The timer
+    /// is one-shot otherwise. Any previous setting is overridden.
+    ///
+    /// # Errors
+    ///
+    /// [`InvalidInput`][`std::io::ErrorKind`] means that `interval`
+    /// is zero or too short for the core.
+    pub fn start_at(&self, date: Instant<CoreClock>, interval: Option<Duration>) -> Result<(), Error> {
+        self.set(instant_to_timespec(date), interval)
+    }
+    /// Arm the timer to expire after `delay`, then every `interval`
+    /// if some. See [`start_at()`](Self::start_at).
+    pub fn start_after(&self, delay: Duration, interval: Option<Duration>) -> Result<(), Error> {
+        let now = CoreClock(self.clock).now();
+        let date = now.duration_since_epoch().integer() + delay.as_nanos() as u64;
+        self.start_at(Instant::new(date), interval)
+    }
+    /// Disarm the timer. A thread waiting for the timer keeps
+    /// waiting until the timer is armed again.
+    pub fn stop(&self) -> Result<(), Error> {
+        self.set(TIMESPEC_ZERO, None)
+    }
+    /// Return the time remaining until the next expiry, or `None` if
+    /// the timer is disarmed, along with the interval of a periodic
+    /// timer.
+    pub fn remaining(&self) -> Result<(Option<Duration>, Option<Duration>), Error> {
+        let mut its: itimerspec = unsafe { mem::zeroed() };
+        let ret: c_int = unsafe { evl_get_timer(self.efd, &mut its) };
+        match ret {
+            0 => {
+                let value = timespec_to_duration(&its.it_value);
+                let interval = timespec_to_duration(&its.it_interval);
+                return Ok((
+                    (!value.is_zero()).then_some(value),
+                    (!interval.is_zero()).then_some(interval),
+                ));
+            },
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    /// Wait for the next expiry, returning the number of expiries
+    /// which were missed since the previous call, i.e. the overrun
+    /// count. This is always zero with a one-shot timer. This must be
+    /// called from the out-of-band stage.
+    pub fn wait(&self) -> Result<u64, Error> {
+        let mut ticks: u64 = 0;
+        let ret = unsafe {
+            oob_read(self.efd,
+                     &mut ticks as *mut u64 as *mut c_void,
+                     mem::size_of::<u64>())
+        };
+        match ret {
+            0.. => return Ok(ticks.saturating_sub(1)),
+            _ => return Err(Error::last_os_error()),
+        };
+    }
+}
+
+impl AsRawFd for Timer {
+    fn as_raw_fd(&self) -> RawFd {
+        self.efd
+    }
+}
+
+impl AsFd for Timer {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.efd) }
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.efd);
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! [`tracing`] layer timestamping with the EVL clock (`tracing`
+//! feature).
+//!
+//! [`RtLayer`] records the events and span transitions observed by a
+//! `tracing` subscriber into a lock-free buffer private to the
+//! emitting thread, timestamped by the EVL monotonic clock. An
+//! [`Exporter`] drains those buffers from the in-band stage, e.g.
+//! periodically from a housekeeping thread, so that the execution of
+//! out-of-band threads can be correlated with the traces of the rest
+//! of the application, without delaying them.
+//!
+//! Each thread gets its own buffer of 128 records on its first trace,
+//! which requires in-band services. Records are dropped if the buffer
+//! of the thread is full, the count of such losses is available from
+//! [`Exporter::lost()`]. Event messages longer than
+//! [`LINE_MAX`](crate::print::LINE_MAX) bytes are truncated. A single
+//! layer should be installed per process.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use tracing_subscriber::prelude::*;
+//!
+//! let (layer, exporter) = revl::trace::layer();
+//! tracing_subscriber::registry().with(layer).init();
+//!
+//! std::thread::spawn(move || loop {
+//!     exporter.drain_to(&mut std::io::stdout()).unwrap();
+//!     std::thread::sleep(Duration::from_millis(100));
+//! });
+//!
+//! // From any thread, including out-of-band ones:
+//! let _cycle = tracing::info_span!("cycle", n = 42).entered();
+//! tracing::debug!(latency_ns = 2400, "sampled");
+//! ```
+
+use std::cell::RefCell;
+use std::fmt::{self, Write as _};
+use std::io::{self, Write};
+use std::str;
+use std::sync::{
+    Arc,
+    Mutex,
+    atomic::AtomicU32,
+    atomic::AtomicU64,
+    atomic::Ordering::Relaxed,
+};
+use embedded_time::Instant;
+use tracing::{
+    field::{Field, Visit},
+    span,
+    Event,
+    Level,
+    Metadata,
+    Subscriber,
+};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+use crate::channel::RecvError;
+use crate::clock::{CoreClock, STEADY_CLOCK};
+use crate::print::LineBuf;
+use crate::spsc::{self, Consumer, Producer};
+
+const BUFFER_ORDER: usize = 7;
+
+/// What a [`TraceRecord`] describes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// An event was emitted.
+    Event,
+    /// A span was entered.
+    Enter,
+    /// A span was exited.
+    Exit,
+}
+
+/// A timestamped trace, as collected by [`RtLayer`].
+#[derive(Clone, Copy)]
+pub struct TraceRecord {
+    timestamp: u64,
+    thread: u32,
+    kind: Kind,
+    metadata: &'static Metadata<'static>,
+    message: LineBuf,
+}
+
+impl TraceRecord {
+    /// Return the date of the trace, based on the monotonic clock.
+    pub fn timestamp(&self) -> Instant<CoreClock> {
+        Instant::new(self.timestamp)
+    }
+    /// Return the index of the thread which emitted the trace, in
+    /// order of first trace.
+    pub fn thread(&self) -> u32 {
+        self.thread
+    }
+    /// Return the kind of trace.
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+    /// Return the level of the event or span.
+    pub fn level(&self) -> Level {
+        *self.metadata.level()
+    }
+    /// Return the name of the event or span.
+    pub fn name(&self) -> &'static str {
+        self.metadata.name()
+    }
+    /// Return the target of the event or span.
+    pub fn target(&self) -> &'static str {
+        self.metadata.target()
+    }
+    /// Return the formatted fields of an event, which is empty for
+    /// span transitions.
+    pub fn message(&self) -> &str {
+        let bytes = self.message.as_bytes();
+        // Truncation may have split a character.
+        match str::from_utf8(bytes) {
+            Ok(s) => s,
+            Err(e) => unsafe { str::from_utf8_unchecked(&bytes[..e.valid_up_to()]) },
+        }
+    }
+}
+
+impl fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{:09} [{}] {:<5} {}: ",
+               self.timestamp / 1_000_000_000,
+               self.timestamp % 1_000_000_000,
+               self.thread, self.level(), self.target())?;
+        match self.kind {
+            Kind::Event => write!(f, "{}", self.message()),
+            Kind::Enter => write!(f, "-> {}", self.name()),
+            Kind::Exit => write!(f, "<- {}", self.name()),
+        }
+    }
+}
+
+// Format the fields of an event.
+struct Visitor<'a>(&'a mut LineBuf);
+
+impl<'a> Visit for Visitor<'a> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let _ = if field.name() == "message" {
+            write!(self.0, "{:?} ", value)
+        } else {
+            write!(self.0, "{}={:?} ", field.name(), value)
+        };
+    }
+    fn record_str(&mut self, field: &Field, value: &str) {
+        let _ = if field.name() == "message" {
+            write!(self.0, "{} ", value)
+        } else {
+            write!(self.0, "{}={} ", field.name(), value)
+        };
+    }
+}
+
+struct Shared {
+    buffers: Mutex<Vec<Consumer<TraceRecord>>>,
+    nr_threads: AtomicU32,
+    lost: AtomicU64,
+}
+
+thread_local! {
+    static PRODUCER: RefCell<Option<(u32, Producer<TraceRecord>)>> = RefCell::new(None);
+}
+
+/// A `tracing` layer recording into per-thread lock-free buffers,
+/// see the [module documentation](crate::trace).
+pub struct RtLayer {
+    shared: Arc<Shared>,
+}
+
+impl RtLayer {
+    fn record(&self, kind: Kind, metadata: &'static Metadata<'static>, message: LineBuf) {
+        let timestamp = STEADY_CLOCK.now().duration_since_epoch().integer();
+        let _ = PRODUCER.try_with(|producer| {
+            let mut producer = producer.borrow_mut();
+            let (thread, tx) = producer.get_or_insert_with(|| {
+                let (tx, rx) = spsc::create::<TraceRecord, BUFFER_ORDER>();
+                if let Ok(mut buffers) = self.shared.buffers.lock() {
+                    buffers.push(rx);
+                }
+                (self.shared.nr_threads.fetch_add(1, Relaxed), tx)
+            });
+            let record = TraceRecord { timestamp, thread: *thread, kind, metadata, message };
+            if tx.send(record).is_err() {
+                self.shared.lost.fetch_add(1, Relaxed);
+            }
+        });
+    }
+}
+
+impl<S> Layer<S> for RtLayer
+where S: Subscriber + for<'a> LookupSpan<'a>
+{
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut message = LineBuf::new();
+        event.record(&mut Visitor(&mut message));
+        self.record(Kind::Event, event.metadata(), message);
+    }
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            self.record(Kind::Enter, span.metadata(), LineBuf::new());
+        }
+    }
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            self.record(Kind::Exit, span.metadata(), LineBuf::new());
+        }
+    }
+}
+
+/// Collects the records of an [`RtLayer`] from the in-band stage.
+pub struct Exporter {
+    shared: Arc<Shared>,
+}
+
+impl Exporter {
+    /// Pass all pending records to `f`, returning their count. The
+    /// records are delivered thread after thread, in chronological
+    /// order for each thread. The buffers of the threads which exited
+    /// are released once drained.
+    pub fn drain<F: FnMut(&TraceRecord)>(&self, mut f: F) -> usize {
+        let mut count = 0;
+        if let Ok(mut buffers) = self.shared.buffers.lock() {
+            buffers.retain(|rx| {
+                loop {
+                    match rx.recv() {
+                        Ok(record) => {
+                            f(&record);
+                            count += 1;
+                        },
+                        Err(RecvError::Disconnected) => return false,
+                        Err(_) => return true,
+                    }
+                }
+            });
+        }
+        count
+    }
+    /// Write all pending records to `w`, one per line, returning
+    /// their count. See [`drain()`](Self::drain).
+    pub fn drain_to<W: Write>(&self, w: &mut W) -> io::Result<usize> {
+        let mut ret = Ok(());
+        let count = self.drain(|record| {
+            if ret.is_ok() {
+                ret = writeln!(w, "{}", record);
+            }
+        });
+        ret.map(|_| count)
+    }
+    /// Return the number of records dropped because the buffer of
+    /// the emitting thread was full.
+    pub fn lost(&self) -> u64 {
+        self.shared.lost.load(Relaxed)
+    }
+}
+
+/// Create a layer to install into a `tracing` subscriber, and the
+/// exporter collecting its records.
+pub fn layer() -> (RtLayer, Exporter) {
+    let shared = Arc::new(Shared {
+        buffers: Mutex::new(Vec::new()),
+        nr_threads: AtomicU32::new(0),
+        lost: AtomicU64::new(0),
+    });
+    (RtLayer { shared: shared.clone() }, Exporter { shared })
+}
@@ -0,0 +1,221 @@
+//! Single value channel retaining the latest state.
+//!
+//! A watch channel holds a single value which the [`Sender`]
+//! overwrites, e.g. a setpoint or a configuration snapshot a
+//! supervisor distributes to many real-time threads. Receivers do
+//! not queue updates: they read the latest value, and may wait for
+//! it to change. Every update bumps a version number, which each
+//! [`Receiver`] compares to the last version it has seen.
+//!
+//! Checking for a change only reads an atomic counter, waiting for
+//! one sleeps on an EVL condition variable, so that both can be done
+//! from the out-of-band stage.
+//!
+//! ```no_run
+//! use revl::watch;
+//!
+//! let (tx, rx) = watch::channel(20.0f32).unwrap();
+//! for _ in 0..4 {
+//!     let rx = rx.clone();
+//!     std::thread::spawn(move || loop {
+//!         rx.changed().unwrap();
+//!         let setpoint = *rx.borrow_and_update().unwrap();
+//!         println!("new setpoint {}", setpoint);
+//!     });
+//! }
+//! tx.send(21.5).unwrap();
+//! ```
+
+use std::cell::Cell;
+use std::io::ErrorKind;
+use std::mem;
+use std::ops::Deref;
+use std::sync::{
+    Arc,
+    atomic::AtomicU64,
+    atomic::Ordering::Acquire,
+    atomic::Ordering::Release,
+};
+use std::time::Duration;
+use crate::condvar::{self, CondvarPair};
+use crate::mutex::{MappedMutexGuard, MutexGuard};
+use crate::Error;
+
+struct Slot<T> {
+    value: T,
+    version: u64,
+    closed: bool,
+}
+
+struct Shared<T> {
+    slot: CondvarPair<Slot<T>>,
+    // Mirrors the slot version, for lockless change detection.
+    version: AtomicU64,
+}
+
+/// The sending half of a watch channel. Receivers waiting for a
+/// change are woken up with an error when it is dropped.
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Replace the value, notifying all receivers.
+    pub fn send(&self, value: T) -> Result<(), Error> {
+        self.send_replace(value).map(|_| ())
+    }
+    /// Replace the value, notifying all receivers, returning the
+    /// previous value.
+    pub fn send_replace(&self, value: T) -> Result<T, Error> {
+        self.send_modify(|v| mem::replace(v, value))
+    }
+    /// Modify the value in place with `f`, notifying all receivers.
+    /// This saves a copy for large values.
+    pub fn send_modify<F, R>(&self, f: F) -> Result<R, Error>
+    where F: FnOnce(&mut T) -> R
+    {
+        let mut slot = self.shared.slot.lock()?;
+        let ret = f(&mut slot.value);
+        slot.version += 1;
+        self.shared.version.store(slot.version, Release);
+        drop(slot);
+        self.shared.slot.notify_all()?;
+        Ok(ret)
+    }
+    /// Return a read-only view of the current value. The channel is
+    /// locked until the view is dropped.
+    pub fn borrow(&self) -> Result<Ref<'_, T>, Error> {
+        Ok(Ref(MutexGuard::map(self.shared.slot.lock()?, |s| &mut s.value)))
+    }
+    /// Create a new receiver, which considers the current value as
+    /// seen.
+    pub fn subscribe(&self) -> Receiver<T> {
+        Receiver {
+            shared: self.shared.clone(),
+            seen: Cell::new(self.shared.version.load(Acquire)),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if let Ok(mut slot) = self.shared.slot.lock() {
+            slot.closed = true;
+        }
+        let _ = self.shared.slot.notify_all();
+    }
+}
+
+/// The receiving half of a watch channel, which can be cloned for
+/// multiple readers. Each receiver tracks the last version it has
+/// seen.
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    seen: Cell<u64>,
+}
+
+impl<T> Receiver<T> {
+    /// Return a read-only view of the current value, without marking
+    /// it as seen. The channel is locked until the view is dropped,
+    /// so it should be held briefly.
+    pub fn borrow(&self) -> Result<Ref<'_, T>, Error> {
+        Ok(Ref(MutexGuard::map(self.shared.slot.lock()?, |s| &mut s.value)))
+    }
+    /// Return a read-only view of the current value, marking it as
+    /// seen.
+    pub fn borrow_and_update(&self) -> Result<Ref<'_, T>, Error> {
+        let slot = self.shared.slot.lock()?;
+        self.seen.set(slot.version);
+        Ok(Ref(MutexGuard::map(slot, |s| &mut s.value)))
+    }
+    /// Return true if the value changed since it was last seen. This
+    /// call never blocks.
+    pub fn has_changed(&self) -> bool {
+        self.shared.version.load(Acquire) != self.seen.get()
+    }
+    /// Wait for the value to change since it was last seen, marking
+    /// the new version as seen. This returns immediately if a change
+    /// is already pending.
+    ///
+    /// # Errors
+    ///
+    /// [`BrokenPipe`][`std::io::ErrorKind`] is returned if the sender
+    /// was dropped, in which case the value will never change.
+    pub fn changed(&self) -> Result<(), Error> {
+        let seen = self.seen.get();
+        let slot = self.shared.slot.lock()?;
+        let slot = self.shared.slot.wait_while(slot, |s| s.version == seen && !s.closed)?;
+        if slot.version == seen {
+            return Err(closed());
+        }
+        self.seen.set(slot.version);
+        Ok(())
+    }
+    /// Wait for the value to change for at most `delay`, see
+    /// [`changed()`](Self::changed).
+    ///
+    /// # Errors
+    ///
+    /// [`TimedOut`][`crate::Error::TimedOut`] is returned if the value
+    /// did not change within the delay.
+    pub fn changed_timed(&self, delay: Duration) -> Result<(), Error> {
+        let seen = self.seen.get();
+        let slot = self.shared.slot.lock()?;
+        let (slot, result) = self.shared.slot.wait_timeout_while(
+            slot, delay, |s| s.version == seen && !s.closed)?;
+        if result.timed_out() {
+            return Err(Error::TimedOut);
+        }
+        if slot.version == seen {
+            return Err(closed());
+        }
+        self.seen.set(slot.version);
+        Ok(())
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            seen: Cell::new(self.seen.get()),
+        }
+    }
+}
+
+/// A read-only view of the value of a watch channel, which keeps the
+/// channel locked.
+pub struct Ref<'a, T>(MappedMutexGuard<'a, T>);
+
+impl<'a, T> Deref for Ref<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+fn closed() -> Error {
+    Error::new(ErrorKind::BrokenPipe, "watch sender dropped")
+}
+
+/// Create a watch channel holding `init`, returning its sending and
+/// receiving halves. The initial value is considered as seen by the
+/// receiver.
+///
+/// # Errors
+///
+/// The EVL mutex and condition variable the channel is based on could
+/// not be created, see [`CondvarPair::new()`].
+pub fn channel<T>(init: T) -> Result<(Sender<T>, Receiver<T>), Error> {
+    let shared = Arc::new(Shared {
+        slot: condvar::Builder::new().create_pair(Slot {
+            value: init,
+            version: 0,
+            closed: false,
+        })?,
+        version: AtomicU64::new(0),
+    });
+    let rx = Receiver { shared: shared.clone(), seen: Cell::new(0) };
+    Ok(( Sender { shared }, rx ))
+}
@@ -0,0 +1,288 @@
+//! Deadline watchdog for periodic threads.
+//!
+//! A [`Watchdog`] supervises a set of real-time threads, each of them
+//! promising to call [`Feeder::feed()`] at least once per time
+//! budget, typically at every cycle of its loop. A monitor thread
+//! checks the heartbeats periodically, applying the [`Policy`] the
+//! thread registered with if it missed its budget: the expiry is
+//! always published to the [observable](crate::observable) returned
+//! by [`Watchdog::events()`] and passed to the expiry callback, the
+//! offending thread may also be demoted to the in-band stage, or the
+//! process aborted.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use revl::thread;
+//! use revl::watchdog::{self, Policy};
+//!
+//! let wd = watchdog::Builder::new()
+//!     .period(Duration::from_millis(1))
+//!     .priority(90)
+//!     .on_expiry(|e| revl::rt_eprintln!("{} missed its deadline by {:?}", e.name, e.late_by))
+//!     .start()
+//!     .unwrap();
+//!
+//! thread::Builder::new().name("control").spawn(move |me| {
+//!     let feeder = wd.register(me, "control", Duration::from_micros(500), Policy::Demote).unwrap();
+//!     loop {
+//!         // One cycle of work.
+//!         feeder.feed();
+//!     }
+//! }).unwrap();
+//! ```
+
+use std::os::fd::AsFd;
+use std::sync::{
+    Arc,
+    atomic::AtomicBool,
+    atomic::AtomicU64,
+    atomic::AtomicUsize,
+    atomic::Ordering::Acquire,
+    atomic::Ordering::Relaxed,
+    atomic::Ordering::Release,
+};
+use std::time::Duration;
+use embedded_time::Instant;
+use crate::clock::STEADY_CLOCK;
+use crate::mutex::{self, Mutex};
+use crate::observable::{self, Notice, Observable};
+use crate::sched::SchedFifo;
+use crate::thread::{self, JoinHandle, Thread};
+use crate::Error;
+
+/// The tag of the notices published on expiry, the value of which
+/// is the index of the offending thread in order of registration.
+pub const NOTICE_EXPIRY: u32 = observable::NOTICE_USER;
+
+/// What to do when a thread misses its budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// Only notify the expiry.
+    Notify,
+    /// Demote the thread to the in-band stage, see
+    /// [`Thread::demote()`].
+    Demote,
+    /// Abort the process.
+    Abort,
+}
+
+/// The description of an expiry, passed to the expiry callback.
+#[derive(Debug, Clone, Copy)]
+pub struct Expiry {
+    /// The name the thread registered with.
+    pub name: &'static str,
+    /// The index of the thread, in order of registration.
+    pub index: usize,
+    /// How late the heartbeat was when the expiry was detected.
+    pub late_by: Duration,
+    /// The policy which was applied.
+    pub policy: Policy,
+}
+
+type Callback = Box<dyn Fn(&Expiry) + Send + Sync>;
+
+struct Entry {
+    name: &'static str,
+    index: usize,
+    budget: u64,
+    policy: Policy,
+    thread: Thread,
+    // Date of the last heartbeat, in nanoseconds.
+    last_feed: AtomicU64,
+    // Set once the expiry was handled, until the next heartbeat.
+    tripped: AtomicBool,
+    // Set when the feeder is dropped.
+    retired: AtomicBool,
+}
+
+struct Inner {
+    entries: Mutex<Vec<Arc<Entry>>>,
+    registered: AtomicUsize,
+    events: Observable,
+    callback: Option<Callback>,
+    stop: AtomicBool,
+}
+
+pub struct Builder {
+    period: Duration,
+    priority: Option<i32>,
+    callback: Option<Callback>,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            period: Duration::from_millis(10),
+            priority: None,
+            callback: None,
+        }
+    }
+    /// Set the period of the checks, which bounds the expiry
+    /// detection delay. This is 10 ms by default.
+    pub fn period(mut self, period: Duration) -> Self {
+        self.period = period;
+        self
+    }
+    /// Run the monitor thread in the SCHED_FIFO class at `prio`,
+    /// which should be higher than the priority of the supervised
+    /// threads. The monitor runs in the SCHED_OTHER class by
+    /// default.
+    pub fn priority(mut self, prio: i32) -> Self {
+        self.priority = Some(prio);
+        self
+    }
+    /// Call `callback` from the monitor thread on every expiry, before
+    /// the policy of the offending thread is applied. The callback
+    /// runs out-of-band, so it should not issue in-band system calls.
+    pub fn on_expiry<F>(mut self, callback: F) -> Self
+    where F: Fn(&Expiry) + Send + Sync + 'static
+    {
+        self.callback = Some(Box::new(callback));
+        self
+    }
+    pub fn start(self) -> Result<Watchdog, Error> {
+        Watchdog::new(self)
+    }
+}
+
+/// A watchdog supervising the heartbeats of real-time threads. The
+/// monitor thread stops when the watchdog is dropped.
+pub struct Watchdog {
+    inner: Arc<Inner>,
+    monitor: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    /// Create a watchdog and start its monitor thread, retrieving the
+    /// settings from a [`builder struct`](Builder).
+    pub fn new(builder: Builder) -> Result<Self, Error> {
+        let inner = Arc::new(Inner {
+            entries: mutex::Builder::new().create(Vec::new())?,
+            registered: AtomicUsize::new(0),
+            events: observable::Builder::new().create()?,
+            callback: builder.callback,
+            stop: AtomicBool::new(false),
+        });
+        let c_inner = inner.clone();
+        let period = builder.period;
+        let priority = builder.priority;
+        let setup = move |me: &Thread| match priority {
+            Some(prio) => me.set_sched(SchedFifo { prio }),
+            None => Ok(()),
+        };
+        let monitor = thread::Builder::new().name("watchdog").spawn_setup(setup, move |_me| {
+            monitor(&c_inner, period);
+        })?;
+        Ok(Self { inner, monitor: Some(monitor) })
+    }
+    /// Register `me` for supervision, returning the feeder it should
+    /// call at least once every `budget`. The first budget starts
+    /// now. `me` is typically the handle a thread receives from
+    /// [`thread::Builder::spawn()`]. Supervision ends when the feeder
+    /// is dropped.
+    pub fn register(
+        &self,
+        me: &Thread,
+        name: &'static str,
+        budget: Duration,
+        policy: Policy,
+    ) -> Result<Feeder, Error> {
+        let thread = Thread::from_owned_fd(me.as_fd().try_clone_to_owned()?);
+        let mut entries = self.inner.entries.lock()?;
+        // Forget about the threads which are gone.
+        entries.retain(|entry| !entry.retired.load(Acquire));
+        let entry = Arc::new(Entry {
+            name,
+            index: self.inner.registered.fetch_add(1, Relaxed),
+            budget: budget.as_nanos() as u64,
+            policy,
+            thread,
+            last_feed: AtomicU64::new(now()),
+            tripped: AtomicBool::new(false),
+            retired: AtomicBool::new(false),
+        });
+        entries.push(entry.clone());
+        Ok(Feeder { entry })
+    }
+    /// Return the observable receiving a [`NOTICE_EXPIRY`] notice on
+    /// every expiry.
+    pub fn events(&self) -> &Observable {
+        &self.inner.events
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.inner.stop.store(true, Release);
+        if let Some(monitor) = self.monitor.take() {
+            let _ = monitor.join();
+        }
+    }
+}
+
+/// The heartbeat handle of a supervised thread, which ends the
+/// supervision when dropped.
+pub struct Feeder {
+    entry: Arc<Entry>,
+}
+
+impl Feeder {
+    /// Signal that the thread is alive, starting a new budget.
+    pub fn feed(&self) {
+        self.entry.last_feed.store(now(), Release);
+        if self.entry.tripped.load(Relaxed) {
+            self.entry.tripped.store(false, Relaxed);
+        }
+    }
+}
+
+impl Drop for Feeder {
+    fn drop(&mut self) {
+        self.entry.retired.store(true, Release);
+    }
+}
+
+fn now() -> u64 {
+    STEADY_CLOCK.now().duration_since_epoch().integer()
+}
+
+fn monitor(inner: &Inner, period: Duration) {
+    let mut next = now();
+    while !inner.stop.load(Acquire) {
+        next += period.as_nanos() as u64;
+        if STEADY_CLOCK.sleep_until(Instant::new(next)).is_err() {
+            break;
+        }
+        let date = now();
+        let entries = match inner.entries.lock() {
+            Ok(entries) => entries,
+            Err(_) => break,
+        };
+        for entry in entries.iter() {
+            if entry.retired.load(Acquire) {
+                continue;
+            }
+            let late = date.saturating_sub(entry.last_feed.load(Acquire));
+            if late <= entry.budget || entry.tripped.swap(true, Relaxed) {
+                continue;
+            }
+            let expiry = Expiry {
+                name: entry.name,
+                index: entry.index,
+                late_by: Duration::from_nanos(late - entry.budget),
+                policy: entry.policy,
+            };
+            let _ = inner.events.update(&[Notice::new(NOTICE_EXPIRY, entry.index as i64)]);
+            if let Some(callback) = &inner.callback {
+                callback(&expiry);
+            }
+            match entry.policy {
+                Policy::Notify => {},
+                Policy::Demote => {
+                    let _ = entry.thread.demote();
+                },
+                Policy::Abort => std::process::abort(),
+            }
+        }
+    }
+}
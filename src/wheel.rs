@@ -0,0 +1,447 @@
+//! Hashed timer wheel.
+//!
+//! A [`TimerWheel`] multiplexes a large number of software timeouts
+//! over a single EVL [timer](crate::timer), which ticks at a fixed
+//! resolution. Timeouts are hashed into a ring of slots by expiry
+//! tick, so that arming and cancelling a timeout are O(1)
+//! operations, and each tick only visits a single slot. Expired
+//! timeouts are dispatched from a dedicated EVL thread to the
+//! callback given to the [builder](Builder), which receives the
+//! token the timeout was armed with.
+//!
+//! All storage is allocated when the wheel is started, up to the
+//! [capacity](Builder::capacity) of the wheel, so arming does not
+//! allocate memory.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use revl::wheel;
+//!
+//! let wheel = wheel::Builder::new()
+//!     .resolution(Duration::from_millis(1))
+//!     .capacity(4096)
+//!     .priority(80)
+//!     .on_expiry(|conn| revl::rt_eprintln!("connection {} timed out", conn))
+//!     .start()
+//!     .unwrap();
+//!
+//! let id = wheel.arm(Duration::from_millis(200), 42).unwrap();
+//! // The peer answered in time.
+//! wheel.cancel(id).unwrap();
+//! ```
+
+use std::sync::{
+    Arc,
+    atomic::AtomicBool,
+    atomic::Ordering::Acquire,
+    atomic::Ordering::Release,
+};
+use std::io::ErrorKind;
+use std::time::Duration;
+use crate::clock::STEADY_CLOCK;
+use crate::mutex::{self, Mutex};
+use crate::sched::SchedFifo;
+use crate::thread::{self, JoinHandle, Thread};
+use crate::timer::Timer;
+use crate::Error;
+
+const NIL: u32 = u32::MAX;
+
+/// Identifies an armed timeout, see [`TimerWheel::cancel()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimeoutId {
+    index: u32,
+    generation: u32,
+}
+
+type Callback = Box<dyn FnMut(u64) + Send>;
+
+struct Node {
+    token: u64,
+    // Full wheel turns remaining before expiry.
+    rounds: u64,
+    slot: u32,
+    // Links in the slot list, or in the free list.
+    prev: u32,
+    next: u32,
+    // Bumped each time the node is released, so that stale ids are
+    // detected.
+    generation: u32,
+    armed: bool,
+}
+
+struct Wheel {
+    nodes: Vec<Node>,
+    slots: Vec<u32>,
+    free: u32,
+    // Index of the slot the dispatcher processes next.
+    cursor: usize,
+    pending: usize,
+}
+
+impl Wheel {
+    fn new(nr_slots: usize, capacity: usize) -> Self {
+        let nodes = (0..capacity).map(|n| Node {
+            token: 0,
+            rounds: 0,
+            slot: 0,
+            prev: NIL,
+            next: if n + 1 < capacity { n as u32 + 1 } else { NIL },
+            generation: 0,
+            armed: false,
+        }).collect();
+        Self {
+            nodes,
+            slots: vec![NIL; nr_slots],
+            free: if capacity > 0 { 0 } else { NIL },
+            cursor: 0,
+            pending: 0,
+        }
+    }
+    fn insert(&mut self, ticks: u64, token: u64) -> Option<TimeoutId> {
+        let index = self.free;
+        if index == NIL {
+            return None;
+        }
+        let mask = self.slots.len() - 1;
+        // The dispatcher processes the cursor slot on the next tick,
+        // which may come anytime since the current tick is partly
+        // elapsed: wait for whole ticks past that one.
+        let slot = self.cursor.wrapping_add(ticks as usize) & mask;
+        let head = self.slots[slot];
+        let node = &mut self.nodes[index as usize];
+        self.free = node.next;
+        node.token = token;
+        node.rounds = ticks / self.slots.len() as u64;
+        node.slot = slot as u32;
+        node.prev = NIL;
+        node.next = head;
+        node.armed = true;
+        let id = TimeoutId { index, generation: node.generation };
+        if head != NIL {
+            self.nodes[head as usize].prev = index;
+        }
+        self.slots[slot] = index;
+        self.pending += 1;
+        Some(id)
+    }
+    fn release(&mut self, index: u32) {
+        let (prev, next, slot) = {
+            let node = &self.nodes[index as usize];
+            (node.prev, node.next, node.slot)
+        };
+        if prev != NIL {
+            self.nodes[prev as usize].next = next;
+        } else {
+            self.slots[slot as usize] = next;
+        }
+        if next != NIL {
+            self.nodes[next as usize].prev = prev;
+        }
+        let node = &mut self.nodes[index as usize];
+        node.armed = false;
+        node.generation = node.generation.wrapping_add(1);
+        node.next = self.free;
+        self.free = index;
+        self.pending -= 1;
+    }
+    fn remove(&mut self, id: TimeoutId) -> bool {
+        match self.nodes.get(id.index as usize) {
+            Some(node) if node.armed && node.generation == id.generation => {
+                self.release(id.index);
+                true
+            },
+            _ => false,
+        }
+    }
+    // Process the slot under the cursor, pushing the expired tokens
+    // into `expired`.
+    fn advance(&mut self, expired: &mut Vec<u64>) {
+        let slot = self.cursor;
+        let mut index = self.slots[slot];
+        while index != NIL {
+            let node = &mut self.nodes[index as usize];
+            let next = node.next;
+            if node.rounds == 0 {
+                expired.push(node.token);
+                self.release(index);
+            } else {
+                node.rounds -= 1;
+            }
+            index = next;
+        }
+        self.cursor = (slot + 1) & (self.slots.len() - 1);
+    }
+}
+
+struct Inner {
+    wheel: Mutex<Wheel>,
+    resolution: u64,
+    stop: AtomicBool,
+}
+
+pub struct Builder {
+    resolution: Duration,
+    slots: usize,
+    capacity: usize,
+    priority: Option<i32>,
+    callback: Option<Callback>,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            resolution: Duration::from_millis(1),
+            slots: 256,
+            capacity: 1024,
+            priority: None,
+            callback: None,
+        }
+    }
+    /// Set the tick of the wheel, which is the resolution of the
+    /// timeouts. This is 1 ms by default.
+    pub fn resolution(mut self, resolution: Duration) -> Self {
+        self.resolution = resolution;
+        self
+    }
+    /// Set the number of slots of the wheel, which is rounded up to
+    /// the next power of two. This should be close to the typical
+    /// timeout divided by the resolution, so that few timeouts
+    /// share a slot. There are 256 slots by default.
+    pub fn slots(mut self, slots: usize) -> Self {
+        self.slots = slots;
+        self
+    }
+    /// Set the maximum number of timeouts which may be armed at the
+    /// same time, 1024 by default.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+    /// Run the dispatcher thread in the SCHED_FIFO class at `prio`.
+    /// The dispatcher runs in the SCHED_OTHER class by default.
+    pub fn priority(mut self, prio: i32) -> Self {
+        self.priority = Some(prio);
+        self
+    }
+    /// Call `callback` from the dispatcher thread with the token of
+    /// every expired timeout. The callback runs out-of-band, so it
+    /// should not issue in-band system calls.
+    pub fn on_expiry<F>(mut self, callback: F) -> Self
+    where F: FnMut(u64) + Send + 'static
+    {
+        self.callback = Some(Box::new(callback));
+        self
+    }
+    pub fn start(self) -> Result<TimerWheel, Error> {
+        TimerWheel::new(self)
+    }
+}
+
+/// A timer wheel, see the [module documentation](crate::wheel). The
+/// dispatcher thread stops when the wheel is dropped, pending
+/// timeouts are discarded.
+pub struct TimerWheel {
+    inner: Arc<Inner>,
+    dispatcher: Option<JoinHandle<()>>,
+}
+
+impl TimerWheel {
+    /// Create a timer wheel and start its dispatcher thread,
+    /// retrieving the settings from a [`builder struct`](Builder).
+    ///
+    /// # Errors
+    ///
+    /// [`InvalidInput`][`std::io::ErrorKind`] means that the
+    /// resolution is zero, or the capacity exceeds `u32::MAX`.
+    pub fn new(builder: Builder) -> Result<Self, Error> {
+        if builder.resolution.is_zero() {
+            return Err(Error::new(ErrorKind::InvalidInput, "zero timer wheel resolution"));
+        }
+        // Timeouts are indexed on 32 bits.
+        if builder.capacity > u32::MAX as usize {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid timer wheel capacity"));
+        }
+        let timer = Timer::new(&STEADY_CLOCK)?;
+        let nr_slots = builder.slots.max(1).next_power_of_two();
+        let inner = Arc::new(Inner {
+            wheel: mutex::Builder::new().create(Wheel::new(nr_slots, builder.capacity))?,
+            resolution: builder.resolution.as_nanos() as u64,
+            stop: AtomicBool::new(false),
+        });
+        timer.start_after(builder.resolution, Some(builder.resolution))?;
+        let c_inner = inner.clone();
+        let priority = builder.priority;
+        let callback = builder.callback;
+        let capacity = builder.capacity;
+        let setup = move |me: &Thread| match priority {
+            Some(prio) => me.set_sched(SchedFifo { prio }),
+            None => Ok(()),
+        };
+        let dispatcher = thread::Builder::new().name("timer-wheel").spawn_setup(setup, move |_me| {
+            dispatch(&c_inner, &timer, callback, capacity);
+        })?;
+        Ok(Self { inner, dispatcher: Some(dispatcher) })
+    }
+    /// Arm a timeout expiring after `delay`, which passes `token` to
+    /// the expiry callback. The delay is rounded up to the resolution
+    /// of the wheel, plus one tick to account for the current one,
+    /// which is partly elapsed: a timeout never expires early, but
+    /// may expire up to two resolutions late.
+    ///
+    /// # Errors
+    ///
+    /// [`WouldBlock`][`crate::Error::WouldBlock`] is returned if the
+    /// wheel is at capacity.
+    pub fn arm(&self, delay: Duration, token: u64) -> Result<TimeoutId, Error> {
+        let ticks = (delay.as_nanos() as u64).div_ceil(self.inner.resolution);
+        match self.inner.wheel.lock()?.insert(ticks, token) {
+            Some(id) => Ok(id),
+            None => Err(Error::WouldBlock),
+        }
+    }
+    /// Cancel a pending timeout, returning false if it already
+    /// expired or was cancelled.
+    pub fn cancel(&self, id: TimeoutId) -> Result<bool, Error> {
+        Ok(self.inner.wheel.lock()?.remove(id))
+    }
+    /// Return the number of pending timeouts.
+    pub fn pending(&self) -> Result<usize, Error> {
+        Ok(self.inner.wheel.lock()?.pending)
+    }
+    /// Return the resolution of the wheel.
+    pub fn resolution(&self) -> Duration {
+        Duration::from_nanos(self.inner.resolution)
+    }
+}
+
+impl Drop for TimerWheel {
+    fn drop(&mut self) {
+        // The dispatcher notices on its next tick.
+        self.inner.stop.store(true, Release);
+        if let Some(dispatcher) = self.dispatcher.take() {
+            let _ = dispatcher.join();
+        }
+    }
+}
+
+fn dispatch(inner: &Inner, timer: &Timer, mut callback: Option<Callback>, capacity: usize) {
+    let mut expired = Vec::with_capacity(capacity);
+    while !inner.stop.load(Acquire) {
+        let overruns = match timer.wait() {
+            Ok(overruns) => overruns,
+            Err(_) => break,
+        };
+        match inner.wheel.lock() {
+            Ok(mut wheel) => {
+                // Catch up with the ticks we missed.
+                for _ in 0..=overruns {
+                    wheel.advance(&mut expired);
+                }
+            },
+            Err(_) => break,
+        }
+        if let Some(callback) = callback.as_mut() {
+            for token in expired.drain(..) {
+                callback(token);
+            }
+        } else {
+            expired.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Advance the wheel by `ticks`, returning the tokens which
+    // expired on the last tick.
+    fn run(wheel: &mut Wheel, ticks: usize) -> Vec<u64> {
+        let mut expired = Vec::new();
+        for _ in 0..ticks {
+            expired.clear();
+            wheel.advance(&mut expired);
+        }
+        expired
+    }
+
+    fn position(wheel: &Wheel, id: TimeoutId) -> (u32, u64) {
+        let node = &wheel.nodes[id.index as usize];
+        (node.slot, node.rounds)
+    }
+
+    #[test]
+    fn expires_on_next_tick() {
+        let mut wheel = Wheel::new(8, 16);
+        wheel.insert(0, 1).unwrap();
+        wheel.insert(0, 2).unwrap();
+        assert_eq!(run(&mut wheel, 1), [2, 1]);
+        assert_eq!(wheel.pending, 0);
+        // The current tick does not count.
+        wheel.insert(1, 3).unwrap();
+        assert!(run(&mut wheel, 1).is_empty());
+        assert_eq!(run(&mut wheel, 1), [3]);
+    }
+
+    #[test]
+    fn slot_and_rounds() {
+        let mut wheel = Wheel::new(8, 16);
+        let last = wheel.insert(7, 7).unwrap();
+        assert_eq!(position(&wheel, last), (7, 0));
+        let wrapped = wheel.insert(8, 8).unwrap();
+        assert_eq!(position(&wheel, wrapped), (0, 1));
+        // The first visit of slot 0 only counts a round down.
+        assert!(run(&mut wheel, 7).is_empty());
+        assert_eq!(run(&mut wheel, 1), [7]);
+        assert_eq!(run(&mut wheel, 1), [8]);
+        assert_eq!(wheel.pending, 0);
+    }
+
+    #[test]
+    fn slots_are_relative_to_cursor() {
+        let mut wheel = Wheel::new(8, 16);
+        run(&mut wheel, 5);
+        let id = wheel.insert(3, 3).unwrap();
+        assert_eq!(position(&wheel, id), (0, 0));
+        assert!(run(&mut wheel, 3).is_empty());
+        assert_eq!(run(&mut wheel, 1), [3]);
+    }
+
+    #[test]
+    fn capacity_is_bounded() {
+        let mut wheel = Wheel::new(8, 2);
+        let first = wheel.insert(3, 1).unwrap();
+        wheel.insert(3, 2).unwrap();
+        assert!(wheel.insert(3, 3).is_none());
+        assert!(wheel.remove(first));
+        assert!(wheel.insert(3, 3).is_some());
+        assert!(Wheel::new(8, 0).insert(1, 0).is_none());
+    }
+
+    #[test]
+    fn stale_ids_are_rejected() {
+        let mut wheel = Wheel::new(8, 1);
+        let id = wheel.insert(2, 1).unwrap();
+        assert!(wheel.remove(id));
+        assert!(!wheel.remove(id));
+        // The node is reused under a new generation.
+        let reused = wheel.insert(2, 2).unwrap();
+        assert_eq!(reused.index, id.index);
+        assert!(!wheel.remove(id));
+        assert_eq!(run(&mut wheel, 3), [2]);
+        assert!(!wheel.remove(reused));
+    }
+
+    #[test]
+    fn removal_unlinks_from_slot() {
+        let mut wheel = Wheel::new(8, 16);
+        let ids: Vec<_> = (0..4).map(|token| wheel.insert(3, token).unwrap()).collect();
+        // Remove from the middle, the tail and the head of the list.
+        assert!(wheel.remove(ids[1]));
+        assert!(wheel.remove(ids[0]));
+        assert!(wheel.remove(ids[3]));
+        assert_eq!(wheel.pending, 1);
+        assert_eq!(run(&mut wheel, 4), [2]);
+    }
+}
@@ -0,0 +1,229 @@
+//! Cross-buffer.
+//!
+//! A cross-buffer is a bi-directional byte channel between the
+//! out-of-band and in-band stages. The inbound buffer carries data
+//! written by in-band threads to out-of-band readers, the outbound
+//! buffer carries data the other way around. The in-band side uses
+//! the regular [`Read`] and [`Write`] interfaces, the out-of-band
+//! side uses [`XBuf::oob_read()`] and [`XBuf::oob_write()`]. Each
+//! write is transferred atomically to the reader.
+//!
+//! Typed messages can be exchanged more conveniently with a
+//! [bridge](crate::bridge), which is based on a cross-buffer.
+
+use std::ffi::{c_void, CString};
+use std::io::{self, Read, Write};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::os::raw::c_int;
+use std::ptr;
+use evl_sys::{
+    evl_create_xbuf,
+    oob_read,
+    oob_write,
+    CloneFlags,
+};
+use crate::Error;
+
+pub struct Builder {
+    name: Option<String>,
+    visible: bool,
+    nonblock: bool,
+    i_bufsz: usize,
+    o_bufsz: usize,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            name: None,
+            visible: false,
+            nonblock: false,
+            i_bufsz: 0,
+            o_bufsz: 0,
+        }
+    }
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+    pub fn public(mut self) -> Self {
+        self.visible = true;
+        self
+    }
+    pub fn private(mut self) -> Self {
+        self.visible = false;
+        self
+    }
+    /// Set the size in bytes of the inbound buffer, which carries
+    /// data from the in-band to the out-of-band stage. Zero disables
+    /// this direction, which is the default.
+    pub fn inbound(mut self, size: usize) -> Self {
+        self.i_bufsz = size;
+        self
+    }
+    /// Set the size in bytes of the outbound buffer, which carries
+    /// data from the out-of-band to the in-band stage. Zero disables
+    /// this direction, which is the default.
+    pub fn outbound(mut self, size: usize) -> Self {
+        self.o_bufsz = size;
+        self
+    }
+    /// Make reads and writes fail with
+    /// [`WouldBlock`][`crate::Error::WouldBlock`] instead of waiting
+    /// for data or room in the buffer.
+    pub fn nonblocking(mut self) -> Self {
+        self.nonblock = true;
+        self
+    }
+    pub fn create(self) -> Result<XBuf, Error> {
+        XBuf::new(self)
+    }
+}
+
+pub struct XBuf(c_int);
+
+unsafe impl Send for XBuf {}
+unsafe impl Sync for XBuf {}
+
+impl XBuf {
+    /// Create an EVL cross-buffer, retrieving the settings from a
+    /// [`builder struct`](Builder).
+    ///
+    /// # Errors
+    ///
+    /// * [`NameConflict`][`crate::Error::NameConflict`] means the
+    /// cross-buffer name is conflicting with an existing one.
+    ///
+    /// * [`InvalidInput`][`std::io::ErrorKind`] means that both
+    /// buffer sizes are zero, or the name contains invalid
+    /// characters.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::io::Read;
+    /// use revl::xbuf::Builder;
+    ///
+    /// let xbuf = Builder::new().name("telemetry").outbound(64 * 1024).create().unwrap();
+    /// // From an out-of-band thread:
+    /// xbuf.oob_write(b"cycle overrun\n").unwrap();
+    /// // From an in-band thread:
+    /// let mut buf = [0u8; 256];
+    /// let n = (&xbuf).read(&mut buf).unwrap();
+    /// ```
+    pub fn new(builder: Builder) -> Result<Self, Error> {
+        let mut c_flags = CloneFlags::PRIVATE.bits() as c_int;
+        if builder.visible {
+            c_flags = CloneFlags::PUBLIC.bits() as c_int;
+        }
+        if builder.nonblock {
+            c_flags |= CloneFlags::NONBLOCK.bits() as c_int;
+        }
+        let ret: c_int = unsafe {
+            if let Some(name) = builder.name {
+                let c_name = CString::new(name).expect("CString::new failed");
+                let c_fmt = CString::new("%s").expect("CString::new failed");
+                evl_create_xbuf(
+                    builder.i_bufsz,
+                    builder.o_bufsz,
+                    c_flags,
+                    c_fmt.as_ptr(),
+                    c_name.as_ptr(),
+                )
+            } else {
+                evl_create_xbuf(builder.i_bufsz,
+                                builder.o_bufsz,
+                                c_flags,
+                                ptr::null())
+            }
+        };
+        match ret {
+            0.. => return Ok(Self(ret)),
+            _ => return Err(Error::from_raw_os_error(-ret)),
+        };
+    }
+    /// Read from the inbound buffer, returning the number of bytes
+    /// read. This must be called from the out-of-band stage.
+    pub fn oob_read(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        let ret = unsafe {
+            oob_read(self.0, buf.as_mut_ptr() as *mut c_void, buf.len())
+        };
+        match ret {
+            0.. => return Ok(ret as usize),
+            _ => return Err(Error::last_os_error()),
+        };
+    }
+    /// Write to the outbound buffer, returning the number of bytes
+    /// written. This must be called from the out-of-band stage.
+    pub fn oob_write(&self, buf: &[u8]) -> Result<usize, Error> {
+        let ret = unsafe {
+            oob_write(self.0, buf.as_ptr() as *const c_void, buf.len())
+        };
+        match ret {
+            0.. => return Ok(ret as usize),
+            _ => return Err(Error::last_os_error()),
+        };
+    }
+}
+
+impl Read for &XBuf {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let ret = unsafe {
+            libc::read(self.0, buf.as_mut_ptr() as *mut c_void, buf.len())
+        };
+        match ret {
+            0.. => return Ok(ret as usize),
+            _ => return Err(io::Error::last_os_error()),
+        };
+    }
+}
+
+impl Read for XBuf {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self).read(buf)
+    }
+}
+
+impl Write for &XBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let ret = unsafe {
+            libc::write(self.0, buf.as_ptr() as *const c_void, buf.len())
+        };
+        match ret {
+            0.. => return Ok(ret as usize),
+            _ => return Err(io::Error::last_os_error()),
+        };
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Write for XBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsRawFd for XBuf {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+impl AsFd for XBuf {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.0) }
+    }
+}
+
+impl Drop for XBuf {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.0);
+        }
+    }
+}
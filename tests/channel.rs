@@ -0,0 +1,116 @@
+//! Channel disconnection tests, run with:
+//!
+//! ```text
+//! cargo test --no-default-features --features sim
+//! ```
+
+#![cfg(feature = "sim")]
+
+use std::collections::HashSet;
+use std::hint;
+use std::thread;
+use revl::channel::{self, RecvError, SendError};
+
+#[test]
+fn fifo_up_to_capacity() {
+    let (tx, rx) = channel::create::<u32, 2>().unwrap();
+    for n in 0..4 {
+        tx.send(n).unwrap();
+    }
+    match tx.send(4) {
+        Err(SendError::Full(msg)) => assert_eq!(msg, 4),
+        other => panic!("unexpected {:?}", other),
+    }
+    for n in 0..4 {
+        assert_eq!(rx.recv().unwrap(), n);
+    }
+    assert!(matches!(rx.recv(), Err(RecvError::Empty)));
+}
+
+#[test]
+fn pending_messages_outlive_senders() {
+    let (tx, rx) = channel::create::<u32, 2>().unwrap();
+    let c_tx = tx.clone();
+    tx.send(1).unwrap();
+    c_tx.send(2).unwrap();
+    drop(tx);
+    // One sender is left.
+    assert_eq!(rx.recv().unwrap(), 1);
+    drop(c_tx);
+    assert_eq!(rx.recv().unwrap(), 2);
+    // Every receiver keeps on seeing the disconnection.
+    let c_rx = rx.clone();
+    for _ in 0..3 {
+        assert!(matches!(rx.recv(), Err(RecvError::Disconnected)));
+        assert!(matches!(c_rx.recv(), Err(RecvError::Disconnected)));
+    }
+    assert_eq!(rx.len(), 0);
+}
+
+#[test]
+fn send_without_receivers() {
+    let (tx, rx) = channel::create::<String, 2>().unwrap();
+    let c_rx = rx.clone();
+    drop(rx);
+    tx.send("kept".to_string()).unwrap();
+    drop(c_rx);
+    match tx.send("returned".to_string()) {
+        Err(SendError::Disconnected(msg)) => assert_eq!(msg, "returned"),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn every_message_is_received_once() {
+    const NR_SENDERS: u64 = 4;
+    const NR_MESSAGES: u64 = 20_000;
+    let (tx, rx) = channel::create::<u64, 4>().unwrap();
+    let senders: Vec<_> = (0..NR_SENDERS).map(|id| {
+        let tx = tx.clone();
+        thread::spawn(move || {
+            for n in 0..NR_MESSAGES {
+                let mut msg = id << 32 | n;
+                loop {
+                    match tx.send(msg) {
+                        Ok(()) => break,
+                        Err(SendError::Full(m)) => msg = m,
+                        Err(e) => panic!("send failed: {}", e),
+                    }
+                    hint::spin_loop();
+                }
+            }
+        })
+    }).collect();
+    drop(tx);
+    let receivers: Vec<_> = (0..2).map(|_| {
+        let rx = rx.clone();
+        thread::spawn(move || {
+            let mut received = Vec::new();
+            loop {
+                match rx.recv() {
+                    Ok(msg) => received.push(msg),
+                    Err(RecvError::Empty) => hint::spin_loop(),
+                    Err(RecvError::Disconnected) => break received,
+                    Err(e) => panic!("recv failed: {}", e),
+                }
+            }
+        })
+    }).collect();
+    drop(rx);
+    for sender in senders {
+        sender.join().unwrap();
+    }
+    let mut seen = HashSet::new();
+    for receiver in receivers {
+        let received = receiver.join().unwrap();
+        // Each receiver gets the messages of a sender in order.
+        for id in 0..NR_SENDERS {
+            let from: Vec<u64> = received.iter().copied().filter(|msg| msg >> 32 == id).collect();
+            assert!(from.windows(2).all(|w| w[0] < w[1]));
+        }
+        for msg in received {
+            assert!(seen.insert(msg), "message {:#x} received twice", msg);
+        }
+    }
+    assert_eq!(seen.len() as u64, NR_SENDERS * NR_MESSAGES);
+}
@@ -0,0 +1,116 @@
+//! Virtual time tests, run with:
+//!
+//! ```text
+//! cargo test --no-default-features --features sim
+//! ```
+
+#![cfg(feature = "sim")]
+
+use std::sync::atomic::{AtomicBool, Ordering::Acquire, Ordering::Release};
+use std::sync::{mpsc, Arc};
+use std::thread as std_thread;
+use std::time::Duration;
+use revl::clock::{Periodic, STEADY_CLOCK};
+use revl::sim::{self, TestClock};
+use revl::thread;
+use revl::wheel::{self, TimerWheel};
+
+const MS: u64 = 1_000_000;
+const RESOLUTION: Duration = Duration::from_millis(1);
+
+fn now_ns() -> u64 {
+    STEADY_CLOCK.now().duration_since_epoch().integer()
+}
+
+#[test]
+fn periodic_follows_virtual_time() {
+    let clock = sim::Builder::new().start().unwrap();
+    let start = clock.now().duration_since_epoch().integer();
+    let handle = thread::Builder::new().spawn(|_| {
+        let mut cycle = Periodic::new(Duration::from_millis(10));
+        let mut dates = Vec::new();
+        for _ in 0..5 {
+            assert_eq!(cycle.tick().unwrap(), 0);
+            dates.push(now_ns());
+        }
+        (dates, cycle.stats())
+    }).unwrap();
+    clock.advance(Duration::from_millis(100));
+    let (dates, stats) = handle.join().unwrap();
+    let expected: Vec<u64> = (1..=5).map(|n| start + n * 10 * MS).collect();
+    assert_eq!(dates, expected);
+    assert_eq!(stats.ticks, 5);
+    assert_eq!(stats.missed, 0);
+    assert_eq!(stats.max_jitter, Duration::ZERO);
+    // The clock stopped at the target date.
+    assert_eq!(clock.now().duration_since_epoch().integer(), start + 100 * MS);
+}
+
+#[test]
+fn periodic_counts_overruns() {
+    let clock = sim::Builder::new().start().unwrap();
+    let start = clock.now().duration_since_epoch().integer();
+    let handle = thread::Builder::new().spawn(|_| {
+        let mut cycle = Periodic::new(Duration::from_millis(10));
+        assert_eq!(cycle.tick().unwrap(), 0);
+        // Overrun the release points at 20 and 30 ms.
+        STEADY_CLOCK.spin_for(Duration::from_millis(25));
+        let missed = cycle.tick().unwrap();
+        (missed, now_ns(), cycle.stats())
+    }).unwrap();
+    clock.advance(Duration::from_millis(100));
+    let (missed, woke, stats) = handle.join().unwrap();
+    assert_eq!(missed, 2);
+    assert_eq!(woke, start + 40 * MS);
+    assert_eq!(stats.ticks, 2);
+    assert_eq!(stats.missed, 2);
+}
+
+// The dispatcher of a wheel only notices the wheel is dropped on its
+// next tick, so the clock has to keep moving meanwhile.
+fn drop_ticking(clock: &TestClock, wheel: TimerWheel) {
+    let dropped = AtomicBool::new(false);
+    std_thread::scope(|s| {
+        s.spawn(|| {
+            while !dropped.load(Acquire) {
+                clock.advance(RESOLUTION);
+            }
+        });
+        drop(wheel);
+        dropped.store(true, Release);
+    });
+}
+
+#[test]
+fn wheel_expires_in_order() {
+    let clock = sim::Builder::new().start().unwrap();
+    let start = clock.now().duration_since_epoch().integer();
+    let (tx, rx) = mpsc::channel();
+    let wheel = Arc::new(wheel::Builder::new()
+        .resolution(RESOLUTION)
+        .slots(16)
+        .on_expiry(move |token| tx.send((token, now_ns())).unwrap())
+        .start()
+        .unwrap());
+    // The wheel lock is an EVL mutex, so timeouts are armed from an
+    // EVL thread. Some delays span multiple turns of the wheel.
+    let c_wheel = wheel.clone();
+    thread::Builder::new().spawn(move |_| {
+        for delay in [30, 10, 45, 20, 10, 5] {
+            c_wheel.arm(Duration::from_millis(delay), delay).unwrap();
+        }
+        let id = c_wheel.arm(Duration::from_millis(15), 15).unwrap();
+        assert!(c_wheel.cancel(id).unwrap());
+    }).unwrap().join().unwrap();
+    clock.advance(Duration::from_millis(50));
+    let expired: Vec<(u64, u64)> = rx.try_iter().collect();
+    let tokens: Vec<u64> = expired.iter().map(|&(token, _)| token).collect();
+    assert_eq!(tokens, [5, 10, 10, 20, 30, 45]);
+    // Each timeout expired on the tick following its delay, since
+    // the tick it was armed in does not count.
+    for (token, date) in expired {
+        assert_eq!(date, start + (token + 1) * MS);
+    }
+    let wheel = Arc::into_inner(wheel).unwrap();
+    drop_ticking(&clock, wheel);
+}